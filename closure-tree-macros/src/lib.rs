@@ -1,3 +1,4 @@
+use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::ext::IdentExt;
@@ -17,17 +18,66 @@ pub fn derive_closure_tree_model(input: TokenStream) -> TokenStream {
 
 #[derive(Default)]
 struct Options {
-    id_field: Option<String>,
+    id_field: Option<syn::LitStr>,
     id_type: Option<Type>,
-    parent_field: Option<String>,
+    id_generator: Option<Path>,
+    id_to_value_with: Option<Path>,
+    hierarchy_id_to_value_with: Option<Path>,
+    new_child_defaults: Option<Path>,
+    parent_field: Option<syn::LitStr>,
     hierarchy_module: Option<Path>,
+    /// Span of the `generate_hierarchy_entity` flag.
+    generate_hierarchy_entity: Option<proc_macro2::Span>,
     hierarchy_table: Option<String>,
-    name_field: Option<String>,
+    /// Columns of the `hierarchy_conflict_columns(...)` list.
+    hierarchy_conflict_columns: Option<Vec<syn::LitStr>>,
+    schema_name: Option<String>,
+    name_field: Option<syn::LitStr>,
+    /// Span of the `no_name` flag, for reporting a conflicting name field.
+    no_name: Option<proc_macro2::Span>,
+    order_field: Option<syn::LitStr>,
+    /// Span of the `fractional_order` flag, which makes `order_field` a
+    /// fractional position.
+    fractional_order: Option<proc_macro2::Span>,
+    touch_field: Option<syn::LitStr>,
+    children_count_field: Option<syn::LitStr>,
+    scope_field: Option<syn::LitStr>,
+    soft_delete_field: Option<syn::LitStr>,
+    dont_order_roots: bool,
+    unique_sibling_names: bool,
+    path_separator: Option<String>,
+    max_depth: Option<syn::LitInt>,
+    max_children: Option<syn::LitInt>,
+    /// `.dependent_behavior(...)` call for the `dependent` attribute.
+    dependent: Option<proc_macro2::TokenStream>,
+    /// `.root_marker(...)` call for the `root_marker` attribute.
+    root_marker: Option<proc_macro2::TokenStream>,
+    /// The `extension_trait` flag, with the trait's name when one is given.
+    extension_trait: Option<Option<syn::LitStr>>,
+    hierarchy_relations: bool,
+    /// The `features(...)` list, when the capabilities are given explicitly.
+    features: Option<Features>,
+    advisory_lock_disabled: Option<syn::LitStr>,
+    advisory_lock_key: Option<syn::LitStr>,
     entity_name: Option<String>,
     hierarchy_name: Option<String>,
     ancestor_field: Option<String>,
     descendant_field: Option<String>,
     generations_field: Option<String>,
+    /// Paths of the SeaORM items, for modules that do not name them `Entity`,
+    /// `Column` and `ActiveModel`.
+    entity: Option<Path>,
+    column_enum: Option<Path>,
+    active_model: Option<Path>,
+}
+
+/// The capabilities named in `features(paths, ordering, counter_cache)`, with
+/// the span of each.
+struct Features {
+    span: proc_macro2::Span,
+    paths: Option<proc_macro2::Span>,
+    ordering: Option<proc_macro2::Span>,
+    counter_cache: Option<proc_macro2::Span>,
 }
 
 fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
@@ -35,34 +85,233 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
 
     let data_struct = match &input.data {
         Data::Struct(data) => data,
-        _ => {
+        Data::Enum(data) => {
             return Err(syn::Error::new(
-                input.span(),
+                data.enum_token.span,
+                "ClosureTreeModel can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
                 "ClosureTreeModel can only be derived for structs",
             ))
         }
     };
 
     let mut options = Options::default();
+    // Attributes with a `tag` configure further trees, each in its own options.
+    let mut tagged: Vec<(syn::LitStr, Options)> = Vec::new();
+    let mut untagged = false;
     let mut table_name: Option<String> = None;
+    let mut sea_orm_schema_name: Option<String> = None;
 
     for attr in &input.attrs {
         if attr.path().is_ident("closure_tree") {
-            parse_closure_tree_attr(attr, &mut options)?;
+            match attr_tag(attr)? {
+                Some(tag) => {
+                    let index = match tagged.iter().position(|(t, _)| t.value() == tag.value()) {
+                        Some(index) => index,
+                        None => {
+                            tagged.push((tag, Options::default()));
+                            tagged.len() - 1
+                        }
+                    };
+                    parse_closure_tree_attr(attr, &mut tagged[index].1)?;
+                }
+                None => {
+                    untagged = true;
+                    parse_closure_tree_attr(attr, &mut options)?;
+                }
+            }
         }
 
         if attr.path().is_ident("sea_orm") {
-            if let Some(name) = parse_sea_orm_table_name(attr)? {
-                table_name = Some(name);
+            let (name, schema) = parse_sea_orm_table_name(attr)?;
+            table_name = name.or(table_name);
+            sea_orm_schema_name = schema.or(sea_orm_schema_name);
+        }
+    }
+    if let Data::Struct(syn::DataStruct {
+        fields: Fields::Named(fields),
+        ..
+    }) = &input.data
+    {
+        parse_field_markers(fields, &mut options)?;
+        // Hierarchy rows hold a single id per node, so a composite primary key
+        // needs the column identifying tree nodes named explicitly.
+        if options.id_field.is_none() {
+            if let [_, second, ..] = primary_key_spans(fields)?.as_slice() {
+                return Err(syn::Error::new(
+                    *second,
+                    "ClosureTreeModel does not support composite primary keys as node ids; \
+                     mark a unique single-column key `#[closure_tree(id)]` or set `id_field`",
+                ));
+            }
+        }
+    }
+
+    apply_features(&mut options)?;
+    for (tag, tree) in &mut tagged {
+        apply_features(tree)?;
+        inherit_options(tree, &options, tag)?;
+    }
+    let mut generated = proc_macro2::TokenStream::new();
+    // A model with only tagged trees has no default one.
+    if untagged || tagged.is_empty() {
+        generated.extend(expand_tree(
+            struct_ident,
+            data_struct,
+            options,
+            None,
+            table_name.as_deref(),
+            sea_orm_schema_name.as_deref(),
+        )?);
+    }
+    for (tag, tree) in tagged {
+        generated.extend(expand_tree(
+            struct_ident,
+            data_struct,
+            tree,
+            Some(&tag),
+            table_name.as_deref(),
+            sea_orm_schema_name.as_deref(),
+        )?);
+    }
+    Ok(generated.into())
+}
+
+/// Check an explicit `features(...)` list against the configured fields: every
+/// listed capability needs its field and every field its capability, and a
+/// tree without `paths` is `no_name`.
+fn apply_features(options: &mut Options) -> syn::Result<()> {
+    let Some(features) = &options.features else {
+        return Ok(());
+    };
+    match (features.paths, &options.name_field, options.no_name) {
+        (None, Some(name_field), _) => {
+            return Err(syn::Error::new(
+                name_field.span(),
+                "the name field needs `paths` in `features`",
+            ))
+        }
+        (None, None, _) => options.no_name = Some(features.span),
+        (Some(span), _, Some(_)) => {
+            return Err(syn::Error::new(
+                span,
+                "`paths` in `features` conflicts with `no_name`",
+            ))
+        }
+        (Some(_), _, None) => {}
+    }
+    let pairs = [
+        (
+            features.ordering,
+            &options.order_field,
+            "ordering",
+            "order_field",
+        ),
+        (
+            features.counter_cache,
+            &options.children_count_field,
+            "counter_cache",
+            "children_count_field",
+        ),
+    ];
+    for (feature, field, name, key) in pairs {
+        match (feature, field) {
+            (Some(span), None) => {
+                return Err(syn::Error::new(
+                    span,
+                    format!("`{name}` in `features` requires `{key}`"),
+                ))
+            }
+            (None, Some(field)) => {
+                return Err(syn::Error::new(
+                    field.span(),
+                    format!("`{key}` needs `{name}` in `features`"),
+                ))
             }
+            _ => {}
         }
     }
+    Ok(())
+}
 
-    let id_field_name = options.id_field.unwrap_or_else(|| "id".to_string());
+/// Options a tagged tree takes from the default tree when it does not set
+/// them: the node id, the name and the SeaORM item paths, which describe the
+/// model rather than one hierarchy.
+fn inherit_options(tree: &mut Options, base: &Options, tag: &syn::LitStr) -> syn::Result<()> {
+    if tree.parent_field.is_none() {
+        return Err(syn::Error::new(
+            tag.span(),
+            format!("the `{}` tree needs its own `parent_field`", tag.value()),
+        ));
+    }
+    tree.id_field = tree.id_field.take().or_else(|| base.id_field.clone());
+    tree.id_type = tree.id_type.take().or_else(|| base.id_type.clone());
+    tree.id_generator = tree
+        .id_generator
+        .take()
+        .or_else(|| base.id_generator.clone());
+    tree.id_to_value_with = tree
+        .id_to_value_with
+        .take()
+        .or_else(|| base.id_to_value_with.clone());
+    tree.hierarchy_id_to_value_with = tree
+        .hierarchy_id_to_value_with
+        .take()
+        .or_else(|| base.hierarchy_id_to_value_with.clone());
+    tree.new_child_defaults = tree
+        .new_child_defaults
+        .take()
+        .or_else(|| base.new_child_defaults.clone());
+    if tree.name_field.is_none() && tree.no_name.is_none() {
+        tree.name_field = base.name_field.clone();
+        tree.no_name = base.no_name;
+    }
+    tree.entity_name = tree.entity_name.take().or_else(|| base.entity_name.clone());
+    tree.schema_name = tree.schema_name.take().or_else(|| base.schema_name.clone());
+    tree.entity = tree.entity.take().or_else(|| base.entity.clone());
+    tree.column_enum = tree.column_enum.take().or_else(|| base.column_enum.clone());
+    tree.active_model = tree
+        .active_model
+        .take()
+        .or_else(|| base.active_model.clone());
+    Ok(())
+}
+
+/// The impls of one tree: the default tree when `tag` is `None`, otherwise
+/// the tree selected by the generated `{Tag}Tree` marker type.
+fn expand_tree(
+    struct_ident: &Ident,
+    data_struct: &syn::DataStruct,
+    options: Options,
+    tag: Option<&syn::LitStr>,
+    table_name: Option<&str>,
+    sea_orm_schema_name: Option<&str>,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let id_field_name = options
+        .id_field
+        .as_ref()
+        .map_or_else(|| "id".to_string(), syn::LitStr::value);
     let parent_field_name = options
         .parent_field
-        .unwrap_or_else(|| "parent_id".to_string());
-    let name_field_name = options.name_field.unwrap_or_else(|| "name".to_string());
+        .as_ref()
+        .map_or_else(|| "parent_id".to_string(), syn::LitStr::value);
+    if let (Some(span), Some(name_field)) = (options.no_name, &options.name_field) {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`no_name` conflicts with the name field `{}`",
+                name_field.value()
+            ),
+        ));
+    }
+    let name_field_name = options
+        .name_field
+        .as_ref()
+        .map_or_else(|| "name".to_string(), syn::LitStr::value);
     let ancestor_field_name = options
         .ancestor_field
         .unwrap_or_else(|| "ancestor_id".to_string());
@@ -73,21 +322,81 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
         .generations_field
         .unwrap_or_else(|| "generations".to_string());
 
-    let id_field_ident = Ident::new(&id_field_name, struct_ident.span());
-    let parent_field_ident = Ident::new(&parent_field_name, struct_ident.span());
-    let name_field_ident = Ident::new(&name_field_name, struct_ident.span());
-    let ancestor_field_ident = Ident::new(&ancestor_field_name, struct_ident.span());
-    let descendant_field_ident = Ident::new(&descendant_field_name, struct_ident.span());
-    let generations_field_ident = Ident::new(&generations_field_name, struct_ident.span());
+    let id_field_ident = field_ident(&id_field_name, struct_ident.span());
+    let parent_field_ident = field_ident(&parent_field_name, struct_ident.span());
+    let name_field_ident = field_ident(&name_field_name, struct_ident.span());
+    let ancestor_field_ident = field_ident(&ancestor_field_name, struct_ident.span());
+    let descendant_field_ident = field_ident(&descendant_field_name, struct_ident.span());
+    let generations_field_ident = field_ident(&generations_field_name, struct_ident.span());
 
     let mut id_field_type: Option<Type> = options.id_type.clone();
+    let mut name_field_type: Option<Type> = None;
 
     if let Fields::Named(ref fields) = data_struct.fields {
         for field in &fields.named {
             if let Some(ident) = &field.ident {
-                if ident == &id_field_ident && id_field_type.is_none() {
+                if ident.unraw() == id_field_ident.unraw() && id_field_type.is_none() {
                     id_field_type = Some(field.ty.clone());
                 }
+                if ident.unraw() == name_field_ident.unraw() {
+                    name_field_type = Some(field.ty.clone());
+                }
+            }
+        }
+
+        // Report a misspelled field at the attribute rather than as an unknown
+        // `Column` variant somewhere in the generated impl.
+        let field_names: Vec<String> = fields
+            .named
+            .iter()
+            .filter_map(|field| field.ident.as_ref())
+            .map(|ident| ident.unraw().to_string())
+            .collect();
+        let missing = |name: &str| format!("`{struct_ident}` has no field named `{name}`");
+        let suggestion = |name: &str| closest_name(unraw(name), &field_names);
+        let field_attributes = [
+            &options.id_field,
+            &options.parent_field,
+            &options.name_field,
+            &options.order_field,
+            &options.touch_field,
+            &options.children_count_field,
+            &options.scope_field,
+            &options.soft_delete_field,
+        ];
+        for literal in field_attributes.into_iter().flatten() {
+            if !field_names
+                .iter()
+                .any(|field| field == unraw(&literal.value()))
+            {
+                let mut message = missing(&literal.value());
+                if let Some(field) = suggestion(&literal.value()) {
+                    message.push_str(&format!("; did you mean `{field}`?"));
+                }
+                return Err(syn::Error::new(literal.span(), message));
+            }
+        }
+        // Fields left at their default names have no attribute to point at.
+        let defaults = [
+            (options.id_field.is_none(), "id", "`#[closure_tree(id)]`"),
+            (
+                options.parent_field.is_none(),
+                "parent_id",
+                "`#[closure_tree(parent)]`",
+            ),
+            (
+                options.name_field.is_none() && options.no_name.is_none(),
+                "name",
+                "`#[closure_tree(name)]`, or mark the model `no_name`",
+            ),
+        ];
+        for (unset, name, marker) in defaults {
+            if unset && !field_names.iter().any(|field| field == name) {
+                let mut message = format!("{}; mark the field with {marker}", missing(name));
+                if let Some(field) = suggestion(name) {
+                    message.push_str(&format!(" (perhaps `{field}`)"));
+                }
+                return Err(syn::Error::new(struct_ident.span(), message));
             }
         }
     } else {
@@ -100,29 +409,88 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
     let id_type = id_field_type.ok_or_else(|| {
         syn::Error::new(
             struct_ident.span(),
-            "Unable to determine id field type; specify `id_type = ...` in #[closure_tree]",
+            "Unable to determine id field type; mark the id field `#[closure_tree(id)]` or specify `id_type = ...`",
         )
     })?;
 
-    let hierarchy_module_path = options
-        .hierarchy_module
-        .ok_or_else(|| syn::Error::new(struct_ident.span(), "`hierarchy_module` must be set"))?;
-
     let entity_name = options
         .entity_name
         .unwrap_or_else(|| struct_ident.unraw().to_string());
+    // `org` names the `OrgTree` marker and prefixes the tree's generated items.
+    let tag_name = tag.map_or_else(String::new, |tag| tag.value().to_upper_camel_case());
+    let tree = tag
+        .map(|tag| {
+            syn::parse_str::<Ident>(&format!("{tag_name}Tree"))
+                .map_err(|_| syn::Error::new(tag.span(), "the tag must be usable in an identifier"))
+        })
+        .transpose()?;
     let hierarchy_name = options.hierarchy_name.unwrap_or_else(|| {
-        if entity_name.ends_with("Hierarchy") {
+        if tag.is_none() && entity_name.ends_with("Hierarchy") {
             entity_name.clone()
         } else {
-            format!("{}Hierarchy", entity_name)
+            format!("{entity_name}{tag_name}Hierarchy")
         }
     });
 
-    let base_table = table_name.unwrap_or_else(|| struct_ident.unraw().to_string());
-    let hierarchy_table = options
-        .hierarchy_table
-        .unwrap_or_else(|| format!("{}_hierarchies", base_table));
+    let base_table = table_name.map_or_else(|| struct_ident.unraw().to_string(), str::to_string);
+    let hierarchy_table = options.hierarchy_table.unwrap_or_else(|| match tag {
+        Some(_) => format!("{base_table}_{}_hierarchies", to_snake_case(&tag_name)),
+        None => format!("{base_table}_hierarchies"),
+    });
+    let schema = options
+        .schema_name
+        .clone()
+        .or(sea_orm_schema_name.map(str::to_string));
+
+    let (hierarchy_module_path, hierarchy_entity) = match (
+        options.hierarchy_module,
+        options.generate_hierarchy_entity,
+    ) {
+        (Some(path), None) => (path, None),
+        (None, Some(_)) => {
+            let module = format_ident!("{}", to_snake_case(&hierarchy_name));
+            let schema_attr = schema.as_ref().map(|schema| {
+                let literal = syn::LitStr::new(schema, struct_ident.span());
+                quote! { , schema_name = #literal }
+            });
+            let table_literal = syn::LitStr::new(&hierarchy_table, struct_ident.span());
+            let entity = quote! {
+                /// Closure table rows generated by `generate_hierarchy_entity`.
+                pub mod #module {
+                    #[allow(unused_imports)]
+                    use super::*;
+
+                    #[derive(Clone, Debug, PartialEq, ::sea_orm::DeriveEntityModel)]
+                    #[sea_orm(table_name = #table_literal #schema_attr)]
+                    pub struct Model {
+                        #[sea_orm(primary_key, auto_increment = false)]
+                        pub #ancestor_field_ident: #id_type,
+                        #[sea_orm(primary_key, auto_increment = false)]
+                        pub #descendant_field_ident: #id_type,
+                        pub #generations_field_ident: i32,
+                    }
+
+                    #[derive(Copy, Clone, Debug, ::sea_orm::EnumIter, ::sea_orm::DeriveRelation)]
+                    pub enum Relation {}
+
+                    impl ::sea_orm::ActiveModelBehavior for ActiveModel {}
+                }
+            };
+            (Path::from(module), Some(entity))
+        }
+        (Some(_), Some(span)) => {
+            return Err(syn::Error::new(
+                span,
+                "`generate_hierarchy_entity` conflicts with `hierarchy_module`; keep one of them",
+            ))
+        }
+        (None, None) => {
+            return Err(syn::Error::new(
+                struct_ident.span(),
+                "`hierarchy_module` must be set, or `generate_hierarchy_entity` given",
+            ))
+        }
+    };
 
     let id_column_variant = format_ident!("{}", to_pascal_case(&id_field_name));
     let parent_column_variant = format_ident!("{}", to_pascal_case(&parent_field_name));
@@ -131,16 +499,355 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
     let descendant_column_variant = format_ident!("{}", to_pascal_case(&descendant_field_name));
     let generations_column_variant = format_ident!("{}", to_pascal_case(&generations_field_name));
 
-    let parent_column_literal = syn::LitStr::new(&parent_field_name, struct_ident.span());
-    let name_column_literal = syn::LitStr::new(&name_field_name, struct_ident.span());
+    // Configured column names are read from the `Column` variants, so a
+    // `#[sea_orm(column_name = "...")]` override gives the physical name.
+    let entity = options
+        .entity
+        .unwrap_or_else(|| Path::from(format_ident!("Entity")));
+    let column = options
+        .column_enum
+        .unwrap_or_else(|| Path::from(format_ident!("Column")));
+    let active_model = options
+        .active_model
+        .unwrap_or_else(|| Path::from(format_ident!("ActiveModel")));
+    let physical = |variant: &Ident| quote! { ::sea_orm::IdenStatic::as_str(&#column::#variant) };
+    let hierarchy_physical = |variant: &Ident| {
+        quote! { ::sea_orm::IdenStatic::as_str(&#hierarchy_module_path::Column::#variant) }
+    };
+    let parent_column_name = physical(&parent_column_variant);
+    let name_column_name = physical(&name_column_variant);
     let hierarchy_table_literal = syn::LitStr::new(&hierarchy_table, struct_ident.span());
+    let ancestor_column_name = hierarchy_physical(&ancestor_column_variant);
+    let descendant_column_name = hierarchy_physical(&descendant_column_variant);
+    let generations_column_name = hierarchy_physical(&generations_column_variant);
     let entity_name_literal = syn::LitStr::new(&entity_name, struct_ident.span());
     let hierarchy_name_literal = syn::LitStr::new(&hierarchy_name, struct_ident.span());
 
-    let generated = quote! {
-        impl ::closure_tree::ClosureTreeModel for #struct_ident {
-            type Entity = Entity;
-            type ActiveModel = ActiveModel;
+    let (order_strategy, order_methods) = match (&options.order_field, options.fractional_order) {
+        (None, Some(span)) => {
+            return Err(syn::Error::new(
+                span,
+                "`fractional_order` needs an `order_field`",
+            ))
+        }
+        (Some(order_column_literal), Some(_)) => {
+            let order_field_ident =
+                field_ident(&order_column_literal.value(), order_column_literal.span());
+            let order_column_variant =
+                format_ident!("{}", to_pascal_case(&order_column_literal.value()));
+            let order_column_name = physical(&order_column_variant);
+            (
+                quote! {
+                    .order_strategy(::closure_tree::OrderStrategy::fractional_column(
+                        #order_column_name,
+                    ))
+                },
+                quote! {
+                    fn order_column() -> ::core::option::Option<<Self::Entity as ::sea_orm::EntityTrait>::Column> {
+                        ::core::option::Option::Some(#column::#order_column_variant)
+                    }
+
+                    fn fractional_order_value(&self) -> ::core::option::Option<f64> {
+                        ::core::option::Option::Some(
+                            ::closure_tree::FractionalPosition::to_f64(&self.#order_field_ident),
+                        )
+                    }
+
+                    fn set_fractional_order_value(active: &mut Self::ActiveModel, value: f64) {
+                        active.#order_field_ident = ::sea_orm::ActiveValue::Set(
+                            ::closure_tree::FractionalPosition::from_f64(value),
+                        );
+                    }
+                },
+            )
+        }
+        (Some(order_column_literal), None) => {
+            let order_field_name = order_column_literal.value();
+            // Spanned at the attribute so a field of the wrong type is reported there.
+            let order_field_ident = field_ident(&order_field_name, order_column_literal.span());
+            let order_column_variant = format_ident!("{}", to_pascal_case(&order_field_name));
+            let order_column_name = physical(&order_column_variant);
+            (
+                quote! {
+                    .order_strategy(::closure_tree::OrderStrategy::numeric_column(
+                        #order_column_name,
+                    ))
+                },
+                quote! {
+                    fn order_column() -> ::core::option::Option<<Self::Entity as ::sea_orm::EntityTrait>::Column> {
+                        ::core::option::Option::Some(#column::#order_column_variant)
+                    }
+
+                    fn order_value(&self) -> ::core::option::Option<i64> {
+                        ::core::option::Option::Some(i64::from(self.#order_field_ident))
+                    }
+
+                    fn set_order_value(
+                        active: &mut Self::ActiveModel,
+                        value: i64,
+                    ) -> ::core::result::Result<(), ::closure_tree::ClosureTreeError> {
+                        active.#order_field_ident = ::sea_orm::ActiveValue::Set(
+                            ::core::convert::TryFrom::try_from(value).map_err(|_| {
+                                ::closure_tree::ClosureTreeError::OrderValueOutOfRange { value }
+                            })?,
+                        );
+                        ::core::result::Result::Ok(())
+                    }
+                },
+            )
+        }
+        (None, None) => (quote! {}, quote! {}),
+    };
+    let (touch, touch_method) = match &options.touch_field {
+        Some(touch_column_literal) => {
+            let touch_column_variant =
+                format_ident!("{}", to_pascal_case(&touch_column_literal.value()));
+            let touch_column_name = physical(&touch_column_variant);
+            (
+                quote! { .touch(#touch_column_name) },
+                quote! {
+                    fn touch_column() -> ::core::option::Option<<Self::Entity as ::sea_orm::EntityTrait>::Column> {
+                        ::core::option::Option::Some(#column::#touch_column_variant)
+                    }
+                },
+            )
+        }
+        None => (quote! {}, quote! {}),
+    };
+    let (scope, scope_method) = match &options.scope_field {
+        Some(scope_column_literal) => {
+            let scope_field_name = scope_column_literal.value();
+            let scope_column_variant = format_ident!("{}", to_pascal_case(&scope_field_name));
+            let scope_field_ident = field_ident(&scope_field_name, scope_column_literal.span());
+            let scope_column_name = physical(&scope_column_variant);
+            (
+                quote! { .scope_column(#scope_column_name) },
+                quote! {
+                    fn scope_column() -> ::core::option::Option<<Self::Entity as ::sea_orm::EntityTrait>::Column> {
+                        ::core::option::Option::Some(#column::#scope_column_variant)
+                    }
+
+                    fn scope_value(&self) -> ::core::option::Option<::sea_orm::Value> {
+                        ::core::option::Option::Some(::sea_orm::Value::from(self.#scope_field_ident.clone()))
+                    }
+                },
+            )
+        }
+        None => (quote! {}, quote! {}),
+    };
+    let children_count = options.children_count_field.as_ref().map(|literal| {
+        let column = physical(&format_ident!("{}", to_pascal_case(&literal.value())));
+        quote! { .children_count_column(#column) }
+    });
+    let soft_delete = options.soft_delete_field.as_ref().map(|literal| {
+        let column = physical(&format_ident!("{}", to_pascal_case(&literal.value())));
+        quote! { .soft_delete_column(#column) }
+    });
+    let dont_order_roots = options
+        .dont_order_roots
+        .then(|| quote! { .dont_order_roots(true) });
+    let unique_sibling_names = options
+        .unique_sibling_names
+        .then(|| quote! { .unique_sibling_names(true) });
+    let path_separator = options.path_separator.as_ref().map(|separator| {
+        let literal = syn::LitStr::new(separator, struct_ident.span());
+        quote! { .path_separator(#literal) }
+    });
+    let max_depth = options
+        .max_depth
+        .as_ref()
+        .map(|limit| quote! { .max_depth(#limit) });
+    let hierarchy_conflict_columns = options
+        .hierarchy_conflict_columns
+        .as_ref()
+        .map(|columns| quote! { .hierarchy_conflict_columns([#(#columns),*]) });
+    let schema_name = schema.map(|schema| {
+        let literal = syn::LitStr::new(&schema, struct_ident.span());
+        quote! { .schema_name(#literal) }
+    });
+    let dependent = &options.dependent;
+    let root_marker = &options.root_marker;
+    let advisory_lock = match (&options.advisory_lock_disabled, &options.advisory_lock_key) {
+        (Some(disabled), Some(_)) => {
+            return Err(syn::Error::new(
+                disabled.span(),
+                "`advisory_lock = \"disabled\"` cannot be combined with `advisory_lock_key`",
+            ))
+        }
+        (Some(_), None) => Some(quote! {
+            .advisory_lock_strategy(::closure_tree::AdvisoryLockStrategy::Disabled)
+        }),
+        (None, Some(key)) => Some(quote! {
+            .advisory_lock_strategy(::closure_tree::AdvisoryLockStrategy::Namespaced(
+                ::closure_tree::AdvisoryLockKey::new(#key),
+            ))
+        }),
+        (None, None) => None,
+    };
+    let max_children = options
+        .max_children
+        .as_ref()
+        .map(|limit| quote! { .max_children(#limit) });
+
+    let generate_id = options.id_generator.as_ref().map(|generator| {
+        quote! {
+            fn generate_id() -> Option<Self::Id> {
+                Some(#generator())
+            }
+        }
+    });
+    // `Into` rather than `Value::from`, so newtypes implementing only `Into<Value>`
+    // work; ids with neither go through the named conversion. The hierarchy
+    // columns use the id's conversion unless they have their own.
+    let id_to_value = options.id_to_value_with.as_ref().map_or_else(
+        || quote! { ::core::convert::Into::into(id.clone()) },
+        |convert| quote! { #convert(id) },
+    );
+    let hierarchy_id_to_value = options
+        .hierarchy_id_to_value_with
+        .as_ref()
+        .map(|convert| quote! { #convert(id) })
+        .unwrap_or_else(|| id_to_value.clone());
+    let new_child_defaults = options.new_child_defaults.as_ref().map(|defaults| {
+        quote! {
+            fn new_child_defaults(active: &mut Self::ActiveModel, parent: Option<&Self>) {
+                #defaults(active, parent)
+            }
+        }
+    });
+
+    let name_type =
+        name_field_type.map_or_else(|| quote! { ::std::string::String }, |ty| quote! { #ty });
+    let tree_arg = tree.as_ref().map(|tree| quote! { <#tree> });
+    let (name_column, name_column_body, named_impl) = if options.no_name.is_some() {
+        (quote! { .no_name_column(true) }, quote! { None }, None)
+    } else {
+        (
+            quote! { .name_column(#name_column_name) },
+            quote! { Some(#column::#name_column_variant) },
+            Some(quote! {
+                impl ::closure_tree::NamedClosureTreeModel #tree_arg for #struct_ident {
+                    type Name = #name_type;
+
+                    fn name(&self) -> &Self::Name {
+                        &self.#name_field_ident
+                    }
+
+                    fn set_name(active: &mut Self::ActiveModel, name: Self::Name) {
+                        active.#name_field_ident = ::sea_orm::ActiveValue::Set(name);
+                    }
+                }
+            }),
+        )
+    };
+
+    let has_ordering = options.order_field.as_ref().map(|_| {
+        quote! { impl ::closure_tree::HasOrdering #tree_arg for #struct_ident {} }
+    });
+    let has_counter_cache = options.children_count_field.as_ref().map(|_| {
+        quote! { impl ::closure_tree::HasCounterCache #tree_arg for #struct_ident {} }
+    });
+
+    let extension_trait = match &options.extension_trait {
+        Some(name) => {
+            let trait_ident = match name {
+                Some(literal) => literal.parse::<Ident>()?,
+                None => format_ident!("{entity_name}{tag_name}TreeExt"),
+            };
+            Some(extension_trait(struct_ident, &trait_ident, tree.as_ref()))
+        }
+        None => None,
+    };
+
+    let hierarchy_relations = options.hierarchy_relations.then(|| {
+        let hierarchy_entity = quote! { #hierarchy_module_path::Entity };
+        let id = quote! { #column::#id_column_variant };
+        let ancestor = quote! { #hierarchy_module_path::Column::#ancestor_column_variant };
+        let descendant = quote! { #hierarchy_module_path::Column::#descendant_column_variant };
+        // `find_linked` joins the links in reverse, so the hierarchy row is `r0`.
+        let link = |to_hierarchy: &proc_macro2::TokenStream, to_node: &proc_macro2::TokenStream| {
+            quote! {
+                ::std::vec![
+                    <#entity as ::sea_orm::EntityTrait>::belongs_to(#hierarchy_entity)
+                        .from(#id)
+                        .to(#to_hierarchy)
+                        .into(),
+                    <#hierarchy_entity as ::sea_orm::EntityTrait>::belongs_to(#entity)
+                        .from(#to_node)
+                        .to(#id)
+                        .into(),
+                ]
+            }
+        };
+        let ancestors = link(&descendant, &ancestor);
+        let descendants = link(&ancestor, &descendant);
+        let ancestors_link = format_ident!("{tag_name}AncestorsLink");
+        let descendants_link = format_ident!("{tag_name}DescendantsLink");
+        let descendants_doc = format!(
+            "`find_linked` from a node to its descendants, like [`{ancestors_link}`] in the \
+             other direction."
+        );
+        quote! {
+            /// Hierarchy rows whose ancestor is the node: one per descendant,
+            /// and the node's own row at generation `0`.
+            impl ::sea_orm::Related<#hierarchy_entity> for #entity {
+                fn to() -> ::sea_orm::RelationDef {
+                    <#entity as ::sea_orm::EntityTrait>::belongs_to(#hierarchy_entity)
+                        .from(#id)
+                        .to(#ancestor)
+                        .into()
+                }
+            }
+
+            /// `find_linked` from a node to its ancestors through the hierarchy
+            /// table, joined as `r0`. The node itself is included at generation
+            /// `0`; filter on `r0`'s generations column to narrow the range.
+            #[derive(Copy, Clone, Debug)]
+            pub struct #ancestors_link;
+
+            impl ::sea_orm::Linked for #ancestors_link {
+                type FromEntity = #entity;
+                type ToEntity = #entity;
+
+                fn link(&self) -> ::std::vec::Vec<::sea_orm::LinkDef> {
+                    #ancestors
+                }
+            }
+
+            #[doc = #descendants_doc]
+            #[derive(Copy, Clone, Debug)]
+            pub struct #descendants_link;
+
+            impl ::sea_orm::Linked for #descendants_link {
+                type FromEntity = #entity;
+                type ToEntity = #entity;
+
+                fn link(&self) -> ::std::vec::Vec<::sea_orm::LinkDef> {
+                    #descendants
+                }
+            }
+        }
+    });
+
+    let marker = tree.as_ref().zip(tag).map(|(tree, tag)| {
+        let doc = format!(
+            "The `{}` tree of [`{struct_ident}`], selected by \
+             `ClosureTreeRepository<{struct_ident}, {tree}>`.",
+            tag.value()
+        );
+        quote! {
+            #[doc = #doc]
+            #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+            pub struct #tree;
+        }
+    });
+
+    Ok(quote! {
+        #marker
+
+        impl ::closure_tree::ClosureTreeModel #tree_arg for #struct_ident {
+            type Entity = #entity;
+            type ActiveModel = #active_model;
             type Id = #id_type;
 
             type HierarchyEntity = #hierarchy_module_path::Entity;
@@ -155,9 +862,27 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
                             #hierarchy_name_literal,
                         );
                         ::closure_tree::ClosureTreeOptions::default()
-                            .parent_column(#parent_column_literal)
-                            .name_column(#name_column_literal)
+                            .parent_column(#parent_column_name)
+                            #name_column
                             .hierarchy_table(#hierarchy_table_literal)
+                            #schema_name
+                            .hierarchy_ancestor_column(#ancestor_column_name)
+                            .hierarchy_descendant_column(#descendant_column_name)
+                            .hierarchy_generations_column(#generations_column_name)
+                            #hierarchy_conflict_columns
+                            #order_strategy
+                            #touch
+                            #children_count
+                            #scope
+                            #soft_delete
+                            #dont_order_roots
+                            #unique_sibling_names
+                            #path_separator
+                            #max_depth
+                            #max_children
+                            #dependent
+                            #root_marker
+                            #advisory_lock
                             .apply(base)
                     });
                 &CONFIG
@@ -176,29 +901,30 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
             }
 
             fn id_to_value(id: &Self::Id) -> ::sea_orm::Value {
-                ::sea_orm::Value::from(id.clone())
-            }
-
-            fn name(&self) -> &str {
-                self.#name_field_ident.as_str()
+                #id_to_value
             }
 
-            fn set_name(active: &mut Self::ActiveModel, name: &str) {
-                active.#name_field_ident = ::sea_orm::ActiveValue::Set(name.to_owned());
-            }
+            #generate_id
+            #new_child_defaults
 
             fn parent_column() -> <Self::Entity as ::sea_orm::EntityTrait>::Column {
-                Column::#parent_column_variant
+                #column::#parent_column_variant
             }
 
             fn id_column() -> <Self::Entity as ::sea_orm::EntityTrait>::Column {
-                Column::#id_column_variant
+                #column::#id_column_variant
             }
 
-            fn name_column() -> <Self::Entity as ::sea_orm::EntityTrait>::Column {
-                Column::#name_column_variant
+            fn name_column() -> Option<<Self::Entity as ::sea_orm::EntityTrait>::Column> {
+                #name_column_body
             }
 
+            #order_methods
+
+            #touch_method
+
+            #scope_method
+
             fn hierarchy_ancestor_column() -> <Self::HierarchyEntity as ::sea_orm::EntityTrait>::Column {
                 #hierarchy_module_path::Column::#ancestor_column_variant
             }
@@ -212,7 +938,7 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
             }
 
             fn hierarchy_id_to_value(id: &Self::Id) -> ::sea_orm::Value {
-                ::sea_orm::Value::from(id.clone())
+                #hierarchy_id_to_value
             }
 
             fn hierarchy_model_ancestor(model: &Self::HierarchyModel) -> Self::Id {
@@ -243,9 +969,130 @@ fn impl_closure_tree_model(input: &DeriveInput) -> syn::Result<TokenStream> {
                 }
             }
         }
+
+        #named_impl
+
+        #has_ordering
+
+        #has_counter_cache
+
+        #extension_trait
+
+        #hierarchy_relations
+
+        #hierarchy_entity
+    })
+}
+
+/// The `extension_trait` option: a trait named `trait_ident` with the common
+/// repository calls as methods of the model, each on a default repository of
+/// the tree `tree` selects.
+fn extension_trait(
+    struct_ident: &Ident,
+    trait_ident: &Ident,
+    tree: Option<&Ident>,
+) -> proc_macro2::TokenStream {
+    let model = match tree {
+        Some(tree) => quote! { ::closure_tree::ClosureTreeModel<#tree> },
+        None => quote! { ::closure_tree::ClosureTreeModel },
+    };
+    let repository = match tree {
+        Some(tree) => quote! { ::closure_tree::ClosureTreeRepository::<Self, #tree> },
+        None => quote! { ::closure_tree::ClosureTreeRepository::<Self> },
     };
+    let doc = format!(
+        "Tree methods on [`{struct_ident}`], generated by `extension_trait`. Each runs \
+         the [`ClosureTreeRepository`](::closure_tree::ClosureTreeRepository) method \
+         without the `tree_` prefix on a default repository."
+    );
+    quote! {
+        #[doc = #doc]
+        #[::closure_tree::async_trait]
+        pub trait #trait_ident: #model {
+            async fn tree_parent(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::core::option::Option<Self>, ::closure_tree::ClosureTreeError>;
 
-    Ok(generated.into())
+            async fn tree_children(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError>;
+
+            async fn tree_siblings(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError>;
+
+            async fn tree_ancestors(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError>;
+
+            async fn tree_descendants(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError>;
+
+            /// Move this node, with its subtree, beneath `parent` (`graft`).
+            /// `self` keeps its old parent; reload it to see the move.
+            async fn tree_move_to_child_of(
+                &self,
+                conn: &::sea_orm::DatabaseConnection,
+                parent: &Self,
+            ) -> ::core::result::Result<(), ::closure_tree::ClosureTreeError>;
+        }
+
+        #[::closure_tree::async_trait]
+        impl #trait_ident for #struct_ident {
+            async fn tree_parent(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::core::option::Option<Self>, ::closure_tree::ClosureTreeError> {
+                #repository::new().parent(conn, self).await
+            }
+
+            async fn tree_children(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError> {
+                #repository::new().children(conn, self).await
+            }
+
+            async fn tree_siblings(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError> {
+                #repository::new().siblings(conn, self).await
+            }
+
+            async fn tree_ancestors(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError> {
+                #repository::new().ancestors(conn, self).await
+            }
+
+            async fn tree_descendants(
+                &self,
+                conn: &impl ::sea_orm::ConnectionTrait,
+            ) -> ::core::result::Result<::std::vec::Vec<Self>, ::closure_tree::ClosureTreeError> {
+                #repository::new().descendants(conn, self).await
+            }
+
+            async fn tree_move_to_child_of(
+                &self,
+                conn: &::sea_orm::DatabaseConnection,
+                parent: &Self,
+            ) -> ::core::result::Result<(), ::closure_tree::ClosureTreeError> {
+                let node = <Self as #model>::id(self);
+                let parent = <Self as #model>::id(parent);
+                #repository::new()
+                    .graft(conn, &node, &parent)
+                    .await
+            }
+        }
+    }
 }
 
 fn parse_closure_tree_attr(attr: &Attribute, options: &mut Options) -> syn::Result<()> {
@@ -258,16 +1105,169 @@ fn parse_closure_tree_attr(attr: &Attribute, options: &mut Options) -> syn::Resu
 
         match ident.as_str() {
             "id_field" => {
-                let value: syn::LitStr = meta.value()?.parse()?;
-                options.id_field = Some(value.value());
+                options.id_field = Some(meta.value()?.parse()?);
             }
             "parent_field" => {
-                let value: syn::LitStr = meta.value()?.parse()?;
-                options.parent_field = Some(value.value());
+                options.parent_field = Some(meta.value()?.parse()?);
             }
             "name_field" => {
+                options.name_field = Some(meta.value()?.parse()?);
+            }
+            "order_field" => {
+                options.order_field = Some(meta.value()?.parse()?);
+            }
+            "touch_field" => {
+                options.touch_field = Some(meta.value()?.parse()?);
+            }
+            "children_count_field" => {
+                options.children_count_field = Some(meta.value()?.parse()?);
+            }
+            "scope_field" => {
+                options.scope_field = Some(meta.value()?.parse()?);
+            }
+            "soft_delete_field" => {
+                options.soft_delete_field = Some(meta.value()?.parse()?);
+            }
+            "dont_order_roots" => {
+                options.dont_order_roots = true;
+            }
+            "unique_sibling_names" => {
+                options.unique_sibling_names = true;
+            }
+            "fractional_order" => {
+                options.fractional_order = Some(meta.path.span());
+            }
+            "generate_hierarchy_entity" => {
+                options.generate_hierarchy_entity = Some(meta.path.span());
+            }
+            "no_name" => {
+                options.no_name = Some(meta.path.span());
+            }
+            // Read by `attr_tag` before the attribute is parsed.
+            "tag" => {
+                meta.value()?.parse::<syn::LitStr>()?;
+            }
+            "hierarchy_relations" => {
+                options.hierarchy_relations = true;
+            }
+            "features" => {
+                let mut features = Features {
+                    span: meta.path.span(),
+                    paths: None,
+                    ordering: None,
+                    counter_cache: None,
+                };
+                meta.parse_nested_meta(|feature| {
+                    let slot = if feature.path.is_ident("paths") {
+                        &mut features.paths
+                    } else if feature.path.is_ident("ordering") {
+                        &mut features.ordering
+                    } else if feature.path.is_ident("counter_cache") {
+                        &mut features.counter_cache
+                    } else {
+                        return Err(syn::Error::new(
+                            feature.path.span(),
+                            "unknown feature; expected one of `paths`, `ordering` or \
+                             `counter_cache`",
+                        ));
+                    };
+                    *slot = Some(feature.path.span());
+                    Ok(())
+                })?;
+                options.features = Some(features);
+            }
+            "extension_trait" => {
+                let name = if meta.input.peek(syn::Token![=]) {
+                    Some(meta.value()?.parse()?)
+                } else {
+                    None
+                };
+                options.extension_trait = Some(name);
+            }
+            "path_separator" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.path_separator = Some(value.value());
+            }
+            "dependent" => {
                 let value: syn::LitStr = meta.value()?.parse()?;
-                options.name_field = Some(value.value());
+                let variant = match value.value().as_str() {
+                    "nullify" => quote! { Nullify },
+                    "destroy" => quote! { Destroy },
+                    "delete_all" => quote! { DeleteAll },
+                    "none" => quote! { None },
+                    other => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            format!(
+                                "unknown dependent behavior `{other}`; expected one of \
+                                 `nullify`, `destroy`, `delete_all` or `none`"
+                            ),
+                        ))
+                    }
+                };
+                options.dependent = Some(quote! {
+                    .dependent_behavior(::closure_tree::DependentBehavior::#variant)
+                });
+            }
+            "root_marker" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                let variant = match value.value().as_str() {
+                    "null" => quote! { Null },
+                    "zero" => quote! { Zero },
+                    "self" => quote! { SelfReference },
+                    other => {
+                        return Err(syn::Error::new(
+                            value.span(),
+                            format!(
+                                "unknown root marker `{other}`; expected one of \
+                                 `null`, `zero` or `self`"
+                            ),
+                        ))
+                    }
+                };
+                options.root_marker = Some(quote! {
+                    .root_marker(::closure_tree::RootMarker::#variant)
+                });
+            }
+            "advisory_lock" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                if value.value() != "disabled" {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        format!(
+                            "unknown advisory_lock setting `{}`; expected `disabled` \
+                             (use `advisory_lock_key` to name the lock)",
+                            value.value()
+                        ),
+                    ));
+                }
+                options.advisory_lock_disabled = Some(value);
+            }
+            "advisory_lock_key" => {
+                options.advisory_lock_key = Some(meta.value()?.parse()?);
+            }
+            "max_depth" => {
+                options.max_depth = Some(meta.value()?.parse()?);
+            }
+            "max_children" => {
+                options.max_children = Some(meta.value()?.parse()?);
+            }
+            "id_generator" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.id_generator = Some(parse_path(&value.value(), value.span())?);
+            }
+            "id_to_value_with" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.id_to_value_with = Some(parse_path(&value.value(), value.span())?);
+            }
+            "hierarchy_id_to_value_with" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.hierarchy_id_to_value_with =
+                    Some(parse_path(&value.value(), value.span())?);
+            }
+            "new_child_defaults" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.new_child_defaults = Some(parse_path(&value.value(), value.span())?);
             }
             "hierarchy_module" => {
                 let value: syn::LitStr = meta.value()?.parse()?;
@@ -277,6 +1277,17 @@ fn parse_closure_tree_attr(attr: &Attribute, options: &mut Options) -> syn::Resu
                 let value: syn::LitStr = meta.value()?.parse()?;
                 options.hierarchy_table = Some(value.value());
             }
+            "hierarchy_conflict_columns" => {
+                let content;
+                syn::parenthesized!(content in meta.input);
+                let columns = content
+                    .parse_terminated(<syn::LitStr as syn::parse::Parse>::parse, syn::Token![,])?;
+                options.hierarchy_conflict_columns = Some(columns.into_iter().collect());
+            }
+            "schema_name" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.schema_name = Some(value.value());
+            }
             "entity_name" => {
                 let value: syn::LitStr = meta.value()?.parse()?;
                 options.entity_name = Some(value.value());
@@ -297,6 +1308,18 @@ fn parse_closure_tree_attr(attr: &Attribute, options: &mut Options) -> syn::Resu
                 let value: syn::LitStr = meta.value()?.parse()?;
                 options.generations_field = Some(value.value());
             }
+            "entity" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.entity = Some(parse_path(&value.value(), value.span())?);
+            }
+            "column_enum" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.column_enum = Some(parse_path(&value.value(), value.span())?);
+            }
+            "active_model" => {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                options.active_model = Some(parse_path(&value.value(), value.span())?);
+            }
             "id_type" => {
                 let ty: Type = meta.value()?.parse()?;
                 options.id_type = Some(ty);
@@ -313,32 +1336,208 @@ fn parse_closure_tree_attr(attr: &Attribute, options: &mut Options) -> syn::Resu
     })
 }
 
-fn parse_sea_orm_table_name(attr: &Attribute) -> syn::Result<Option<String>> {
+/// The `tag` of a struct-level `#[closure_tree(...)]` attribute, naming the
+/// tree the attribute configures.
+fn attr_tag(attr: &Attribute) -> syn::Result<Option<syn::LitStr>> {
+    let mut tag = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("tag") {
+            tag = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("id_type") {
+            meta.value()?.parse::<Type>()?;
+        } else if meta.input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in meta.input);
+            content.parse::<proc_macro2::TokenStream>()?;
+        } else if meta.input.peek(syn::Token![=]) {
+            meta.value()?.parse::<syn::Expr>()?;
+        }
+        Ok(())
+    })?;
+    Ok(tag)
+}
+
+/// Apply `#[closure_tree(id)]`-style markers on fields, which name the field
+/// carrying each role in place of the struct-level `*_field` strings.
+fn parse_field_markers(fields: &syn::FieldsNamed, options: &mut Options) -> syn::Result<()> {
+    for field in &fields.named {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        let name = ident.unraw().to_string();
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("closure_tree"))
+        {
+            attr.parse_nested_meta(|meta| {
+                let role = meta
+                    .path
+                    .get_ident()
+                    .map(ToString::to_string)
+                    .unwrap_or_default();
+                let marked = |key: &str, existing: &str| {
+                    syn::Error::new(
+                        meta.path.span(),
+                        format!(
+                            "`{name}` is marked `#[closure_tree({role})]` but `{key}` already \
+                             names `{existing}`"
+                        ),
+                    )
+                };
+                let (key, slot) = match role.as_str() {
+                    "id" => ("id_field", &mut options.id_field),
+                    "parent" => ("parent_field", &mut options.parent_field),
+                    "name" => ("name_field", &mut options.name_field),
+                    "order" => ("order_field", &mut options.order_field),
+                    "touch" => ("touch_field", &mut options.touch_field),
+                    "children_count" => ("children_count_field", &mut options.children_count_field),
+                    "scope" => ("scope_field", &mut options.scope_field),
+                    "soft_delete" => ("soft_delete_field", &mut options.soft_delete_field),
+                    _ => {
+                        return Err(syn::Error::new(
+                            meta.path.span(),
+                            "Unsupported closure_tree field marker; expected one of `id`, \
+                             `parent`, `name`, `order`, `touch`, `children_count`, \
+                             `scope` or `soft_delete`",
+                        ))
+                    }
+                };
+                match slot {
+                    Some(existing) if unraw(&existing.value()) != name => {
+                        Err(marked(key, &existing.value()))
+                    }
+                    _ => {
+                        *slot = Some(syn::LitStr::new(&name, ident.span()));
+                        Ok(())
+                    }
+                }
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Spans of the `primary_key` flags in the fields' `#[sea_orm(...)]` attributes.
+fn primary_key_spans(fields: &syn::FieldsNamed) -> syn::Result<Vec<proc_macro2::Span>> {
+    let mut spans = Vec::new();
+    for attr in fields
+        .named
+        .iter()
+        .flat_map(|field| &field.attrs)
+        .filter(|attr| attr.path().is_ident("sea_orm"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("primary_key") {
+                spans.push(meta.path.span());
+            } else if meta.input.peek(syn::Token![=]) {
+                meta.value()?.parse::<syn::Expr>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(spans)
+}
+
+/// `table_name` and `schema_name` from a `#[sea_orm(...)]` attribute.
+fn parse_sea_orm_table_name(attr: &Attribute) -> syn::Result<(Option<String>, Option<String>)> {
     let mut table_name: Option<String> = None;
+    let mut schema_name: Option<String> = None;
     attr.parse_nested_meta(|meta| {
         if meta.path.is_ident("table_name") {
             let value: syn::LitStr = meta.value()?.parse()?;
             table_name = Some(value.value());
+        } else if meta.path.is_ident("schema_name") {
+            let value: syn::LitStr = meta.value()?.parse()?;
+            schema_name = Some(value.value());
+        } else if meta.input.peek(syn::Token![=]) {
+            // Other sea_orm options are not ours to check; skip their values.
+            meta.value()?.parse::<syn::Expr>()?;
         }
         Ok(())
     })?;
-    Ok(table_name)
+    Ok((table_name, schema_name))
 }
 
 fn parse_path(value: &str, span: proc_macro2::Span) -> syn::Result<Path> {
     syn::parse_str::<Path>(value).map_err(|_| syn::Error::new(span, "Invalid path"))
 }
 
+/// The identifier of the field named `name`, raw (`r#type`) for keywords
+/// whether or not the name was written with the `r#` prefix.
+fn field_ident(name: &str, span: proc_macro2::Span) -> Ident {
+    let name = unraw(name);
+    if syn::parse_str::<Ident>(name).is_ok() {
+        Ident::new(name, span)
+    } else {
+        Ident::new_raw(name, span)
+    }
+}
+
+fn unraw(name: &str) -> &str {
+    name.strip_prefix("r#").unwrap_or(name)
+}
+
+/// The field name nearest to a misspelled `name`, if one is close enough to
+/// be what was meant.
+fn closest_name<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Levenshtein distance, ignoring ASCII case.
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    for (i, l) in left.chars().map(|c| c.to_ascii_lowercase()).enumerate() {
+        let mut current = vec![i + 1];
+        for (j, r) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(l != *r);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
+
+/// `NodeHierarchy` as `node_hierarchy`, keeping acronyms whole (`HTTPNode` is
+/// `http_node`).
+fn to_snake_case(value: &str) -> String {
+    value.to_snake_case()
+}
+
+/// The `Column` variant `DeriveEntityModel` generates for a field: heck's
+/// `UpperCamelCase` of the unraw name, as in sea-orm-macros, so `parentID` is
+/// `ParentId`.
 fn to_pascal_case(value: &str) -> String {
-    value
-        .split('_')
-        .filter(|segment| !segment.is_empty())
-        .map(|segment| {
-            let mut chars = segment.chars();
-            match chars.next() {
-                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
-                None => String::new(),
-            }
-        })
-        .collect()
+    unraw(value).to_upper_camel_case()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_pascal_case, to_snake_case};
+
+    #[test]
+    fn pascal_case_matches_the_column_variants_of_sea_orm() {
+        assert_eq!(to_pascal_case("parent_id"), "ParentId");
+        assert_eq!(to_pascal_case("parent_id2"), "ParentId2");
+        assert_eq!(to_pascal_case("parent__id"), "ParentId");
+        assert_eq!(to_pascal_case("_parent_id_"), "ParentId");
+        assert_eq!(to_pascal_case("uuid_v7"), "UuidV7");
+        assert_eq!(to_pascal_case("parentID"), "ParentId");
+        assert_eq!(to_pascal_case("r#type"), "Type");
+        assert_eq!(to_pascal_case("r#parent_ref"), "ParentRef");
+    }
+
+    #[test]
+    fn snake_case_keeps_acronyms_whole() {
+        assert_eq!(to_snake_case("NodeHierarchy"), "node_hierarchy");
+        assert_eq!(to_snake_case("HTTPNode"), "http_node");
+        assert_eq!(to_snake_case("Node2Hierarchy"), "node2_hierarchy");
+        assert_eq!(to_snake_case("reporting"), "reporting");
+    }
 }
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use sea_orm::{ConnectionTrait, DbBackend, Statement};
+
+use crate::error::ClosureTreeError;
+
+/// How a backend serializes concurrent tree mutations.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum LockSupport {
+    /// Advisory locks held by the database server (`pg_advisory_xact_lock`).
+    Server,
+    /// No server-side advisory locks; emulated with a process-local mutex.
+    #[cfg(feature = "sqlite")]
+    ProcessLocal,
+    /// No way to take the lock on this backend yet.
+    Unsupported,
+}
+
+/// Backend-specific behaviour, selected from the connection's [`DbBackend`].
+///
+/// Portable queries are issued through SeaORM directly; only the capabilities
+/// that differ between databases are routed through here, so a backend can be
+/// supported piece by piece.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct BackendOps {
+    backend: DbBackend,
+}
+
+impl BackendOps {
+    pub(crate) fn of(conn: &impl ConnectionTrait) -> Self {
+        Self {
+            backend: conn.get_database_backend(),
+        }
+    }
+
+    pub(crate) fn lock_support(self) -> LockSupport {
+        match self.backend {
+            DbBackend::Postgres => LockSupport::Server,
+            #[cfg(feature = "sqlite")]
+            DbBackend::Sqlite => LockSupport::ProcessLocal,
+            _ => LockSupport::Unsupported,
+        }
+    }
+
+    /// Whether ancestors are loaded with a recursive CTE when the configuration
+    /// prefers it.
+    pub(crate) fn recursive_ancestors(self) -> bool {
+        self.backend == DbBackend::Postgres
+    }
+
+    /// Statement scoping a timeout to the current transaction, if the backend has one.
+    pub(crate) fn statement_timeout(self, timeout: Duration) -> Option<Statement> {
+        match self.backend {
+            DbBackend::Postgres => Some(Statement::from_string(
+                self.backend,
+                // `0` would disable the timeout altogether.
+                format!(
+                    "SET LOCAL statement_timeout = {}",
+                    timeout.as_millis().max(1)
+                ),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Error for an operation this backend cannot perform.
+    pub(crate) fn unsupported(self, operation: &'static str) -> ClosureTreeError {
+        ClosureTreeError::UnsupportedBackend {
+            backend: self.backend,
+            operation,
+        }
+    }
+}
@@ -1,17 +1,54 @@
-use crc32fast::Hasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use crc32fast::Hasher as Crc32;
+use sea_orm::sea_query::SimpleExpr;
+use sea_orm::Order;
+
+use crate::schema::SchemaFinding;
 
 /// Static configuration describing how a SeaORM model integrates with
 /// the closure-table hierarchy.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct ClosureTreeConfig {
     entity_name: String,
     hierarchy_name: String,
     parent_column: String,
-    name_column: String,
+    name_column: Option<String>,
     hierarchy_table: String,
+    schema_name: Option<String>,
+    hierarchy_ancestor_column: String,
+    hierarchy_descendant_column: String,
+    hierarchy_generations_column: String,
+    hierarchy_conflict_columns: Option<Vec<String>>,
+    ltree_column: Option<String>,
+    touch_column: Option<String>,
+    touch_ancestors: bool,
+    children_count_column: Option<String>,
+    scope_column: Option<String>,
+    soft_delete_column: Option<String>,
     dependent_behavior: DependentBehavior,
+    root_marker: RootMarker,
+    prefer_recursive_cte: bool,
     order_strategy: Option<OrderStrategy>,
+    order_gap: i64,
+    order_scope: OrderScope,
+    dont_order_roots: bool,
+    name_order_descending: bool,
+    unique_sibling_names: bool,
+    name_matching: NameMatching,
+    name_normalizer: Option<NameNormalizer>,
+    path_separator: String,
+    reject_blank_segments: bool,
+    trim_segments: bool,
+    max_depth: Option<u32>,
+    max_children: Option<u64>,
     advisory_lock_strategy: AdvisoryLockStrategy,
+    lock_acquisition: LockAcquisition,
+    lock_scope: AdvisoryLockScope,
+    statement_timeout: Option<Duration>,
+    cleanup_policy: CleanupPolicy,
 }
 
 impl ClosureTreeConfig {
@@ -29,11 +66,40 @@ impl ClosureTreeConfig {
             entity_name,
             hierarchy_name,
             parent_column: "parent_id".to_string(),
-            name_column: "name".to_string(),
+            name_column: Some("name".to_string()),
             hierarchy_table: String::new(),
+            schema_name: None,
+            hierarchy_ancestor_column: "ancestor_id".to_string(),
+            hierarchy_descendant_column: "descendant_id".to_string(),
+            hierarchy_generations_column: "generations".to_string(),
+            hierarchy_conflict_columns: None,
+            ltree_column: None,
+            touch_column: None,
+            touch_ancestors: true,
+            children_count_column: None,
+            scope_column: None,
+            soft_delete_column: None,
             dependent_behavior: DependentBehavior::default(),
+            root_marker: RootMarker::default(),
+            prefer_recursive_cte: false,
             order_strategy: None,
+            order_gap: 1,
+            order_scope: OrderScope::default(),
+            dont_order_roots: false,
+            name_order_descending: false,
+            unique_sibling_names: false,
+            name_matching: NameMatching::default(),
+            name_normalizer: None,
+            path_separator: "/".to_string(),
+            reject_blank_segments: true,
+            trim_segments: false,
+            max_depth: None,
+            max_children: None,
             advisory_lock_strategy: default_lock,
+            lock_acquisition: LockAcquisition::default(),
+            lock_scope: AdvisoryLockScope::default(),
+            statement_timeout: None,
+            cleanup_policy: CleanupPolicy::default(),
         }
     }
 
@@ -43,20 +109,110 @@ impl ClosureTreeConfig {
             self.parent_column = parent_column;
         }
         if let Some(name_column) = options.name_column {
-            self.name_column = name_column;
+            self.name_column = Some(name_column);
+        }
+        if options.no_name_column == Some(true) {
+            self.name_column = None;
         }
         if let Some(hierarchy_table) = options.hierarchy_table {
             self.hierarchy_table = hierarchy_table;
         }
+        if let Some(schema) = options.schema_name {
+            self.schema_name = Some(schema);
+        }
+        if let Some(column) = options.hierarchy_ancestor_column {
+            self.hierarchy_ancestor_column = column;
+        }
+        if let Some(column) = options.hierarchy_descendant_column {
+            self.hierarchy_descendant_column = column;
+        }
+        if let Some(column) = options.hierarchy_generations_column {
+            self.hierarchy_generations_column = column;
+        }
+        if let Some(columns) = options.hierarchy_conflict_columns {
+            self.hierarchy_conflict_columns = Some(columns);
+        }
+        if let Some(ltree_column) = options.ltree_column {
+            self.ltree_column = Some(ltree_column);
+        }
+        if let Some(touch_column) = options.touch_column {
+            self.touch_column = Some(touch_column);
+        }
+        if let Some(touch_ancestors) = options.touch_ancestors {
+            self.touch_ancestors = touch_ancestors;
+        }
+        if let Some(column) = options.children_count_column {
+            self.children_count_column = Some(column);
+        }
+        if let Some(column) = options.scope_column {
+            self.scope_column = Some(column);
+        }
+        if let Some(column) = options.soft_delete_column {
+            self.soft_delete_column = Some(column);
+        }
         if let Some(behavior) = options.dependent_behavior {
             self.dependent_behavior = behavior;
         }
+        if let Some(marker) = options.root_marker {
+            self.root_marker = marker;
+        }
+        if let Some(prefer) = options.prefer_recursive_cte {
+            self.prefer_recursive_cte = prefer;
+        }
         if let Some(order_strategy) = options.order_strategy {
             self.order_strategy = Some(order_strategy);
         }
+        if let Some(gap) = options.order_gap {
+            self.order_gap = gap;
+        }
+        if let Some(scope) = options.order_scope {
+            self.order_scope = scope;
+        }
+        if let Some(dont_order_roots) = options.dont_order_roots {
+            self.dont_order_roots = dont_order_roots;
+        }
+        if let Some(descending) = options.name_order_descending {
+            self.name_order_descending = descending;
+        }
+        if let Some(unique) = options.unique_sibling_names {
+            self.unique_sibling_names = unique;
+        }
+        if let Some(matching) = options.name_matching {
+            self.name_matching = matching;
+        }
+        if let Some(normalizer) = options.name_normalizer {
+            self.name_normalizer = Some(NameNormalizer(normalizer));
+        }
+        if let Some(separator) = options.path_separator {
+            self.path_separator = separator;
+        }
+        if let Some(reject) = options.reject_blank_segments {
+            self.reject_blank_segments = reject;
+        }
+        if let Some(trim) = options.trim_segments {
+            self.trim_segments = trim;
+        }
+        if let Some(max_depth) = options.max_depth {
+            self.max_depth = Some(max_depth);
+        }
+        if let Some(max_children) = options.max_children {
+            self.max_children = Some(max_children);
+        }
         if let Some(strategy) = options.advisory_lock_strategy {
             self.advisory_lock_strategy = strategy;
         }
+        if let Some(acquisition) = options.lock_acquisition {
+            self.lock_acquisition = acquisition;
+        }
+        if let Some(scope) = options.lock_scope {
+            self.lock_scope = scope;
+        }
+        if let Some(timeout) = options.statement_timeout {
+            self.statement_timeout = Some(timeout);
+        }
+        if let Some(policy) = options.cleanup_policy {
+            self.cleanup_policy = policy;
+        }
         self
     }
 
@@ -75,9 +231,10 @@ impl ClosureTreeConfig {
         &self.parent_column
     }
 
-    /// Column name storing the display name.
-    pub fn name_column(&self) -> &str {
-        &self.name_column
+    /// Column name storing the display name, or `None` for trees whose nodes
+    /// have no names (and so no paths).
+    pub fn name_column(&self) -> Option<&str> {
+        self.name_column.as_deref()
     }
 
     /// Table backing the hierarchy entity.
@@ -85,20 +242,342 @@ impl ClosureTreeConfig {
         &self.hierarchy_table
     }
 
+    /// PostgreSQL schema holding the hierarchy table, e.g. `trees`; `None` leaves
+    /// it to the `search_path`. The derive takes it from its `schema_name`
+    /// attribute or the entity's `#[sea_orm(schema_name)]`.
+    ///
+    /// Queries qualify tables with the SeaORM entities' own schema names; this
+    /// setting is checked against them by
+    /// [`check_schema`](crate::ClosureTreeRepository::check_schema).
+    pub fn schema_name(&self) -> Option<&str> {
+        self.schema_name.as_deref()
+    }
+
+    /// Hierarchy column referencing the ancestor node.
+    pub fn hierarchy_ancestor_column(&self) -> &str {
+        &self.hierarchy_ancestor_column
+    }
+
+    /// Hierarchy column referencing the descendant node.
+    pub fn hierarchy_descendant_column(&self) -> &str {
+        &self.hierarchy_descendant_column
+    }
+
+    /// Hierarchy column storing the distance between ancestor and descendant
+    /// (`0` for a node's row to itself).
+    pub fn hierarchy_generations_column(&self) -> &str {
+        &self.hierarchy_generations_column
+    }
+
+    /// Columns of a unique index on the hierarchy table, used as the
+    /// `ON CONFLICT` target when writing hierarchy rows.
+    ///
+    /// With the default `None` rows are inserted plainly and a duplicate link
+    /// is an error. Setting it, usually to the ancestor and descendant columns
+    /// of a table whose primary key is a surrogate `id`, makes rows already
+    /// present be skipped instead.
+    pub fn hierarchy_conflict_columns(&self) -> Option<&[String]> {
+        self.hierarchy_conflict_columns.as_deref()
+    }
+
+    /// `ltree` column kept in sync with the node's ancestry (PostgreSQL only).
+    ///
+    /// Each label is an ancestor id, root first, with `-` replaced by `_`.
+    pub fn ltree_column(&self) -> Option<&str> {
+        self.ltree_column.as_deref()
+    }
+
+    /// Timestamp column set to the current time, in the same transaction, on
+    /// nodes that are renamed, grafted or moved among their siblings, and on the
+    /// parent of nodes that are created or destroyed. Each operation issues one
+    /// or two extra `UPDATE`s.
+    ///
+    /// Under [`touch_ancestors`](Self::touch_ancestors) every ancestor of those
+    /// nodes is touched as well.
+    pub fn touch_column(&self) -> Option<&str> {
+        self.touch_column.as_deref()
+    }
+
+    /// Whether touches propagate to every ancestor (the default), so caches keyed
+    /// on an ancestor's timestamp see changes below it. When off, only renamed,
+    /// grafted and reordered nodes themselves are touched.
+    pub fn touch_ancestors(&self) -> bool {
+        self.touch_ancestors
+    }
+
+    /// Integer column caching each node's number of direct children, kept up to
+    /// date by every write. It should default to `0` for new rows. Soft-deleted
+    /// children are not counted.
+    pub fn children_count_column(&self) -> Option<&str> {
+        self.children_count_column.as_deref()
+    }
+
+    /// Column partitioning the table into independent forests, e.g. `tenant_id`.
+    /// A repository narrowed with
+    /// [`scoped`](crate::ClosureTreeRepository::scoped) only sees and creates
+    /// nodes with its scope value.
+    pub fn scope_column(&self) -> Option<&str> {
+        self.scope_column.as_deref()
+    }
+
+    /// Nullable timestamp column marking soft-deleted nodes, e.g. `deleted_at`.
+    ///
+    /// Reads skip rows where it is set unless the repository was built
+    /// [`with_deleted`](crate::ClosureTreeRepository::with_deleted); hierarchy
+    /// rows are kept so a subtree can be restored.
+    pub fn soft_delete_column(&self) -> Option<&str> {
+        self.soft_delete_column.as_deref()
+    }
+
     /// Dependent behavior when deleting nodes.
     pub fn dependent_behavior(&self) -> DependentBehavior {
         self.dependent_behavior
     }
 
+    /// What the parent column of a root holds.
+    pub fn root_marker(&self) -> RootMarker {
+        self.root_marker
+    }
+
+    /// Whether ancestor queries walk the parent column with a recursive CTE,
+    /// returning the models in one round trip instead of reading the hierarchy
+    /// table first. Only PostgreSQL takes this path; other backends keep the
+    /// two queries.
+    pub fn prefer_recursive_cte(&self) -> bool {
+        self.prefer_recursive_cte
+    }
+
     /// Ordering strategy to apply when returning descendants.
     pub fn order_strategy(&self) -> Option<&OrderStrategy> {
         self.order_strategy.as_ref()
     }
 
+    /// Step between the order values assigned to consecutive new siblings
+    /// under [`OrderStrategy::NumericColumn`].
+    pub fn order_gap(&self) -> i64 {
+        self.order_gap
+    }
+
+    /// Whether numeric positions are numbered per parent or across the tree,
+    /// which decides how moves renumber them.
+    pub fn order_scope(&self) -> OrderScope {
+        self.order_scope
+    }
+
+    /// Whether roots are left out of the numeric ordering: they are returned by
+    /// name, then id, and are not assigned order values.
+    pub fn dont_order_roots(&self) -> bool {
+        self.dont_order_roots
+    }
+
+    /// Whether the secondary ordering by name is descending.
+    pub fn name_order_descending(&self) -> bool {
+        self.name_order_descending
+    }
+
+    /// Whether writes check, inside their locked transaction, that no two
+    /// children of a parent (and no two roots) share a name.
+    pub fn unique_sibling_names(&self) -> bool {
+        self.unique_sibling_names
+    }
+
+    /// How path segments and sibling names are compared.
+    pub fn name_matching(&self) -> NameMatching {
+        self.name_matching
+    }
+
+    /// Function applied to every incoming name before it is compared or stored:
+    /// path segments, new nodes' names and renames. It should be idempotent,
+    /// since it is also applied to names read back from the table when they are
+    /// used as lookups.
+    pub fn name_normalizer(&self) -> Option<fn(&str) -> String> {
+        self.name_normalizer.map(|normalizer| normalizer.0)
+    }
+
+    /// Separator between names in the path strings of
+    /// [`find_by_path_str`](crate::ClosureTreeRepository::find_by_path_str) and
+    /// friends; `/` by default. A backslash escapes the next character, so names
+    /// may still contain the separator.
+    pub fn path_separator(&self) -> &str {
+        &self.path_separator
+    }
+
+    /// Whether path-creating writes fail with
+    /// [`ClosureTreeError::BlankSegment`](crate::ClosureTreeError::BlankSegment)
+    /// for an empty or whitespace-only segment instead of creating a node with
+    /// a blank name. On by default.
+    pub fn reject_blank_segments(&self) -> bool {
+        self.reject_blank_segments
+    }
+
+    /// Whether leading and trailing whitespace is trimmed from path segments
+    /// before they are looked up or created.
+    pub fn trim_segments(&self) -> bool {
+        self.trim_segments
+    }
+
+    /// Deepest depth a node may have, counting roots as depth `0`. Creating a
+    /// node or moving a subtree whose deepest node would end up below it fails
+    /// with [`ClosureTreeError::DepthLimitExceeded`](crate::ClosureTreeError::DepthLimitExceeded).
+    pub fn max_depth(&self) -> Option<u32> {
+        self.max_depth
+    }
+
+    /// Most children a node may have. Writes that would add one more fail with
+    /// [`ClosureTreeError::ChildLimitExceeded`](crate::ClosureTreeError::ChildLimitExceeded);
+    /// roots are not limited.
+    pub fn max_children(&self) -> Option<u64> {
+        self.max_children
+    }
+
     /// Advisory lock strategy (PostgreSQL only).
     pub fn advisory_lock_strategy(&self) -> &AdvisoryLockStrategy {
         &self.advisory_lock_strategy
     }
+
+    /// How long operations wait for the advisory lock.
+    pub fn lock_acquisition(&self) -> LockAcquisition {
+        self.lock_acquisition
+    }
+
+    /// Portion of the table covered by each operation's advisory lock.
+    pub fn lock_scope(&self) -> &AdvisoryLockScope {
+        &self.lock_scope
+    }
+
+    /// Statement timeout applied inside transactional operations (PostgreSQL only).
+    pub fn statement_timeout(&self) -> Option<Duration> {
+        self.statement_timeout
+    }
+
+    /// What to report when rolling back after a failed operation fails as well.
+    pub fn cleanup_policy(&self) -> CleanupPolicy {
+        self.cleanup_policy
+    }
+
+    /// Check the configuration for internal consistency without touching the
+    /// database: empty names, one column serving two roles, and settings that
+    /// cannot take effect. See
+    /// [`ClosureTreeRepository::check_schema`](crate::ClosureTreeRepository::check_schema)
+    /// to check the tables as well.
+    pub fn validate(&self) -> Vec<SchemaFinding> {
+        let mut problems = Vec::new();
+        let invalid = |detail: String| SchemaFinding::InvalidConfig(detail);
+
+        let hierarchy_columns = [
+            (
+                "hierarchy ancestor",
+                self.hierarchy_ancestor_column.as_str(),
+            ),
+            (
+                "hierarchy descendant",
+                self.hierarchy_descendant_column.as_str(),
+            ),
+            (
+                "hierarchy generations",
+                self.hierarchy_generations_column.as_str(),
+            ),
+        ];
+        if self.hierarchy_table.is_empty() {
+            problems.push(invalid("the hierarchy table is empty".to_owned()));
+        }
+        if self.schema_name.as_deref() == Some("") {
+            problems.push(invalid("the schema name is empty".to_owned()));
+        }
+        match self.hierarchy_conflict_columns.as_deref() {
+            Some([]) => problems.push(invalid(
+                "the hierarchy conflict target has no columns".to_owned(),
+            )),
+            Some(columns) if columns.iter().any(String::is_empty) => problems.push(invalid(
+                "the hierarchy conflict target has an empty column".to_owned(),
+            )),
+            _ => {}
+        }
+        for columns in [&self.columns()[..], &hierarchy_columns[..]] {
+            for (index, (role, column)) in columns.iter().enumerate() {
+                if column.is_empty() {
+                    problems.push(invalid(format!("the {role} column is empty")));
+                } else if let Some((other, _)) = columns[..index]
+                    .iter()
+                    .find(|(_, earlier)| earlier == column)
+                {
+                    problems.push(invalid(format!(
+                        "`{column}` is both the {other} and the {role} column"
+                    )));
+                }
+            }
+        }
+
+        if self.path_separator.is_empty() || self.path_separator.contains('\\') {
+            problems.push(invalid(format!(
+                "the path separator must be non-empty and not contain `\\`, got {:?}",
+                self.path_separator
+            )));
+        }
+        if self.order_gap < 1 {
+            problems.push(invalid(format!(
+                "order_gap must be positive, got {}",
+                self.order_gap
+            )));
+        }
+        if self.dont_order_roots
+            && self
+                .order_strategy
+                .as_ref()
+                .and_then(OrderStrategy::position_column)
+                .is_none()
+        {
+            problems.push(invalid(
+                "dont_order_roots needs a numeric column order strategy".to_owned(),
+            ));
+        }
+        if self.name_column.is_none() {
+            if self.unique_sibling_names {
+                problems.push(invalid(
+                    "unique_sibling_names needs a name column".to_owned(),
+                ));
+            }
+            if matches!(self.order_strategy, Some(OrderStrategy::ByName { .. })) {
+                problems.push(invalid("ordering by name needs a name column".to_owned()));
+            }
+        }
+        if let LockAcquisition::TryFor { poll_interval, .. } = self.lock_acquisition {
+            if poll_interval.is_zero() {
+                problems.push(invalid(
+                    "the lock acquisition poll interval is zero".to_owned(),
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Base-table columns by role, as configured.
+    pub(crate) fn columns(&self) -> Vec<(&'static str, &str)> {
+        let mut columns = vec![("parent", self.parent_column.as_str())];
+        if let Some(name) = &self.name_column {
+            columns.push(("name", name.as_str()));
+        }
+        if let Some((column, _)) = self
+            .order_strategy
+            .as_ref()
+            .and_then(OrderStrategy::position_column)
+        {
+            columns.push(("order", column));
+        }
+        let optional = [
+            ("ltree", &self.ltree_column),
+            ("touch", &self.touch_column),
+            ("children count", &self.children_count_column),
+            ("scope", &self.scope_column),
+            ("soft-delete", &self.soft_delete_column),
+        ];
+        columns.extend(
+            optional
+                .into_iter()
+                .filter_map(|(role, column)| Some((role, column.as_deref()?))),
+        );
+        columns
+    }
 }
 
 /// Builder-style options consumed by the derive macro.
@@ -106,10 +585,40 @@ impl ClosureTreeConfig {
 pub struct ClosureTreeOptions {
     parent_column: Option<String>,
     name_column: Option<String>,
+    no_name_column: Option<bool>,
     hierarchy_table: Option<String>,
+    schema_name: Option<String>,
+    hierarchy_ancestor_column: Option<String>,
+    hierarchy_descendant_column: Option<String>,
+    hierarchy_generations_column: Option<String>,
+    hierarchy_conflict_columns: Option<Vec<String>>,
+    ltree_column: Option<String>,
+    touch_column: Option<String>,
+    touch_ancestors: Option<bool>,
+    children_count_column: Option<String>,
+    scope_column: Option<String>,
+    soft_delete_column: Option<String>,
     dependent_behavior: Option<DependentBehavior>,
+    root_marker: Option<RootMarker>,
+    prefer_recursive_cte: Option<bool>,
     order_strategy: Option<OrderStrategy>,
+    order_gap: Option<i64>,
+    order_scope: Option<OrderScope>,
+    dont_order_roots: Option<bool>,
+    name_order_descending: Option<bool>,
+    unique_sibling_names: Option<bool>,
+    name_matching: Option<NameMatching>,
+    name_normalizer: Option<fn(&str) -> String>,
+    path_separator: Option<String>,
+    reject_blank_segments: Option<bool>,
+    trim_segments: Option<bool>,
+    max_depth: Option<u32>,
+    max_children: Option<u64>,
     advisory_lock_strategy: Option<AdvisoryLockStrategy>,
+    lock_acquisition: Option<LockAcquisition>,
+    lock_scope: Option<AdvisoryLockScope>,
+    statement_timeout: Option<Duration>,
+    cleanup_policy: Option<CleanupPolicy>,
 }
 
 impl ClosureTreeOptions {
@@ -123,86 +632,452 @@ impl ClosureTreeOptions {
         self
     }
 
+    pub fn no_name_column(mut self, value: bool) -> Self {
+        self.no_name_column = Some(value);
+        self
+    }
+
     pub fn hierarchy_table(mut self, value: impl Into<String>) -> Self {
         self.hierarchy_table = Some(value.into());
         self
     }
 
+    pub fn schema_name(mut self, value: impl Into<String>) -> Self {
+        self.schema_name = Some(value.into());
+        self
+    }
+
+    pub fn hierarchy_ancestor_column(mut self, value: impl Into<String>) -> Self {
+        self.hierarchy_ancestor_column = Some(value.into());
+        self
+    }
+
+    pub fn hierarchy_descendant_column(mut self, value: impl Into<String>) -> Self {
+        self.hierarchy_descendant_column = Some(value.into());
+        self
+    }
+
+    pub fn hierarchy_generations_column(mut self, value: impl Into<String>) -> Self {
+        self.hierarchy_generations_column = Some(value.into());
+        self
+    }
+
+    pub fn hierarchy_conflict_columns<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.hierarchy_conflict_columns = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn ltree_column(mut self, value: impl Into<String>) -> Self {
+        self.ltree_column = Some(value.into());
+        self
+    }
+
+    pub fn touch(mut self, column: impl Into<String>) -> Self {
+        self.touch_column = Some(column.into());
+        self
+    }
+
+    pub fn touch_ancestors(mut self, touch_ancestors: bool) -> Self {
+        self.touch_ancestors = Some(touch_ancestors);
+        self
+    }
+
+    pub fn children_count_column(mut self, column: impl Into<String>) -> Self {
+        self.children_count_column = Some(column.into());
+        self
+    }
+
+    pub fn scope_column(mut self, column: impl Into<String>) -> Self {
+        self.scope_column = Some(column.into());
+        self
+    }
+
+    pub fn soft_delete_column(mut self, column: impl Into<String>) -> Self {
+        self.soft_delete_column = Some(column.into());
+        self
+    }
+
     pub fn dependent_behavior(mut self, behavior: DependentBehavior) -> Self {
         self.dependent_behavior = Some(behavior);
         self
     }
 
+    pub fn root_marker(mut self, marker: RootMarker) -> Self {
+        self.root_marker = Some(marker);
+        self
+    }
+
+    pub fn prefer_recursive_cte(mut self, prefer: bool) -> Self {
+        self.prefer_recursive_cte = Some(prefer);
+        self
+    }
+
     pub fn order_strategy(mut self, strategy: OrderStrategy) -> Self {
         self.order_strategy = Some(strategy);
         self
     }
 
+    pub fn order_gap(mut self, gap: i64) -> Self {
+        self.order_gap = Some(gap);
+        self
+    }
+
+    pub fn order_scope(mut self, scope: OrderScope) -> Self {
+        self.order_scope = Some(scope);
+        self
+    }
+
+    pub fn dont_order_roots(mut self, dont_order_roots: bool) -> Self {
+        self.dont_order_roots = Some(dont_order_roots);
+        self
+    }
+
+    pub fn name_order_descending(mut self, descending: bool) -> Self {
+        self.name_order_descending = Some(descending);
+        self
+    }
+
+    pub fn unique_sibling_names(mut self, unique: bool) -> Self {
+        self.unique_sibling_names = Some(unique);
+        self
+    }
+
+    pub fn name_matching(mut self, matching: NameMatching) -> Self {
+        self.name_matching = Some(matching);
+        self
+    }
+
+    pub fn name_normalizer(mut self, normalizer: fn(&str) -> String) -> Self {
+        self.name_normalizer = Some(normalizer);
+        self
+    }
+
+    pub fn path_separator(mut self, separator: impl Into<String>) -> Self {
+        self.path_separator = Some(separator.into());
+        self
+    }
+
+    pub fn reject_blank_segments(mut self, reject: bool) -> Self {
+        self.reject_blank_segments = Some(reject);
+        self
+    }
+
+    pub fn trim_segments(mut self, trim: bool) -> Self {
+        self.trim_segments = Some(trim);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn max_children(mut self, max_children: u64) -> Self {
+        self.max_children = Some(max_children);
+        self
+    }
+
     pub fn advisory_lock_strategy(mut self, strategy: AdvisoryLockStrategy) -> Self {
         self.advisory_lock_strategy = Some(strategy);
         self
     }
 
+    pub fn lock_acquisition(mut self, acquisition: LockAcquisition) -> Self {
+        self.lock_acquisition = Some(acquisition);
+        self
+    }
+
+    pub fn lock_scope(mut self, scope: AdvisoryLockScope) -> Self {
+        self.lock_scope = Some(scope);
+        self
+    }
+
+    pub fn statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+
+    pub fn cleanup_policy(mut self, policy: CleanupPolicy) -> Self {
+        self.cleanup_policy = Some(policy);
+        self
+    }
+
     pub fn apply(self, base: ClosureTreeConfig) -> ClosureTreeConfig {
         base.apply_options(self)
     }
 }
 
 /// Behaviour to apply to dependent nodes when destroying a record.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
 pub enum DependentBehavior {
+    #[default]
     Nullify,
     Destroy,
     DeleteAll,
     None,
 }
 
-impl Default for DependentBehavior {
-    fn default() -> Self {
-        Self::Nullify
-    }
+/// What the parent column holds for a root, for schemas that keep it
+/// `NOT NULL`.
+///
+/// Whatever the marker, a `NULL` parent is still read as a root, so a table can
+/// be switched over gradually; writes store the configured marker. Roots are
+/// recognised by [`ClosureTreeRepository::is_root`](crate::ClosureTreeRepository::is_root)
+/// and by every query that looks for roots or siblings, while
+/// [`ClosureTreeModel::parent_id`](crate::ClosureTreeModel::parent_id) keeps
+/// returning the stored value.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum RootMarker {
+    /// `NULL`.
+    #[default]
+    Null,
+    /// `0`, for integer ids that never take that value.
+    Zero,
+    /// The node's own id. Ids the database assigns are only known after the
+    /// `INSERT`, so such roots are inserted with a `NULL` parent and then
+    /// updated, and the column must accept `NULL` unless the model generates
+    /// its ids ([`ClosureTreeModel::generate_id`](crate::ClosureTreeModel::generate_id)).
+    SelfReference,
 }
 
 /// Strategy used to generate deterministic ordering.
-#[derive(Clone, Debug, Eq, PartialEq)]
+///
+/// Applied to `children`, `siblings`, `roots` and `descendants`. `NumericColumn`
+/// and `Expression` break ties by name (see
+/// [`ClosureTreeConfig::name_order_descending`]); `None` leaves the order to the
+/// database. Without a strategy, siblings are ordered by name.
+///
+/// With `NumericColumn`, reads sort by [`ClosureTreeModel::order_column`] and new
+/// nodes are appended after their siblings: the first child gets `0`, later ones
+/// the siblings' maximum plus [`ClosureTreeConfig::order_gap`].
+///
+/// `FractionalColumn` sorts and appends the same way on a floating-point (or
+/// decimal) column, stepping by `1`, but places a node between two siblings at
+/// the midpoint of their positions, so inserting or moving into a slot writes
+/// one row. When two neighbours get too close for a midpoint between them, the
+/// siblings are renumbered `0, 1, 2, ...` once.
+///
+/// [`ClosureTreeModel::order_column`]: crate::ClosureTreeModel::order_column
+#[derive(Clone, Debug, PartialEq)]
 pub enum OrderStrategy {
+    /// Order by name only; same as having no strategy.
     Manual,
-    NumericColumn { column: String },
+    NumericColumn {
+        column: String,
+        direction: OrderDirection,
+    },
+    FractionalColumn {
+        column: String,
+        direction: OrderDirection,
+    },
+    ByName {
+        direction: OrderDirection,
+    },
+    /// Order by an arbitrary expression, e.g. `Expr::col(Column::CreatedAt).into()`.
+    Expression {
+        expr: SimpleExpr,
+        direction: OrderDirection,
+    },
+    /// No `ORDER BY` at all.
+    None,
 }
 
 impl OrderStrategy {
+    /// Order siblings by a numeric position column, lowest first.
     pub fn numeric_column(column: impl Into<String>) -> Self {
         Self::NumericColumn {
             column: column.into(),
+            direction: OrderDirection::Asc,
+        }
+    }
+
+    /// Order siblings by a numeric position column, highest first.
+    pub fn numeric_column_desc(column: impl Into<String>) -> Self {
+        Self::NumericColumn {
+            column: column.into(),
+            direction: OrderDirection::Desc,
+        }
+    }
+
+    /// Order siblings by a fractional position column, lowest first.
+    pub fn fractional_column(column: impl Into<String>) -> Self {
+        Self::FractionalColumn {
+            column: column.into(),
+            direction: OrderDirection::Asc,
+        }
+    }
+
+    /// The position column of `NumericColumn` and `FractionalColumn`, with its
+    /// direction.
+    pub(crate) fn position_column(&self) -> Option<(&str, OrderDirection)> {
+        match self {
+            Self::NumericColumn { column, direction }
+            | Self::FractionalColumn { column, direction } => Some((column, *direction)),
+            _ => None,
+        }
+    }
+
+    /// Order siblings by `expr`, lowest first.
+    pub fn expression(expr: impl Into<SimpleExpr>) -> Self {
+        Self::Expression {
+            expr: expr.into(),
+            direction: OrderDirection::Asc,
+        }
+    }
+}
+
+// `SimpleExpr` is neither `Eq` nor `Hash`; expressions compare structurally and
+// hash by direction only, which keeps `Hash` consistent with `Eq`.
+impl Eq for OrderStrategy {}
+
+impl Hash for OrderStrategy {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::NumericColumn { column, direction }
+            | Self::FractionalColumn { column, direction } => {
+                column.hash(state);
+                direction.hash(state);
+            }
+            Self::ByName { direction } | Self::Expression { direction, .. } => {
+                direction.hash(state)
+            }
+            Self::Manual | Self::None => {}
+        }
+    }
+}
+
+/// What the positions of [`OrderStrategy::NumericColumn`] are unique within.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum OrderScope {
+    /// Positions count from `0` under each parent. Moving a node to another
+    /// parent closes the gap it leaves among its old siblings and appends it
+    /// after its new ones.
+    #[default]
+    PerParent,
+    /// Positions are unique across the tree, e.g. under a unique index on the
+    /// column. New nodes are appended after the tree's highest position, and
+    /// moves between parents keep the positions they find;
+    /// [`move_to_child_of_at`](crate::ClosureTreeRepository::move_to_child_of_at)
+    /// ignores its index.
+    Global,
+}
+
+/// Sort direction for an [`OrderStrategy`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum OrderDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl From<OrderDirection> for Order {
+    fn from(direction: OrderDirection) -> Self {
+        match direction {
+            OrderDirection::Asc => Order::Asc,
+            OrderDirection::Desc => Order::Desc,
         }
     }
 }
 
 /// Key used for PostgreSQL advisory locks.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub struct AdvisoryLockKey(String);
+pub enum AdvisoryLockKey {
+    /// Locked as `(hashtext(name), 0)`; the numeric key is computed by the server.
+    Named(String),
+    /// Locked as the single `bigint` key, so other services can take the same
+    /// lock with `pg_advisory_lock(key)` or `pg_advisory_xact_lock(key)`.
+    Numeric(i64),
+}
 
 impl AdvisoryLockKey {
     pub fn new(value: impl Into<String>) -> Self {
-        Self(value.into())
+        Self::Named(value.into())
+    }
+
+    pub fn numeric(value: i64) -> Self {
+        Self::Numeric(value)
     }
 
-    pub fn as_str(&self) -> &str {
-        self.0.as_str()
+    /// Name of a [`Named`](Self::Named) key.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Named(name) => Some(name.as_str()),
+            Self::Numeric(_) => None,
+        }
+    }
+
+    /// The exact `bigint` passed to PostgreSQL for a [`Numeric`](Self::Numeric) key.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Named(_) => None,
+            Self::Numeric(value) => Some(*value),
+        }
+    }
+
+    /// Key guarding one scope (see [`AdvisoryLockScope`]) beneath this key.
+    ///
+    /// Named keys become `"{name}/{scope}"`. Numeric keys put the CRC-32 of
+    /// `scope` in the low half and, in the high half, the key itself when it
+    /// fits in 32 bits (as derived keys do) or else the CRC-32 of its eight
+    /// big-endian bytes. Distinct keys wider than 32 bits therefore share
+    /// scoped keys only when their checksums collide.
+    pub fn scoped(&self, scope: &str) -> Self {
+        match self {
+            Self::Named(name) => Self::Named(format!("{name}/{scope}")),
+            Self::Numeric(value) => {
+                let high = match u32::try_from(*value) {
+                    Ok(narrow) => narrow,
+                    Err(_) => crc32fast::hash(&value.to_be_bytes()),
+                };
+                let low = crc32fast::hash(scope.as_bytes());
+                Self::Numeric(((u64::from(high) << 32) | u64::from(low)) as i64)
+            }
+        }
+    }
+
+    /// Key used when no explicit key is configured.
+    ///
+    /// This derivation is stable across releases: the CRC-32 (IEEE) checksum of
+    /// `"{entity}/{hierarchy}"`, as a non-negative numeric key. Releases before
+    /// it used [`legacy_derived_from`](Self::legacy_derived_from); the two do not
+    /// exclude each other.
+    pub fn derived_from(entity: &str, hierarchy: &str) -> Self {
+        let mut hasher = Crc32::new();
+        hasher.update(entity.as_bytes());
+        hasher.update(b"/");
+        hasher.update(hierarchy.as_bytes());
+        Self::Numeric(i64::from(hasher.finalize()))
     }
 
-    fn derived_from(entity: &str, hierarchy: &str) -> Self {
-        let mut hasher = Hasher::new();
+    /// The named key derived by default before numeric keys: for binaries that
+    /// must keep excluding older releases during a rolling deploy, configure it
+    /// explicitly (e.g. with `advisory_lock_key`) until every instance upgrades.
+    pub fn legacy_derived_from(entity: &str, hierarchy: &str) -> Self {
+        let mut hasher = Crc32::new();
         hasher.update(entity.as_bytes());
         hasher.update(b"/");
         hasher.update(hierarchy.as_bytes());
         let crc = hasher.finalize();
-        Self(format!("closure-tree::{entity}::{hierarchy}::{crc:x}"))
+        Self::Named(format!("closure-tree::{entity}::{hierarchy}::{crc:x}"))
+    }
+}
+
+impl fmt::Display for AdvisoryLockKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(name) => f.write_str(name),
+            Self::Numeric(value) => write!(f, "{value}"),
+        }
     }
 }
 
 /// Configuration describing how to acquire advisory locks.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum AdvisoryLockStrategy {
     Disabled,
     Namespaced(AdvisoryLockKey),
@@ -216,3 +1091,116 @@ impl AdvisoryLockStrategy {
         }
     }
 }
+
+/// How long to wait for the advisory lock before giving up.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum LockAcquisition {
+    /// Wait until the lock is granted (`pg_advisory_xact_lock`).
+    #[default]
+    Blocking,
+    /// Poll `pg_try_advisory_xact_lock` every `poll_interval` until `timeout` has elapsed,
+    /// then fail with [`ClosureTreeError::LockTimeout`](crate::ClosureTreeError::LockTimeout).
+    TryFor {
+        timeout: Duration,
+        poll_interval: Duration,
+    },
+}
+
+impl LockAcquisition {
+    /// Give up after `timeout`, polling every 50ms.
+    pub fn try_for(timeout: Duration) -> Self {
+        Self::TryFor {
+            timeout,
+            poll_interval: Duration::from_millis(50),
+        }
+    }
+}
+
+/// How names are compared by `find_by_path`, `find_or_create_by_path` and the
+/// [`unique_sibling_names`](ClosureTreeConfig::unique_sibling_names) check.
+///
+/// `CaseInsensitive` compares `LOWER(name)` on both sides, so case folding is
+/// whatever the database does: PostgreSQL follows the column's collation and
+/// locale (e.g. Turkish dotted/dotless i, or `ß` which has no single-letter
+/// uppercase), while SQLite's `LOWER` only folds ASCII letters. A plain index on
+/// the name column cannot serve these lookups; index `LOWER(name)` instead, as
+/// [`recommended_indexes`](crate::ClosureTreeRepository::recommended_indexes) does.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum NameMatching {
+    #[default]
+    Exact,
+    CaseInsensitive,
+}
+
+/// A name normalizer, compared and hashed by address so the configuration can
+/// keep its derived `Eq` and `Hash`.
+#[derive(Copy, Clone, Debug)]
+struct NameNormalizer(fn(&str) -> String);
+
+impl PartialEq for NameNormalizer {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::fn_addr_eq(self.0, other.0)
+    }
+}
+
+impl Eq for NameNormalizer {}
+
+impl Hash for NameNormalizer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 as usize).hash(state);
+    }
+}
+
+/// Handling of a rollback that fails after an operation already failed.
+///
+/// Either way the failure is logged, including the advisory lock key: with
+/// `tracing::error!` under the `tracing` feature, otherwise to stderr.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum CleanupPolicy {
+    /// Return the operation's original error.
+    #[default]
+    BestEffort,
+    /// Return [`ClosureTreeError::CleanupFailed`](crate::ClosureTreeError::CleanupFailed)
+    /// carrying both errors.
+    Strict,
+}
+
+/// Portion of the table covered by an operation's advisory lock.
+///
+/// Scoped operations hold the configured key in shared mode plus an exclusive
+/// lock on [`AdvisoryLockKey::scoped`], so unrelated trees do not block each
+/// other while [`Global`](Self::Global) operations still exclude everything.
+/// Operations whose scope cannot be determined up front fall back to `Global`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum AdvisoryLockScope {
+    /// One lock for the whole table.
+    #[default]
+    Global,
+    /// Path operations lock the tree named by their first segment, using the
+    /// scope string `"segment:{first_segment}"`.
+    PerFirstSegment,
+    /// Lock the tree rooted at `root_id`, using the scope string `"root:{root_id}"`.
+    PerTree { root_id: String },
+}
+
+impl AdvisoryLockScope {
+    /// Scope for the tree rooted at `root_id`, written with `Display`: the id
+    /// `42` gives the scope string `"root:42"`, whose lock key is
+    /// [`AdvisoryLockKey::scoped`]`("root:42")`, so other services can take the
+    /// same lock. Newtype ids should display as the underlying value.
+    pub fn per_tree(root_id: impl fmt::Display) -> Self {
+        Self::PerTree {
+            root_id: root_id.to_string(),
+        }
+    }
+
+    /// Scope string for an operation whose paths start with `first_segment`,
+    /// or `None` when the whole table must be locked.
+    pub(crate) fn resolve(&self, first_segment: Option<&str>) -> Option<String> {
+        match self {
+            Self::Global => None,
+            Self::PerFirstSegment => first_segment.map(|segment| format!("segment:{segment}")),
+            Self::PerTree { root_id } => Some(format!("root:{root_id}")),
+        }
+    }
+}
@@ -1,13 +1,31 @@
+use std::time::Duration;
+
+use sea_orm::{DbBackend, DbErr, RuntimeErr, SqlErr};
 use thiserror::Error;
 
 /// Errors returned by the closure-tree helper APIs.
 #[derive(Debug, Error)]
 pub enum ClosureTreeError {
-    #[error("closure-tree currently supports PostgreSQL connections only")]
-    UnsupportedBackend,
+    #[error("{operation} is not supported on the {backend:?} backend")]
+    UnsupportedBackend {
+        backend: DbBackend,
+        operation: &'static str,
+    },
+
+    /// The operation needs an option this tree is not configured with, such as
+    /// a numeric order column for `move_up`.
+    #[error("{operation} is not supported: {reason}")]
+    UnsupportedOperation {
+        operation: &'static str,
+        reason: &'static str,
+    },
 
     #[error("database error: {0}")]
-    Database(#[from] sea_orm::DbErr),
+    Database(#[source] DbErr),
+
+    /// A statement was cancelled by `statement_timeout` (SQLSTATE 57014).
+    #[error("statement timed out: {0}")]
+    Timeout(#[source] DbErr),
 
     #[error("sqlx error: {0}")]
     Sqlx(#[from] sqlx::Error),
@@ -15,12 +33,159 @@ pub enum ClosureTreeError {
     #[error("path cannot be empty")]
     EmptyPath,
 
+    /// Segment `index` of a path is empty or only whitespace; see
+    /// [`reject_blank_segments`](crate::ClosureTreeConfig::reject_blank_segments).
+    #[error("path segment {index} is blank")]
+    BlankSegment { index: usize },
+
+    #[error("{entity} with id {id} not found")]
+    NodeNotFound { entity: String, id: String },
+
+    /// Another child of the same parent (or another root, when `parent_id` is
+    /// `None`) already has this name, so paths through it would be ambiguous.
+    #[error("a sibling named `{name}` already exists {}", under(.parent_id))]
+    DuplicateName {
+        parent_id: Option<String>,
+        name: String,
+    },
+
+    /// A sibling-reordering operation was asked to reorder nodes that have no order.
+    #[error("{entity} siblings cannot be reordered: {reason}")]
+    NotOrderable {
+        entity: String,
+        reason: &'static str,
+    },
+
+    /// A sibling position does not fit the numeric order field, e.g. an `i16`
+    /// column after many appends at the configured
+    /// [`order_gap`](crate::ClosureTreeConfig::order_gap).
+    #[error("order value {value} is out of range for the order field")]
+    OrderValueOutOfRange { value: i64 },
+
+    /// The write would place a node at `depth`, below the configured
+    /// [`max_depth`](crate::ClosureTreeConfig::max_depth).
+    #[error("depth {depth} exceeds the maximum depth of {max_depth}")]
+    DepthLimitExceeded { depth: u32, max_depth: u32 },
+
+    /// The write would give `parent_id` more than the configured
+    /// [`max_children`](crate::ClosureTreeConfig::max_children).
+    #[error("parent {parent_id} already has the maximum of {max_children} children")]
+    ChildLimitExceeded {
+        parent_id: String,
+        max_children: u64,
+    },
+
+    /// A move would place `node_id` beneath itself or one of its own descendants.
+    #[error("cannot move {node_id} beneath {new_parent_id}, which is the node itself or one of its descendants")]
+    CycleDetected {
+        node_id: String,
+        new_parent_id: String,
+    },
+
+    #[error("timed out after {waited:?} waiting for advisory lock `{key}`")]
+    LockTimeout { key: String, waited: Duration },
+
+    /// The operation failed, and rolling back its transaction failed too.
+    #[error("{source}; rollback also failed: {cleanup}")]
+    CleanupFailed {
+        source: Box<ClosureTreeError>,
+        cleanup: Box<ClosureTreeError>,
+    },
+
     #[error("closure-tree invariant violation: {0}")]
     Invariant(String),
 }
 
+impl From<DbErr> for ClosureTreeError {
+    fn from(err: DbErr) -> Self {
+        match sqlstate(&err).as_deref() {
+            Some("57014") => Self::Timeout(err),
+            _ => Self::Database(err),
+        }
+    }
+}
+
+fn under(parent_id: &Option<String>) -> String {
+    match parent_id {
+        Some(parent_id) => format!("under parent {parent_id}"),
+        None => "among the roots".to_owned(),
+    }
+}
+
+fn sqlstate(err: &DbErr) -> Option<String> {
+    let (DbErr::Conn(RuntimeErr::SqlxError(err))
+    | DbErr::Exec(RuntimeErr::SqlxError(err))
+    | DbErr::Query(RuntimeErr::SqlxError(err))) = err
+    else {
+        return None;
+    };
+    err.as_database_error()?
+        .code()
+        .map(|code| code.into_owned())
+}
+
 impl ClosureTreeError {
     pub fn invariant(detail: impl Into<String>) -> Self {
         Self::Invariant(detail.into())
     }
+
+    /// Whether this is a database unique-constraint violation.
+    pub(crate) fn is_unique_violation(&self) -> bool {
+        matches!(
+            self,
+            Self::Database(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_)))
+        )
+    }
+}
+
+/// Coarse classification of [`ClosureTreeError`] for mapping onto HTTP status
+/// codes (404, 409, 400 and 500 respectively).
+#[cfg(feature = "error-kind")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClosureTreeErrorKind {
+    NotFound,
+    /// The request clashed with existing data or a concurrent writer; retrying may succeed.
+    Conflict,
+    BadRequest,
+    ServerError,
+}
+
+#[cfg(feature = "error-kind")]
+impl ClosureTreeError {
+    pub fn kind(&self) -> ClosureTreeErrorKind {
+        match self {
+            Self::EmptyPath
+            | Self::BlankSegment { .. }
+            | Self::NotOrderable { .. }
+            | Self::UnsupportedOperation { .. }
+            | Self::DepthLimitExceeded { .. }
+            | Self::ChildLimitExceeded { .. }
+            | Self::OrderValueOutOfRange { .. } => ClosureTreeErrorKind::BadRequest,
+            Self::NodeNotFound { .. } => ClosureTreeErrorKind::NotFound,
+            Self::LockTimeout { .. } | Self::DuplicateName { .. } | Self::CycleDetected { .. } => {
+                ClosureTreeErrorKind::Conflict
+            }
+            err if err.is_unique_violation() => ClosureTreeErrorKind::Conflict,
+            Self::CleanupFailed { source, .. } => source.kind(),
+            Self::UnsupportedBackend { .. }
+            | Self::Database(_)
+            | Self::Timeout(_)
+            | Self::Sqlx(_)
+            | Self::Invariant(_) => ClosureTreeErrorKind::ServerError,
+        }
+    }
+}
+
+#[cfg(feature = "error-kind")]
+impl From<&ClosureTreeError> for ClosureTreeErrorKind {
+    fn from(err: &ClosureTreeError) -> Self {
+        err.kind()
+    }
+}
+
+#[cfg(feature = "error-kind")]
+impl From<ClosureTreeError> for ClosureTreeErrorKind {
+    fn from(err: ClosureTreeError) -> Self {
+        err.kind()
+    }
 }
@@ -0,0 +1,101 @@
+use async_trait::async_trait;
+use sea_orm::ConnectionTrait;
+
+use crate::error::ClosureTreeError;
+use crate::repository::ClosureTreeRepository;
+use crate::traits::ClosureTreeModel;
+
+/// Tree queries callable on a model, e.g. `node.children(&db)`, on any
+/// connection or transaction.
+///
+/// Implemented for every [`ClosureTreeModel`]; each method runs the
+/// [`ClosureTreeRepository`] method of the same name on a default repository.
+/// Build a repository yourself for per-call options such as an observer or a
+/// configuration override.
+#[async_trait]
+pub trait ClosureTreeModelExt: ClosureTreeModel {
+    fn is_root(&self) -> bool;
+
+    async fn parent(&self, conn: &impl ConnectionTrait) -> Result<Option<Self>, ClosureTreeError>;
+
+    async fn children(&self, conn: &impl ConnectionTrait) -> Result<Vec<Self>, ClosureTreeError>;
+
+    async fn siblings(&self, conn: &impl ConnectionTrait) -> Result<Vec<Self>, ClosureTreeError>;
+
+    async fn ancestors(&self, conn: &impl ConnectionTrait) -> Result<Vec<Self>, ClosureTreeError>;
+
+    async fn descendants(&self, conn: &impl ConnectionTrait)
+        -> Result<Vec<Self>, ClosureTreeError>;
+
+    async fn self_and_descendants(
+        &self,
+        conn: &impl ConnectionTrait,
+    ) -> Result<Vec<Self>, ClosureTreeError>;
+
+    async fn subtree_size(&self, conn: &impl ConnectionTrait) -> Result<u64, ClosureTreeError>;
+
+    async fn has_children(&self, conn: &impl ConnectionTrait) -> Result<bool, ClosureTreeError>;
+
+    async fn count_children(&self, conn: &impl ConnectionTrait) -> Result<u64, ClosureTreeError>;
+}
+
+#[async_trait]
+impl<M: ClosureTreeModel> ClosureTreeModelExt for M {
+    fn is_root(&self) -> bool {
+        ClosureTreeRepository::<M>::new().is_root(self)
+    }
+
+    async fn parent(&self, conn: &impl ConnectionTrait) -> Result<Option<Self>, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new().parent(conn, self).await
+    }
+
+    async fn children(&self, conn: &impl ConnectionTrait) -> Result<Vec<Self>, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new().children(conn, self).await
+    }
+
+    async fn siblings(&self, conn: &impl ConnectionTrait) -> Result<Vec<Self>, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new().siblings(conn, self).await
+    }
+
+    async fn ancestors(&self, conn: &impl ConnectionTrait) -> Result<Vec<Self>, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new()
+            .ancestors(conn, self)
+            .await
+    }
+
+    async fn descendants(
+        &self,
+        conn: &impl ConnectionTrait,
+    ) -> Result<Vec<Self>, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new()
+            .descendants(conn, self)
+            .await
+    }
+
+    async fn self_and_descendants(
+        &self,
+        conn: &impl ConnectionTrait,
+    ) -> Result<Vec<Self>, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new()
+            .self_and_descendants(conn, self)
+            .await
+    }
+
+    async fn subtree_size(&self, conn: &impl ConnectionTrait) -> Result<u64, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new()
+            .subtree_size(conn, self)
+            .await
+    }
+
+    async fn has_children(&self, conn: &impl ConnectionTrait) -> Result<bool, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new()
+            .has_children(conn, self)
+            .await
+    }
+
+    async fn count_children(&self, conn: &impl ConnectionTrait) -> Result<u64, ClosureTreeError> {
+        ClosureTreeRepository::<M>::new()
+            .count_children(conn, self)
+            .await
+    }
+}
@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, ExecResult, QueryResult, Statement};
+
+use crate::error::ClosureTreeError;
+use crate::traits::{ClosureTreeModel, DefaultTree};
+
+/// Callbacks run inside the transaction of a tree change, registered with
+/// [`ClosureTreeRepository::with_hooks`](crate::ClosureTreeRepository::with_hooks).
+///
+/// Unlike a [`TreeObserver`](crate::TreeObserver), hooks see the change before
+/// it commits and can write through `conn`, so denormalized data such as search
+/// index rows stays consistent with the tree. An error returned by a hook rolls
+/// back the whole operation and is returned by it. Every method defaults to
+/// doing nothing.
+///
+/// Hooks are not called for dry runs.
+#[async_trait]
+pub trait ClosureTreeHooks<M, Tag = DefaultTree>: Send + Sync
+where
+    M: ClosureTreeModel<Tag>,
+{
+    /// Before a node is inserted beneath `parent` (`None` for a root), by path
+    /// creation, `import_tree` or `duplicate_subtree`. Changes to `child` are
+    /// inserted with it.
+    async fn before_create_child(
+        &self,
+        _conn: &HookConnection<'_>,
+        _parent: Option<&M::Id>,
+        _child: &mut M::ActiveModel,
+    ) -> Result<(), ClosureTreeError> {
+        Ok(())
+    }
+
+    /// Once the operation that created `child` has linked it into the tree.
+    async fn after_create_child(
+        &self,
+        _conn: &HookConnection<'_>,
+        _child: &M,
+    ) -> Result<(), ClosureTreeError> {
+        Ok(())
+    }
+
+    /// Before `node` and its subtree move beneath `new_parent`, once the move
+    /// has been checked.
+    async fn before_move(
+        &self,
+        _conn: &HookConnection<'_>,
+        _node: &M,
+        _new_parent: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        Ok(())
+    }
+
+    /// After `node`, as updated, moved away from `old_parent`.
+    async fn after_move(
+        &self,
+        _conn: &HookConnection<'_>,
+        _node: &M,
+        _old_parent: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        Ok(())
+    }
+
+    /// Before `destroy` removes `root` under the configured
+    /// [`DependentBehavior`](crate::DependentBehavior).
+    async fn before_destroy_subtree(
+        &self,
+        _conn: &HookConnection<'_>,
+        _root: &M,
+    ) -> Result<(), ClosureTreeError> {
+        Ok(())
+    }
+}
+
+/// The transaction a [`ClosureTreeHooks`] callback runs in.
+pub struct HookConnection<'a> {
+    conn: &'a dyn ConnectionTrait,
+}
+
+impl<'a> HookConnection<'a> {
+    pub(crate) fn new(conn: &'a impl ConnectionTrait) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait]
+impl ConnectionTrait for HookConnection<'_> {
+    fn get_database_backend(&self) -> DbBackend {
+        self.conn.get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        self.conn.execute(stmt).await
+    }
+
+    async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+        self.conn.execute_unprepared(sql).await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        self.conn.query_one(stmt).await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        self.conn.query_all(stmt).await
+    }
+
+    fn support_returning(&self) -> bool {
+        self.conn.support_returning()
+    }
+
+    fn is_mock_connection(&self) -> bool {
+        self.conn.is_mock_connection()
+    }
+}
@@ -1,32 +1,201 @@
 //! SeaORM-centric closure tree implementation.
 //!
 //! This crate mirrors the behaviour of the Ruby `closure_tree` gem while exposing
-//! an asynchronous API that composes with SeaORM entities. At this stage the
-//! implementation focuses on PostgreSQL support; the public API is kept backend
-//! agnostic so MySQL can follow.
+//! an asynchronous API that composes with SeaORM entities. PostgreSQL is always
+//! supported, and SQLite is available behind the `sqlite` feature; the public API
+//! is kept backend agnostic so MySQL can follow.
 
+mod backend;
 pub mod config;
 pub mod error;
+pub mod ext;
+pub mod hooks;
 pub mod lock;
+mod metrics;
+#[cfg(feature = "migration")]
+pub mod migration;
+pub mod observer;
+mod path;
+pub mod report;
 pub mod repository;
+pub mod schema;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod traits;
+pub mod tree;
 
 pub mod prelude {
     //! Convenient re-exports for consumers.
     pub use crate::config::{
-        AdvisoryLockStrategy, ClosureTreeConfig, ClosureTreeOptions, DependentBehavior,
-        OrderStrategy,
+        AdvisoryLockScope, AdvisoryLockStrategy, CleanupPolicy, ClosureTreeConfig,
+        ClosureTreeOptions, DependentBehavior, LockAcquisition, NameMatching, OrderDirection,
+        OrderScope, OrderStrategy, RootMarker,
     };
-    pub use crate::traits::ClosureTreeModel;
+    pub use crate::ext::ClosureTreeModelExt;
+    pub use crate::hooks::{ClosureTreeHooks, HookConnection};
+    pub use crate::lock::{LockedTransaction, SessionLockGuard};
+    pub use crate::observer::TreeObserver;
+    pub use crate::traits::{
+        ClosureTreeModel, DefaultTree, FractionalPosition, HasCounterCache, HasOrdering,
+        NamedClosureTreeModel, NodeRef,
+    };
+    pub use crate::tree::{GenerationRange, TreeInput, TreeNode};
 }
 
+/// For implementing [`ClosureTreeHooks`]; also used by the trait the derive
+/// generates for `extension_trait`.
+pub use async_trait::async_trait;
+/// Derives [`ClosureTreeModel`] (and [`NamedClosureTreeModel`] unless the
+/// model is marked `no_name`) for a SeaORM `Model`.
+///
+/// The fields the derive reads are checked against the struct, so a typo such
+/// as `parent_field = "parentid"` is reported at the attribute value, with the
+/// nearest field name as a suggestion ("did you mean `parent_id`?"). Fields
+/// left at their default names (`id`, `parent_id`, `name`) are reported at the
+/// struct, unknown options at their key, and non-struct targets at the `enum`
+/// or `union` keyword; `tests/ui` holds the exact messages.
+///
+/// Hierarchy rows hold one id per node, so a composite primary key needs the
+/// unique column identifying nodes marked `#[closure_tree(id)]` (or named by
+/// `id_field`):
+///
+/// ```compile_fail
+/// use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+/// use sea_orm::entity::prelude::*;
+///
+/// #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+/// #[sea_orm(table_name = "nodes")]
+/// #[closure_tree(generate_hierarchy_entity)]
+/// pub struct Model {
+///     #[sea_orm(primary_key, auto_increment = false)]
+///     pub tenant_id: i32,
+///     // error: ClosureTreeModel does not support composite primary keys as node ids; ...
+///     #[sea_orm(primary_key, auto_increment = false)]
+///     pub id: i32,
+///     pub parent_id: Option<i32>,
+///     pub name: String,
+/// }
+///
+/// #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// pub enum Relation {}
+///
+/// impl ActiveModelBehavior for ActiveModel {}
+/// # fn main() {}
+/// ```
+///
+/// Repository methods a schema cannot support are left out by their bounds:
+/// `move_up` and `move_down` need [`HasOrdering`], implemented for models with
+/// an `order_field`, `recount_children` needs [`HasCounterCache`], from a
+/// `children_count_field`, and path lookups need [`NamedClosureTreeModel`].
+///
+/// ```compile_fail
+/// use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+/// use closure_tree::ClosureTreeRepository;
+/// use sea_orm::entity::prelude::*;
+///
+/// #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+/// #[sea_orm(table_name = "nodes")]
+/// #[closure_tree(generate_hierarchy_entity)]
+/// pub struct Model {
+///     #[sea_orm(primary_key)]
+///     pub id: i32,
+///     pub parent_id: Option<i32>,
+///     pub name: String,
+/// }
+///
+/// #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// pub enum Relation {}
+///
+/// impl ActiveModelBehavior for ActiveModel {}
+///
+/// async fn raise(db: &DatabaseConnection, node: &Model) {
+///     // error: `Model: HasOrdering` is not satisfied
+///     ClosureTreeRepository::<Model>::new().move_up(db, node).await;
+/// }
+/// # fn main() {}
+/// ```
+///
+/// `features(paths, ordering, counter_cache)` lists the capabilities
+/// explicitly. Each listed one needs its field (`name`, `order_field`,
+/// `children_count_field`), each of those fields needs its capability, and a
+/// list without `paths` makes the model `no_name`:
+///
+/// ```compile_fail
+/// use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+/// use sea_orm::entity::prelude::*;
+///
+/// #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+/// #[sea_orm(table_name = "nodes")]
+/// // error: `ordering` in `features` requires `order_field`
+/// #[closure_tree(generate_hierarchy_entity, features(paths, ordering))]
+/// pub struct Model {
+///     #[sea_orm(primary_key)]
+///     pub id: i32,
+///     pub parent_id: Option<i32>,
+///     pub name: String,
+/// }
+///
+/// #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// pub enum Relation {}
+///
+/// impl ActiveModelBehavior for ActiveModel {}
+/// # fn main() {}
+/// ```
+///
+/// `extension_trait = "NodeTreeExt"` also generates a trait of that name (a
+/// bare `extension_trait` names it after `entity_name`, e.g. `ModelTreeExt`)
+/// whose `tree_parent`, `tree_children`, `tree_siblings`, `tree_ancestors`,
+/// `tree_descendants` and `tree_move_to_child_of` methods call the repository
+/// for the model:
+///
+/// ```no_run
+/// use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+/// use sea_orm::entity::prelude::*;
+///
+/// #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+/// #[sea_orm(table_name = "nodes")]
+/// #[closure_tree(generate_hierarchy_entity, extension_trait = "NodeTreeExt")]
+/// pub struct Model {
+///     #[sea_orm(primary_key)]
+///     pub id: i32,
+///     pub parent_id: Option<i32>,
+///     pub name: String,
+/// }
+///
+/// #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+/// pub enum Relation {}
+///
+/// impl ActiveModelBehavior for ActiveModel {}
+///
+/// // Move `node` up a level, next to its parent.
+/// async fn promote(db: &DatabaseConnection, node: &Model) -> Result<(), closure_tree::ClosureTreeError> {
+///     if let Some(grandparent) = node.tree_ancestors(db).await?.get(1) {
+///         node.tree_move_to_child_of(db, grandparent).await?;
+///     }
+///     Ok(())
+/// }
+/// # fn main() {}
+/// ```
 pub use closure_tree_macros::ClosureTreeModel as ClosureTreeModelDerive;
 #[doc(hidden)]
 pub use closure_tree_macros::ClosureTreeModel;
 pub use config::{
-    AdvisoryLockKey, AdvisoryLockStrategy, ClosureTreeConfig, ClosureTreeOptions,
-    DependentBehavior, OrderStrategy,
+    AdvisoryLockKey, AdvisoryLockScope, AdvisoryLockStrategy, CleanupPolicy, ClosureTreeConfig,
+    ClosureTreeOptions, DependentBehavior, LockAcquisition, NameMatching, OrderDirection,
+    OrderScope, OrderStrategy, RootMarker,
 };
 pub use error::ClosureTreeError;
+#[cfg(feature = "error-kind")]
+pub use error::ClosureTreeErrorKind;
+pub use ext::ClosureTreeModelExt;
+pub use hooks::{ClosureTreeHooks, HookConnection};
+pub use lock::{LockedTransaction, SessionLockGuard};
+pub use observer::TreeObserver;
+pub use report::ChangeReport;
 pub use repository::ClosureTreeRepository;
-pub use traits::ClosureTreeModel;
+pub use schema::SchemaFinding;
+pub use traits::{
+    ClosureTreeModel, DefaultTree, FractionalPosition, HasCounterCache, HasOrdering,
+    NamedClosureTreeModel, NodeRef,
+};
+pub use tree::{diff_trees, GenerationRange, NodeChange, TreeInput, TreeNode};
@@ -1,14 +1,58 @@
+use std::borrow::Cow;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
 use sea_orm::{
-    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, Statement,
-    TransactionTrait, Value,
+    ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr,
+    SqlxPostgresConnector, Statement, TransactionTrait, Value,
 };
+use sqlx::postgres::PgPoolOptions;
 
-use crate::config::AdvisoryLockStrategy;
+use crate::backend::{BackendOps, LockSupport};
+use crate::config::{AdvisoryLockKey, AdvisoryLockStrategy, CleanupPolicy, LockAcquisition};
 use crate::error::ClosureTreeError;
 
+/// Transaction holding the tree's advisory lock.
+///
+/// On PostgreSQL the lock is transaction-scoped (`pg_advisory_xact_lock`), so
+/// the server releases it when the transaction commits or rolls back, even if
+/// the transaction was aborted by an error. With
+/// [`AdvisoryLockStrategy::Disabled`] it is a plain transaction.
+///
+/// ```no_run
+/// # async fn run(db: &sea_orm::DatabaseConnection) -> Result<(), closure_tree::ClosureTreeError> {
+/// use closure_tree::{AdvisoryLockKey, AdvisoryLockStrategy, LockedTransaction};
+/// use sea_orm::ConnectionTrait;
+///
+/// let strategy = AdvisoryLockStrategy::Namespaced(AdvisoryLockKey::new("categories"));
+/// let locked = LockedTransaction::acquire(&strategy, db).await?;
+/// locked
+///     .connection()
+///     .execute_unprepared("UPDATE categories SET position = position + 1")
+///     .await?;
+/// locked.commit().await?;
+/// # Ok(())
+/// # }
+/// ```
 pub struct LockedTransaction {
-    txn: Option<DatabaseTransaction>,
-    key: Option<String>,
+    txn: DatabaseTransaction,
+    key: Option<AdvisoryLockKey>,
+    /// Every lock taken so far, consulted when operations are nested inside.
+    held: Mutex<Vec<(AdvisoryLockKey, LockMode)>>,
+    acquired_at: Instant,
+    waited: Duration,
+    cleanup: CleanupPolicy,
+    /// Callbacks to run once the transaction has committed.
+    on_commit: Mutex<Vec<Box<dyn FnOnce() + Send>>>,
+    #[cfg(feature = "sqlite")]
+    local_guards: Mutex<Vec<local::LocalLockGuard>>,
+}
+
+/// Whether a lock may be shared with other holders of the same key.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum LockMode {
+    Shared,
+    Exclusive,
 }
 
 impl LockedTransaction {
@@ -16,75 +60,548 @@ impl LockedTransaction {
         strategy: &AdvisoryLockStrategy,
         db: &DatabaseConnection,
     ) -> Result<Self, ClosureTreeError> {
-        let key = match strategy {
-            AdvisoryLockStrategy::Disabled => None,
-            AdvisoryLockStrategy::Namespaced(key) => Some(key.as_str().to_owned()),
-        };
+        Self::acquire_with(strategy, LockAcquisition::Blocking, db).await
+    }
+
+    /// Like [`acquire`](Self::acquire), bounding the wait according to `acquisition`.
+    pub async fn acquire_with(
+        strategy: &AdvisoryLockStrategy,
+        acquisition: LockAcquisition,
+        db: &DatabaseConnection,
+    ) -> Result<Self, ClosureTreeError> {
+        Self::acquire_scoped(strategy, None, acquisition, db).await
+    }
+
+    /// Lock a single scope (see [`AdvisoryLockScope`](crate::AdvisoryLockScope)):
+    /// the strategy's key is held shared and [`AdvisoryLockKey::scoped`] exclusively.
+    /// Without a scope the strategy's key is held exclusively.
+    pub async fn acquire_scoped(
+        strategy: &AdvisoryLockStrategy,
+        scope: Option<&str>,
+        acquisition: LockAcquisition,
+        db: &DatabaseConnection,
+    ) -> Result<Self, ClosureTreeError> {
+        Self::acquire_for(strategy, scope, acquisition, CleanupPolicy::default(), db).await
+    }
+
+    /// [`acquire_scoped`](Self::acquire_scoped), reporting failed rollbacks
+    /// according to `cleanup`.
+    pub(crate) async fn acquire_for(
+        strategy: &AdvisoryLockStrategy,
+        scope: Option<&str>,
+        acquisition: LockAcquisition,
+        cleanup: CleanupPolicy,
+        db: &DatabaseConnection,
+    ) -> Result<Self, ClosureTreeError> {
+        let locks = required_locks(strategy, scope);
+        let ops = BackendOps::of(db);
+        let started = Instant::now();
+
+        if !locks.is_empty() && ops.lock_support() == LockSupport::Unsupported {
+            return Err(ops.unsupported("advisory locking"));
+        }
+
+        // Backends without advisory locks serialize within the process instead. The
+        // locks are taken before opening the transaction so waiters do not hold one
+        // of the (usually single) pooled connections.
+        #[cfg(feature = "sqlite")]
+        let mut local_guards = Vec::new();
+        #[cfg(feature = "sqlite")]
+        if ops.lock_support() == LockSupport::ProcessLocal {
+            for (key, mode) in &locks {
+                local_guards.push(local::lock(key, *mode, acquisition).await?);
+            }
+        }
 
         let txn = db.begin().await?;
 
-        if let Some(ref key) = key {
-            if let Err(err) = acquire_lock(&txn, key).await {
-                let _ = txn.rollback().await;
-                return Err(err);
+        if ops.lock_support() == LockSupport::Server {
+            for (key, mode) in &locks {
+                let (blocking, attempt) = xact_lock_functions(*mode);
+                if let Err(err) = acquire_lock(&txn, key, blocking, attempt, acquisition).await {
+                    let rollback = txn.rollback().await;
+                    return Err(after_rollback(cleanup, Some(key), err, rollback));
+                }
             }
         }
 
+        let waited = started.elapsed();
+        trace_acquired(&locks, waited);
         Ok(Self {
-            txn: Some(txn),
-            key,
+            txn,
+            key: locks
+                .iter()
+                .find(|(_, mode)| *mode == LockMode::Exclusive)
+                .map(|(key, _)| key.clone()),
+            held: Mutex::new(locks),
+            acquired_at: Instant::now(),
+            waited,
+            cleanup,
+            on_commit: Mutex::new(Vec::new()),
+            #[cfg(feature = "sqlite")]
+            local_guards: Mutex::new(local_guards),
         })
     }
 
-    pub fn connection(&self) -> &DatabaseTransaction {
-        self.txn.as_ref().expect("transaction already consumed")
-    }
+    /// Take whatever `strategy` and `scope` require that this transaction does
+    /// not hold yet, so operations nested inside it neither wait on its own
+    /// locks nor take them a second time.
+    pub(crate) async fn reenter(
+        &self,
+        strategy: &AdvisoryLockStrategy,
+        scope: Option<&str>,
+        acquisition: LockAcquisition,
+    ) -> Result<(), ClosureTreeError> {
+        let missing: Vec<_> = {
+            let held = self.held.lock().unwrap_or_else(PoisonError::into_inner);
+            required_locks(strategy, scope)
+                .into_iter()
+                .filter(|(key, mode)| {
+                    !held.iter().any(|(held_key, held_mode)| {
+                        held_key == key && (*held_mode == LockMode::Exclusive || held_mode == mode)
+                    })
+                })
+                .collect()
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
 
-    pub async fn commit(mut self) -> Result<(), ClosureTreeError> {
-        if let Some(ref key) = self.key {
-            if let Some(txn) = self.txn.as_ref() {
-                release_lock(txn, key).await?;
+        let ops = BackendOps::of(&self.txn);
+        let started = Instant::now();
+        match ops.lock_support() {
+            LockSupport::Server => {
+                for (key, mode) in &missing {
+                    let (blocking, attempt) = xact_lock_functions(*mode);
+                    acquire_lock(&self.txn, key, blocking, attempt, acquisition).await?;
+                }
+            }
+            #[cfg(feature = "sqlite")]
+            LockSupport::ProcessLocal => {
+                for (key, mode) in &missing {
+                    // A process-local shared lock cannot be upgraded by its own holder.
+                    let upgrade = self
+                        .held
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .iter()
+                        .any(|(held_key, _)| held_key == key);
+                    if upgrade {
+                        return Err(ClosureTreeError::invariant(format!(
+                            "cannot upgrade the shared process-local lock `{key}`"
+                        )));
+                    }
+                    let guard = local::lock(key, *mode, acquisition).await?;
+                    self.local_guards
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push(guard);
+                }
             }
+            LockSupport::Unsupported => return Err(ops.unsupported("advisory locking")),
         }
 
-        if let Some(txn) = self.txn.take() {
-            txn.commit().await?;
+        trace_acquired(&missing, started.elapsed());
+        self.held
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(missing);
+        Ok(())
+    }
+
+    /// Key held exclusively: the scoped key for [`acquire_scoped`](Self::acquire_scoped),
+    /// otherwise the strategy's key. `None` when locking is disabled.
+    pub fn key(&self) -> Option<&AdvisoryLockKey> {
+        self.key.as_ref()
+    }
+
+    /// [`key`](Self::key) as text: a named key's name, or a numeric key's
+    /// decimal value (the `bigint` passed to PostgreSQL), as numeric keys are
+    /// the default. `None` when locking is disabled.
+    pub fn key_str(&self) -> Option<Cow<'_, str>> {
+        self.key.as_ref().map(|key| match key {
+            AdvisoryLockKey::Named(name) => Cow::Borrowed(name.as_str()),
+            AdvisoryLockKey::Numeric(value) => Cow::Owned(value.to_string()),
+        })
+    }
+
+    /// Whether an advisory lock was taken at all.
+    pub fn is_locked(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Time spent waiting for the locks taken when the transaction was opened.
+    pub fn lock_wait(&self) -> Duration {
+        self.waited
+    }
+
+    /// Run `callback` after a successful [`commit`](Self::commit); it is dropped
+    /// on rollback.
+    pub(crate) fn after_commit(&self, callback: impl FnOnce() + Send + 'static) {
+        self.on_commit
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(callback));
+    }
+
+    pub fn connection(&self) -> &DatabaseTransaction {
+        &self.txn
+    }
+
+    /// Hand over the transaction itself.
+    ///
+    /// Pending observer notifications are discarded, since the commit is no longer
+    /// visible here. Process-local locks are released immediately. PostgreSQL has no way to
+    /// release a transaction-scoped lock early, so there the lock stays held
+    /// until the returned transaction commits or rolls back.
+    pub fn into_transaction(self) -> DatabaseTransaction {
+        self.txn
+    }
+
+    /// Commit the transaction, releasing the advisory lock with it.
+    pub async fn commit(self) -> Result<(), ClosureTreeError> {
+        self.txn.commit().await?;
+        trace_released(self.key.as_ref(), self.acquired_at.elapsed());
+
+        let callbacks = self
+            .on_commit
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        for callback in callbacks {
+            callback();
         }
+        Ok(())
+    }
 
+    /// Roll back the transaction, releasing the advisory lock with it.
+    pub async fn rollback(self) -> Result<(), ClosureTreeError> {
+        self.txn.rollback().await?;
+        trace_released(self.key.as_ref(), self.acquired_at.elapsed());
         Ok(())
     }
 
-    pub async fn rollback(mut self) -> Result<(), ClosureTreeError> {
-        if let Some(ref key) = self.key {
-            if let Some(txn) = self.txn.as_ref() {
-                let _ = release_lock(txn, key).await;
-            }
+    /// Roll back after `error`, returning the error to report according to the
+    /// cleanup policy.
+    pub(crate) async fn abort(self, error: ClosureTreeError) -> ClosureTreeError {
+        let rollback = self.txn.rollback().await;
+        if rollback.is_ok() {
+            trace_released(self.key.as_ref(), self.acquired_at.elapsed());
+        }
+        after_rollback(self.cleanup, self.key.as_ref(), error, rollback)
+    }
+}
+
+/// Locks an operation on `scope` needs: the strategy's key exclusively, or
+/// shared plus the scoped key exclusively.
+fn required_locks(
+    strategy: &AdvisoryLockStrategy,
+    scope: Option<&str>,
+) -> Vec<(AdvisoryLockKey, LockMode)> {
+    match (strategy.key(), scope) {
+        (None, _) => Vec::new(),
+        (Some(key), None) => vec![(key.clone(), LockMode::Exclusive)],
+        (Some(key), Some(scope)) => vec![
+            (key.clone(), LockMode::Shared),
+            (key.scoped(scope), LockMode::Exclusive),
+        ],
+    }
+}
+
+/// Blocking and non-blocking transaction-scoped lock functions for `mode`.
+fn xact_lock_functions(mode: LockMode) -> (&'static str, &'static str) {
+    match mode {
+        LockMode::Shared => (
+            "pg_advisory_xact_lock_shared",
+            "pg_try_advisory_xact_lock_shared",
+        ),
+        LockMode::Exclusive => ("pg_advisory_xact_lock", "pg_try_advisory_xact_lock"),
+    }
+}
+
+/// Debug event for locks granted after waiting `waited` (`tracing` feature).
+fn trace_acquired(_locks: &[(AdvisoryLockKey, LockMode)], _waited: Duration) {
+    #[cfg(feature = "tracing")]
+    for (key, mode) in _locks {
+        tracing::debug!(
+            key = %key,
+            mode = ?mode,
+            waited_ms = _waited.as_millis() as u64,
+            "closure_tree advisory lock acquired"
+        );
+    }
+}
+
+/// Debug event for a lock released after being held for `held` (`tracing` feature).
+fn trace_released(_key: Option<&AdvisoryLockKey>, _held: Duration) {
+    #[cfg(feature = "tracing")]
+    if let Some(key) = _key {
+        tracing::debug!(
+            key = %key,
+            held_ms = _held.as_millis() as u64,
+            "closure_tree advisory lock released"
+        );
+    }
+}
+
+/// Fold a failed rollback into the error that triggered it.
+fn after_rollback(
+    policy: CleanupPolicy,
+    key: Option<&AdvisoryLockKey>,
+    error: ClosureTreeError,
+    rollback: Result<(), DbErr>,
+) -> ClosureTreeError {
+    let Err(cleanup) = rollback else {
+        return error;
+    };
+
+    #[cfg(feature = "tracing")]
+    tracing::error!(
+        key = key.map(ToString::to_string),
+        %error,
+        %cleanup,
+        "rollback after a failed closure-tree operation failed; the connection may still hold the lock"
+    );
+    #[cfg(not(feature = "tracing"))]
+    eprintln!(
+        "closure-tree: rollback after a failed operation failed; the connection may still hold the lock (key: {}, error: {error}, rollback: {cleanup})",
+        key.map_or_else(|| "none".to_owned(), ToString::to_string),
+    );
+    match policy {
+        CleanupPolicy::BestEffort => error,
+        CleanupPolicy::Strict => ClosureTreeError::CleanupFailed {
+            source: Box::new(error),
+            cleanup: Box::new(cleanup.into()),
+        },
+    }
+}
+
+/// Session-level advisory lock (`pg_advisory_lock`) held outside any transaction.
+///
+/// The lock lives on a dedicated database session opened for the guard, so it
+/// survives across any number of transactions. Run those transactions through
+/// [`connection`](Self::connection): transaction-scoped locks on the same key,
+/// including the ones taken by [`LockedTransaction`] and the repository, are
+/// re-entrant within that session and do not wait on the guard.
+///
+/// Call [`release`](Self::release) when done. Async work cannot run in `Drop`,
+/// so a guard dropped without being released logs an error and closes its
+/// session instead; the server releases the lock once the session ends.
+pub struct SessionLockGuard {
+    conn: Option<DatabaseConnection>,
+    key: AdvisoryLockKey,
+    acquired_at: Instant,
+}
+
+impl SessionLockGuard {
+    /// Open a dedicated session on `db`'s server and take `key`, waiting as long as needed.
+    pub async fn acquire(
+        key: &AdvisoryLockKey,
+        db: &DatabaseConnection,
+    ) -> Result<Self, ClosureTreeError> {
+        Self::acquire_with(key, LockAcquisition::Blocking, db).await
+    }
+
+    /// Like [`acquire`](Self::acquire), bounding the wait according to `acquisition`.
+    pub async fn acquire_with(
+        key: &AdvisoryLockKey,
+        acquisition: LockAcquisition,
+        db: &DatabaseConnection,
+    ) -> Result<Self, ClosureTreeError> {
+        let DatabaseConnection::SqlxPostgresPoolConnection(_) = db else {
+            return Err(BackendOps::of(db).unsupported("session advisory locking"));
+        };
+
+        let options = db.get_postgres_connection_pool().connect_options();
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with((*options).clone())
+            .await?;
+        let conn = SqlxPostgresConnector::from_sqlx_postgres_pool(pool);
+
+        let started = Instant::now();
+        if let Err(err) = acquire_lock(
+            &conn,
+            key,
+            "pg_advisory_lock",
+            "pg_try_advisory_lock",
+            acquisition,
+        )
+        .await
+        {
+            let _ = conn.close().await;
+            return Err(err);
         }
 
-        if let Some(txn) = self.txn.take() {
-            txn.rollback().await?;
+        let key = key.clone();
+        trace_acquired(&[(key.clone(), LockMode::Exclusive)], started.elapsed());
+        Ok(Self {
+            conn: Some(conn),
+            key,
+            acquired_at: Instant::now(),
+        })
+    }
+
+    pub fn key(&self) -> &AdvisoryLockKey {
+        &self.key
+    }
+
+    /// Connection bound to the session holding the lock.
+    pub fn connection(&self) -> &DatabaseConnection {
+        self.conn.as_ref().expect("session lock already released")
+    }
+
+    /// Unlock the key and close the dedicated session.
+    pub async fn release(mut self) -> Result<(), ClosureTreeError> {
+        let Some(conn) = self.conn.take() else {
+            return Ok(());
+        };
+
+        let unlocked = conn
+            .query_one(lock_statement("pg_advisory_unlock", &self.key))
+            .await;
+        let _ = conn.close().await;
+        let unlocked = match unlocked? {
+            Some(row) => row.try_get::<bool>("", "locked")?,
+            None => false,
+        };
+        if !unlocked {
+            return Err(ClosureTreeError::invariant(format!(
+                "session advisory lock `{}` was not held at release",
+                self.key
+            )));
         }
 
+        trace_released(Some(&self.key), self.acquired_at.elapsed());
         Ok(())
     }
 }
 
-async fn acquire_lock(txn: &DatabaseTransaction, key: &str) -> Result<(), ClosureTreeError> {
-    txn.execute(Statement::from_sql_and_values(
-        DbBackend::Postgres,
-        "SELECT pg_advisory_lock(hashtext($1), 0)",
-        vec![Value::from(key)],
-    ))
-    .await?;
-    Ok(())
+impl Drop for SessionLockGuard {
+    fn drop(&mut self) {
+        if self.conn.is_some() {
+            #[cfg(feature = "tracing")]
+            tracing::error!(
+                key = %self.key,
+                "SessionLockGuard dropped without release(); closing its session to free the lock"
+            );
+            #[cfg(not(feature = "tracing"))]
+            eprintln!(
+                "closure-tree: SessionLockGuard `{}` dropped without release(); closing its session to free the lock",
+                self.key
+            );
+        }
+    }
 }
 
-async fn release_lock(txn: &DatabaseTransaction, key: &str) -> Result<(), ClosureTreeError> {
-    txn.execute(Statement::from_sql_and_values(
-        DbBackend::Postgres,
-        "SELECT pg_advisory_unlock(hashtext($1), 0)",
-        vec![Value::from(key)],
-    ))
-    .await?;
-    Ok(())
+/// Take `key` with the `blocking` lock function, or by polling `attempt` when
+/// the wait is bounded.
+async fn acquire_lock(
+    conn: &impl ConnectionTrait,
+    key: &AdvisoryLockKey,
+    blocking: &str,
+    attempt: &str,
+    acquisition: LockAcquisition,
+) -> Result<(), ClosureTreeError> {
+    let (timeout, poll_interval) = match acquisition {
+        LockAcquisition::Blocking => {
+            conn.execute(lock_statement(blocking, key)).await?;
+            return Ok(());
+        }
+        LockAcquisition::TryFor {
+            timeout,
+            poll_interval,
+        } => (timeout, poll_interval),
+    };
+
+    let started = Instant::now();
+    loop {
+        let row = conn.query_one(lock_statement(attempt, key)).await?;
+        let locked = match row {
+            Some(row) => row.try_get::<bool>("", "locked")?,
+            None => false,
+        };
+        if locked {
+            return Ok(());
+        }
+
+        let waited = started.elapsed();
+        if waited >= timeout {
+            return Err(ClosureTreeError::LockTimeout {
+                key: key.to_string(),
+                waited,
+            });
+        }
+        tokio::time::sleep(poll_interval.min(timeout - waited)).await;
+    }
+}
+
+/// `SELECT function(...) AS locked` for the two-int form of named keys or the
+/// single-bigint form of numeric keys.
+fn lock_statement(function: &str, key: &AdvisoryLockKey) -> Statement {
+    match key {
+        AdvisoryLockKey::Named(name) => Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!("SELECT {function}(hashtext($1), 0) AS locked"),
+            vec![Value::from(name.as_str())],
+        ),
+        AdvisoryLockKey::Numeric(value) => Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!("SELECT {function}($1) AS locked"),
+            vec![Value::from(*value)],
+        ),
+    }
+}
+
+/// Process-local stand-in for advisory locks on backends that lack them.
+#[cfg(feature = "sqlite")]
+mod local {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use once_cell::sync::Lazy;
+    use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+
+    use super::LockMode;
+    use crate::config::{AdvisoryLockKey, LockAcquisition};
+    use crate::error::ClosureTreeError;
+
+    pub(super) enum LocalLockGuard {
+        Shared { _guard: OwnedRwLockReadGuard<()> },
+        Exclusive { _guard: OwnedRwLockWriteGuard<()> },
+    }
+
+    static LOCKS: Lazy<Mutex<HashMap<AdvisoryLockKey, Arc<RwLock<()>>>>> =
+        Lazy::new(|| Mutex::new(HashMap::new()));
+
+    pub(super) async fn lock(
+        key: &AdvisoryLockKey,
+        mode: LockMode,
+        acquisition: LockAcquisition,
+    ) -> Result<LocalLockGuard, ClosureTreeError> {
+        let lock = {
+            let mut locks = LOCKS
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            locks.entry(key.clone()).or_default().clone()
+        };
+        let guard = async move {
+            match mode {
+                LockMode::Shared => LocalLockGuard::Shared {
+                    _guard: lock.read_owned().await,
+                },
+                LockMode::Exclusive => LocalLockGuard::Exclusive {
+                    _guard: lock.write_owned().await,
+                },
+            }
+        };
+        match acquisition {
+            LockAcquisition::Blocking => Ok(guard.await),
+            LockAcquisition::TryFor { timeout, .. } => tokio::time::timeout(timeout, guard)
+                .await
+                .map_err(|_| ClosureTreeError::LockTimeout {
+                    key: key.to_string(),
+                    waited: timeout,
+                }),
+        }
+    }
 }
@@ -0,0 +1,131 @@
+//! Per-operation query counts and durations, reported through
+//! [`TreeObserver::on_operation`]. Compiled only with the `metrics` feature;
+//! without it [`metered!`] expands to nothing.
+
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, ExecResult, QueryResult, Statement};
+
+#[cfg(feature = "metrics")]
+use crate::observer::TreeObserver;
+
+/// Count the queries issued through `$conn` for the rest of the enclosing
+/// block, shadowing it with a counting wrapper, and report them under
+/// `$operation` when the block ends.
+#[cfg(feature = "metrics")]
+macro_rules! metered {
+    ($repo:expr, $operation:literal, $conn:ident) => {
+        let meter = $repo.meter($operation);
+        let $conn = &meter.wrap($conn);
+    };
+}
+
+#[cfg(not(feature = "metrics"))]
+macro_rules! metered {
+    ($repo:expr, $operation:literal, $conn:ident) => {};
+}
+
+pub(crate) use metered;
+
+/// One running operation; reports to the observer when dropped, so failed
+/// operations are measured as well.
+#[cfg(feature = "metrics")]
+pub(crate) struct Meter<'a, Id> {
+    observer: Option<&'a dyn TreeObserver<Id>>,
+    entity: &'a str,
+    operation: &'static str,
+    started: Instant,
+    queries: AtomicU32,
+}
+
+#[cfg(feature = "metrics")]
+impl<'a, Id> Meter<'a, Id> {
+    pub(crate) fn new(
+        observer: Option<&'a dyn TreeObserver<Id>>,
+        entity: &'a str,
+        operation: &'static str,
+    ) -> Self {
+        Self {
+            observer,
+            entity,
+            operation,
+            started: Instant::now(),
+            queries: AtomicU32::new(0),
+        }
+    }
+
+    pub(crate) fn wrap<'m, C: ConnectionTrait>(&'m self, conn: &'m C) -> Metered<'m, C> {
+        Metered {
+            conn,
+            queries: &self.queries,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<Id> Drop for Meter<'_, Id> {
+    fn drop(&mut self) {
+        if let Some(observer) = self.observer {
+            observer.on_operation(
+                self.entity,
+                self.operation,
+                self.queries.load(Ordering::Relaxed),
+                self.started.elapsed(),
+            );
+        }
+    }
+}
+
+/// Connection wrapper counting every statement sent through it.
+#[cfg(feature = "metrics")]
+pub(crate) struct Metered<'a, C> {
+    conn: &'a C,
+    queries: &'a AtomicU32,
+}
+
+#[cfg(feature = "metrics")]
+impl<C> Metered<'_, C> {
+    fn count(&self) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "metrics")]
+#[async_trait::async_trait]
+impl<C: ConnectionTrait> ConnectionTrait for Metered<'_, C> {
+    fn get_database_backend(&self) -> DbBackend {
+        self.conn.get_database_backend()
+    }
+
+    async fn execute(&self, stmt: Statement) -> Result<ExecResult, DbErr> {
+        self.count();
+        self.conn.execute(stmt).await
+    }
+
+    async fn execute_unprepared(&self, sql: &str) -> Result<ExecResult, DbErr> {
+        self.count();
+        self.conn.execute_unprepared(sql).await
+    }
+
+    async fn query_one(&self, stmt: Statement) -> Result<Option<QueryResult>, DbErr> {
+        self.count();
+        self.conn.query_one(stmt).await
+    }
+
+    async fn query_all(&self, stmt: Statement) -> Result<Vec<QueryResult>, DbErr> {
+        self.count();
+        self.conn.query_all(stmt).await
+    }
+
+    fn support_returning(&self) -> bool {
+        self.conn.support_returning()
+    }
+
+    fn is_mock_connection(&self) -> bool {
+        self.conn.is_mock_connection()
+    }
+}
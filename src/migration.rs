@@ -0,0 +1,120 @@
+//! Hierarchy table DDL derived from a [`ClosureTreeConfig`], for migrations,
+//! enabled by the `migration` feature.
+//!
+//! The statements are plain sea-query statements, so they can be passed to a
+//! `sea_orm_migration::SchemaManager`, e.g.
+//! `manager.create_table(create_hierarchy_table_statement(config, ColumnType::Integer))`.
+//! Names come from the same configuration the repository uses at runtime.
+
+use sea_orm::sea_query::{
+    Alias, ColumnDef, ColumnType, ForeignKey, ForeignKeyAction, ForeignKeyCreateStatement, Index,
+    IndexCreateStatement, IntoTableRef, Table, TableCreateStatement, TableDropStatement, TableRef,
+};
+
+use crate::config::ClosureTreeConfig;
+
+/// `CREATE TABLE` for the hierarchy table: ancestor and descendant columns of
+/// `id_type`, the integer generations column and the composite primary key on
+/// (ancestor, descendant).
+pub fn create_hierarchy_table_statement(
+    config: &ClosureTreeConfig,
+    id_type: ColumnType,
+) -> TableCreateStatement {
+    Table::create()
+        .table(hierarchy_table(config))
+        .if_not_exists()
+        .col(
+            ColumnDef::new_with_type(
+                Alias::new(config.hierarchy_ancestor_column()),
+                id_type.clone(),
+            )
+            .not_null(),
+        )
+        .col(
+            ColumnDef::new_with_type(Alias::new(config.hierarchy_descendant_column()), id_type)
+                .not_null(),
+        )
+        .col(
+            ColumnDef::new(Alias::new(config.hierarchy_generations_column()))
+                .integer()
+                .not_null(),
+        )
+        .primary_key(
+            Index::create()
+                .col(Alias::new(config.hierarchy_ancestor_column()))
+                .col(Alias::new(config.hierarchy_descendant_column())),
+        )
+        .to_owned()
+}
+
+/// The secondary indexes of the hierarchy table recommended by
+/// [`ClosureTreeRepository::recommended_indexes`](crate::ClosureTreeRepository::recommended_indexes):
+/// one on the descendant column, for ancestor lookups the primary key cannot
+/// serve, and a unique one on the
+/// [`hierarchy_conflict_columns`](ClosureTreeConfig::hierarchy_conflict_columns)
+/// when they are configured.
+pub fn hierarchy_index_statements(config: &ClosureTreeConfig) -> Vec<IndexCreateStatement> {
+    let mut indexes = vec![Index::create()
+        .name(format!("{}_descendant_idx", config.hierarchy_table()))
+        .table(hierarchy_table(config))
+        .col(Alias::new(config.hierarchy_descendant_column()))
+        .if_not_exists()
+        .to_owned()];
+    if let Some(columns) = config.hierarchy_conflict_columns() {
+        let mut index = Index::create()
+            .name(format!("{}_conflict_idx", config.hierarchy_table()))
+            .table(hierarchy_table(config))
+            .unique()
+            .if_not_exists()
+            .to_owned();
+        for column in columns {
+            index.col(Alias::new(column));
+        }
+        indexes.push(index);
+    }
+    indexes
+}
+
+/// Foreign keys from the ancestor and descendant columns to `id_column` of the
+/// node table `table`, deleting hierarchy rows with their nodes.
+pub fn hierarchy_foreign_key_statements(
+    config: &ClosureTreeConfig,
+    table: &str,
+    id_column: &str,
+) -> Vec<ForeignKeyCreateStatement> {
+    let nodes = table_ref(config.schema_name(), table);
+    [
+        config.hierarchy_ancestor_column(),
+        config.hierarchy_descendant_column(),
+    ]
+    .into_iter()
+    .map(|column| {
+        ForeignKey::create()
+            .name(format!("{}_{column}_fkey", config.hierarchy_table()))
+            .from(hierarchy_table(config), Alias::new(column))
+            .to(nodes.clone(), Alias::new(id_column))
+            .on_delete(ForeignKeyAction::Cascade)
+            .to_owned()
+    })
+    .collect()
+}
+
+/// `DROP TABLE` for the hierarchy table, for `MigrationTrait::down`.
+pub fn drop_hierarchy_table_statement(config: &ClosureTreeConfig) -> TableDropStatement {
+    Table::drop()
+        .table(hierarchy_table(config))
+        .if_exists()
+        .to_owned()
+}
+
+/// The hierarchy table, qualified with the configured schema.
+fn hierarchy_table(config: &ClosureTreeConfig) -> TableRef {
+    table_ref(config.schema_name(), config.hierarchy_table())
+}
+
+fn table_ref(schema: Option<&str>, table: &str) -> TableRef {
+    match schema {
+        Some(schema) => (Alias::new(schema), Alias::new(table)).into_table_ref(),
+        None => Alias::new(table).into_table_ref(),
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use crate::report::ChangeReport;
+
+/// Callbacks for tree changes, registered with
+/// [`ClosureTreeRepository::with_observer`](crate::ClosureTreeRepository::with_observer).
+///
+/// Mutation callbacks run only after the transaction that made the change has
+/// committed, so nothing is reported for work that was rolled back. `entity` is
+/// the configured entity name, letting one observer serve several models.
+/// Every method defaults to doing nothing.
+pub trait TreeObserver<Id>: Send + Sync {
+    fn on_node_created(&self, _entity: &str, _id: &Id) {}
+
+    fn on_node_moved(
+        &self,
+        _entity: &str,
+        _id: &Id,
+        _old_parent: Option<&Id>,
+        _new_parent: Option<&Id>,
+    ) {
+    }
+
+    /// `deleted` lists every removed node, `root` included.
+    fn on_subtree_deleted(&self, _entity: &str, _root: &Id, _deleted: &[Id]) {}
+
+    /// Time spent waiting for the advisory lock, reported as soon as it is granted.
+    fn on_lock_wait(&self, _entity: &str, _waited: Duration) {}
+
+    /// Called when a repository operation returns, successfully or not, with the
+    /// number of statements it sent and its wall-clock time.
+    ///
+    /// Mutations are measured once their lock is held, so lock waits are not
+    /// included (see [`on_lock_wait`](Self::on_lock_wait)), nor are the
+    /// transaction's `BEGIN`, lock and `COMMIT` statements. `children_of` and
+    /// `descendants_of` report their node lookup, then the listing as
+    /// `children` or `descendants`.
+    #[cfg(feature = "metrics")]
+    fn on_operation(
+        &self,
+        _entity: &str,
+        _operation: &'static str,
+        _queries: u32,
+        _elapsed: Duration,
+    ) {
+    }
+}
+
+/// A change recorded during an operation, delivered to the observer after commit.
+pub(crate) enum TreeChange<Id> {
+    Created(Id),
+    Moved {
+        id: Id,
+        from: Option<Id>,
+        to: Option<Id>,
+    },
+    SubtreeDeleted {
+        root: Id,
+        deleted: Vec<Id>,
+    },
+}
+
+impl<Id: Clone> TreeChange<Id> {
+    /// Changes described by the report of destroying `root`; none for a dry run.
+    pub(crate) fn destroyed(root: Id, report: &ChangeReport<Id>) -> Vec<Self> {
+        if report.dry_run {
+            return Vec::new();
+        }
+        let mut changes: Vec<Self> = report
+            .reparented
+            .iter()
+            .map(|id| Self::Moved {
+                id: id.clone(),
+                from: Some(root.clone()),
+                to: None,
+            })
+            .collect();
+        changes.push(Self::SubtreeDeleted {
+            root,
+            deleted: report.deleted.clone(),
+        });
+        changes
+    }
+
+    pub(crate) fn notify(&self, observer: &dyn TreeObserver<Id>, entity: &str) {
+        match self {
+            Self::Created(id) => observer.on_node_created(entity, id),
+            Self::Moved { id, from, to } => {
+                observer.on_node_moved(entity, id, from.as_ref(), to.as_ref())
+            }
+            Self::SubtreeDeleted { root, deleted } => {
+                observer.on_subtree_deleted(entity, root, deleted)
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Splitting and joining the separator-delimited path strings accepted by
+//! [`ClosureTreeRepository::find_by_path_str`](crate::ClosureTreeRepository::find_by_path_str)
+//! and its siblings.
+//!
+//! A backslash makes the next character part of the name, so `a\/b` is the
+//! single name `a/b` and `a\\` is `a\`.
+
+use sea_orm::Value;
+
+const ESCAPE: char = '\\';
+
+/// The names in `path`, skipping the empty segments left by leading, trailing
+/// or repeated separators. A trailing lone backslash is kept as a literal.
+pub(crate) fn split_path(path: &str, separator: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut rest = path;
+    while let Some(next) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix(separator) {
+            if !current.is_empty() {
+                segments.push(std::mem::take(&mut current));
+            }
+            rest = after;
+            continue;
+        }
+        rest = &rest[next.len_utf8()..];
+        if next == ESCAPE {
+            if let Some(escaped) = rest.chars().next() {
+                current.push(escaped);
+                rest = &rest[escaped.len_utf8()..];
+                continue;
+            }
+        }
+        current.push(next);
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+/// Join `names` with `separator`, escaping backslashes and every character of
+/// the separator, so [`split_path`] returns the same names.
+pub(crate) fn join_path<'a>(names: impl IntoIterator<Item = &'a str>, separator: &str) -> String {
+    let mut path = String::new();
+    for (index, name) in names.into_iter().enumerate() {
+        if index > 0 {
+            path.push_str(separator);
+        }
+        for char in name.chars() {
+            if char == ESCAPE || separator.contains(char) {
+                path.push(ESCAPE);
+            }
+            path.push(char);
+        }
+    }
+    path
+}
+
+/// A name value as path or lock-key text: strings as they are, anything else
+/// through its `Display` form.
+pub(crate) fn segment_text(name: &Value) -> String {
+    match name {
+        Value::String(Some(name)) => name.to_string(),
+        name => name.to_string(),
+    }
+}
@@ -0,0 +1,42 @@
+/// Rows changed by a destructive repository operation.
+///
+/// In dry-run mode (see
+/// [`ClosureTreeRepository::with_dry_run`](crate::ClosureTreeRepository::with_dry_run))
+/// the report lists what would have changed, and nothing is written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeReport<Id> {
+    pub dry_run: bool,
+    /// Nodes deleted.
+    pub deleted: Vec<Id>,
+    /// Nodes whose parent was cleared because their parent was deleted.
+    pub reparented: Vec<Id>,
+    /// Nodes whose hierarchy rows were rewritten from `parent_id`.
+    pub rebuilt: Vec<Id>,
+    /// `(ancestor, descendant)` hierarchy rows removed because a node is missing.
+    pub pruned: Vec<(Id, Id)>,
+}
+
+impl<Id> ChangeReport<Id> {
+    pub(crate) fn new(dry_run: bool) -> Self {
+        Self {
+            dry_run,
+            deleted: Vec::new(),
+            reparented: Vec::new(),
+            rebuilt: Vec::new(),
+            pruned: Vec::new(),
+        }
+    }
+
+    /// Number of nodes and hierarchy rows listed in the report.
+    pub fn rows_affected(&self) -> usize {
+        self.deleted.len() + self.reparented.len() + self.rebuilt.len() + self.pruned.len()
+    }
+
+    /// Whether the operation changed (or would change) anything.
+    pub fn is_empty(&self) -> bool {
+        self.deleted.is_empty()
+            && self.reparented.is_empty()
+            && self.rebuilt.is_empty()
+            && self.pruned.is_empty()
+    }
+}
@@ -1,329 +1,5121 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
 
 use sea_orm::{
     entity::prelude::*, ColumnTrait, Condition, ConnectionTrait, DbBackend, EntityTrait,
-    QueryFilter, QueryOrder,
+    FromQueryResult, Order, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Select, Statement,
 };
 
-use sea_orm::sea_query::Expr;
+use sea_orm::sea_query::{
+    Alias, Asterisk, BinOper, ColumnType, CommonTableExpression, Expr, Func, Keyword, OnConflict,
+    PostgresQueryBuilder, Query, QueryBuilder, SelectStatement, SimpleExpr, SubQueryStatement,
+    UnionType, WithClause,
+};
 
-use crate::config::{ClosureTreeConfig, OrderStrategy};
+use crate::backend::BackendOps;
+use crate::config::{
+    AdvisoryLockScope, AdvisoryLockStrategy, ClosureTreeConfig, DependentBehavior, LockAcquisition,
+    NameMatching, OrderDirection, OrderScope, OrderStrategy, RootMarker,
+};
 use crate::error::ClosureTreeError;
+use crate::hooks::{ClosureTreeHooks, HookConnection};
 use crate::lock::LockedTransaction;
-use crate::traits::ClosureTreeModel;
+use crate::metrics::metered;
+#[cfg(feature = "metrics")]
+use crate::metrics::Meter;
+use crate::observer::{TreeChange, TreeObserver};
+use crate::path;
+use crate::report::ChangeReport;
+use crate::schema::{self, ColumnKind, ExpectedColumn, SchemaFinding};
+use crate::traits::{
+    ClosureTreeModel, DefaultTree, HasCounterCache, HasOrdering, NamedClosureTreeModel, NodeRef,
+};
+use crate::tree::{GenerationRange, TreeInput, TreeNode};
 
 /// Repository exposing the higher-level closure-tree operations for a given model.
-#[derive(Debug, Default)]
-pub struct ClosureTreeRepository<M>
+///
+/// A repository is cheap to clone (its observer and hooks are shared behind
+/// `Arc`s), and [`new`](Self::new) is `const`, so one can live in a `static`
+/// or in shared application state and be cloned into spawned tasks.
+///
+/// Read methods accept any [`ConnectionTrait`] and run single statements on
+/// it, without opening a transaction or taking a lock, so they can be pointed
+/// at a read replica or at [`LockedTransaction::connection`] to see the
+/// transaction's own writes. Writes take a `DatabaseConnection` and lock it.
+pub struct ClosureTreeRepository<M, Tag = DefaultTree>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    statement_timeout: Option<Duration>,
+    lock_scope: Option<AdvisoryLockScope>,
+    lock_strategy: Option<AdvisoryLockStrategy>,
+    lock_acquisition: Option<LockAcquisition>,
+    dry_run: bool,
+    include_deleted: bool,
+    observer: Option<Arc<dyn TreeObserver<M::Id>>>,
+    hooks: Option<Arc<dyn ClosureTreeHooks<M, Tag>>>,
+    config: Option<ClosureTreeConfig>,
+    scope: Option<Value>,
+    _marker: PhantomData<(M, fn() -> Tag)>,
+}
+
+// Implemented by hand so that tree markers need not be `Clone`.
+impl<M, Tag> Clone for ClosureTreeRepository<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            statement_timeout: self.statement_timeout,
+            lock_scope: self.lock_scope.clone(),
+            lock_strategy: self.lock_strategy.clone(),
+            lock_acquisition: self.lock_acquisition,
+            dry_run: self.dry_run,
+            include_deleted: self.include_deleted,
+            observer: self.observer.clone(),
+            hooks: self.hooks.clone(),
+            config: self.config.clone(),
+            scope: self.scope.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, Tag> fmt::Debug for ClosureTreeRepository<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClosureTreeRepository")
+            .field("statement_timeout", &self.statement_timeout)
+            .field("lock_scope", &self.lock_scope)
+            .field("lock_strategy", &self.lock_strategy)
+            .field("lock_acquisition", &self.lock_acquisition)
+            .field("dry_run", &self.dry_run)
+            .field("include_deleted", &self.include_deleted)
+            .field("observer", &self.observer.is_some())
+            .field("hooks", &self.hooks.is_some())
+            .field("config", &self.config)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+impl<M, Tag> Default for ClosureTreeRepository<M, Tag>
 where
-    M: ClosureTreeModel,
+    M: ClosureTreeModel<Tag>,
 {
-    _marker: PhantomData<M>,
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl<M> ClosureTreeRepository<M>
+impl<M, Tag> ClosureTreeRepository<M, Tag>
 where
-    M: ClosureTreeModel,
+    M: ClosureTreeModel<Tag>,
 {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
+            statement_timeout: None,
+            lock_scope: None,
+            lock_strategy: None,
+            lock_acquisition: None,
+            dry_run: false,
+            include_deleted: false,
+            observer: None,
+            hooks: None,
+            config: None,
+            scope: None,
             _marker: PhantomData,
         }
     }
 
-    fn config(&self) -> &'static ClosureTreeConfig {
-        M::closure_tree_config()
+    /// Override the configured statement timeout for operations run through this repository.
+    pub fn with_statement_timeout(mut self, timeout: Duration) -> Self {
+        self.statement_timeout = Some(timeout);
+        self
+    }
+
+    /// Override the configured advisory lock scope for operations run through this repository.
+    pub fn with_lock_scope(mut self, scope: AdvisoryLockScope) -> Self {
+        self.lock_scope = Some(scope);
+        self
+    }
+
+    /// Override the configured advisory lock strategy for operations run through
+    /// this repository, e.g. [`AdvisoryLockStrategy::Disabled`] in a batch job that
+    /// already holds an external lock.
+    pub fn with_lock_strategy(mut self, strategy: AdvisoryLockStrategy) -> Self {
+        self.lock_strategy = Some(strategy);
+        self
+    }
+
+    /// Override the configured lock acquisition for operations run through this
+    /// repository, e.g. a shorter [`LockAcquisition::try_for`] on a hot endpoint.
+    pub fn with_lock_acquisition(mut self, acquisition: LockAcquisition) -> Self {
+        self.lock_acquisition = Some(acquisition);
+        self
+    }
+
+    /// Make [`destroy`](Self::destroy), [`soft_destroy`](Self::soft_destroy),
+    /// [`rebuild`](Self::rebuild) and [`prune_orphans`](Self::prune_orphans) report
+    /// what they would change without writing anything.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Include soft-deleted nodes (see
+    /// [`soft_delete_column`](ClosureTreeConfig::soft_delete_column)) in every
+    /// read, e.g. for admin views or to find a subtree to restore.
+    pub fn with_deleted(mut self, include: bool) -> Self {
+        self.include_deleted = include;
+        self
+    }
+
+    /// Report node creations, moves, deletions and lock waits to `observer`.
+    ///
+    /// Changes made through the `*_in` variants are reported when the caller's
+    /// [`LockedTransaction`] commits.
+    pub fn with_observer(mut self, observer: Arc<dyn TreeObserver<M::Id>>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    /// Run `hooks` inside the transaction of every node creation, move and
+    /// `destroy`; see [`ClosureTreeHooks`].
+    pub fn with_hooks(mut self, hooks: Arc<dyn ClosureTreeHooks<M, Tag>>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// `CREATE INDEX` statements (valid on PostgreSQL and SQLite) for the lookups
+    /// this repository runs. Under
+    /// [`unique_sibling_names`](ClosureTreeConfig::unique_sibling_names) they
+    /// include partial unique indexes on `(parent, name)` and on root names, so
+    /// the database enforces what the repository checks, and with
+    /// [`hierarchy_conflict_columns`](ClosureTreeConfig::hierarchy_conflict_columns)
+    /// the unique index their `ON CONFLICT` target needs.
+    pub fn recommended_indexes(&self) -> Vec<String> {
+        let table = M::Entity::default().table_name().to_owned();
+        let hierarchy = M::HierarchyEntity::default().table_name().to_owned();
+        let parent = quote_ident(M::parent_column().as_str());
+
+        let mut indexes = vec![format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+            quote_ident(&format!("{hierarchy}_descendant_idx")),
+            qualified_table(M::HierarchyEntity::default()),
+            quote_ident(M::hierarchy_descendant_column().as_str()),
+        )];
+        if let Some(columns) = self.config().hierarchy_conflict_columns() {
+            let columns: Vec<String> = columns.iter().map(|column| quote_ident(column)).collect();
+            indexes.push(format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({})",
+                quote_ident(&format!("{hierarchy}_conflict_idx")),
+                qualified_table(M::HierarchyEntity::default()),
+                columns.join(", "),
+            ));
+        }
+        let Some(name) = M::name_column() else {
+            indexes.push(format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} ({parent})",
+                quote_ident(&format!("{table}_parent_idx")),
+                qualified_table(M::Entity::default()),
+            ));
+            return indexes;
+        };
+        let name = match self.config().name_matching() {
+            NameMatching::Exact => quote_ident(name.as_str()),
+            NameMatching::CaseInsensitive => format!("LOWER({})", quote_ident(name.as_str())),
+        };
+        if self.config().unique_sibling_names() {
+            let (root, child) = match self.config().root_marker() {
+                RootMarker::Null => (format!("{parent} IS NULL"), format!("{parent} IS NOT NULL")),
+                RootMarker::Zero => (
+                    format!("({parent} IS NULL OR {parent} = 0)"),
+                    format!("{parent} <> 0"),
+                ),
+                RootMarker::SelfReference => {
+                    let id = quote_ident(M::id_column().as_str());
+                    (
+                        format!("({parent} IS NULL OR {parent} = {id})"),
+                        format!("{parent} <> {id}"),
+                    )
+                }
+            };
+            // Soft-deleted nodes do not block reusing their name.
+            let live = self
+                .config()
+                .soft_delete_column()
+                .map(|column| format!(" AND {} IS NULL", quote_ident(column)))
+                .unwrap_or_default();
+            indexes.push(format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({parent}, {name}) \
+                 WHERE {child}{live}",
+                quote_ident(&format!("{table}_parent_name_key")),
+                qualified_table(M::Entity::default()),
+            ));
+            // Root names are unique per scope; parents already belong to one.
+            let scope = self
+                .config()
+                .scope_column()
+                .map(|column| format!("{}, ", quote_ident(column)))
+                .unwrap_or_default();
+            indexes.push(format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} ({scope}{name}) WHERE {root}{live}",
+                quote_ident(&format!("{table}_root_name_key")),
+                qualified_table(M::Entity::default()),
+            ));
+        } else {
+            indexes.push(format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} ({parent}, {name})",
+                quote_ident(&format!("{table}_parent_name_idx")),
+                qualified_table(M::Entity::default()),
+            ));
+        }
+        indexes
+    }
+
+    /// Check the configuration ([`ClosureTreeConfig::validate`]), its agreement
+    /// with the model, and that the entity and hierarchy tables have every
+    /// configured column with a compatible type, e.g. once at startup. Returns
+    /// every problem found; an empty list means the schema is usable.
+    ///
+    /// Reads `information_schema` for the entities' schemas, or the current schema
+    /// for tables without one (PostgreSQL only).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.check_schema",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn check_schema(
+        &self,
+        conn: &impl ConnectionTrait,
+    ) -> Result<Vec<SchemaFinding>, ClosureTreeError> {
+        if conn.get_database_backend() != DbBackend::Postgres {
+            return Err(BackendOps::of(conn).unsupported("schema checks"));
+        }
+        metered!(self, "check_schema", conn);
+        let config = self.config();
+        let mut findings = config.validate();
+
+        let table = M::Entity::default().table_name().to_owned();
+        let schema = M::Entity::default().schema_name().map(str::to_owned);
+        let hierarchy = M::HierarchyEntity::default().table_name().to_owned();
+        let hierarchy_schema = M::HierarchyEntity::default()
+            .schema_name()
+            .map(str::to_owned);
+        if !config.hierarchy_table().is_empty() && config.hierarchy_table() != hierarchy {
+            findings.push(SchemaFinding::InvalidConfig(format!(
+                "hierarchy table `{}` is not the hierarchy entity's table `{hierarchy}`",
+                config.hierarchy_table()
+            )));
+        }
+        if config.schema_name() != hierarchy_schema.as_deref() {
+            findings.push(SchemaFinding::InvalidConfig(format!(
+                "schema {:?} is not the hierarchy entity's schema {:?}",
+                config.schema_name(),
+                hierarchy_schema
+            )));
+        }
+        let mut typed = vec![
+            (
+                "parent",
+                config.parent_column(),
+                M::parent_column().as_str().to_owned(),
+            ),
+            (
+                "hierarchy ancestor",
+                config.hierarchy_ancestor_column(),
+                M::hierarchy_ancestor_column().as_str().to_owned(),
+            ),
+            (
+                "hierarchy descendant",
+                config.hierarchy_descendant_column(),
+                M::hierarchy_descendant_column().as_str().to_owned(),
+            ),
+            (
+                "hierarchy generations",
+                config.hierarchy_generations_column(),
+                M::hierarchy_generations_column().as_str().to_owned(),
+            ),
+        ];
+        match (config.name_column(), M::name_column()) {
+            (Some(configured), Some(model)) => {
+                typed.insert(1, ("name", configured, model.as_str().to_owned()));
+            }
+            (None, None) => {}
+            (configured, model) => findings.push(SchemaFinding::InvalidConfig(format!(
+                "the name column is {configured:?} but the model uses {:?}",
+                model.map(|column| column.as_str().to_owned())
+            ))),
+        }
+        for (role, configured, model) in typed {
+            if configured != model {
+                findings.push(SchemaFinding::InvalidConfig(format!(
+                    "the {role} column is `{configured}` but the model uses `{model}`"
+                )));
+            }
+        }
+
+        let expect =
+            |(schema, table): (&Option<String>, &str), role, column: &str, kind| ExpectedColumn {
+                schema: schema.clone(),
+                table: table.to_owned(),
+                column: column.to_owned(),
+                role,
+                kind,
+                nullable: matches!(role, "parent" | "soft-delete"),
+            };
+        let base = (&schema, table.as_str());
+        let mut expected = vec![expect(base, "id", M::id_column().as_str(), ColumnKind::Any)];
+        expected.extend(config.columns().into_iter().map(|(role, column)| {
+            let kind = match role {
+                "parent" => ColumnKind::Id,
+                "name" if Self::text_names() => ColumnKind::Text,
+                "order"
+                    if matches!(
+                        config.order_strategy(),
+                        Some(OrderStrategy::FractionalColumn { .. })
+                    ) =>
+                {
+                    ColumnKind::Fractional
+                }
+                "order" | "children count" => ColumnKind::Integer,
+                "ltree" => ColumnKind::Ltree,
+                "touch" | "soft-delete" => ColumnKind::Timestamp,
+                _ => ColumnKind::Any,
+            };
+            expect(base, role, column, kind)
+        }));
+        let hierarchy = (&hierarchy_schema, hierarchy.as_str());
+        expected.extend([
+            expect(
+                hierarchy,
+                "hierarchy ancestor",
+                M::hierarchy_ancestor_column().as_str(),
+                ColumnKind::Id,
+            ),
+            expect(
+                hierarchy,
+                "hierarchy descendant",
+                M::hierarchy_descendant_column().as_str(),
+                ColumnKind::Id,
+            ),
+            expect(
+                hierarchy,
+                "hierarchy generations",
+                M::hierarchy_generations_column().as_str(),
+                ColumnKind::Integer,
+            ),
+        ]);
+        expected.extend(
+            config
+                .hierarchy_conflict_columns()
+                .unwrap_or_default()
+                .iter()
+                .map(|column| {
+                    expect(
+                        hierarchy,
+                        "hierarchy conflict target",
+                        column,
+                        ColumnKind::Any,
+                    )
+                }),
+        );
+        findings.extend(schema::inspect(conn, &expected).await?);
+        record_rows(findings.len());
+        Ok(findings)
+    }
+
+    /// Use `config` instead of the model's static configuration for this repository.
+    ///
+    /// Typically built from the static one, e.g.
+    /// `ClosureTreeOptions::default().order_strategy(..).apply(M::closure_tree_config().clone())`.
+    /// The numeric order column itself always comes from
+    /// [`ClosureTreeModel::order_column`].
+    pub fn with_config(mut self, config: ClosureTreeConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// A copy of this repository confined to the forest whose
+    /// [`scope_column`](ClosureTreeConfig::scope_column) equals `value`, e.g. one
+    /// tenant's trees.
+    ///
+    /// Every read filters on the scope, created nodes are stamped with it, and
+    /// writes lock only this scope's key (see [`AdvisoryLockKey::scoped`]), so
+    /// different scopes do not wait for each other. `prune_orphans` and
+    /// `recount_children` still cover the whole table.
+    ///
+    /// Fails with [`UnsupportedOperation`](ClosureTreeError::UnsupportedOperation)
+    /// when the config has no scope column, since nothing would keep the
+    /// scopes apart.
+    ///
+    /// [`AdvisoryLockKey::scoped`]: crate::AdvisoryLockKey::scoped
+    pub fn scoped(&self, value: impl Into<Value>) -> Result<Self, ClosureTreeError> {
+        self.scope_column_for("scoped")?;
+        Ok(Self {
+            scope: Some(value.into()),
+            ..self.clone()
+        })
+    }
+
+    fn scope_column_for(&self, operation: &'static str) -> Result<&str, ClosureTreeError> {
+        self.config()
+            .scope_column()
+            .ok_or(ClosureTreeError::UnsupportedOperation {
+                operation,
+                reason: "no scope_column is configured",
+            })
+    }
+
+    /// Nodes visible to reads: those in [`scoped`](Self::scoped)'s scope that are
+    /// not soft-deleted, unless [`with_deleted`](Self::with_deleted).
+    fn find(&self) -> Select<M::Entity> {
+        let query = self.find_with_deleted();
+        match self.config().soft_delete_column() {
+            Some(column) if !self.include_deleted => {
+                query.filter(Expr::col(Alias::new(column)).is_null())
+            }
+            _ => query,
+        }
+    }
+
+    /// Nodes of the base table, narrowed to [`scoped`](Self::scoped)'s value.
+    /// Writes that must see whole subtrees use this over [`find`](Self::find).
+    fn find_with_deleted(&self) -> Select<M::Entity> {
+        match self.scope_condition() {
+            Some(condition) => M::Entity::find().filter(condition),
+            None => M::Entity::find(),
+        }
+    }
+
+    /// `scope_column = value` for a [`scoped`](Self::scoped) repository.
+    fn scope_condition(&self) -> Option<SimpleExpr> {
+        match (&self.scope, self.config().scope_column()) {
+            (Some(value), Some(column)) => Some(Expr::col(Alias::new(column)).eq(value.clone())),
+            _ => None,
+        }
+    }
+
+    /// Stamp [`scoped`](Self::scoped)'s value onto a node about to be inserted,
+    /// through the column of this repository's config.
+    fn stamp_scope(&self, active: &mut M::ActiveModel) -> Result<(), ClosureTreeError> {
+        let Some(value) = &self.scope else {
+            return Ok(());
+        };
+        // A config swapped in by `with_config` after `scoped` may have dropped it.
+        let column = self.scope_column_for("create")?;
+        match M::scope_column().filter(|typed| typed.as_str() == column) {
+            Some(_) => M::set_scope_value(active, value.clone()),
+            // A column the model does not declare, e.g. one named through `with_config`.
+            None => {
+                let column = column
+                    .parse::<<M::Entity as EntityTrait>::Column>()
+                    .map_err(|_| ClosureTreeError::UnsupportedOperation {
+                        operation: "create",
+                        reason: "the scope_column is not a column of the entity",
+                    })?;
+                active.set(column, value.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn config(&self) -> &ClosureTreeConfig {
+        self.config
+            .as_ref()
+            .unwrap_or_else(|| M::closure_tree_config())
+    }
+
+    /// Apply the configured sibling ordering, breaking ties by name.
+    fn ordered(&self, query: Select<M::Entity>) -> Select<M::Entity> {
+        let query = match self.config().order_strategy() {
+            Some(OrderStrategy::None) => return query,
+            Some(OrderStrategy::ByName { direction }) => {
+                if let Some(column) = M::name_column() {
+                    return query.order_by(column, Order::from(*direction));
+                }
+                query
+            }
+            Some(
+                OrderStrategy::NumericColumn { direction, .. }
+                | OrderStrategy::FractionalColumn { direction, .. },
+            ) => match M::order_column() {
+                Some(column) => query.order_by(column, Order::from(*direction)),
+                None => query,
+            },
+            Some(OrderStrategy::Expression { expr, direction }) => {
+                query.order_by(expr.clone(), Order::from(*direction))
+            }
+            Some(OrderStrategy::Manual) | None => query,
+        };
+        self.then_by_name(query)
+    }
+
+    /// [`ordered`](Self::ordered) for a query over roots, which only sorts by name
+    /// and id under [`dont_order_roots`](ClosureTreeConfig::dont_order_roots).
+    fn ordered_roots(&self, query: Select<M::Entity>) -> Select<M::Entity> {
+        if !self.config().dont_order_roots() {
+            return self.ordered(query);
+        }
+        self.then_by_name(query).order_by_asc(M::id_column())
+    }
+
+    /// `model`'s parent, or `None` for a root: a `NULL` parent or the
+    /// configured [`RootMarker`].
+    fn parent_of(&self, model: &M) -> Option<M::Id> {
+        let parent = model.parent_id()?;
+        let marks_root = match self.config().root_marker() {
+            RootMarker::Null => false,
+            RootMarker::Zero => is_zero(&M::id_to_value(&parent)),
+            RootMarker::SelfReference => parent == model.id(),
+        };
+        (!marks_root).then_some(parent)
+    }
+
+    /// Nodes whose parent is `parent_id`, or roots for `None`.
+    fn parent_filter(&self, parent_id: Option<&M::Id>) -> SimpleExpr {
+        let marker = self.config().root_marker();
+        match parent_id {
+            // A self-referencing root is not its own child.
+            Some(parent_id) if marker == RootMarker::SelfReference => M::parent_column()
+                .eq(M::id_to_value(parent_id))
+                .and(M::id_column().ne(M::id_to_value(parent_id))),
+            Some(parent_id) => M::parent_column().eq(M::id_to_value(parent_id)),
+            None => match marker {
+                RootMarker::Null => M::parent_column().is_null(),
+                RootMarker::Zero => M::parent_column()
+                    .is_null()
+                    .or(M::parent_column().eq(zero_value::<M, Tag>())),
+                RootMarker::SelfReference => M::parent_column().is_null().or(Expr::col((
+                    M::Entity::default(),
+                    M::parent_column(),
+                ))
+                .equals((M::Entity::default(), M::id_column()))),
+            },
+        }
+    }
+
+    /// Point `active` at `parent_id`, storing the configured [`RootMarker`]
+    /// for a root. A self-referencing root whose id the database assigns is
+    /// completed by [`link_root`](Self::link_root) once inserted.
+    fn set_parent(&self, active: &mut M::ActiveModel, parent_id: Option<&M::Id>) {
+        M::set_parent(active, parent_id.cloned());
+        if parent_id.is_some() {
+            return;
+        }
+        match self.config().root_marker() {
+            RootMarker::Null => {}
+            RootMarker::Zero => active.set(M::parent_column(), zero_value::<M, Tag>()),
+            RootMarker::SelfReference => {
+                if let Some(id) = active.get(M::id_column()).into_value() {
+                    active.set(M::parent_column(), id);
+                }
+            }
+        }
+    }
+
+    /// Point a freshly inserted root at itself under
+    /// [`RootMarker::SelfReference`]; other nodes are returned as they are.
+    async fn link_root<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        model: M,
+    ) -> Result<M, ClosureTreeError> {
+        if self.config().root_marker() != RootMarker::SelfReference || model.parent_id().is_some() {
+            return Ok(model);
+        }
+        let id = model.id();
+        let mut active = model.into_active_model();
+        M::set_parent(&mut active, Some(id));
+        Ok(active.update(conn).await?)
+    }
+
+    /// Run [`ClosureTreeHooks::before_create_child`] for a node about to be inserted.
+    async fn before_create<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+        active: &mut M::ActiveModel,
+    ) -> Result<(), ClosureTreeError> {
+        match &self.hooks {
+            Some(hooks) => {
+                hooks
+                    .before_create_child(&HookConnection::new(conn), parent_id, active)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Run [`ClosureTreeHooks::after_create_child`] for each of `created`.
+    async fn after_create<'a, C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        created: impl IntoIterator<Item = &'a M>,
+    ) -> Result<(), ClosureTreeError> {
+        let Some(hooks) = &self.hooks else {
+            return Ok(());
+        };
+        let conn = HookConnection::new(conn);
+        for child in created {
+            hooks.after_create_child(&conn, child).await?;
+        }
+        Ok(())
+    }
+
+    /// The parent value of a root under the configured [`RootMarker`], for
+    /// bulk updates.
+    fn root_parent_expr(&self) -> SimpleExpr {
+        match self.config().root_marker() {
+            RootMarker::Null => SimpleExpr::Keyword(Keyword::Null),
+            RootMarker::Zero => zero_value::<M, Tag>().into(),
+            RootMarker::SelfReference => Expr::col(M::id_column()).into(),
+        }
+    }
+
+    /// Break ordering ties by name, or by id for trees without names.
+    fn then_by_name(&self, query: Select<M::Entity>) -> Select<M::Entity> {
+        match M::name_column() {
+            Some(column) => query.order_by(column, self.name_order()),
+            None => query.order_by_asc(M::id_column()),
+        }
+    }
+
+    /// The numeric or fractional order column, when one is configured.
+    fn order_column(&self) -> Option<<M::Entity as EntityTrait>::Column> {
+        self.config()
+            .order_strategy()
+            .and_then(OrderStrategy::position_column)
+            .and_then(|_| M::order_column())
+    }
+
+    /// Whether positions are [`OrderStrategy::FractionalColumn`] ones.
+    fn fractional(&self) -> bool {
+        matches!(
+            self.config().order_strategy(),
+            Some(OrderStrategy::FractionalColumn { .. })
+        )
+    }
+
+    /// The position `rank` places from the start of an evenly numbered
+    /// sibling list.
+    fn nth_position(&self, rank: i64) -> Position {
+        if self.fractional() {
+            Position::Fractional(rank as f64)
+        } else {
+            Position::Numeric(rank * self.config().order_gap())
+        }
+    }
+
+    /// The stored position of `model`.
+    fn position_of(&self, model: &M) -> Option<Position> {
+        if self.fractional() {
+            model.fractional_order_value().map(Position::Fractional)
+        } else {
+            model.order_value().map(Position::Numeric)
+        }
+    }
+
+    fn set_position(
+        active: &mut M::ActiveModel,
+        position: Position,
+    ) -> Result<(), ClosureTreeError> {
+        match position {
+            Position::Numeric(value) => M::set_order_value(active, value),
+            Position::Fractional(value) => {
+                M::set_fractional_order_value(active, value);
+                Ok(())
+            }
+        }
+    }
+
+    fn name_order(&self) -> Order {
+        if self.config().name_order_descending() {
+            Order::Desc
+        } else {
+            Order::Asc
+        }
+    }
+
+    /// Whether `model` is a root: its parent is `NULL` or the configured
+    /// [`RootMarker`].
+    pub fn is_root(&self, model: &M) -> bool {
+        self.parent_of(model).is_none()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.parent",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+            )
+        )
+    )]
+    pub async fn parent(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Option<M>, ClosureTreeError> {
+        metered!(self, "parent", conn);
+        match self.parent_of(model) {
+            Some(parent_id) => {
+                let parent = self
+                    .find()
+                    .filter(M::id_column().eq(M::id_to_value(&parent_id)))
+                    .one(conn)
+                    .await?;
+                Ok(parent)
+            }
+            None => Ok(None),
+        }
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.children",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn children(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "children", conn);
+        let query = self.find().filter(self.parent_filter(Some(&model.id())));
+        let rows = self.ordered(query).all(conn).await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// Other children of `model`'s parent (other roots, for a root), in sibling order.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.siblings",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn siblings(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "siblings", conn);
+        let query = self
+            .find()
+            .filter(M::id_column().ne(M::id_to_value(&model.id())));
+        let parent_id = self.parent_of(model);
+        let query = query.filter(self.parent_filter(parent_id.as_ref()));
+        let query = match parent_id {
+            Some(_) => self.ordered(query),
+            None => self.ordered_roots(query),
+        };
+        let rows = query.all(conn).await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.roots",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn roots(&self, conn: &impl ConnectionTrait) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "roots", conn);
+        let query = self.find().filter(self.parent_filter(None));
+        let rows = self.ordered_roots(query).all(conn).await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// Ancestors of `model`, nearest first: its parent, then the parent's parent,
+    /// up to the root.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestors",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestors(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "ancestors", conn);
+        let rows = self
+            .load_ancestors(conn, &model.id(), GenerationRange::AtLeast(1))
+            .await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// Ancestors of `model` from the root down to its parent, the order of a
+    /// breadcrumb. `model` itself is not included. The order is part of the
+    /// contract: the root is always first, the parent always last.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestors_root_first",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestors_root_first(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "ancestors_root_first", conn);
+        let mut rows = self
+            .load_ancestors(conn, &model.id(), GenerationRange::AtLeast(1))
+            .await?;
+        rows.reverse();
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// Ancestors of `model` from its parent up to the root, the same rows and
+    /// order as [`ancestors`](Self::ancestors) with the direction in the name.
+    /// `model` itself is not included; the parent is always first, the root
+    /// always last.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestors_node_first",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestors_node_first(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "ancestors_node_first", conn);
+        let rows = self
+            .load_ancestors(conn, &model.id(), GenerationRange::AtLeast(1))
+            .await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// The nearest `max_generations` ancestors of `model`, nearest first, e.g.
+    /// parent and grandparent for `2`; for breadcrumbs that collapse deep paths.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestors_to_depth",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestors_to_depth(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+        max_generations: i32,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "ancestors_to_depth", conn);
+        let rows = self
+            .load_ancestors(conn, &model.id(), GenerationRange::UpTo(max_generations))
+            .await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// Ancestors of `model` in the generations `range` covers, nearest first,
+    /// e.g. only the grandparent for `GenerationRange::Exactly(2)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestors_within",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestors_within(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+        range: GenerationRange,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "ancestors_within", conn);
+        let rows = self.load_ancestors(conn, &model.id(), range).await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// The query behind [`descendants`](Self::descendants), unexecuted, so
+    /// callers can add filters, joins or pagination before running it.
+    ///
+    /// Descendants are selected with a subquery on the hierarchy table, and the
+    /// configured order, scope and soft-delete filters are already applied. It
+    /// must still be run on a backend the repository supports.
+    pub fn descendants_query(&self, model: &M) -> Select<M::Entity> {
+        let descendants = Query::select()
+            .column(M::hierarchy_descendant_column())
+            .from(M::HierarchyEntity::default().table_ref())
+            .and_where(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(&model.id())))
+            .and_where(M::hierarchy_generations_column().gt(0))
+            .to_owned();
+        self.ordered(self.find().filter(M::id_column().in_subquery(descendants)))
+    }
+
+    /// `SELECT descendant FROM hierarchy WHERE ancestor = ?` as an expression,
+    /// for filtering other entities by subtree membership:
+    ///
+    /// ```ignore
+    /// Product::find().filter(
+    ///     Expr::col(product::Column::CategoryId)
+    ///         .binary(BinOper::In, categories.descendant_subquery(&shoes.id)),
+    /// )
+    /// ```
+    ///
+    /// The ancestor itself is included. Only the hierarchy table is read, so
+    /// scope and soft-delete filters are not applied; identifiers are quoted
+    /// for whichever backend the outer query is built for.
+    pub fn descendant_subquery(&self, ancestor: &M::Id) -> SimpleExpr {
+        let descendants = Query::select()
+            .column(M::hierarchy_descendant_column())
+            .from(M::HierarchyEntity::default().table_ref())
+            .and_where(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(ancestor)))
+            .to_owned();
+        SimpleExpr::SubQuery(
+            None,
+            Box::new(SubQueryStatement::SelectStatement(descendants)),
+        )
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.descendants",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn descendants(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "descendants", conn);
+        let rows = self.descendants_with_conn(conn, &model.id(), true).await?;
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// Descendants of `model` in the generations `range` covers, in the
+    /// configured order, e.g. the grandchildren for `GenerationRange::Exactly(2)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.descendants_within",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn descendants_within(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+        range: GenerationRange,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "descendants_within", conn);
+        let rows = match self
+            .load_descendants_query(conn, &model.id(), range.bounds())
+            .await?
+        {
+            Some(query) => query.all(conn).await?,
+            None => Vec::new(),
+        };
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    /// [`descendants`](Self::descendants) decoded into a projection `P` instead
+    /// of full models, e.g. a struct with only `id` and `name` for a tree picker.
+    ///
+    /// `P` reads its fields from the model's columns by name.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.descendants_as",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn descendants_as<P: FromQueryResult>(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<P>, ClosureTreeError> {
+        metered!(self, "descendants_as", conn);
+        let rows = match self
+            .load_descendants_query(conn, &model.id(), (1, None))
+            .await?
+        {
+            Some(query) => query.into_model::<P>().all(conn).await?,
+            None => Vec::new(),
+        };
+        record_rows(rows.len());
+        Ok(rows)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.self_and_descendants",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn self_and_descendants(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        metered!(self, "self_and_descendants", conn);
+        let mut nodes = Vec::with_capacity(1);
+        nodes.push(model.clone());
+        let mut descendants = self.descendants_with_conn(conn, &model.id(), true).await?;
+        nodes.append(&mut descendants);
+        record_rows(nodes.len());
+        Ok(nodes)
+    }
+
+    /// The descendants of `root` grouped by parent id, each group in sibling
+    /// order, from a single query: the adjacency form [`TreeNode`]s are nested
+    /// from, for front-ends that expand one level at a time.
+    ///
+    /// `root` is not included, so its id keys its children; leaves have no entry.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.children_by_parent",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?root.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn children_by_parent(
+        &self,
+        conn: &impl ConnectionTrait,
+        root: &M,
+    ) -> Result<HashMap<M::Id, Vec<M>>, ClosureTreeError> {
+        metered!(self, "children_by_parent", conn);
+        let descendants = self.descendants_with_conn(conn, &root.id(), true).await?;
+        record_rows(descendants.len());
+        let mut children: HashMap<M::Id, Vec<M>> = HashMap::new();
+        for node in descendants {
+            if let Some(parent) = self.parent_of(&node) {
+                children.entry(parent).or_default().push(node);
+            }
+        }
+        Ok(children)
+    }
+
+    /// Direct children of `node`, loading it first when given by id.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.children_of",
+            skip_all,
+            fields(entity = %self.config().entity_name())
+        )
+    )]
+    pub async fn children_of(
+        &self,
+        conn: &impl ConnectionTrait,
+        node: impl Into<NodeRef<M, Tag>>,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        let model = match node.into() {
+            NodeRef::Loaded(model) => model,
+            NodeRef::Id(id) => {
+                metered!(self, "children_of", conn);
+                self.load(conn, &id).await?
+            }
+        };
+        self.children(conn, &model).await
+    }
+
+    /// Descendants of `node`, loading it first when given by id.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.descendants_of",
+            skip_all,
+            fields(entity = %self.config().entity_name())
+        )
+    )]
+    pub async fn descendants_of(
+        &self,
+        conn: &impl ConnectionTrait,
+        node: impl Into<NodeRef<M, Tag>>,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        let model = match node.into() {
+            NodeRef::Loaded(model) => model,
+            NodeRef::Id(id) => {
+                metered!(self, "descendants_of", conn);
+                self.load(conn, &id).await?
+            }
+        };
+        self.descendants(conn, &model).await
+    }
+
+    /// Number of nodes in the subtree rooted at `model`, including `model` itself.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.subtree_size",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+            )
+        )
+    )]
+    pub async fn subtree_size(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<u64, ClosureTreeError> {
+        metered!(self, "subtree_size", conn);
+        let mut query = M::HierarchyEntity::find()
+            .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(&model.id())));
+        if self.config().soft_delete_column().is_some() && !self.include_deleted {
+            let live = self
+                .find()
+                .select_only()
+                .column(M::id_column())
+                .into_query();
+            query = query.filter(M::hierarchy_descendant_column().in_subquery(live));
+        }
+        let count = query.count(conn).await?;
+        Ok(count)
+    }
+
+    /// Number of direct children of `model`, counted with a single `COUNT(*)`
+    /// on any backend, without loading them. Soft-deleted children and those
+    /// outside the [`scoped`](Self::scoped) value are not counted.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.count_children",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+            )
+        )
+    )]
+    pub async fn count_children(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<u64, ClosureTreeError> {
+        metered!(self, "count_children", conn);
+        self.count_children_on(conn, &model.id()).await
+    }
+
+    /// Number of root nodes, counted with a single `COUNT(*)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.roots_count",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+            )
+        )
+    )]
+    pub async fn roots_count(&self, conn: &impl ConnectionTrait) -> Result<u64, ClosureTreeError> {
+        metered!(self, "roots_count", conn);
+        let count = self
+            .find()
+            .filter(self.parent_filter(None))
+            .count(conn)
+            .await?;
+        Ok(count)
+    }
+
+    /// Number of nodes in the table, counted with a single `COUNT(*)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.node_count",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+            )
+        )
+    )]
+    pub async fn node_count(&self, conn: &impl ConnectionTrait) -> Result<u64, ClosureTreeError> {
+        metered!(self, "node_count", conn);
+        let count = self.find().count(conn).await?;
+        Ok(count)
+    }
+
+    /// Whether `model` has at least one direct child, checked with a single `EXISTS` query.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.has_children",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+            )
+        )
+    )]
+    pub async fn has_children(
+        &self,
+        conn: &impl ConnectionTrait,
+        model: &M,
+    ) -> Result<bool, ClosureTreeError> {
+        metered!(self, "has_children", conn);
+        let child = self
+            .find()
+            .select_only()
+            .column(M::id_column())
+            .filter(self.parent_filter(Some(&model.id())))
+            .into_query();
+        let query = Query::select()
+            .expr_as(Expr::exists(child), Alias::new("has_children"))
+            .to_owned();
+
+        let row = conn
+            .query_one(conn.get_database_backend().build(&query))
+            .await?
+            .ok_or_else(|| ClosureTreeError::invariant("EXISTS query returned no row"))?;
+        Ok(row.try_get("", "has_children")?)
+    }
+
+    /// The `candidates` that are descendants of `ancestor`, in the order given,
+    /// e.g. which of a page of items lie in a user's permitted subtree.
+    ///
+    /// Candidates are checked with `ancestor = ? AND descendant IN (...)` on the
+    /// hierarchy table, a query per thousand candidates rather than one per
+    /// candidate. `ancestor` itself is not its own descendant, and scope and
+    /// soft-delete filters are not applied.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.filter_descendants_of",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?ancestor,
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn filter_descendants_of(
+        &self,
+        conn: &impl ConnectionTrait,
+        ancestor: &M::Id,
+        candidates: &[M::Id],
+    ) -> Result<Vec<M::Id>, ClosureTreeError> {
+        metered!(self, "filter_descendants_of", conn);
+        let mut descendants = HashSet::new();
+        for chunk in candidates.chunks(HIERARCHY_INSERT_CHUNK) {
+            let rows = M::HierarchyEntity::find()
+                .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(ancestor)))
+                .filter(M::hierarchy_generations_column().gt(0))
+                .filter(
+                    M::hierarchy_descendant_column()
+                        .is_in(chunk.iter().map(M::hierarchy_id_to_value)),
+                )
+                .all(conn)
+                .await?;
+            descendants.extend(rows.iter().map(M::hierarchy_model_descendant));
+        }
+        let found: Vec<M::Id> = candidates
+            .iter()
+            .filter(|candidate| descendants.contains(*candidate))
+            .cloned()
+            .collect();
+        record_rows(found.len());
+        Ok(found)
+    }
+
+    /// Walk down from the roots matching one level per value on `column`
+    /// instead of the name column, e.g. a stable integer `code`.
+    ///
+    /// [`find_by_path`](Self::find_by_path) is the name-column case of this lookup.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.find_by_attribute_path",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path_len = values.len(),
+            )
+        )
+    )]
+    pub async fn find_by_attribute_path<V>(
+        &self,
+        conn: &impl ConnectionTrait,
+        column: <M::Entity as EntityTrait>::Column,
+        values: &[V],
+    ) -> Result<Option<M>, ClosureTreeError>
+    where
+        V: Clone + Into<Value>,
+    {
+        metered!(self, "find_by_attribute_path", conn);
+        self.find_by_attribute_path_on(conn, column, values).await
+    }
+
+    /// Open a transaction holding the model's configured advisory lock (table-wide),
+    /// with the statement timeout applied.
+    ///
+    /// Run closure-tree mutations through the `*_in` variants and your own writes
+    /// through [`LockedTransaction::connection`], then commit them together.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.lock",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+            )
+        )
+    )]
+    pub async fn lock(
+        &self,
+        conn: &DatabaseConnection,
+    ) -> Result<LockedTransaction, ClosureTreeError> {
+        self.lock_transaction(conn, None).await
+    }
+
+    /// Copy `source` and all of its descendants beneath `new_parent` (or as a new
+    /// root), returning the root of the copy.
+    ///
+    /// Every column except the primary key is copied, so names and order values
+    /// are preserved; ids and hierarchy rows are generated afresh. With a numeric
+    /// order column the copied root is placed after its new siblings.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.duplicate_subtree",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?source.id(),
+            )
+        )
+    )]
+    pub async fn duplicate_subtree(
+        &self,
+        conn: &DatabaseConnection,
+        source: &M,
+        new_parent: Option<&M::Id>,
+    ) -> Result<M, ClosureTreeError> {
+        let first_segment = new_parent
+            .is_none()
+            .then(|| Self::name_of(source).map(|name| path::segment_text(&name)));
+        let guard = self
+            .lock_transaction(conn, first_segment.flatten().as_deref())
+            .await?;
+        let conn = guard.connection();
+        metered!(self, "duplicate_subtree", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .duplicate_subtree_on(conn, source, new_parent, &mut changes)
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// [`duplicate_subtree`](Self::duplicate_subtree) inside `txn`, taking only the
+    /// locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.duplicate_subtree_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?source.id(),
+            )
+        )
+    )]
+    pub async fn duplicate_subtree_in(
+        &self,
+        txn: &LockedTransaction,
+        source: &M,
+        new_parent: Option<&M::Id>,
+    ) -> Result<M, ClosureTreeError> {
+        let first_segment = new_parent
+            .is_none()
+            .then(|| Self::name_of(source).map(|name| path::segment_text(&name)));
+        self.reenter(txn, first_segment.flatten().as_deref())
+            .await?;
+        let conn = txn.connection();
+        metered!(self, "duplicate_subtree_in", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .duplicate_subtree_on(conn, source, new_parent, &mut changes)
+            .await;
+        self.observe(txn, changes);
+        result
+    }
+
+    /// Attach the node `node`, with its subtree, beneath `onto`.
+    ///
+    /// Meant for roots and orphans (nodes whose parent row is gone): with no
+    /// ancestor rows to remove, only the new rows linking `onto`'s ancestry to
+    /// the subtree are inserted. A node that still has ancestors is moved the
+    /// general way, detaching it from them first. Fails with
+    /// [`ClosureTreeError::CycleDetected`] if `onto` is inside the subtree.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.graft",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node,
+                parent_id = ?onto,
+            )
+        )
+    )]
+    pub async fn graft(
+        &self,
+        conn: &DatabaseConnection,
+        node: &M::Id,
+        onto: &M::Id,
+    ) -> Result<(), ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "graft", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .reparent_on(
+                conn,
+                node,
+                Some(onto),
+                self.config().order_scope(),
+                &mut changes,
+            )
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// [`graft`](Self::graft) inside `txn`, taking only the locks `txn` does not
+    /// already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.graft_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node,
+                parent_id = ?onto,
+            )
+        )
+    )]
+    pub async fn graft_in(
+        &self,
+        txn: &LockedTransaction,
+        node: &M::Id,
+        onto: &M::Id,
+    ) -> Result<(), ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "graft_in", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .reparent_on(
+                conn,
+                node,
+                Some(onto),
+                self.config().order_scope(),
+                &mut changes,
+            )
+            .await;
+        self.observe(txn, changes);
+        result
+    }
+
+    /// Move `node`, with its subtree, beneath `new_parent`, or make it a root
+    /// for `None`, keeping every position as it is whatever the configured
+    /// [`order_scope`](ClosureTreeConfig::order_scope): the move of
+    /// [`OrderScope::Global`], for positions that mean something outside the
+    /// parent. Fails with [`ClosureTreeError::CycleDetected`] if `new_parent` is
+    /// inside the subtree.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.reparent_preserving_order",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node,
+                parent_id = ?new_parent,
+            )
+        )
+    )]
+    pub async fn reparent_preserving_order(
+        &self,
+        conn: &DatabaseConnection,
+        node: &M::Id,
+        new_parent: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "reparent_preserving_order", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .reparent_on(conn, node, new_parent, OrderScope::Global, &mut changes)
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// [`reparent_preserving_order`](Self::reparent_preserving_order) inside
+    /// `txn`, taking only the locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.reparent_preserving_order_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node,
+                parent_id = ?new_parent,
+            )
+        )
+    )]
+    pub async fn reparent_preserving_order_in(
+        &self,
+        txn: &LockedTransaction,
+        node: &M::Id,
+        new_parent: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "reparent_preserving_order_in", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .reparent_on(conn, node, new_parent, OrderScope::Global, &mut changes)
+            .await;
+        self.observe(txn, changes);
+        result
+    }
+
+    /// Move every node of `nodes`, with its subtree, beneath `new_parent`, or
+    /// make them roots for `None`, in one transaction under one lock; for
+    /// "move selected to folder" actions.
+    ///
+    /// The moves are validated before anything is written: if `new_parent` is
+    /// one of `nodes` or inside one of their subtrees, this fails with
+    /// [`ClosureTreeError::CycleDetected`]. Moved nodes are appended after the new
+    /// siblings in the order given; repeated ids are moved once.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_many",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                nodes = nodes.len(),
+                parent_id = ?new_parent,
+            )
+        )
+    )]
+    pub async fn move_many(
+        &self,
+        conn: &DatabaseConnection,
+        nodes: &[M::Id],
+        new_parent: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "move_many", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .move_many_on(conn, nodes, new_parent, &mut changes)
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// [`move_many`](Self::move_many) inside `txn`, taking only the locks `txn`
+    /// does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_many_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                nodes = nodes.len(),
+                parent_id = ?new_parent,
+            )
+        )
+    )]
+    pub async fn move_many_in(
+        &self,
+        txn: &LockedTransaction,
+        nodes: &[M::Id],
+        new_parent: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "move_many_in", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .move_many_on(conn, nodes, new_parent, &mut changes)
+            .await;
+        self.observe(txn, changes);
+        result
+    }
+
+    /// Move `node`, with its subtree, beneath `new_parent` at index `position`
+    /// among its new siblings, shifting the later ones down; for drag and drop
+    /// into a chosen slot of another branch.
+    ///
+    /// `position` counts from `0` in the configured sibling order and is
+    /// clamped to the siblings that exist. Sibling positions are renumbered by
+    /// [`order_gap`](ClosureTreeConfig::order_gap); under
+    /// [`OrderStrategy::FractionalColumn`] only the moved node is written, at
+    /// the midpoint of its new neighbours. Without a numeric or fractional
+    /// column, or under [`OrderScope::Global`], the position is ignored and
+    /// this is [`graft`](Self::graft).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_to_child_of_at",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+                parent_id = ?new_parent,
+            )
+        )
+    )]
+    pub async fn move_to_child_of_at(
+        &self,
+        conn: &DatabaseConnection,
+        node: &M,
+        new_parent: &M::Id,
+        position: i32,
+    ) -> Result<(), ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "move_to_child_of_at", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .move_to_child_of_at_on(conn, &node.id(), new_parent, position, &mut changes)
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// [`move_to_child_of_at`](Self::move_to_child_of_at) inside `txn`, taking
+    /// only the locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_to_child_of_at_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+                parent_id = ?new_parent,
+            )
+        )
+    )]
+    pub async fn move_to_child_of_at_in(
+        &self,
+        txn: &LockedTransaction,
+        node: &M,
+        new_parent: &M::Id,
+        position: i32,
+    ) -> Result<(), ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "move_to_child_of_at_in", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .move_to_child_of_at_on(conn, &node.id(), new_parent, position, &mut changes)
+            .await;
+        self.observe(txn, changes);
+        result
+    }
+
+    /// Delete `model`, handling its descendants according to the configured
+    /// [`DependentBehavior`]:
+    ///
+    /// * `Nullify` deletes `model` only; its children become roots.
+    /// * `Destroy` deletes the whole subtree one node at a time, deepest first, so
+    ///   `ActiveModelBehavior` hooks run for every node.
+    /// * `DeleteAll` deletes the whole subtree with a single statement.
+    /// * `None` deletes `model` and its hierarchy rows only; children keep their
+    ///   `parent_id`, so the schema must allow (or cascade) the dangling reference.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.destroy",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn destroy(
+        &self,
+        conn: &DatabaseConnection,
+        model: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "destroy", conn);
+        let result = self.destroy_on(conn, model).await;
+        if let Ok(report) = &result {
+            self.observe(&guard, TreeChange::destroyed(model.id(), report));
+        }
+        Self::finish(guard, result)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// [`destroy`](Self::destroy) inside `txn`, taking only the locks `txn` does
+    /// not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.destroy_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn destroy_in(
+        &self,
+        txn: &LockedTransaction,
+        model: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "destroy_in", conn);
+        self.destroy_on(conn, model).await.inspect(|report| {
+            record_rows(report.rows_affected());
+            self.observe(txn, TreeChange::destroyed(model.id(), report));
+        })
+    }
+
+    /// Stamp the [`soft_delete_column`](ClosureTreeConfig::soft_delete_column) of
+    /// `model` and every live node below it with the current time, in one
+    /// `UPDATE`. Hierarchy rows are kept, so
+    /// [`restore_subtree`](Self::restore_subtree) only has to clear the column.
+    ///
+    /// The report lists the newly stamped nodes as `deleted`, and observers are
+    /// told the subtree was deleted.
+    /// Without a soft-delete column this fails with
+    /// [`UnsupportedOperation`](ClosureTreeError::UnsupportedOperation), as does
+    /// `restore_subtree`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.soft_destroy",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn soft_destroy(
+        &self,
+        conn: &DatabaseConnection,
+        model: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "soft_destroy", conn);
+        let result = self.soft_destroy_on(conn, model).await;
+        if let Ok(report) = &result {
+            self.observe(&guard, TreeChange::destroyed(model.id(), report));
+        }
+        Self::finish(guard, result)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// [`soft_destroy`](Self::soft_destroy) inside `txn`, taking only the locks
+    /// `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.soft_destroy_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn soft_destroy_in(
+        &self,
+        txn: &LockedTransaction,
+        model: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "soft_destroy_in", conn);
+        self.soft_destroy_on(conn, model).await.inspect(|report| {
+            record_rows(report.rows_affected());
+            self.observe(txn, TreeChange::destroyed(model.id(), report));
+        })
+    }
+
+    /// Clear the [`soft_delete_column`](ClosureTreeConfig::soft_delete_column) of
+    /// `model` and its whole subtree, returning how many nodes were restored.
+    ///
+    /// Look `model` up through a repository built
+    /// [`with_deleted`](Self::with_deleted). Restore from the topmost deleted
+    /// node: a subtree restored beneath a deleted parent stays unreachable by
+    /// path.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.restore_subtree",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn restore_subtree(
+        &self,
+        conn: &DatabaseConnection,
+        model: &M,
+    ) -> Result<u64, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "restore_subtree", conn);
+        let result = self.restore_subtree_on(conn, model).await;
+        Self::finish(guard, result)
+            .await
+            .inspect(|rows| record_rows(*rows as usize))
+    }
+
+    /// [`restore_subtree`](Self::restore_subtree) inside `txn`, taking only the
+    /// locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.restore_subtree_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn restore_subtree_in(
+        &self,
+        txn: &LockedTransaction,
+        model: &M,
+    ) -> Result<u64, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "restore_subtree_in", conn);
+        self.restore_subtree_on(conn, model)
+            .await
+            .inspect(|rows| record_rows(*rows as usize))
+    }
+
+    /// Recompute the hierarchy rows of every node from `parent_id`, rewriting
+    /// only the nodes whose rows differ.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.rebuild",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn rebuild(
+        &self,
+        conn: &DatabaseConnection,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "rebuild", conn);
+        let result = self.rebuild_on(conn).await;
+        Self::finish(guard, result)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// [`rebuild`](Self::rebuild) inside `txn`, taking only the locks `txn` does
+    /// not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.rebuild_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn rebuild_in(
+        &self,
+        txn: &LockedTransaction,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "rebuild_in", conn);
+        self.rebuild_on(conn)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// Recompute the hierarchy rows of `root` and its descendants from
+    /// `parent_id`, attached beneath the ancestors the hierarchy table already
+    /// records for `root`'s parent.
+    ///
+    /// The targeted repair after editing one branch directly in SQL: the
+    /// subtree is found by following `parent_id` down from `root`, so nodes
+    /// moved into it are picked up even though their rows are stale. Like
+    /// [`rebuild`](Self::rebuild), only the nodes whose rows differ are
+    /// rewritten.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.rebuild_subtree",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?root.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn rebuild_subtree(
+        &self,
+        conn: &DatabaseConnection,
+        root: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "rebuild_subtree", conn);
+        let result = self.rebuild_subtree_on(conn, root).await;
+        Self::finish(guard, result)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// [`rebuild_subtree`](Self::rebuild_subtree) inside `txn`, taking only the
+    /// locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.rebuild_subtree_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?root.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn rebuild_subtree_in(
+        &self,
+        txn: &LockedTransaction,
+        root: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "rebuild_subtree_in", conn);
+        self.rebuild_subtree_on(conn, root)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// Delete hierarchy rows whose ancestor or descendant no longer exists.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.prune_orphans",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn prune_orphans(
+        &self,
+        conn: &DatabaseConnection,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "prune_orphans", conn);
+        let result = self.prune_orphans_on(conn).await;
+        Self::finish(guard, result)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// [`prune_orphans`](Self::prune_orphans) inside `txn`, taking only the locks
+    /// `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.prune_orphans_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn prune_orphans_in(
+        &self,
+        txn: &LockedTransaction,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "prune_orphans_in", conn);
+        self.prune_orphans_on(conn)
+            .await
+            .inspect(|report| record_rows(report.rows_affected()))
+    }
+
+    /// Delete every node and hierarchy row, hierarchy rows first so foreign keys
+    /// into the base table hold throughout. A [`scoped`](Self::scoped) repository
+    /// clears only its own scope.
+    ///
+    /// Observers are not notified.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.clear",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+            )
+        )
+    )]
+    pub async fn clear(&self, conn: &DatabaseConnection) -> Result<(), ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "clear", conn);
+        let result = self.clear_on(conn).await;
+        Self::finish(guard, result).await
+    }
+
+    /// [`clear`](Self::clear) inside `txn`, taking only the locks `txn` does not
+    /// already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.clear_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+            )
+        )
+    )]
+    pub async fn clear_in(&self, txn: &LockedTransaction) -> Result<(), ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "clear_in", conn);
+        self.clear_on(conn).await
+    }
+
+    /// Open a transaction holding the configured advisory lock, with the
+    /// statement timeout applied.
+    ///
+    /// `first_segment` names the root the operation works under, when known, for
+    /// [`AdvisoryLockScope::PerFirstSegment`].
+    async fn lock_transaction(
+        &self,
+        conn: &DatabaseConnection,
+        first_segment: Option<&str>,
+    ) -> Result<LockedTransaction, ClosureTreeError> {
+        let config = self.config();
+        let scope = self.resolve_scope(first_segment);
+        let guard = LockedTransaction::acquire_for(
+            self.lock_strategy(),
+            scope.as_deref(),
+            self.lock_acquisition(),
+            config.cleanup_policy(),
+            conn,
+        )
+        .await?;
+        if let (Some(observer), true) = (&self.observer, guard.is_locked()) {
+            observer.on_lock_wait(config.entity_name(), guard.lock_wait());
+        }
+
+        let timeout = self
+            .statement_timeout
+            .or_else(|| config.statement_timeout())
+            .and_then(|timeout| BackendOps::of(conn).statement_timeout(timeout));
+        if let Some(statement) = timeout {
+            let result = guard.connection().execute(statement).await;
+            if let Err(err) = result {
+                return Err(guard.abort(err.into()).await);
+            }
+        }
+
+        Ok(guard)
+    }
+
+    /// Take the locks an operation needs inside the caller's `txn`, skipping the
+    /// ones it already holds.
+    async fn reenter(
+        &self,
+        txn: &LockedTransaction,
+        first_segment: Option<&str>,
+    ) -> Result<(), ClosureTreeError> {
+        let scope = self.resolve_scope(first_segment);
+        txn.reenter(
+            self.lock_strategy(),
+            scope.as_deref(),
+            self.lock_acquisition(),
+        )
+        .await
+    }
+
+    fn lock_strategy(&self) -> &AdvisoryLockStrategy {
+        self.lock_strategy
+            .as_ref()
+            .unwrap_or_else(|| self.config().advisory_lock_strategy())
+    }
+
+    fn lock_acquisition(&self) -> LockAcquisition {
+        self.lock_acquisition
+            .unwrap_or_else(|| self.config().lock_acquisition())
+    }
+
+    fn resolve_scope(&self, first_segment: Option<&str>) -> Option<String> {
+        let resolved = self
+            .lock_scope
+            .as_ref()
+            .unwrap_or_else(|| self.config().lock_scope())
+            .resolve(
+                first_segment
+                    .map(|segment| {
+                        let segment = path::segment_text(
+                            &self.normalize_value(self.trim_value(segment.into())),
+                        );
+                        // Names matching case-insensitively must share a lock.
+                        match self.config().name_matching() {
+                            NameMatching::CaseInsensitive => segment.to_lowercase(),
+                            NameMatching::Exact => segment,
+                        }
+                    })
+                    .as_deref(),
+            );
+        let scope = self.scope.as_ref().map(scope_key_text);
+        match (scope, resolved) {
+            (Some(scope), Some(resolved)) => Some(format!("scope:{scope}/{resolved}")),
+            (Some(scope), None) => Some(format!("scope:{scope}")),
+            (None, resolved) => resolved,
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    fn meter(&self, operation: &'static str) -> Meter<'_, M::Id> {
+        Meter::new(
+            self.observer.as_deref(),
+            self.config().entity_name(),
+            operation,
+        )
+    }
+
+    /// Hand `changes` to the observer once `txn` has committed.
+    fn observe(&self, txn: &LockedTransaction, changes: Vec<TreeChange<M::Id>>) {
+        let Some(observer) = self.observer.clone() else {
+            return;
+        };
+        if changes.is_empty() {
+            return;
+        }
+        let entity = self.config().entity_name().to_owned();
+        txn.after_commit(move || {
+            for change in &changes {
+                change.notify(observer.as_ref(), &entity);
+            }
+        });
+    }
+
+    /// Commit the guarded transaction if `result` is a success, roll it back otherwise.
+    async fn finish<T>(
+        guard: LockedTransaction,
+        result: Result<T, ClosureTreeError>,
+    ) -> Result<T, ClosureTreeError> {
+        match result {
+            Ok(value) => {
+                guard.commit().await?;
+                Ok(value)
+            }
+            Err(err) => Err(guard.abort(err).await),
+        }
+    }
+
+    /// Fetch a node that must exist, failing with [`ClosureTreeError::NodeNotFound`].
+    async fn load<C: ConnectionTrait>(&self, conn: &C, id: &M::Id) -> Result<M, ClosureTreeError> {
+        self.find()
+            .filter(M::id_column().eq(M::id_to_value(id)))
+            .one(conn)
+            .await?
+            .ok_or_else(|| ClosureTreeError::NodeNotFound {
+                entity: self.config().entity_name().to_owned(),
+                id: format!("{id:?}"),
+            })
+    }
+
+    async fn find_by_attribute_path_on<V: Clone + Into<Value>, C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        column: <M::Entity as EntityTrait>::Column,
+        values: &[V],
+    ) -> Result<Option<M>, ClosureTreeError> {
+        let conditions = values.iter().map(|value| column.eq(value.clone()));
+        self.find_path_by(conn, conditions).await
+    }
+
+    /// Walk down from the roots, picking the child matching each condition in turn.
+    async fn find_path_by<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        conditions: impl IntoIterator<Item = SimpleExpr>,
+    ) -> Result<Option<M>, ClosureTreeError> {
+        let mut current_parent: Option<M::Id> = None;
+        let mut current: Option<M> = None;
+
+        for condition in conditions {
+            let node = self
+                .find_child_by(conn, current_parent.as_ref(), condition)
+                .await?;
+
+            match node {
+                Some(model) => {
+                    current_parent = Some(model.id());
+                    current = Some(model);
+                }
+                None => return Ok(None),
+            }
+        }
+
+        Ok(current)
+    }
+
+    async fn duplicate_subtree_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        source: &M,
+        new_parent: Option<&M::Id>,
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<M, ClosureTreeError> {
+        if let Some(new_parent) = new_parent {
+            self.load(conn, new_parent).await?;
+        }
+        if let Some(name) = Self::name_of(source) {
+            self.ensure_unique_name(conn, new_parent, name, None)
+                .await?;
+        }
+        self.ensure_child_capacity(conn, new_parent, 1).await?;
+
+        let source_id = source.id();
+        let generations: HashMap<M::Id, i32> = M::HierarchyEntity::find()
+            .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(&source_id)))
+            .all(conn)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    M::hierarchy_model_descendant(row),
+                    M::hierarchy_model_generations(row),
+                )
+            })
+            .collect();
+        let height = generations.values().copied().max().unwrap_or(0);
+        self.ensure_depth(
+            &Ancestry::<M, Tag>::load(conn, new_parent).await?,
+            height as u32,
+        )?;
+
+        // Parents must be copied before their children; within a level the
+        // configured sibling order is kept.
+        let mut originals = self.descendants_with_conn(conn, &source_id, false).await?;
+        originals.sort_by_key(|model| generations.get(&model.id()).copied().unwrap_or_default());
+
+        let mut copies: HashMap<_, (M::Id, Ancestry<M, Tag>)> = HashMap::new();
+        let mut created = Vec::new();
+        let mut rows = Vec::new();
+        let mut root_copy = None;
+
+        for original in originals {
+            let (parent_id, ancestry) = if original.id() == source_id {
+                (
+                    new_parent.cloned(),
+                    Ancestry::<M, Tag>::load(conn, new_parent).await?,
+                )
+            } else {
+                let parent = original
+                    .parent_id()
+                    .and_then(|parent| copies.get(&parent))
+                    .ok_or_else(|| {
+                        ClosureTreeError::invariant("subtree node copied before its parent")
+                    })?;
+                (Some(parent.0.clone()), parent.1.child(parent.0.clone()))
+            };
+
+            let mut active = original.clone().into_active_model().reset_all();
+            active.not_set(M::id_column());
+            Self::assign_id(&mut active);
+            if original.id() == source_id {
+                if let Some(position) = self.next_order_value(conn, new_parent).await? {
+                    Self::set_position(&mut active, position)?;
+                }
+            }
+            self.set_parent(&mut active, parent_id.as_ref());
+            self.stamp_scope(&mut active)?;
+            self.before_create(conn, parent_id.as_ref(), &mut active)
+                .await?;
+            let copy = self.link_root(conn, active.insert(conn).await?).await?;
+            changes.push(TreeChange::Created(copy.id()));
+            created.push(copy.clone());
+
+            rows.extend(ancestry.hierarchy_rows(&copy.id()));
+            copies.insert(original.id(), (copy.id(), ancestry));
+            if root_copy.is_none() {
+                root_copy = Some(copy);
+            }
+        }
+
+        insert_hierarchy_rows::<M, Tag, C>(conn, self.config(), rows).await?;
+        let copy_ids: Vec<M::Id> = copies.into_values().map(|(id, _)| id).collect();
+        self.refresh_ltree_paths(conn, Some(&copy_ids)).await?;
+        self.recount_children_on(conn, Some(&copy_ids)).await?;
+        self.adjust_children_count(conn, new_parent, 1).await?;
+        self.touch_ancestors(conn, new_parent).await?;
+        self.after_create(conn, &created).await?;
+        root_copy.ok_or_else(|| ClosureTreeError::invariant("source node has no hierarchy rows"))
+    }
+
+    /// Move `node_id`, with its subtree, beneath `new_parent`, or make it a
+    /// root for `None`, renumbering positions as `order_scope` says.
+    async fn reparent_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        node_id: &M::Id,
+        new_parent: Option<&M::Id>,
+        order_scope: OrderScope,
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<(), ClosureTreeError> {
+        let node = self.load(conn, node_id).await?;
+        if let Some(new_parent) = new_parent {
+            self.load(conn, new_parent).await?;
+        }
+
+        let subtree: Vec<(M::Id, i32)> = M::HierarchyEntity::find()
+            .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(node_id)))
+            .all(conn)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    M::hierarchy_model_descendant(row),
+                    M::hierarchy_model_generations(row),
+                )
+            })
+            .collect();
+        if let Some(new_parent) =
+            new_parent.filter(|parent| subtree.iter().any(|(id, _)| id == *parent))
+        {
+            return Err(ClosureTreeError::CycleDetected {
+                node_id: format!("{node_id:?}"),
+                new_parent_id: format!("{new_parent:?}"),
+            });
+        }
+        if let Some(name) = Self::name_of(&node) {
+            self.ensure_unique_name(conn, new_parent, name, Some(node_id))
+                .await?;
+        }
+        if self.parent_of(&node).as_ref() != new_parent {
+            self.ensure_child_capacity(conn, new_parent, 1).await?;
+        }
+        // The deepest node of the subtree, not just its root, must fit below the parent.
+        let ancestry = Ancestry::<M, Tag>::load(conn, new_parent).await?;
+        let height = subtree.iter().map(|(_, below)| *below).max().unwrap_or(0);
+        self.ensure_depth(&ancestry, height as u32)?;
+        if let Some(hooks) = &self.hooks {
+            hooks
+                .before_move(&HookConnection::new(conn), &node, new_parent)
+                .await?;
+        }
+
+        let subtree_ids: Vec<M::Id> = subtree.iter().map(|(id, _)| id.clone()).collect();
+        let has_ancestors = M::HierarchyEntity::find()
+            .filter(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(node_id)))
+            .filter(M::hierarchy_generations_column().gt(0))
+            .count(conn)
+            .await?
+            > 0;
+        if has_ancestors {
+            // General move: drop every row linking the subtree to what is above it.
+            M::HierarchyEntity::delete_many()
+                .filter(
+                    M::hierarchy_descendant_column()
+                        .is_in(subtree_ids.iter().map(M::hierarchy_id_to_value)),
+                )
+                .filter(
+                    M::hierarchy_ancestor_column()
+                        .is_not_in(subtree_ids.iter().map(M::hierarchy_id_to_value)),
+                )
+                .exec(conn)
+                .await?;
+        }
+        insert_hierarchy_rows::<M, Tag, C>(conn, self.config(), ancestry.subtree_rows(&subtree))
+            .await?;
+
+        let old_parent = self.parent_of(&node);
+        let position = match order_scope {
+            OrderScope::PerParent => self.next_order_value(conn, new_parent).await?,
+            OrderScope::Global => None,
+        };
+        let mut active = node.into_active_model();
+        self.set_parent(&mut active, new_parent);
+        if let Some(position) = position {
+            Self::set_position(&mut active, position)?;
+        }
+        let moved = active.update(conn).await?;
+        if order_scope == OrderScope::PerParent {
+            self.compact_siblings(conn, old_parent.as_ref()).await?;
+        }
+
+        self.refresh_ltree_paths(conn, Some(&subtree_ids)).await?;
+        self.adjust_children_count(conn, old_parent.as_ref(), -1)
+            .await?;
+        self.adjust_children_count(conn, new_parent, 1).await?;
+        self.touch_ancestors(conn, old_parent.as_ref()).await?;
+        self.touch_node(conn, node_id).await?;
+        if let Some(hooks) = &self.hooks {
+            hooks
+                .after_move(&HookConnection::new(conn), &moved, old_parent.as_ref())
+                .await?;
+        }
+        changes.push(TreeChange::Moved {
+            id: node_id.clone(),
+            from: old_parent,
+            to: new_parent.cloned(),
+        });
+        Ok(())
+    }
+
+    /// Under [`unique_sibling_names`](ClosureTreeConfig::unique_sibling_names), fail
+    /// if a child of `parent_id` other than `except` is already called `name`.
+    async fn ensure_unique_name<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+        name: Value,
+        except: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        if !self.config().unique_sibling_names() {
+            return Ok(());
+        }
+        let clash = self
+            .find_child_by_name(conn, parent_id, name.clone())
+            .await?
+            .filter(|sibling| Some(&sibling.id()) != except);
+        match clash {
+            Some(_) => Err(ClosureTreeError::DuplicateName {
+                parent_id: parent_id.map(|id| format!("{id:?}")),
+                name: path::segment_text(&name),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Under [`max_depth`](ClosureTreeConfig::max_depth), fail if a subtree
+    /// `height` generations tall cannot be attached below `ancestry`'s parent.
+    fn ensure_depth(
+        &self,
+        ancestry: &Ancestry<M, Tag>,
+        height: u32,
+    ) -> Result<(), ClosureTreeError> {
+        let Some(max_depth) = self.config().max_depth() else {
+            return Ok(());
+        };
+        let depth = ancestry.child_depth() + height;
+        if depth > max_depth {
+            return Err(ClosureTreeError::DepthLimitExceeded { depth, max_depth });
+        }
+        Ok(())
+    }
+
+    /// Under [`max_children`](ClosureTreeConfig::max_children), fail if
+    /// `parent_id` has no room for `adding` more children.
+    async fn ensure_child_capacity<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+        adding: u64,
+    ) -> Result<(), ClosureTreeError> {
+        let (Some(max_children), Some(parent_id)) = (self.config().max_children(), parent_id)
+        else {
+            return Ok(());
+        };
+        let children = self.count_children_on(conn, parent_id).await?;
+        if children + adding > max_children {
+            return Err(ClosureTreeError::ChildLimitExceeded {
+                parent_id: format!("{parent_id:?}"),
+                max_children,
+            });
+        }
+        Ok(())
+    }
+
+    async fn count_children_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: &M::Id,
+    ) -> Result<u64, ClosureTreeError> {
+        let count = self
+            .find()
+            .filter(self.parent_filter(Some(parent_id)))
+            .count(conn)
+            .await?;
+        Ok(count)
+    }
+
+    async fn move_many_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        nodes: &[M::Id],
+        new_parent: Option<&M::Id>,
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<(), ClosureTreeError> {
+        if let Some(new_parent) = new_parent {
+            let above: HashSet<M::Id> = M::HierarchyEntity::find()
+                .filter(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(new_parent)))
+                .all(conn)
+                .await?
+                .iter()
+                .map(M::hierarchy_model_ancestor)
+                .collect();
+            if let Some(node) = nodes.iter().find(|node| above.contains(*node)) {
+                return Err(ClosureTreeError::CycleDetected {
+                    node_id: format!("{node:?}"),
+                    new_parent_id: format!("{new_parent:?}"),
+                });
+            }
+        }
+        let mut seen = HashSet::new();
+        for node in nodes.iter().filter(|node| seen.insert(*node)) {
+            self.reparent_on(conn, node, new_parent, self.config().order_scope(), changes)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn move_to_child_of_at_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        node_id: &M::Id,
+        new_parent: &M::Id,
+        position: i32,
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<(), ClosureTreeError> {
+        let scope = self.config().order_scope();
+        self.reparent_on(conn, node_id, Some(new_parent), scope, changes)
+            .await?;
+        let Some((_, direction)) = self
+            .config()
+            .order_strategy()
+            .and_then(OrderStrategy::position_column)
+        else {
+            return Ok(());
+        };
+        if scope == OrderScope::Global {
+            return Ok(());
+        }
+        // `reparent_on` appended the node; take it out and put it back at `position`.
+        let mut siblings = self
+            .ordered(self.find().filter(self.parent_filter(Some(new_parent))))
+            .all(conn)
+            .await?;
+        let index = siblings
+            .iter()
+            .position(|sibling| &sibling.id() == node_id)
+            .ok_or_else(|| ClosureTreeError::invariant("node missing from its siblings"))?;
+        let node = siblings.remove(index);
+        let position = usize::try_from(position).unwrap_or(0).min(siblings.len());
+        if self.fractional() {
+            if let Some(slot) = Self::fractional_slot(&siblings, position, direction) {
+                let mut active = node.into_active_model();
+                Self::set_position(&mut active, slot)?;
+                active.update(conn).await?;
+                return Ok(());
+            }
+            // The neighbours are too close to split: renumber them once.
+        }
+        siblings.insert(position, node);
+        self.renumber_siblings(conn, siblings, direction).await
+    }
+
+    /// A fractional position between the siblings either side of `index`, or
+    /// `None` when no value fits strictly between them.
+    fn fractional_slot(
+        siblings: &[M],
+        index: usize,
+        direction: OrderDirection,
+    ) -> Option<Position> {
+        // Siblings are in display order, so a descending column counts down.
+        let step = match direction {
+            OrderDirection::Asc => 1.0,
+            OrderDirection::Desc => -1.0,
+        };
+        let before = index
+            .checked_sub(1)
+            .map(|before| siblings[before].fractional_order_value());
+        let after = siblings.get(index).map(M::fractional_order_value);
+        let slot = match (before, after) {
+            (Some(Some(before)), Some(Some(after))) => before + (after - before) / 2.0,
+            (Some(Some(before)), None) => before + step,
+            (None, Some(Some(after))) => after - step,
+            (None, None) => 0.0,
+            _ => return None,
+        };
+        let clear = |neighbour: Option<Option<f64>>| neighbour.flatten() != Some(slot);
+        (slot.is_finite() && clear(before) && clear(after)).then_some(Position::Fractional(slot))
+    }
+
+    /// Renumber the children of `parent_id` from `0` in their current order,
+    /// closing the gap a node moved away leaves; a no-op without a numeric
+    /// order column (fractional positions keep their gaps) or for unordered
+    /// roots.
+    async fn compact_siblings<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        let Some(OrderStrategy::NumericColumn { direction, .. }) = self.config().order_strategy()
+        else {
+            return Ok(());
+        };
+        if parent_id.is_none() && self.config().dont_order_roots() {
+            return Ok(());
+        }
+        let siblings = self
+            .ordered(self.find().filter(self.parent_filter(parent_id)))
+            .all(conn)
+            .await?;
+        self.renumber_siblings(conn, siblings, *direction).await
+    }
+
+    /// Store the order values of `siblings`, given in their new order, spaced
+    /// by [`order_gap`](ClosureTreeConfig::order_gap) (by `1` for fractional
+    /// positions); unchanged rows are not written.
+    async fn renumber_siblings<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        siblings: Vec<M>,
+        direction: OrderDirection,
+    ) -> Result<(), ClosureTreeError> {
+        let last = siblings.len() as i64 - 1;
+        for (index, sibling) in siblings.into_iter().enumerate() {
+            let rank = match direction {
+                OrderDirection::Asc => index as i64,
+                OrderDirection::Desc => last - index as i64,
+            };
+            let position = self.nth_position(rank);
+            if self.position_of(&sibling) != Some(position) {
+                let mut active = sibling.into_active_model();
+                Self::set_position(&mut active, position)?;
+                active.update(conn).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move `node` by `offset` places among its siblings, renumbering them.
+    async fn move_among_siblings_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        node: &M,
+        offset: isize,
+    ) -> Result<bool, ClosureTreeError> {
+        let not_orderable = |reason| ClosureTreeError::NotOrderable {
+            entity: self.config().entity_name().to_owned(),
+            reason,
+        };
+        let Some((_, direction)) = self
+            .config()
+            .order_strategy()
+            .and_then(OrderStrategy::position_column)
+        else {
+            return Err(ClosureTreeError::UnsupportedOperation {
+                operation: if offset < 0 { "move_up" } else { "move_down" },
+                reason: "no numeric order column is configured",
+            });
+        };
+        let node = self.load(conn, &node.id()).await?;
+        let parent_id = self.parent_of(&node);
+        if parent_id.is_none() && self.config().dont_order_roots() {
+            return Err(not_orderable("roots are not ordered (dont_order_roots)"));
+        }
+
+        let siblings = self.parent_filter(parent_id.as_ref());
+        let mut siblings = self.ordered(self.find().filter(siblings)).all(conn).await?;
+        let index = siblings
+            .iter()
+            .position(|sibling| sibling.id() == node.id())
+            .ok_or_else(|| ClosureTreeError::invariant("node missing from its siblings"))?;
+        let Some(target) = index
+            .checked_add_signed(offset)
+            .filter(|target| *target < siblings.len())
+        else {
+            return Ok(false);
+        };
+        // Distinct fractional positions are swapped in place.
+        let swapped = match (
+            self.fractional(),
+            self.position_of(&siblings[index]),
+            self.position_of(&siblings[target]),
+        ) {
+            (true, Some(from), Some(to)) if from != to => Some((from, to)),
+            _ => None,
+        };
+        if let Some((from, to)) = swapped {
+            for (sibling, position) in [(index, to), (target, from)] {
+                let mut active = siblings[sibling].clone().into_active_model();
+                Self::set_position(&mut active, position)?;
+                active.update(conn).await?;
+            }
+        } else {
+            siblings.swap(index, target);
+            self.renumber_siblings(conn, siblings, direction).await?;
+        }
+        self.touch_node(conn, &node.id()).await?;
+        Ok(true)
+    }
+
+    async fn destroy_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        model: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let id = model.id();
+        let mut report = ChangeReport::new(self.dry_run);
+        if let (Some(hooks), false) = (&self.hooks, self.dry_run) {
+            hooks
+                .before_destroy_subtree(&HookConnection::new(conn), model)
+                .await?;
+        }
+
+        let mut subtree: Vec<(M::Id, i32)> = M::HierarchyEntity::find()
+            .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(&id)))
+            .all(conn)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    M::hierarchy_model_descendant(row),
+                    M::hierarchy_model_generations(row),
+                )
+            })
+            .collect();
+        subtree.sort_by_key(|(_, generations)| std::cmp::Reverse(*generations));
+        let subtree_ids: Vec<M::Id> = subtree.into_iter().map(|(id, _)| id).collect();
+
+        match self.config().dependent_behavior() {
+            DependentBehavior::Nullify => {
+                report.deleted.push(id.clone());
+                report.reparented = self
+                    .find_with_deleted()
+                    .filter(self.parent_filter(Some(&id)))
+                    .all(conn)
+                    .await?
+                    .iter()
+                    .map(|child| child.id())
+                    .collect();
+                if self.dry_run {
+                    return Ok(report);
+                }
+
+                // Detach the child subtrees from `model` and everything above it.
+                let ancestors: Vec<Value> = M::HierarchyEntity::find()
+                    .filter(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(&id)))
+                    .all(conn)
+                    .await?
+                    .iter()
+                    .map(|row| M::hierarchy_id_to_value(&M::hierarchy_model_ancestor(row)))
+                    .collect();
+                M::HierarchyEntity::delete_many()
+                    .filter(M::hierarchy_ancestor_column().is_in(ancestors))
+                    .filter(
+                        M::hierarchy_descendant_column()
+                            .is_in(subtree_ids.iter().map(M::hierarchy_id_to_value)),
+                    )
+                    .exec(conn)
+                    .await?;
+                M::Entity::update_many()
+                    .col_expr(M::parent_column(), self.root_parent_expr())
+                    .filter(self.parent_filter(Some(&id)))
+                    .exec(conn)
+                    .await?;
+                model.clone().into_active_model().delete(conn).await?;
+                self.refresh_ltree_paths(conn, Some(&subtree_ids)).await?;
+            }
+            behavior @ (DependentBehavior::Destroy | DependentBehavior::DeleteAll) => {
+                report.deleted = subtree_ids;
+                if self.dry_run {
+                    return Ok(report);
+                }
+
+                // Every row touching the subtree has its descendant inside it.
+                M::HierarchyEntity::delete_many()
+                    .filter(
+                        M::hierarchy_descendant_column()
+                            .is_in(report.deleted.iter().map(M::hierarchy_id_to_value)),
+                    )
+                    .exec(conn)
+                    .await?;
+                let condition = M::id_column().is_in(report.deleted.iter().map(M::id_to_value));
+                if behavior == DependentBehavior::DeleteAll {
+                    M::Entity::delete_many()
+                        .filter(condition)
+                        .exec(conn)
+                        .await?;
+                } else {
+                    let mut models: HashMap<M::Id, M> = self
+                        .find_with_deleted()
+                        .filter(condition)
+                        .all(conn)
+                        .await?
+                        .into_iter()
+                        .map(|model| (model.id(), model))
+                        .collect();
+                    for id in &report.deleted {
+                        if let Some(model) = models.remove(id) {
+                            model.into_active_model().delete(conn).await?;
+                        }
+                    }
+                }
+            }
+            DependentBehavior::None => {
+                report.deleted.push(id.clone());
+                if self.dry_run {
+                    return Ok(report);
+                }
+
+                let value = M::hierarchy_id_to_value(&id);
+                M::HierarchyEntity::delete_many()
+                    .filter(
+                        Condition::any()
+                            .add(M::hierarchy_ancestor_column().eq(value.clone()))
+                            .add(M::hierarchy_descendant_column().eq(value)),
+                    )
+                    .exec(conn)
+                    .await?;
+                model.clone().into_active_model().delete(conn).await?;
+                self.refresh_ltree_paths(conn, Some(&subtree_ids)).await?;
+            }
+        }
+
+        let parent = self.parent_of(model);
+        self.adjust_children_count(conn, parent.as_ref(), -1)
+            .await?;
+        self.touch_ancestors(conn, parent.as_ref()).await?;
+        Ok(report)
+    }
+
+    async fn soft_destroy_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        model: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let column = self.require_soft_delete_column("soft_destroy")?;
+        let mut report = ChangeReport::new(self.dry_run);
+
+        let live = Condition::all()
+            .add(M::id_column().in_subquery(self.subtree_of(&model.id())))
+            .add(Expr::col(Alias::new(column)).is_null());
+        report.deleted = self
+            .find_with_deleted()
+            .filter(live.clone())
+            .all(conn)
+            .await?
+            .iter()
+            .map(|node| node.id())
+            .collect();
+        if self.dry_run || report.deleted.is_empty() {
+            return Ok(report);
+        }
+
+        let statement = Query::update()
+            .table(M::Entity::default().table_ref())
+            .value(Alias::new(column), Expr::current_timestamp())
+            .cond_where(live)
+            .to_owned();
+        conn.execute(conn.get_database_backend().build(&statement))
+            .await?;
+
+        let parent = self.parent_of(model);
+        self.recount_children_on(conn, Some(parent.as_slice()))
+            .await?;
+        self.touch_ancestors(conn, parent.as_ref()).await?;
+        Ok(report)
+    }
+
+    async fn restore_subtree_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        model: &M,
+    ) -> Result<u64, ClosureTreeError> {
+        let column = self.require_soft_delete_column("restore_subtree")?;
+
+        let deleted = Condition::all()
+            .add(M::id_column().in_subquery(self.subtree_of(&model.id())))
+            .add(Expr::col(Alias::new(column)).is_not_null());
+        let mut restored: Vec<M::Id> = self
+            .find_with_deleted()
+            .filter(deleted.clone())
+            .all(conn)
+            .await?
+            .iter()
+            .map(|node| node.id())
+            .collect();
+        if restored.is_empty() {
+            return Ok(0);
+        }
+
+        let statement = Query::update()
+            .table(M::Entity::default().table_ref())
+            .value(Alias::new(column), SimpleExpr::Keyword(Keyword::Null))
+            .cond_where(deleted)
+            .to_owned();
+        conn.execute(conn.get_database_backend().build(&statement))
+            .await?;
+
+        let rows = restored.len() as u64;
+        // Counters inside the subtree may have dropped when part of it was
+        // deleted on its own; recounting them all is exact.
+        let parent = self.parent_of(model);
+        restored.extend(parent.clone());
+        self.recount_children_on(conn, Some(&restored)).await?;
+        self.touch_ancestors(conn, parent.as_ref()).await?;
+        Ok(rows)
+    }
+
+    fn require_soft_delete_column(
+        &self,
+        operation: &'static str,
+    ) -> Result<&str, ClosureTreeError> {
+        self.config()
+            .soft_delete_column()
+            .ok_or(ClosureTreeError::UnsupportedOperation {
+                operation,
+                reason: "no soft_delete_column is configured",
+            })
+    }
+
+    /// Ids of `id` and all of its descendants, as a hierarchy subquery.
+    fn subtree_of(&self, id: &M::Id) -> SelectStatement {
+        Query::select()
+            .column(M::hierarchy_descendant_column())
+            .from(M::HierarchyEntity::default().table_ref())
+            .and_where(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(id)))
+            .to_owned()
+    }
+
+    async fn clear_on<C: ConnectionTrait>(&self, conn: &C) -> Result<(), ClosureTreeError> {
+        let Some(condition) = self.scope_condition() else {
+            M::HierarchyEntity::delete_many().exec(conn).await?;
+            M::Entity::delete_many().exec(conn).await?;
+            return Ok(());
+        };
+
+        // Trees never span scopes, so every row touching the scope has its
+        // descendant inside it.
+        let nodes = Query::select()
+            .column(M::id_column())
+            .from(M::Entity::default().table_ref())
+            .and_where(condition.clone())
+            .to_owned();
+        M::HierarchyEntity::delete_many()
+            .filter(M::hierarchy_descendant_column().in_subquery(nodes))
+            .exec(conn)
+            .await?;
+        M::Entity::delete_many()
+            .filter(condition)
+            .exec(conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn rebuild_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let mut report = ChangeReport::new(self.dry_run);
+
+        let nodes = self
+            .find_with_deleted()
+            .order_by_asc(M::id_column())
+            .all(conn)
+            .await?;
+        let parents: HashMap<M::Id, Option<M::Id>> = nodes
+            .iter()
+            .map(|node| (node.id(), self.parent_of(node)))
+            .collect();
+
+        let mut existing: HashMap<M::Id, HashSet<(M::Id, i32)>> = HashMap::new();
+        for row in M::HierarchyEntity::find().all(conn).await? {
+            existing
+                .entry(M::hierarchy_model_descendant(&row))
+                .or_default()
+                .insert((
+                    M::hierarchy_model_ancestor(&row),
+                    M::hierarchy_model_generations(&row),
+                ));
+        }
+
+        let mut rows = Vec::new();
+        for node in &nodes {
+            let id = node.id();
+            let mut expected = HashSet::from([(id.clone(), 0)]);
+            let mut current = self.parent_of(node);
+            let mut generations = 1;
+            // A parent outside the table ends the chain; `prune_orphans` deals with it.
+            while let Some(parent) = current.filter(|parent| parents.contains_key(parent)) {
+                if generations as usize > parents.len() {
+                    return Err(ClosureTreeError::invariant(format!(
+                        "parent_id cycle through {id:?}"
+                    )));
+                }
+                current = parents[&parent].clone();
+                expected.insert((parent, generations));
+                generations += 1;
+            }
+
+            if existing.get(&id) != Some(&expected) {
+                rows.extend(expected.into_iter().map(|(ancestor, generations)| {
+                    M::hierarchy_build_row(ancestor, id.clone(), generations)
+                }));
+                report.rebuilt.push(id);
+            }
+        }
+
+        if self.dry_run {
+            return Ok(report);
+        }
+
+        if !report.rebuilt.is_empty() {
+            M::HierarchyEntity::delete_many()
+                .filter(
+                    M::hierarchy_descendant_column()
+                        .is_in(report.rebuilt.iter().map(M::hierarchy_id_to_value)),
+                )
+                .exec(conn)
+                .await?;
+            insert_hierarchy_rows::<M, Tag, C>(conn, self.config(), rows).await?;
+        }
+        // Paths may be stale even where the hierarchy rows were right, e.g. when
+        // the column was added to an existing table.
+        self.refresh_ltree_paths(conn, None).await?;
+
+        Ok(report)
+    }
+
+    async fn rebuild_subtree_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        root: &M,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let mut report = ChangeReport::new(self.dry_run);
+        let root_id = root.id();
+
+        // Walk `parent_id` down from the root, one query per level and chunk.
+        let mut parents: HashMap<M::Id, M::Id> = HashMap::new();
+        let mut order = vec![root_id.clone()];
+        let mut level = vec![root_id.clone()];
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for chunk in level.chunks(HIERARCHY_INSERT_CHUNK) {
+                let children = self
+                    .find_with_deleted()
+                    .filter(M::parent_column().is_in(chunk.iter().map(M::id_to_value)))
+                    .order_by_asc(M::id_column())
+                    .all(conn)
+                    .await?;
+                for child in children {
+                    let Some(parent) = self.parent_of(&child) else {
+                        continue;
+                    };
+                    let id = child.id();
+                    if id == root_id || parents.contains_key(&id) {
+                        return Err(ClosureTreeError::invariant(format!(
+                            "parent_id cycle through {id:?}"
+                        )));
+                    }
+                    parents.insert(id.clone(), parent);
+                    order.push(id.clone());
+                    next.push(id);
+                }
+            }
+            level = next;
+        }
+
+        let ancestry = Ancestry::<M, Tag>::load(conn, self.parent_of(root).as_ref()).await?;
+        let mut existing: HashMap<M::Id, HashSet<(M::Id, i32)>> = HashMap::new();
+        for chunk in order.chunks(HIERARCHY_INSERT_CHUNK) {
+            let rows = M::HierarchyEntity::find()
+                .filter(
+                    M::hierarchy_descendant_column()
+                        .is_in(chunk.iter().map(M::hierarchy_id_to_value)),
+                )
+                .all(conn)
+                .await?;
+            for row in rows {
+                existing
+                    .entry(M::hierarchy_model_descendant(&row))
+                    .or_default()
+                    .insert((
+                        M::hierarchy_model_ancestor(&row),
+                        M::hierarchy_model_generations(&row),
+                    ));
+            }
+        }
+
+        let mut rows = Vec::new();
+        for id in &order {
+            let mut expected = HashSet::from([(id.clone(), 0)]);
+            let mut current = id;
+            let mut generations = 1;
+            while let Some(parent) = parents.get(current) {
+                expected.insert((parent.clone(), generations));
+                current = parent;
+                generations += 1;
+            }
+            // `current` is the root now, `generations - 1` levels up.
+            expected.extend(
+                ancestry
+                    .rows
+                    .iter()
+                    .map(|(ancestor, above)| (ancestor.clone(), above + generations)),
+            );
+
+            if existing.get(id) != Some(&expected) {
+                rows.extend(expected.into_iter().map(|(ancestor, generations)| {
+                    M::hierarchy_build_row(ancestor, id.clone(), generations)
+                }));
+                report.rebuilt.push(id.clone());
+            }
+        }
+
+        if self.dry_run || report.rebuilt.is_empty() {
+            return Ok(report);
+        }
+        for chunk in report.rebuilt.chunks(HIERARCHY_INSERT_CHUNK) {
+            M::HierarchyEntity::delete_many()
+                .filter(
+                    M::hierarchy_descendant_column()
+                        .is_in(chunk.iter().map(M::hierarchy_id_to_value)),
+                )
+                .exec(conn)
+                .await?;
+        }
+        insert_hierarchy_rows::<M, Tag, C>(conn, self.config(), rows).await?;
+        self.refresh_ltree_paths(conn, Some(&report.rebuilt))
+            .await?;
+
+        Ok(report)
+    }
+
+    async fn prune_orphans_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+    ) -> Result<ChangeReport<M::Id>, ClosureTreeError> {
+        let mut report = ChangeReport::new(self.dry_run);
+
+        // Existence is checked across every scope: orphans are a table-wide notion.
+        let ids: HashSet<M::Id> = M::Entity::find()
+            .all(conn)
+            .await?
+            .iter()
+            .map(|node| node.id())
+            .collect();
+        let mut missing = HashSet::new();
+        for row in M::HierarchyEntity::find().all(conn).await? {
+            let ancestor = M::hierarchy_model_ancestor(&row);
+            let descendant = M::hierarchy_model_descendant(&row);
+            for id in [&ancestor, &descendant] {
+                if !ids.contains(id) {
+                    missing.insert(id.clone());
+                }
+            }
+            if !ids.contains(&ancestor) || !ids.contains(&descendant) {
+                report.pruned.push((ancestor, descendant));
+            }
+        }
+
+        if self.dry_run || missing.is_empty() {
+            return Ok(report);
+        }
+
+        let missing: Vec<Value> = missing.iter().map(M::hierarchy_id_to_value).collect();
+        M::HierarchyEntity::delete_many()
+            .filter(
+                Condition::any()
+                    .add(M::hierarchy_ancestor_column().is_in(missing.clone()))
+                    .add(M::hierarchy_descendant_column().is_in(missing)),
+            )
+            .exec(conn)
+            .await?;
+
+        Ok(report)
+    }
+
+    /// Order value for a node appended after the current children of
+    /// `parent_id` (after every ordered node under [`OrderScope::Global`]), or
+    /// `None` without a numeric or fractional order column (or for a root
+    /// under [`dont_order_roots`](ClosureTreeConfig::dont_order_roots)).
+    async fn next_order_value<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+    ) -> Result<Option<Position>, ClosureTreeError> {
+        let Some(column) = self.order_column() else {
+            return Ok(None);
+        };
+        if parent_id.is_none() && self.config().dont_order_roots() {
+            return Ok(None);
+        }
+
+        // Global positions continue after the highest one in the tree.
+        let mut query = self.find();
+        if self.config().order_scope() == OrderScope::PerParent {
+            query = query.filter(self.parent_filter(parent_id));
+        }
+        let last = query
+            .filter(column.is_not_null())
+            .order_by_desc(column)
+            .one(conn)
+            .await?;
+        Ok(Some(
+            match last.and_then(|model| self.position_of(&model)) {
+                Some(Position::Numeric(value)) => {
+                    Position::Numeric(value + self.config().order_gap())
+                }
+                Some(Position::Fractional(value)) => Position::Fractional(value + 1.0),
+                None => self.nth_position(0),
+            },
+        ))
+    }
+
+    /// The configured `ltree` column, if `conn` is a PostgreSQL connection.
+    fn ltree_column<C: ConnectionTrait>(&self, conn: &C) -> Option<&str> {
+        self.config()
+            .ltree_column()
+            .filter(|_| conn.get_database_backend() == DbBackend::Postgres)
+    }
+
+    /// Recompute the `ltree` path of `ids` (every node when `None`) from their
+    /// hierarchy rows; a no-op without an `ltree` column.
+    async fn refresh_ltree_paths<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        ids: Option<&[M::Id]>,
+    ) -> Result<(), ClosureTreeError> {
+        let Some(column) = self.config().ltree_column() else {
+            return Ok(());
+        };
+        if conn.get_database_backend() != DbBackend::Postgres {
+            return Err(BackendOps::of(conn).unsupported("ltree path maintenance"));
+        }
+
+        let statement = |filter: &str, values: Vec<Value>| {
+            let sql = format!(
+                "UPDATE {table} AS n SET {column} = p.path \
+                 FROM (SELECT h.{descendant} AS id, \
+                 text2ltree(string_agg(replace(h.{ancestor}::text, '-', '_'), '.' \
+                 ORDER BY h.{generations} DESC)) AS path \
+                 FROM {hierarchy} AS h{filter} GROUP BY h.{descendant}) AS p \
+                 WHERE n.{id} = p.id",
+                table = qualified_table(M::Entity::default()),
+                column = quote_ident(column),
+                hierarchy = qualified_table(M::HierarchyEntity::default()),
+                descendant = quote_ident(M::hierarchy_descendant_column().as_str()),
+                ancestor = quote_ident(M::hierarchy_ancestor_column().as_str()),
+                generations = quote_ident(M::hierarchy_generations_column().as_str()),
+                id = quote_ident(M::id_column().as_str()),
+            );
+            Statement::from_sql_and_values(DbBackend::Postgres, sql, values)
+        };
+
+        let Some(ids) = ids else {
+            conn.execute(statement("", Vec::new())).await?;
+            return Ok(());
+        };
+        for chunk in ids.chunks(HIERARCHY_INSERT_CHUNK) {
+            let placeholders: Vec<String> = (1..=chunk.len()).map(|i| format!("${i}")).collect();
+            let filter = format!(
+                " WHERE h.{} IN ({})",
+                quote_ident(M::hierarchy_descendant_column().as_str()),
+                placeholders.join(", ")
+            );
+            let values = chunk.iter().map(M::hierarchy_id_to_value).collect();
+            conn.execute(statement(&filter, values)).await?;
+        }
+        Ok(())
+    }
+
+    /// Add `delta` to the [`children_count_column`](ClosureTreeConfig::children_count_column)
+    /// of `parent` in place (`SET col = col + delta`); a no-op without a counter
+    /// column or for `None`.
+    async fn adjust_children_count<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent: Option<&M::Id>,
+        delta: i64,
+    ) -> Result<(), ClosureTreeError> {
+        let (Some(column), Some(parent)) = (self.config().children_count_column(), parent) else {
+            return Ok(());
+        };
+
+        let statement = Query::update()
+            .table(M::Entity::default().table_ref())
+            .value(Alias::new(column), Expr::col(Alias::new(column)).add(delta))
+            .and_where(M::id_column().eq(M::id_to_value(parent)))
+            .to_owned();
+        conn.execute(conn.get_database_backend().build(&statement))
+            .await?;
+        Ok(())
+    }
+
+    /// Set the children counter of `ids` (every node when `None`) to a fresh
+    /// `COUNT(*)` of their children, returning the rows updated; a no-op
+    /// without a counter column.
+    async fn recount_children_on<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        ids: Option<&[M::Id]>,
+    ) -> Result<u64, ClosureTreeError> {
+        let Some(column) = self.config().children_count_column() else {
+            return Ok(0);
+        };
+
+        let child = Alias::new("child");
+        let mut count = Query::select()
+            .expr(Expr::col(Asterisk).count())
+            .from_as(M::Entity::default().table_ref(), child.clone())
+            .and_where(
+                Expr::col((child.clone(), M::parent_column()))
+                    .equals((M::Entity::default(), M::id_column())),
+            )
+            .to_owned();
+        if self.config().root_marker() == RootMarker::SelfReference {
+            count.and_where(
+                Expr::col((child.clone(), M::id_column()))
+                    .ne(Expr::col((M::Entity::default(), M::id_column()))),
+            );
+        }
+        if let Some(deleted) = self.config().soft_delete_column() {
+            count.and_where(Expr::col((child, Alias::new(deleted))).is_null());
+        }
+        let statement = |condition: Option<SimpleExpr>| {
+            let mut update = Query::update()
+                .table(M::Entity::default().table_ref())
+                .value(
+                    Alias::new(column),
+                    SimpleExpr::SubQuery(None, Box::new(count.clone().into_sub_query_statement())),
+                )
+                .to_owned();
+            if let Some(condition) = condition {
+                update.and_where(condition);
+            }
+            conn.get_database_backend().build(&update)
+        };
+
+        let Some(ids) = ids else {
+            return Ok(conn.execute(statement(None)).await?.rows_affected());
+        };
+        let mut rows = 0;
+        for chunk in ids.chunks(HIERARCHY_INSERT_CHUNK) {
+            let condition = M::id_column().is_in(chunk.iter().map(M::id_to_value));
+            rows += conn
+                .execute(statement(Some(condition)))
+                .await?
+                .rows_affected();
+        }
+        Ok(rows)
+    }
+
+    /// Set the [`touch_column`](ClosureTreeConfig::touch_column) of `node` and all
+    /// of its ancestors to the current time, in one `UPDATE`; a no-op without a
+    /// touch column, under [`touch_ancestors(false)`](ClosureTreeConfig::touch_ancestors)
+    /// or for `None`.
+    async fn touch_ancestors<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        node: Option<&M::Id>,
+    ) -> Result<(), ClosureTreeError> {
+        let Some(node) = node.filter(|_| self.config().touch_ancestors()) else {
+            return Ok(());
+        };
+
+        let ancestors = Query::select()
+            .column(M::hierarchy_ancestor_column())
+            .from(M::HierarchyEntity::default().table_ref())
+            .and_where(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(node)))
+            .to_owned();
+        self.touch(conn, M::id_column().in_subquery(ancestors))
+            .await
+    }
+
+    /// Touch a node that was itself changed, along with its ancestors unless
+    /// [`touch_ancestors(false)`](ClosureTreeConfig::touch_ancestors).
+    async fn touch_node<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        node: &M::Id,
+    ) -> Result<(), ClosureTreeError> {
+        if self.config().touch_ancestors() {
+            return self.touch_ancestors(conn, Some(node)).await;
+        }
+        self.touch(conn, M::id_column().eq(M::id_to_value(node)))
+            .await
+    }
+
+    /// Set the touch column of the nodes matching `condition` to the current time.
+    async fn touch<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        condition: SimpleExpr,
+    ) -> Result<(), ClosureTreeError> {
+        let Some(column) = self.config().touch_column() else {
+            return Ok(());
+        };
+        match M::touch_column().filter(|typed| typed.as_str() == column) {
+            Some(typed) => {
+                M::Entity::update_many()
+                    .col_expr(typed, Expr::current_timestamp().into())
+                    .filter(condition)
+                    .exec(conn)
+                    .await?;
+            }
+            // A column the model does not declare, e.g. one named through `with_config`.
+            None => {
+                let statement = Query::update()
+                    .table(M::Entity::default().table_ref())
+                    .value(Alias::new(column), Expr::current_timestamp())
+                    .and_where(condition)
+                    .to_owned();
+                conn.execute(conn.get_database_backend().build(&statement))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn find_child_by_name<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+        name: Value,
+    ) -> Result<Option<M>, ClosureTreeError> {
+        self.find_child_by(conn, parent_id, self.name_matches(name)?)
+            .await
+    }
+
+    /// A string `name` as passed through the configured
+    /// [`name_normalizer`](ClosureTreeConfig::name_normalizer); other values
+    /// are returned as they are.
+    fn normalize_value(&self, name: Value) -> Value {
+        match (self.config().name_normalizer(), name) {
+            (Some(normalize), Value::String(Some(name))) => normalize(&name).into(),
+            (_, name) => name,
+        }
+    }
+
+    /// A string `name` without surrounding whitespace under
+    /// [`trim_segments`](ClosureTreeConfig::trim_segments).
+    fn trim_value(&self, name: Value) -> Value {
+        match name {
+            Value::String(Some(name)) if self.config().trim_segments() => name.trim().into(),
+            name => name,
+        }
+    }
+
+    /// Path segments about to be created, trimmed and, under
+    /// [`reject_blank_segments`](ClosureTreeConfig::reject_blank_segments),
+    /// checked for blanks before anything is written.
+    fn creatable_segments(
+        &self,
+        segments: impl IntoIterator<Item = Value>,
+    ) -> Result<Vec<Value>, ClosureTreeError> {
+        segments
+            .into_iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let segment = self.trim_value(segment);
+                let blank = matches!(&segment, Value::String(Some(name)) if name.trim().is_empty());
+                if self.config().reject_blank_segments() && blank {
+                    return Err(ClosureTreeError::BlankSegment { index });
+                }
+                Ok(segment)
+            })
+            .collect()
+    }
+
+    /// Condition matching `name` under the configured [`NameMatching`], which
+    /// only folds the case of string names.
+    fn name_matches(&self, name: Value) -> Result<SimpleExpr, ClosureTreeError> {
+        let column = Self::name_column_for("name lookup")?;
+        Ok(
+            match (self.config().name_matching(), self.normalize_value(name)) {
+                (NameMatching::CaseInsensitive, name @ Value::String(_)) => {
+                    Expr::expr(Func::lower(column.into_expr())).eq(Func::lower(Expr::val(name)))
+                }
+                (_, name) => column.eq(name),
+            },
+        )
+    }
+
+    /// Set the id of a node about to be inserted from
+    /// [`ClosureTreeModel::generate_id`], if the model generates its own and
+    /// the id is still `NotSet`.
+    fn assign_id(active: &mut M::ActiveModel) {
+        if !active.is_not_set(M::id_column()) {
+            return;
+        }
+        if let Some(id) = M::generate_id() {
+            active.set(M::id_column(), M::id_to_value(&id));
+        }
+    }
+
+    /// Whether the name column is declared as a string type; other
+    /// [`NamedClosureTreeModel::Name`] types are not checked by
+    /// [`check_schema`](Self::check_schema).
+    fn text_names() -> bool {
+        M::name_column().is_some_and(|column| {
+            matches!(
+                column.def().get_column_type(),
+                ColumnType::String(_) | ColumnType::Text | ColumnType::Char(_)
+            )
+        })
+    }
+
+    fn name_column_for(
+        operation: &'static str,
+    ) -> Result<<M::Entity as EntityTrait>::Column, ClosureTreeError> {
+        M::name_column().ok_or(ClosureTreeError::UnsupportedOperation {
+            operation,
+            reason: "no name column is configured",
+        })
+    }
+
+    /// `model`'s name, read through [`ClosureTreeModel::name_column`]; `None`
+    /// for trees without names.
+    fn name_of(model: &M) -> Option<Value> {
+        let column = M::name_column()?;
+        model.clone().into_active_model().get(column).into_value()
+    }
+
+    async fn find_child_by<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent_id: Option<&M::Id>,
+        matches: SimpleExpr,
+    ) -> Result<Option<M>, ClosureTreeError> {
+        let condition = Condition::all()
+            .add(matches)
+            .add(self.parent_filter(parent_id));
+        let model = self.find().filter(condition).one(conn).await?;
+        Ok(model)
+    }
+
+    /// Ancestors of `id` in the generations `range` covers, ordered by
+    /// generations ascending, i.e. nearest first.
+    async fn load_ancestors<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        id: &M::Id,
+        range: GenerationRange,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        if self.config().prefer_recursive_cte() && BackendOps::of(conn).recursive_ancestors() {
+            return self.load_ancestors_recursive(conn, id, range).await;
+        }
+        let generations = M::HierarchyEntity::find()
+            .filter(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(id)))
+            .filter(generations_filter::<M, Tag>(range.bounds()));
+        let generations: HashMap<M::Id, i32> = generations
+            .all(conn)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    M::hierarchy_model_ancestor(row),
+                    M::hierarchy_model_generations(row),
+                )
+            })
+            .collect();
+        if generations.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut rows = self
+            .find()
+            .filter(M::id_column().is_in(generations.keys().map(M::id_to_value)))
+            .all(conn)
+            .await?;
+        rows.sort_by_key(|row| generations.get(&row.id()).copied());
+        Ok(rows)
+    }
+
+    /// [`load_ancestors`](Self::load_ancestors) in one statement: a recursive
+    /// CTE follows the parent column up from `id`, and the models are joined to
+    /// it and sorted by its generations.
+    async fn load_ancestors_recursive<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        id: &M::Id,
+        range: GenerationRange,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        let (min, max) = range.bounds();
+        let ancestry = Alias::new("closure_tree_ancestry");
+        let (node_id, generations) = (Alias::new("node_id"), Alias::new("generations"));
+        let node = Alias::new("node");
+        // A parent equal to the node's own id is a root marker, not an edge.
+        let has_parent = |table: &Alias| {
+            Expr::col((table.clone(), M::parent_column()))
+                .is_not_null()
+                .and(
+                    Expr::col((table.clone(), M::parent_column()))
+                        .ne(Expr::col((table.clone(), M::id_column()))),
+                )
+        };
+
+        let mut walk = Query::select()
+            .column((node.clone(), M::parent_column()))
+            .expr(Expr::val(1))
+            .from_as(M::Entity::default().table_ref(), node.clone())
+            .and_where(Expr::col((node.clone(), M::id_column())).eq(M::id_to_value(id)))
+            .and_where(has_parent(&node))
+            .to_owned();
+        let mut step = Query::select()
+            .column((node.clone(), M::parent_column()))
+            .expr(Expr::col((ancestry.clone(), generations.clone())).add(1))
+            .from_as(M::Entity::default().table_ref(), node.clone())
+            .inner_join(
+                ancestry.clone(),
+                Expr::col((node.clone(), M::id_column()))
+                    .equals((ancestry.clone(), node_id.clone())),
+            )
+            .and_where(has_parent(&node))
+            .to_owned();
+        if let Some(max) = max {
+            step.and_where(Expr::col((ancestry.clone(), generations.clone())).lt(max));
+        }
+        walk.union(UnionType::All, step);
+        let cte = CommonTableExpression::new()
+            .query(walk)
+            .columns([node_id.clone(), generations.clone()])
+            .table_name(ancestry.clone())
+            .to_owned();
+
+        let select = self
+            .find()
+            .into_query()
+            .inner_join(
+                ancestry.clone(),
+                Expr::col((ancestry.clone(), node_id))
+                    .equals((M::Entity::default(), M::id_column())),
+            )
+            .and_where(Expr::col((ancestry.clone(), generations.clone())).gte(min))
+            .order_by((ancestry, generations), Order::Asc)
+            .to_owned();
+        let query = select.with(WithClause::new().recursive(true).cte(cte).to_owned());
+        let statement = conn.get_database_backend().build(&query);
+        Ok(M::Entity::find().from_raw_sql(statement).all(conn).await?)
+    }
+
+    async fn descendants_with_conn<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        ancestor_id: &M::Id,
+        exclude_root: bool,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        let generations = (i32::from(exclude_root), None);
+        match self
+            .load_descendants_query(conn, ancestor_id, generations)
+            .await?
+        {
+            Some(query) => Ok(query.all(conn).await?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Ordered query for the descendants of `ancestor_id` in the inclusive
+    /// `(min, max)` generations, or `None` when the hierarchy shows there are
+    /// none. A `min` of `0` includes the ancestor itself.
+    async fn load_descendants_query<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        ancestor_id: &M::Id,
+        generations: (i32, Option<i32>),
+    ) -> Result<Option<Select<M::Entity>>, ClosureTreeError> {
+        // The path column answers whole subtrees; bounded ones need the
+        // generations stored in the hierarchy table.
+        let whole_subtree = generations.0 <= 1 && generations.1.is_none();
+        if let Some(path) = self.ltree_column(conn).filter(|_| whole_subtree) {
+            let root_path = Query::select()
+                .column(Alias::new(path))
+                .from(M::Entity::default().table_ref())
+                .and_where(M::id_column().eq(M::id_to_value(ancestor_id)))
+                .to_owned();
+            let mut query = self.find().filter(Expr::col(Alias::new(path)).binary(
+                BinOper::Custom("<@"),
+                SimpleExpr::SubQuery(None, Box::new(root_path.into_sub_query_statement())),
+            ));
+            if generations.0 == 1 {
+                query = query.filter(M::id_column().ne(M::id_to_value(ancestor_id)));
+            }
+            return Ok(Some(self.ordered(query)));
+        }
+
+        let rows = M::HierarchyEntity::find()
+            .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(ancestor_id)))
+            .filter(generations_filter::<M, Tag>(generations))
+            .all(conn)
+            .await?;
+
+        let mut descendant_ids = Vec::with_capacity(rows.len());
+        for hierarchy in rows {
+            let descendant = M::hierarchy_model_descendant(&hierarchy);
+            descendant_ids.push(descendant);
+        }
+
+        if descendant_ids.is_empty() {
+            return Ok(None);
+        }
+
+        let values = descendant_ids
+            .iter()
+            .map(|id| M::id_to_value(id))
+            .collect::<Vec<_>>();
+
+        let query = self.find().filter(M::id_column().is_in(values));
+        Ok(Some(self.ordered(query)))
+    }
+}
+
+/// Sibling moves, for models with a numeric position (see [`HasOrdering`]).
+impl<M, Tag> ClosureTreeRepository<M, Tag>
+where
+    M: HasOrdering<Tag>,
+{
+    /// Swap `node` with the previous sibling in the configured order, returning
+    /// `false` if it is already at the top.
+    ///
+    /// Requires [`OrderStrategy::NumericColumn`] or `FractionalColumn`, failing
+    /// with [`UnsupportedOperation`](ClosureTreeError::UnsupportedOperation)
+    /// without them; sibling positions are renumbered
+    /// by [`order_gap`](ClosureTreeConfig::order_gap) in the process, while
+    /// fractional ones are swapped between the two nodes. Roots cannot
+    /// be moved under [`dont_order_roots`](ClosureTreeConfig::dont_order_roots).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_up",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+            )
+        )
+    )]
+    pub async fn move_up(
+        &self,
+        conn: &DatabaseConnection,
+        node: &M,
+    ) -> Result<bool, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "move_up", conn);
+        let result = self.move_among_siblings_on(conn, node, -1).await;
+        Self::finish(guard, result).await
+    }
+
+    /// [`move_up`](Self::move_up) inside `txn`, taking only the locks `txn` does
+    /// not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_up_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+            )
+        )
+    )]
+    pub async fn move_up_in(
+        &self,
+        txn: &LockedTransaction,
+        node: &M,
+    ) -> Result<bool, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "move_up_in", conn);
+        self.move_among_siblings_on(conn, node, -1).await
+    }
+
+    /// Swap `node` with the next sibling in the configured order, returning
+    /// `false` if it is already at the bottom.
+    ///
+    /// Requires [`OrderStrategy::NumericColumn`] or `FractionalColumn`, failing
+    /// with [`UnsupportedOperation`](ClosureTreeError::UnsupportedOperation)
+    /// without them; sibling positions are renumbered
+    /// by [`order_gap`](ClosureTreeConfig::order_gap) in the process, while
+    /// fractional ones are swapped between the two nodes. Roots cannot
+    /// be moved under [`dont_order_roots`](ClosureTreeConfig::dont_order_roots).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_down",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+            )
+        )
+    )]
+    pub async fn move_down(
+        &self,
+        conn: &DatabaseConnection,
+        node: &M,
+    ) -> Result<bool, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "move_down", conn);
+        let result = self.move_among_siblings_on(conn, node, 1).await;
+        Self::finish(guard, result).await
+    }
+
+    /// [`move_down`](Self::move_down) inside `txn`, taking only the locks `txn` does
+    /// not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.move_down_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+            )
+        )
+    )]
+    pub async fn move_down_in(
+        &self,
+        txn: &LockedTransaction,
+        node: &M,
+    ) -> Result<bool, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "move_down_in", conn);
+        self.move_among_siblings_on(conn, node, 1).await
+    }
+}
+
+/// Counter cache upkeep, for models with a children count (see
+/// [`HasCounterCache`]).
+impl<M, Tag> ClosureTreeRepository<M, Tag>
+where
+    M: HasCounterCache<Tag>,
+{
+    /// Recompute every node's [`children_count_column`](ClosureTreeConfig::children_count_column)
+    /// with one `UPDATE`, repairing counters that drifted through writes made
+    /// outside the repository. Returns the number of rows updated.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.recount_children",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn recount_children(
+        &self,
+        conn: &DatabaseConnection,
+    ) -> Result<u64, ClosureTreeError> {
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "recount_children", conn);
+        let result = self.recount_children_on(conn, None).await;
+        Self::finish(guard, result)
+            .await
+            .inspect(|rows| record_rows(*rows as usize))
+    }
+
+    /// [`recount_children`](Self::recount_children) inside `txn`, taking only the
+    /// locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.recount_children_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn recount_children_in(
+        &self,
+        txn: &LockedTransaction,
+    ) -> Result<u64, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "recount_children_in", conn);
+        self.recount_children_on(conn, None)
+            .await
+            .inspect(|rows| record_rows(*rows as usize))
+    }
+}
+
+/// Path lookups and the writes that name nodes, for models whose nodes have
+/// names (see [`NamedClosureTreeModel`]).
+impl<M, Tag> ClosureTreeRepository<M, Tag>
+where
+    M: NamedClosureTreeModel<Tag>,
+{
+    /// The subtree under `root`, or the whole forest when `None`, nested into
+    /// [`TreeNode`]s in sibling order, from a single query.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.hash_tree",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?root.map(|root| root.id()),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn hash_tree(
+        &self,
+        conn: &impl ConnectionTrait,
+        root: Option<&M>,
+    ) -> Result<Vec<TreeNode<M>>, ClosureTreeError> {
+        metered!(self, "hash_tree", conn);
+        self.load_forest(conn, root).await
+    }
+
+    /// The subtree under `root`, or the whole forest when `None`, as a text
+    /// outline for logs and CLIs: one line per node in sibling order, its name
+    /// prefixed by `indent` once per level below the top.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.to_indented_string",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?root.map(|root| root.id()),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn to_indented_string(
+        &self,
+        conn: &impl ConnectionTrait,
+        root: Option<&M>,
+        indent: &str,
+    ) -> Result<String, ClosureTreeError> {
+        metered!(self, "to_indented_string", conn);
+        let mut lines = Vec::new();
+        for tree in self.load_forest(conn, root).await? {
+            tree.outline::<Tag>(indent, 0, &mut lines);
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// [`hash_tree`](Self::hash_tree) without its metering.
+    async fn load_forest(
+        &self,
+        conn: &impl ConnectionTrait,
+        root: Option<&M>,
+    ) -> Result<Vec<TreeNode<M>>, ClosureTreeError> {
+        let forest = match root {
+            Some(root) => {
+                let nodes = self.descendants_with_conn(conn, &root.id(), false).await?;
+                record_rows(nodes.len());
+                let id = root.id();
+                TreeNode::forest::<Tag>(nodes, |node| node.id() == id)
+            }
+            None => {
+                let nodes = self.ordered(self.find()).all(conn).await?;
+                record_rows(nodes.len());
+                TreeNode::forest::<Tag>(nodes, |node| self.is_root(node))
+            }
+        };
+        Ok(forest)
     }
 
-    fn ensure_postgres(conn: &impl ConnectionTrait) -> Result<(), ClosureTreeError> {
-        if conn.get_database_backend() == DbBackend::Postgres {
-            Ok(())
-        } else {
-            Err(ClosureTreeError::UnsupportedBackend)
-        }
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.find_by_path",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path_len = segments.len(),
+            )
+        )
+    )]
+    pub async fn find_by_path<S: Clone + Into<M::Name>>(
+        &self,
+        conn: &impl ConnectionTrait,
+        segments: &[S],
+    ) -> Result<Option<M>, ClosureTreeError> {
+        metered!(self, "find_by_path", conn);
+        self.find_by_path_on(conn, segments).await
     }
 
-    pub async fn parent(
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.find_or_create_by_path",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path_len = segments.len(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn find_or_create_by_path<S: Clone + Into<M::Name>>(
         &self,
         conn: &DatabaseConnection,
-        model: &M,
-    ) -> Result<Option<M>, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-        match model.parent_id() {
-            Some(parent_id) => {
-                let parent = M::Entity::find()
-                    .filter(M::id_column().eq(M::id_to_value(&parent_id)))
-                    .one(conn)
-                    .await?;
-                Ok(parent)
-            }
-            None => Ok(None),
+        segments: &[S],
+    ) -> Result<M, ClosureTreeError> {
+        if segments.is_empty() {
+            return Err(ClosureTreeError::EmptyPath);
         }
+
+        let first_segment = Self::lock_segment(&segments[0]);
+        let guard = self.lock_transaction(conn, Some(&first_segment)).await?;
+        let conn = guard.connection();
+        metered!(self, "find_or_create_by_path", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .find_or_create_by_path_on(conn, segments, &mut changes)
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
     }
 
-    pub async fn children(
+    /// [`find_by_path`](Self::find_by_path) for a path string such as `"a/b/c"`,
+    /// split on the configured [`path_separator`](ClosureTreeConfig::path_separator).
+    ///
+    /// Empty segments are skipped and a backslash escapes the next character, so
+    /// `"a\\/b"` names a single node `a/b`. A path with no names is
+    /// [`ClosureTreeError::EmptyPath`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.find_by_path_str",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path = %path,
+            )
+        )
+    )]
+    pub async fn find_by_path_str(
+        &self,
+        conn: &impl ConnectionTrait,
+        path: &str,
+    ) -> Result<Option<M>, ClosureTreeError>
+    where
+        M::Name: From<String>,
+    {
+        let segments = self.split_path(path)?;
+        metered!(self, "find_by_path_str", conn);
+        self.find_by_path_on(conn, &segments).await
+    }
+
+    /// Like [`find_or_create_by_path`](Self::find_or_create_by_path), but return
+    /// every node along the path, root first, instead of only the leaf; e.g. to
+    /// build navigation with a handle for each level.
+    ///
+    /// Nodes are returned as they were found or inserted, without being read
+    /// back, so columns the write maintains afterwards (touch timestamps, child
+    /// counts, `ltree` paths) may be stale.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ensure_path",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path_len = segments.len(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ensure_path<S: Clone + Into<M::Name>>(
         &self,
         conn: &DatabaseConnection,
-        model: &M,
+        segments: &[S],
     ) -> Result<Vec<M>, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-        let id = model.id();
-        let parent_value = M::id_to_value(&id);
-        let mut query = M::Entity::find().filter(M::parent_column().eq(parent_value));
-        if let Some(OrderStrategy::NumericColumn { column }) = self.config().order_strategy() {
-            query = query.order_by_asc(Expr::cust(column.clone()));
+        if segments.is_empty() {
+            return Err(ClosureTreeError::EmptyPath);
         }
-        query = query.order_by_asc(M::name_column());
-        let rows = query.all(conn).await?;
-        Ok(rows)
-    }
 
-    pub async fn roots(&self, conn: &DatabaseConnection) -> Result<Vec<M>, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-        let rows = M::Entity::find()
-            .filter(M::parent_column().is_null())
-            .order_by_asc(M::name_column())
-            .all(conn)
+        let guard = self
+            .lock_transaction(conn, Some(&Self::lock_segment(&segments[0])))
             .await?;
-        Ok(rows)
+        let conn = guard.connection();
+        metered!(self, "ensure_path", conn);
+        let mut changes = Vec::new();
+        let result = self.ensure_path_on(conn, segments, &mut changes).await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
     }
 
-    pub async fn descendants(
+    /// [`ensure_path`](Self::ensure_path) inside `txn`, taking only the locks
+    /// `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ensure_path_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path_len = segments.len(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ensure_path_in<S: Clone + Into<M::Name>>(
         &self,
-        conn: &DatabaseConnection,
-        model: &M,
+        txn: &LockedTransaction,
+        segments: &[S],
     ) -> Result<Vec<M>, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-        let rows = self.descendants_with_conn(conn, &model.id(), true).await?;
-        Ok(rows)
+        if segments.is_empty() {
+            return Err(ClosureTreeError::EmptyPath);
+        }
+
+        self.reenter(txn, Some(&Self::lock_segment(&segments[0])))
+            .await?;
+        let conn = txn.connection();
+        metered!(self, "ensure_path_in", conn);
+        let mut changes = Vec::new();
+        let result = self.ensure_path_on(conn, segments, &mut changes).await;
+        self.observe(txn, changes);
+        result
     }
 
-    pub async fn self_and_descendants(
+    /// [`find_or_create_by_path`](Self::find_or_create_by_path) for a path
+    /// string, split as in [`find_by_path_str`](Self::find_by_path_str).
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.find_or_create_by_path_str",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path = %path,
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn find_or_create_by_path_str(
         &self,
         conn: &DatabaseConnection,
+        path: &str,
+    ) -> Result<M, ClosureTreeError>
+    where
+        M::Name: From<String>,
+    {
+        let segments = self.split_path(path)?;
+        let guard = self.lock_transaction(conn, Some(&segments[0])).await?;
+        let conn = guard.connection();
+        metered!(self, "find_or_create_by_path_str", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .find_or_create_by_path_on(conn, &segments, &mut changes)
+            .await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// The names from the root down to `model`, joined with the configured
+    /// [`path_separator`](ClosureTreeConfig::path_separator) and escaped so that
+    /// [`find_by_path_str`](Self::find_by_path_str) finds `model` again.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestry_path_str",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?model.id(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestry_path_str(
+        &self,
+        conn: &impl ConnectionTrait,
         model: &M,
-    ) -> Result<Vec<M>, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-        let mut nodes = Vec::with_capacity(1);
-        nodes.push(model.clone());
-        let mut descendants = self.descendants_with_conn(conn, &model.id(), true).await?;
-        nodes.append(&mut descendants);
-        Ok(nodes)
+    ) -> Result<String, ClosureTreeError> {
+        metered!(self, "ancestry_path_str", conn);
+        let ancestors = self
+            .load_ancestors(conn, &model.id(), GenerationRange::AtLeast(1))
+            .await?;
+        record_rows(ancestors.len());
+        let names: Vec<String> = ancestors
+            .iter()
+            .rev()
+            .chain(std::iter::once(model))
+            .map(|node| path::segment_text(&node.name().clone().into()))
+            .collect();
+        Ok(path::join_path(
+            names.iter().map(String::as_str),
+            self.config().path_separator(),
+        ))
     }
 
-    pub async fn find_by_path<S: AsRef<str>>(
+    /// The ancestor names of each of `ids`, root first and without the node's
+    /// own name, e.g. "Category > Subcategory" labels for a page of items.
+    ///
+    /// Takes two queries per thousand ids, one on the hierarchy table and one
+    /// loading each distinct ancestor once. Every id is a key; roots and
+    /// unknown ids map to an empty list.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.ancestor_names",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                ids = ids.len(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn ancestor_names(
         &self,
-        conn: &DatabaseConnection,
-        segments: &[S],
-    ) -> Result<Option<M>, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-        self.find_by_path_on(conn, segments).await
+        conn: &impl ConnectionTrait,
+        ids: &[M::Id],
+    ) -> Result<HashMap<M::Id, Vec<String>>, ClosureTreeError> {
+        metered!(self, "ancestor_names", conn);
+        let mut names: HashMap<M::Id, Vec<String>> =
+            ids.iter().map(|id| (id.clone(), Vec::new())).collect();
+        let mut loaded = 0;
+        for chunk in ids.chunks(HIERARCHY_INSERT_CHUNK) {
+            let mut rows = M::HierarchyEntity::find()
+                .filter(
+                    M::hierarchy_descendant_column()
+                        .is_in(chunk.iter().map(M::hierarchy_id_to_value)),
+                )
+                .filter(M::hierarchy_generations_column().gt(0))
+                .all(conn)
+                .await?;
+            if rows.is_empty() {
+                continue;
+            }
+            let ancestor_ids: HashSet<M::Id> =
+                rows.iter().map(M::hierarchy_model_ancestor).collect();
+            let ancestors: HashMap<M::Id, String> = self
+                .find()
+                .filter(M::id_column().is_in(ancestor_ids.iter().map(M::id_to_value)))
+                .all(conn)
+                .await?
+                .into_iter()
+                .map(|node| (node.id(), path::segment_text(&node.name().clone().into())))
+                .collect();
+            loaded += ancestors.len();
+
+            rows.sort_by_key(|row| std::cmp::Reverse(M::hierarchy_model_generations(row)));
+            for row in &rows {
+                let ancestor = M::hierarchy_model_ancestor(row);
+                if let (Some(list), Some(name)) = (
+                    names.get_mut(&M::hierarchy_model_descendant(row)),
+                    ancestors.get(&ancestor),
+                ) {
+                    list.push(name.clone());
+                }
+            }
+        }
+        record_rows(loaded);
+        Ok(names)
     }
 
-    pub async fn find_or_create_by_path<S: AsRef<str>>(
+    /// [`find_or_create_by_path`](Self::find_or_create_by_path) inside `txn`,
+    /// taking only the locks `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.find_or_create_by_path_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                path_len = segments.len(),
+                rows = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn find_or_create_by_path_in<S: Clone + Into<M::Name>>(
         &self,
-        conn: &DatabaseConnection,
+        txn: &LockedTransaction,
         segments: &[S],
     ) -> Result<M, ClosureTreeError> {
-        Self::ensure_postgres(conn)?;
-
         if segments.is_empty() {
             return Err(ClosureTreeError::EmptyPath);
         }
 
-        let strategy = self.config().advisory_lock_strategy().clone();
-        let guard = LockedTransaction::acquire(&strategy, conn).await?;
-        self.find_or_create_with_guard(guard, segments).await
+        self.reenter(txn, Some(&Self::lock_segment(&segments[0])))
+            .await?;
+        let conn = txn.connection();
+        metered!(self, "find_or_create_by_path_in", conn);
+        let mut changes = Vec::new();
+        let result = self
+            .find_or_create_by_path_on(conn, segments, &mut changes)
+            .await;
+        self.observe(txn, changes);
+        result
     }
 
-    async fn find_or_create_with_guard<S: AsRef<str>>(
+    /// Create `root` and all of its nested children beneath `parent` (or as a new
+    /// root) in one locked transaction, returning the created root.
+    ///
+    /// Hierarchy rows are inserted in one batch per tree level.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.import_tree",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                parent_id = ?parent,
+            )
+        )
+    )]
+    pub async fn import_tree(
         &self,
-        guard: LockedTransaction,
-        segments: &[S],
+        conn: &DatabaseConnection,
+        root: &TreeInput,
+        parent: Option<&M::Id>,
+    ) -> Result<M, ClosureTreeError>
+    where
+        M::Name: From<String>,
+    {
+        let first_segment = parent.is_none().then_some(root.name.as_str());
+        let guard = self.lock_transaction(conn, first_segment).await?;
+        let conn = guard.connection();
+        metered!(self, "import_tree", conn);
+        let mut changes = Vec::new();
+        let result = self.import_tree_on(conn, root, parent, &mut changes).await;
+        self.observe(&guard, changes);
+        Self::finish(guard, result).await
+    }
+
+    /// [`import_tree`](Self::import_tree) inside `txn`, taking only the locks
+    /// `txn` does not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.import_tree_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                parent_id = ?parent,
+            )
+        )
+    )]
+    pub async fn import_tree_in(
+        &self,
+        txn: &LockedTransaction,
+        root: &TreeInput,
+        parent: Option<&M::Id>,
+    ) -> Result<M, ClosureTreeError>
+    where
+        M::Name: From<String>,
+    {
+        let first_segment = parent.is_none().then_some(root.name.as_str());
+        self.reenter(txn, first_segment).await?;
+        let conn = txn.connection();
+        metered!(self, "import_tree_in", conn);
+        let mut changes = Vec::new();
+        let result = self.import_tree_on(conn, root, parent, &mut changes).await;
+        self.observe(txn, changes);
+        result
+    }
+
+    /// Rename `node`, returning the updated model.
+    ///
+    /// With [`unique_sibling_names`](ClosureTreeConfig::unique_sibling_names) set,
+    /// fails with [`ClosureTreeError::DuplicateName`] if a sibling already has
+    /// `new_name`. `ltree` paths are built from ids, so they are
+    /// unaffected.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.rename",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+            )
+        )
+    )]
+    pub async fn rename(
+        &self,
+        conn: &DatabaseConnection,
+        node: &M,
+        new_name: impl Into<M::Name>,
     ) -> Result<M, ClosureTreeError> {
-        let result = self
-            .find_or_create_by_path_on(guard.connection(), segments)
-            .await;
+        let guard = self.lock_transaction(conn, None).await?;
+        let conn = guard.connection();
+        metered!(self, "rename", conn);
+        let result = self.rename_on(conn, node, new_name.into()).await;
+        Self::finish(guard, result).await
+    }
 
-        match result {
-            Ok(model) => {
-                guard.commit().await?;
-                Ok(model)
-            }
-            Err(err) => {
-                let _ = guard.rollback().await;
-                Err(err)
-            }
+    /// [`rename`](Self::rename) inside `txn`, taking only the locks `txn` does
+    /// not already hold.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "closure_tree.rename_in",
+            skip_all,
+            fields(
+                entity = %self.config().entity_name(),
+                node_id = ?node.id(),
+            )
+        )
+    )]
+    pub async fn rename_in(
+        &self,
+        txn: &LockedTransaction,
+        node: &M,
+        new_name: impl Into<M::Name>,
+    ) -> Result<M, ClosureTreeError> {
+        self.reenter(txn, None).await?;
+        let conn = txn.connection();
+        metered!(self, "rename_in", conn);
+        self.rename_on(conn, node, new_name.into()).await
+    }
+
+    fn split_path(&self, path: &str) -> Result<Vec<String>, ClosureTreeError> {
+        let segments = path::split_path(path, self.config().path_separator());
+        if segments.is_empty() {
+            return Err(ClosureTreeError::EmptyPath);
         }
+        Ok(segments)
+    }
+
+    /// `segment` as the text used for lock keys.
+    fn lock_segment<S: Clone + Into<M::Name>>(segment: &S) -> String {
+        path::segment_text(&Self::name_value(segment))
+    }
+
+    fn name_value<S: Clone + Into<M::Name>>(segment: &S) -> Value {
+        let name: M::Name = segment.clone().into();
+        name.into()
     }
 
-    async fn find_by_path_on<S: AsRef<str>, C: ConnectionTrait>(
+    async fn find_by_path_on<S: Clone + Into<M::Name>, C: ConnectionTrait>(
         &self,
         conn: &C,
         segments: &[S],
     ) -> Result<Option<M>, ClosureTreeError> {
-        if segments.is_empty() {
-            return Ok(None);
-        }
+        let conditions = segments
+            .iter()
+            .map(|segment| self.name_matches(self.trim_value(Self::name_value(segment))))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.find_path_by(conn, conditions).await
+    }
+
+    async fn find_or_create_by_path_on<S: Clone + Into<M::Name>, C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        segments: &[S],
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<M, ClosureTreeError> {
+        self.ensure_path_on(conn, segments, changes)
+            .await?
+            .pop()
+            .ok_or_else(|| ClosureTreeError::invariant("path segments produced no model"))
+    }
 
+    /// Every node along `segments`, root first, creating the missing ones.
+    async fn ensure_path_on<S: Clone + Into<M::Name>, C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        segments: &[S],
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<Vec<M>, ClosureTreeError> {
+        let segments = self.creatable_segments(segments.iter().map(Self::name_value))?;
         let mut current_parent: Option<M::Id> = None;
-        let mut current: Option<M> = None;
+        let mut chain: Vec<M> = Vec::with_capacity(segments.len());
+        let mut ancestry: Option<Ancestry<M, Tag>> = None;
+        let mut created_ids = Vec::new();
 
-        for segment in segments {
-            let name = segment.as_ref();
-            let node = self
-                .find_child_by_name(conn, current_parent.as_ref(), name)
-                .await?;
+        for name in segments {
+            // Once a segment had to be created, every following segment is new as well,
+            // so the lookup is skipped and the cached ancestry is extended in memory.
+            let parent_ancestry = match ancestry.take() {
+                Some(ancestry) => ancestry,
+                None => match self
+                    .find_child_by_name(conn, current_parent.as_ref(), name.clone())
+                    .await?
+                {
+                    Some(model) => {
+                        current_parent = Some(model.id());
+                        chain.push(model);
+                        continue;
+                    }
+                    None => {
+                        self.ensure_child_capacity(conn, current_parent.as_ref(), 1)
+                            .await?;
+                        Ancestry::<M, Tag>::load(conn, current_parent.as_ref()).await?
+                    }
+                },
+            };
+            self.ensure_depth(&parent_ancestry, 0)?;
 
-            match node {
-                Some(model) => {
-                    current_parent = Some(model.id());
-                    current = Some(model);
+            // The insert runs in a savepoint: if a concurrent writer created the same
+            // child first and a unique index rejects ours, the transaction stays usable
+            // and the other writer's row is returned instead.
+            conn.execute_unprepared("SAVEPOINT closure_tree_insert")
+                .await?;
+            let created = match self
+                .insert_child(conn, chain.last(), &parent_ancestry, name.clone())
+                .await
+            {
+                Ok(created) => {
+                    conn.execute_unprepared("RELEASE SAVEPOINT closure_tree_insert")
+                        .await?;
+                    created
                 }
-                None => return Ok(None),
+                Err(err) if err.is_unique_violation() => {
+                    conn.execute_unprepared("ROLLBACK TO SAVEPOINT closure_tree_insert")
+                        .await?;
+                    let existing = self
+                        .find_child_by_name(conn, current_parent.as_ref(), name)
+                        .await?
+                        .ok_or(err)?;
+                    current_parent = Some(existing.id());
+                    chain.push(existing);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            // Nodes created after the first one sit below it, so only the
+            // existing branch above the first needs touching.
+            if created_ids.is_empty() {
+                self.touch_ancestors(conn, current_parent.as_ref()).await?;
+                self.adjust_children_count(conn, current_parent.as_ref(), 1)
+                    .await?;
             }
+            changes.push(TreeChange::Created(created.id()));
+            ancestry = Some(parent_ancestry.child(created.id()));
+            current_parent = Some(created.id());
+            created_ids.push(created.id());
+            chain.push(created);
         }
 
-        Ok(current)
+        self.refresh_ltree_paths(conn, Some(&created_ids)).await?;
+        self.recount_children_on(conn, Some(&created_ids)).await?;
+        let created = chain.len() - created_ids.len();
+        self.after_create(conn, &chain[created..]).await?;
+        record_rows(created_ids.len());
+        Ok(chain)
     }
 
-    async fn find_or_create_by_path_on<S: AsRef<str>, C: ConnectionTrait>(
+    async fn import_tree_on<C: ConnectionTrait>(
         &self,
         conn: &C,
-        segments: &[S],
-    ) -> Result<M, ClosureTreeError> {
-        let mut current_parent: Option<M::Id> = None;
-        let mut current: Option<M> = None;
+        root: &TreeInput,
+        parent: Option<&M::Id>,
+        changes: &mut Vec<TreeChange<M::Id>>,
+    ) -> Result<M, ClosureTreeError>
+    where
+        M::Name: From<String>,
+    {
+        let parent_model = match parent {
+            Some(parent) => Some(self.load(conn, parent).await?),
+            None => None,
+        };
+        self.ensure_unique_name(conn, parent, Self::name_value(&root.name), None)
+            .await?;
+        self.ensure_child_capacity(conn, parent, 1).await?;
+        let ancestry = Ancestry::<M, Tag>::load(conn, parent).await?;
+        self.ensure_depth(&ancestry, root.height())?;
+        let root_model = self
+            .insert_child(
+                conn,
+                parent_model.as_ref(),
+                &ancestry,
+                Self::name_value(&root.name),
+            )
+            .await?;
+        changes.push(TreeChange::Created(root_model.id()));
+        let mut created_ids = vec![root_model.id()];
+        let mut created = vec![root_model.clone()];
 
-        for segment in segments {
-            let name = segment.as_ref();
-            match self
-                .find_child_by_name(conn, current_parent.as_ref(), name)
-                .await?
-            {
-                Some(model) => {
-                    current_parent = Some(model.id());
-                    current = Some(model);
+        let mut level = vec![(
+            root_model.clone(),
+            ancestry.child(root_model.id()),
+            &root.children,
+        )];
+        while !level.is_empty() {
+            let mut next_level = Vec::new();
+            let mut rows = Vec::new();
+
+            for (parent, ancestry, children) in &level {
+                let parent_id = parent.id();
+                // Every parent in `level` is new, so only `children` can clash with
+                // each other, and they are numbered from zero.
+                if self.config().unique_sibling_names() {
+                    let mut names = HashSet::new();
+                    if let Some(child) = children.iter().find(|child| {
+                        let name = self.normalize_value(Self::name_value(&child.name));
+                        !names.insert(path::segment_text(&name))
+                    }) {
+                        return Err(ClosureTreeError::DuplicateName {
+                            parent_id: Some(format!("{parent_id:?}")),
+                            name: child.name.clone(),
+                        });
+                    }
+                }
+                if let Some(max_children) = self.config().max_children() {
+                    if children.len() as u64 > max_children {
+                        return Err(ClosureTreeError::ChildLimitExceeded {
+                            parent_id: format!("{parent_id:?}"),
+                            max_children,
+                        });
+                    }
                 }
-                None => {
-                    let created = self
-                        .insert_child(conn, current_parent.as_ref(), name)
+                for (index, child) in children.iter().enumerate() {
+                    let position = self.order_column().map(|_| self.nth_position(index as i64));
+                    let model = self
+                        .insert_node(conn, Some(parent), Self::name_value(&child.name), position)
                         .await?;
-                    current_parent = Some(created.id());
-                    current = Some(created);
+                    changes.push(TreeChange::Created(model.id()));
+                    created_ids.push(model.id());
+                    rows.extend(ancestry.hierarchy_rows(&model.id()));
+                    if !child.children.is_empty() {
+                        next_level.push((
+                            model.clone(),
+                            ancestry.child(model.id()),
+                            &child.children,
+                        ));
+                    }
+                    created.push(model);
                 }
             }
+
+            insert_hierarchy_rows::<M, Tag, C>(conn, self.config(), rows).await?;
+            level = next_level;
         }
 
-        current.ok_or_else(|| ClosureTreeError::invariant("path segments produced no model"))
+        self.refresh_ltree_paths(conn, Some(&created_ids)).await?;
+        self.recount_children_on(conn, Some(&created_ids)).await?;
+        self.adjust_children_count(conn, parent, 1).await?;
+        self.touch_ancestors(conn, parent).await?;
+        self.after_create(conn, &created).await?;
+        Ok(root_model)
     }
 
-    async fn insert_child<C: ConnectionTrait>(
+    async fn rename_on<C: ConnectionTrait>(
         &self,
         conn: &C,
-        parent_id: Option<&M::Id>,
-        name: &str,
+        node: &M,
+        new_name: M::Name,
     ) -> Result<M, ClosureTreeError> {
-        let mut active = M::ActiveModel::default();
-        M::set_parent(&mut active, parent_id.cloned());
-        M::set_name(&mut active, name);
+        let new_name: Value = new_name.into();
+        self.ensure_unique_name(
+            conn,
+            self.parent_of(node).as_ref(),
+            new_name.clone(),
+            Some(&node.id()),
+        )
+        .await?;
+        let mut active = node.clone().into_active_model();
+        active.set(
+            Self::name_column_for("rename")?,
+            self.normalize_value(new_name),
+        );
+        let renamed = active.update(conn).await?;
+        self.touch_node(conn, &node.id()).await?;
+        Ok(renamed)
+    }
 
-        let model = active.insert(conn).await?;
-        self.insert_hierarchy_rows(conn, &model, parent_id).await?;
+    async fn insert_child<C: ConnectionTrait>(
+        &self,
+        conn: &C,
+        parent: Option<&M>,
+        ancestry: &Ancestry<M, Tag>,
+        name: Value,
+    ) -> Result<M, ClosureTreeError> {
+        let parent_id = parent.map(M::id);
+        let position = self.next_order_value(conn, parent_id.as_ref()).await?;
+        let model = self.insert_node(conn, parent, name, position).await?;
+        insert_hierarchy_rows::<M, Tag, C>(
+            conn,
+            self.config(),
+            ancestry.hierarchy_rows(&model.id()),
+        )
+        .await?;
         Ok(model)
     }
 
-    async fn insert_hierarchy_rows<C: ConnectionTrait>(
+    /// Insert the base row only; the caller is responsible for its hierarchy rows.
+    async fn insert_node<C: ConnectionTrait>(
         &self,
         conn: &C,
-        model: &M,
-        parent_id: Option<&M::Id>,
-    ) -> Result<(), ClosureTreeError> {
-        let mut rows = Vec::new();
-        let model_id = model.id();
+        parent: Option<&M>,
+        name: Value,
+        position: Option<Position>,
+    ) -> Result<M, ClosureTreeError> {
+        let parent_id = parent.map(M::id);
+        let mut active = M::ActiveModel::default();
+        Self::assign_id(&mut active);
+        self.set_parent(&mut active, parent_id.as_ref());
+        active.set(Self::name_column_for("create")?, self.normalize_value(name));
+        M::new_child_defaults(&mut active, parent);
+        self.stamp_scope(&mut active)?;
+        if let Some(position) = position {
+            Self::set_position(&mut active, position)?;
+        }
+        self.before_create(conn, parent_id.as_ref(), &mut active)
+            .await?;
 
-        rows.push(M::hierarchy_build_row(
-            model_id.clone(),
-            model_id.clone(),
-            0,
-        ));
+        let model = active.insert(conn).await?;
+        self.link_root(conn, model).await
+    }
+}
 
-        if let Some(parent_id) = parent_id {
-            let ancestors = M::HierarchyEntity::find()
-                .filter(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(parent_id)))
-                .all(conn)
-                .await?;
+/// A sibling position, in the representation of the configured
+/// [`OrderStrategy`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Position {
+    Numeric(i64),
+    Fractional(f64),
+}
 
-            for ancestor in ancestors {
-                let ancestor_id = M::hierarchy_model_ancestor(&ancestor);
-                let generations = M::hierarchy_model_generations(&ancestor) + 1;
-                rows.push(M::hierarchy_build_row(
-                    ancestor_id,
-                    model_id.clone(),
-                    generations,
-                ));
-            }
-        }
+/// A scope value as lock-key text: its PostgreSQL literal (`42`, `'acme'`), the
+/// same for every build and reproducible outside Rust, unlike `Value`'s
+/// `Debug` form.
+fn scope_key_text(value: &Value) -> String {
+    PostgresQueryBuilder.value_to_string(value)
+}
 
-        M::HierarchyEntity::insert_many(rows).exec(conn).await?;
-        Ok(())
+/// Record the rows an operation returned or changed on its tracing span.
+fn record_rows(_rows: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("rows", _rows);
+}
+
+/// Hierarchy rows whose generations lie in the inclusive `(min, max)` bounds.
+fn generations_filter<M: ClosureTreeModel<Tag>, Tag>((min, max): (i32, Option<i32>)) -> SimpleExpr {
+    match max {
+        Some(max) => M::hierarchy_generations_column().between(min, max),
+        None => M::hierarchy_generations_column().gte(min),
+    }
+}
+
+/// `0` typed like the parent column, the [`RootMarker::Zero`] marker.
+fn zero_value<M: ClosureTreeModel<Tag>, Tag>() -> Value {
+    match M::parent_column().def().get_column_type() {
+        ColumnType::TinyInteger => Value::TinyInt(Some(0)),
+        ColumnType::SmallInteger => Value::SmallInt(Some(0)),
+        ColumnType::BigInteger => Value::BigInt(Some(0)),
+        ColumnType::TinyUnsigned => Value::TinyUnsigned(Some(0)),
+        ColumnType::SmallUnsigned => Value::SmallUnsigned(Some(0)),
+        ColumnType::Unsigned => Value::Unsigned(Some(0)),
+        ColumnType::BigUnsigned => Value::BigUnsigned(Some(0)),
+        _ => Value::Int(Some(0)),
     }
+}
 
-    async fn find_child_by_name<C: ConnectionTrait>(
-        &self,
-        conn: &C,
-        parent_id: Option<&M::Id>,
-        name: &str,
-    ) -> Result<Option<M>, ClosureTreeError> {
-        let mut condition = Condition::all().add(M::name_column().eq(name));
+/// Whether `value` is an integer `0`.
+fn is_zero(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::TinyInt(Some(0))
+            | Value::SmallInt(Some(0))
+            | Value::Int(Some(0))
+            | Value::BigInt(Some(0))
+            | Value::TinyUnsigned(Some(0))
+            | Value::SmallUnsigned(Some(0))
+            | Value::Unsigned(Some(0))
+            | Value::BigUnsigned(Some(0))
+    )
+}
 
-        if let Some(parent_id) = parent_id {
-            condition = condition.add(M::parent_column().eq(M::id_to_value(parent_id)));
-        } else {
-            condition = condition.add(M::parent_column().is_null());
-        }
+/// Quote an SQL identifier (PostgreSQL and SQLite syntax).
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
 
-        let model = M::Entity::find().filter(condition).one(conn).await?;
-        Ok(model)
+/// The quoted table of `entity`, qualified with its schema when it has one.
+fn qualified_table(entity: impl EntityName) -> String {
+    match entity.schema_name() {
+        Some(schema) => format!(
+            "{}.{}",
+            quote_ident(schema),
+            quote_ident(entity.table_name())
+        ),
+        None => quote_ident(entity.table_name()),
     }
+}
 
-    async fn descendants_with_conn<C: ConnectionTrait>(
-        &self,
-        conn: &C,
-        ancestor_id: &M::Id,
-        exclude_root: bool,
-    ) -> Result<Vec<M>, ClosureTreeError> {
-        let mut query = M::HierarchyEntity::find()
-            .filter(M::hierarchy_ancestor_column().eq(M::hierarchy_id_to_value(ancestor_id)));
+/// Maximum number of hierarchy rows sent in one `INSERT`, keeping well below the
+/// bind-parameter limits of the supported backends.
+const HIERARCHY_INSERT_CHUNK: usize = 1000;
 
-        if exclude_root {
-            query = query.filter(M::hierarchy_generations_column().gt(0));
+/// Insert hierarchy rows in chunks, skipping rows that collide on the
+/// [`hierarchy_conflict_columns`](ClosureTreeConfig::hierarchy_conflict_columns)
+/// when they are configured.
+///
+/// Only the ancestor, descendant and generations columns are written; other
+/// columns of the hierarchy table (a surrogate key, `created_at`) take their
+/// database defaults.
+async fn insert_hierarchy_rows<M, Tag, C>(
+    conn: &C,
+    config: &ClosureTreeConfig,
+    rows: Vec<M::HierarchyActiveModel>,
+) -> Result<(), ClosureTreeError>
+where
+    M: ClosureTreeModel<Tag>,
+    C: ConnectionTrait,
+{
+    let on_conflict = config.hierarchy_conflict_columns().map(|columns| {
+        OnConflict::columns(columns.iter().map(Alias::new))
+            .do_nothing()
+            .to_owned()
+    });
+    let mut rows = rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let chunk: Vec<_> = rows.by_ref().take(HIERARCHY_INSERT_CHUNK).collect();
+        let insert = M::HierarchyEntity::insert_many(chunk);
+        match &on_conflict {
+            // `exec` would fail with `RecordNotInserted` when every row is skipped.
+            Some(on_conflict) => {
+                let statement = insert
+                    .on_conflict(on_conflict.clone())
+                    .build(conn.get_database_backend());
+                conn.execute(statement).await?;
+            }
+            None => {
+                insert.exec(conn).await?;
+            }
         }
+    }
+    Ok(())
+}
 
-        let rows = query.all(conn).await?;
+/// Self-and-ancestor closure rows of a parent node.
+///
+/// Loaded once per parent and reused for every child inserted beneath it, so
+/// creating many siblings (or a chain of new nodes) does not re-query the
+/// hierarchy table for each insert.
+struct Ancestry<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    /// `(ancestor, generations)` pairs relative to the parent, including the parent itself.
+    rows: Vec<(M::Id, i32)>,
+    _tree: PhantomData<fn() -> Tag>,
+}
 
-        let mut descendant_ids = Vec::with_capacity(rows.len());
-        for hierarchy in rows {
-            let descendant = M::hierarchy_model_descendant(&hierarchy);
-            descendant_ids.push(descendant);
-        }
+impl<M, Tag> Ancestry<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    async fn load<C: ConnectionTrait>(
+        conn: &C,
+        parent_id: Option<&M::Id>,
+    ) -> Result<Self, ClosureTreeError> {
+        let Some(parent_id) = parent_id else {
+            return Ok(Self::from_rows(Vec::new()));
+        };
 
-        if descendant_ids.is_empty() {
-            return Ok(Vec::new());
+        let rows = M::HierarchyEntity::find()
+            .filter(M::hierarchy_descendant_column().eq(M::hierarchy_id_to_value(parent_id)))
+            .all(conn)
+            .await?
+            .iter()
+            .map(|row| {
+                (
+                    M::hierarchy_model_ancestor(row),
+                    M::hierarchy_model_generations(row),
+                )
+            })
+            .collect();
+
+        Ok(Self::from_rows(rows))
+    }
+
+    fn from_rows(rows: Vec<(M::Id, i32)>) -> Self {
+        Self {
+            rows,
+            _tree: PhantomData,
         }
+    }
 
-        let values = descendant_ids
-            .iter()
-            .map(|id| M::id_to_value(id))
-            .collect::<Vec<_>>();
+    /// Hierarchy rows for a new child of the parent.
+    fn hierarchy_rows(&self, child_id: &M::Id) -> Vec<M::HierarchyActiveModel> {
+        let mut rows = Vec::with_capacity(self.rows.len() + 1);
+        rows.push(M::hierarchy_build_row(
+            child_id.clone(),
+            child_id.clone(),
+            0,
+        ));
+        for (ancestor, generations) in &self.rows {
+            rows.push(M::hierarchy_build_row(
+                ancestor.clone(),
+                child_id.clone(),
+                generations + 1,
+            ));
+        }
+        rows
+    }
 
-        let mut query = M::Entity::find().filter(M::id_column().is_in(values));
-        if let Some(OrderStrategy::NumericColumn { column }) = self.config().order_strategy() {
-            query = query.order_by_asc(Expr::cust(column.clone()));
+    /// Hierarchy rows attaching `subtree`, given as `(descendant, generations)`
+    /// pairs relative to its root, beneath the parent.
+    fn subtree_rows(&self, subtree: &[(M::Id, i32)]) -> Vec<M::HierarchyActiveModel> {
+        let mut rows = Vec::with_capacity(self.rows.len() * subtree.len());
+        for (ancestor, above) in &self.rows {
+            for (descendant, below) in subtree {
+                rows.push(M::hierarchy_build_row(
+                    ancestor.clone(),
+                    descendant.clone(),
+                    above + below + 1,
+                ));
+            }
         }
-        query = query.order_by_asc(M::name_column());
+        rows
+    }
+
+    /// Depth of a new child of the parent; `0` for a root.
+    fn child_depth(&self) -> u32 {
+        self.rows.len() as u32
+    }
 
-        let models = query.all(conn).await?;
-        Ok(models)
+    /// Ancestry of a freshly inserted child, derived without touching the database.
+    fn child(&self, child_id: M::Id) -> Self {
+        let mut rows = Vec::with_capacity(self.rows.len() + 1);
+        rows.push((child_id, 0));
+        rows.extend(
+            self.rows
+                .iter()
+                .map(|(ancestor, generations)| (ancestor.clone(), generations + 1)),
+        );
+        Self::from_rows(rows)
     }
 }
@@ -0,0 +1,243 @@
+//! Startup checks of a [`ClosureTreeConfig`](crate::ClosureTreeConfig) and of
+//! the tables it describes, so misconfiguration fails fast with readable
+//! messages instead of surfacing as SQL errors on the first write.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, Statement, Value};
+
+/// A problem found by [`ClosureTreeConfig::validate`](crate::ClosureTreeConfig::validate)
+/// or [`ClosureTreeRepository::check_schema`](crate::ClosureTreeRepository::check_schema).
+///
+/// `role` names what the configuration uses the column for, e.g. `"order"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SchemaFinding {
+    /// The configuration contradicts itself or the model.
+    InvalidConfig(String),
+    MissingTable {
+        table: String,
+    },
+    MissingColumn {
+        table: String,
+        column: String,
+        role: &'static str,
+    },
+    IncompatibleType {
+        table: String,
+        column: String,
+        role: &'static str,
+        expected: &'static str,
+        found: String,
+    },
+    /// A column that must accept `NULL` is declared `NOT NULL`.
+    NotNullable {
+        table: String,
+        column: String,
+        role: &'static str,
+    },
+}
+
+impl fmt::Display for SchemaFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidConfig(detail) => write!(f, "invalid configuration: {detail}"),
+            Self::MissingTable { table } => write!(f, "table `{table}` does not exist"),
+            Self::MissingColumn {
+                table,
+                column,
+                role,
+            } => write!(f, "{role} column `{table}.{column}` does not exist"),
+            Self::IncompatibleType {
+                table,
+                column,
+                role,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{role} column `{table}.{column}` has type `{found}`, expected {expected}"
+            ),
+            Self::NotNullable {
+                table,
+                column,
+                role,
+            } => write!(f, "{role} column `{table}.{column}` must be nullable"),
+        }
+    }
+}
+
+/// Types a configured column may have.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ColumnKind {
+    Any,
+    /// The type of the entity's `id` column, for columns referencing nodes.
+    Id,
+    Integer,
+    /// A floating-point or `NUMERIC` type, for fractional positions.
+    Fractional,
+    Text,
+    /// Something `CURRENT_TIMESTAMP` can be assigned to.
+    Timestamp,
+    Ltree,
+}
+
+impl ColumnKind {
+    fn expected(self) -> &'static str {
+        match self {
+            Self::Any => "any type",
+            Self::Id => "the type of the id column",
+            Self::Integer => "an integer type",
+            Self::Fractional => "a floating-point or numeric type",
+            Self::Text => "a text type",
+            Self::Timestamp => "a timestamp, date or text type",
+            Self::Ltree => "`ltree`",
+        }
+    }
+
+    fn accepts(self, found: &str, id: Option<&str>) -> bool {
+        const INTEGER: &[&str] = &["smallint", "integer", "bigint"];
+        const FRACTIONAL: &[&str] = &["real", "double precision", "numeric"];
+        const TEXT: &[&str] = &["text", "character varying", "character"];
+        const TIMESTAMP: &[&str] = &[
+            "timestamp with time zone",
+            "timestamp without time zone",
+            "date",
+        ];
+        match self {
+            Self::Any => true,
+            Self::Id => id.is_none_or(|id| id == found),
+            Self::Integer => INTEGER.contains(&found),
+            Self::Fractional => FRACTIONAL.contains(&found),
+            Self::Text => TEXT.contains(&found),
+            Self::Timestamp => TIMESTAMP.contains(&found) || TEXT.contains(&found),
+            Self::Ltree => found == "ltree",
+        }
+    }
+}
+
+/// A column the configuration relies on.
+pub(crate) struct ExpectedColumn {
+    /// `None` for the first schema on the `search_path`.
+    pub(crate) schema: Option<String>,
+    pub(crate) table: String,
+    pub(crate) column: String,
+    pub(crate) role: &'static str,
+    pub(crate) kind: ColumnKind,
+    pub(crate) nullable: bool,
+}
+
+/// Compare `expected` with `information_schema.columns` (PostgreSQL). The
+/// column with role `"id"` sets the type of [`ColumnKind::Id`].
+pub(crate) async fn inspect<C: ConnectionTrait>(
+    conn: &C,
+    expected: &[ExpectedColumn],
+) -> Result<Vec<SchemaFinding>, DbErr> {
+    let mut tables: Vec<(&Option<String>, &str)> = expected
+        .iter()
+        .map(|column| (&column.schema, column.table.as_str()))
+        .collect();
+    tables.sort_unstable();
+    tables.dedup();
+    let mut names: Vec<&str> = tables.iter().map(|(_, table)| *table).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    let placeholders: Vec<String> = (1..=names.len()).map(|n| format!("${n}")).collect();
+    let sql = format!(
+        "SELECT table_schema::text AS table_schema, \
+         table_schema = current_schema() AS is_current, \
+         table_name::text AS table_name, column_name::text AS column_name, \
+         CASE WHEN data_type = 'USER-DEFINED' THEN udt_name::text ELSE data_type::text END \
+         AS data_type, is_nullable = 'YES' AS nullable \
+         FROM information_schema.columns WHERE table_name IN ({})",
+        placeholders.join(", ")
+    );
+    let values = names
+        .iter()
+        .map(|table| Value::from(*table))
+        .collect::<Vec<_>>();
+    // Keyed by schema, with `None` for the current one, then table and column.
+    let mut columns: HashMap<(Option<String>, String, String), (String, bool)> = HashMap::new();
+    for row in conn
+        .query_all(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            sql,
+            values,
+        ))
+        .await?
+    {
+        let schema: String = row.try_get("", "table_schema")?;
+        let is_current: bool = row.try_get("", "is_current")?;
+        let table: String = row.try_get("", "table_name")?;
+        let column: String = row.try_get("", "column_name")?;
+        let found: (String, bool) = (row.try_get("", "data_type")?, row.try_get("", "nullable")?);
+        if is_current {
+            columns.insert((None, table.clone(), column.clone()), found.clone());
+        }
+        columns.insert((Some(schema), table, column), found);
+    }
+    let lookup = |column: &ExpectedColumn| {
+        columns.get(&(
+            column.schema.clone(),
+            column.table.clone(),
+            column.column.clone(),
+        ))
+    };
+    let display = |schema: &Option<String>, table: &str| match schema {
+        Some(schema) => format!("{schema}.{table}"),
+        None => table.to_owned(),
+    };
+
+    let missing_tables: Vec<(&Option<String>, &str)> = tables
+        .iter()
+        .filter(|(schema, table)| {
+            !columns.keys().any(|(existing_schema, existing, _)| {
+                existing_schema == *schema && existing == table
+            })
+        })
+        .copied()
+        .collect();
+    let mut findings: Vec<SchemaFinding> = missing_tables
+        .iter()
+        .map(|(schema, table)| SchemaFinding::MissingTable {
+            table: display(schema, table),
+        })
+        .collect();
+    let id_type = expected
+        .iter()
+        .find(|column| column.role == "id")
+        .and_then(lookup)
+        .map(|(data_type, _)| data_type.as_str());
+    for column in expected {
+        if missing_tables.contains(&(&column.schema, column.table.as_str())) {
+            continue;
+        }
+        let table = display(&column.schema, &column.table);
+        let Some((data_type, nullable)) = lookup(column) else {
+            findings.push(SchemaFinding::MissingColumn {
+                table,
+                column: column.column.clone(),
+                role: column.role,
+            });
+            continue;
+        };
+        if !column.kind.accepts(data_type, id_type) {
+            findings.push(SchemaFinding::IncompatibleType {
+                table: table.clone(),
+                column: column.column.clone(),
+                role: column.role,
+                expected: column.kind.expected(),
+                found: data_type.clone(),
+            });
+        }
+        if column.nullable && !nullable {
+            findings.push(SchemaFinding::NotNullable {
+                table,
+                column: column.column.clone(),
+                role: column.role,
+            });
+        }
+    }
+    Ok(findings)
+}
@@ -0,0 +1,52 @@
+//! Reference implementations for checking a closure table in tests, enabled by
+//! the `testing` feature.
+//!
+//! They work on plain `(id, parent)` edges and recurse naively, sharing no code
+//! with the repository, so a disagreement points at the hierarchy maintenance.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// The closure rows `(ancestor, descendant, generations)` implied by the
+/// `(id, parent)` edges, including the `0`-generation row of every node,
+/// sorted. A parent missing from `edges` ends the chain, as in
+/// [`rebuild`](crate::ClosureTreeRepository::rebuild).
+///
+/// # Panics
+///
+/// If the edges contain a cycle.
+pub fn rebuild_from_edges<Id>(
+    edges: impl IntoIterator<Item = (Id, Option<Id>)>,
+) -> Vec<(Id, Id, i32)>
+where
+    Id: Clone + Ord + Hash,
+{
+    let parents: HashMap<Id, Option<Id>> = edges.into_iter().collect();
+    let mut rows = Vec::new();
+    for id in parents.keys() {
+        collect_ancestors(&parents, id, id, 0, &mut rows);
+    }
+    rows.sort();
+    rows
+}
+
+fn collect_ancestors<Id>(
+    parents: &HashMap<Id, Option<Id>>,
+    descendant: &Id,
+    ancestor: &Id,
+    generations: i32,
+    rows: &mut Vec<(Id, Id, i32)>,
+) where
+    Id: Clone + Ord + Hash,
+{
+    assert!(
+        generations as usize <= parents.len(),
+        "parent edges contain a cycle"
+    );
+    rows.push((ancestor.clone(), descendant.clone(), generations));
+    if let Some(Some(parent)) = parents.get(ancestor) {
+        if parents.contains_key(parent) {
+            collect_ancestors(parents, descendant, parent, generations + 1, rows);
+        }
+    }
+}
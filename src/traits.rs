@@ -2,17 +2,28 @@ use sea_orm::{
     ActiveModelBehavior, ActiveModelTrait, EntityTrait, FromQueryResult, IntoActiveModel, Value,
 };
 
-use crate::config::ClosureTreeConfig;
+use crate::config::{ClosureTreeConfig, OrderStrategy};
+use crate::error::ClosureTreeError;
+
+/// The tree of a model with a single hierarchy, the default `Tag` of
+/// [`ClosureTreeModel`] and [`ClosureTreeRepository`](crate::ClosureTreeRepository).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DefaultTree;
 
 /// Trait implemented by SeaORM `Model` types that participate in the closure tree.
 ///
 /// Implementations are normally provided by the `#[derive(ClosureTreeModel)]` macro.
-pub trait ClosureTreeModel:
+///
+/// A model in several independent trees, each with its own parent column and
+/// hierarchy table, implements the trait once per tree, with a marker type as
+/// `Tag`; [`ClosureTreeRepository<M, Tag>`](crate::ClosureTreeRepository)
+/// selects the tree. `Tag` is [`DefaultTree`] otherwise.
+pub trait ClosureTreeModel<Tag = DefaultTree>:
     Clone + Send + Sync + 'static + IntoActiveModel<Self::ActiveModel> + FromQueryResult
 {
     type Entity: EntityTrait<Model = Self>;
     type ActiveModel: ActiveModelTrait<Entity = Self::Entity> + ActiveModelBehavior + Send;
-    type Id: Clone + Send + Sync + 'static;
+    type Id: Clone + std::fmt::Debug + Eq + std::hash::Hash + Send + Sync + 'static;
 
     type HierarchyEntity: EntityTrait<Model = Self::HierarchyModel>;
     type HierarchyModel: Clone + Send + Sync + 'static + FromQueryResult;
@@ -27,12 +38,97 @@ pub trait ClosureTreeModel:
     fn set_parent(active: &mut Self::ActiveModel, parent: Option<Self::Id>);
     fn id_to_value(id: &Self::Id) -> Value;
 
-    fn name(&self) -> &str;
-    fn set_name(active: &mut Self::ActiveModel, name: &str);
+    /// A fresh id for a node about to be inserted, for keys the database does
+    /// not generate (UUIDs, snowflakes). The default `None` leaves the id to
+    /// the database; the derive macro calls `id_generator` when it is set.
+    fn generate_id() -> Option<Self::Id> {
+        None
+    }
 
     fn parent_column() -> <Self::Entity as EntityTrait>::Column;
     fn id_column() -> <Self::Entity as EntityTrait>::Column;
-    fn name_column() -> <Self::Entity as EntityTrait>::Column;
+
+    /// Column named by [`ClosureTreeConfig::name_column`], `None` for trees
+    /// without names. The default looks it up by name; the derive macro
+    /// generates it from `name_field`.
+    fn name_column() -> Option<<Self::Entity as EntityTrait>::Column> {
+        Self::closure_tree_config()
+            .name_column()
+            .and_then(|column| column.parse().ok())
+    }
+
+    /// Column holding the sibling position for [`OrderStrategy::NumericColumn`]
+    /// and [`OrderStrategy::FractionalColumn`].
+    ///
+    /// The default looks up the column named in the configured strategy. The
+    /// derive macro generates this and the methods below from `order_field`.
+    fn order_column() -> Option<<Self::Entity as EntityTrait>::Column> {
+        Self::closure_tree_config()
+            .order_strategy()
+            .and_then(OrderStrategy::position_column)
+            .and_then(|(column, _)| column.parse().ok())
+    }
+
+    /// This node's sibling position. Without an implementation new nodes are
+    /// not assigned positions and keep the column's default.
+    fn order_value(&self) -> Option<i64> {
+        None
+    }
+
+    /// Store a sibling position, failing with
+    /// [`OrderValueOutOfRange`](crate::ClosureTreeError::OrderValueOutOfRange)
+    /// when it does not fit the field.
+    fn set_order_value(
+        _active: &mut Self::ActiveModel,
+        _value: i64,
+    ) -> Result<(), ClosureTreeError> {
+        Ok(())
+    }
+
+    /// This node's position under [`OrderStrategy::FractionalColumn`]; like
+    /// [`order_value`](Self::order_value), new nodes keep the column's default
+    /// without an implementation.
+    fn fractional_order_value(&self) -> Option<f64> {
+        None
+    }
+
+    fn set_fractional_order_value(_active: &mut Self::ActiveModel, _value: f64) {}
+
+    /// Fill in columns of a node about to be created by path lookups or
+    /// `import_tree`, once its parent and name are set; `parent` is `None` for
+    /// a root. Lets models with `NOT NULL` columns be created without every
+    /// caller supplying them. The default does nothing; the derive calls the
+    /// function named by `new_child_defaults` when it is set.
+    fn new_child_defaults(_active: &mut Self::ActiveModel, _parent: Option<&Self>) {}
+
+    /// Column named by [`ClosureTreeConfig::touch_column`]. The default looks it
+    /// up by name; the derive macro generates it from `touch_field`.
+    fn touch_column() -> Option<<Self::Entity as EntityTrait>::Column> {
+        Self::closure_tree_config()
+            .touch_column()
+            .and_then(|column| column.parse().ok())
+    }
+
+    /// Column holding the tenant or other scope value of
+    /// [`ClosureTreeConfig::scope_column`]. The default looks it up by name; the
+    /// derive macro generates it from `scope_field`.
+    fn scope_column() -> Option<<Self::Entity as EntityTrait>::Column> {
+        Self::closure_tree_config()
+            .scope_column()
+            .and_then(|column| column.parse().ok())
+    }
+
+    /// This node's scope value; `None` unless implemented (the derive macro does).
+    fn scope_value(&self) -> Option<Value> {
+        None
+    }
+
+    /// Stamp a scope value onto a node about to be inserted.
+    fn set_scope_value(active: &mut Self::ActiveModel, value: Value) {
+        if let Some(column) = Self::scope_column() {
+            active.set(column, value);
+        }
+    }
 
     fn hierarchy_ancestor_column() -> <Self::HierarchyEntity as EntityTrait>::Column;
     fn hierarchy_descendant_column() -> <Self::HierarchyEntity as EntityTrait>::Column;
@@ -48,3 +144,114 @@ pub trait ClosureTreeModel:
         generations: i32,
     ) -> Self::HierarchyActiveModel;
 }
+
+/// A [`ClosureTreeModel`] whose nodes have names, required by path lookups,
+/// `import_tree`, `rename` and `hash_tree`.
+///
+/// The derive macro implements it unless the model is marked `no_name`.
+/// Implementations must configure a [`name_column`](ClosureTreeConfig::name_column).
+///
+/// `Name` is the type of that column, which the derive macro takes from the
+/// field. The [`name_normalizer`](ClosureTreeConfig::name_normalizer), segment
+/// trimming and [`NameMatching::CaseInsensitive`](crate::NameMatching) apply to
+/// string names only; other names are compared and stored as they are.
+pub trait NamedClosureTreeModel<Tag = DefaultTree>: ClosureTreeModel<Tag> {
+    type Name: Clone + std::fmt::Debug + PartialEq + Into<Value> + Send + Sync + 'static;
+
+    fn name(&self) -> &Self::Name;
+    fn set_name(active: &mut Self::ActiveModel, name: Self::Name);
+}
+
+/// A node given either by id or as a model already at hand, taken by
+/// [`children_of`](crate::ClosureTreeRepository::children_of) and
+/// [`descendants_of`](crate::ClosureTreeRepository::descendants_of) so that a
+/// loaded model is not fetched again.
+///
+/// Models convert with `From`; ids are wrapped in [`NodeRef::Id`], since a
+/// `From<M::Id>` would overlap the standard `From<T> for T`.
+#[derive(Clone, Debug)]
+pub enum NodeRef<M, Tag = DefaultTree>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    Id(M::Id),
+    Loaded(M),
+}
+
+impl<M, Tag> NodeRef<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    /// The id of the node, loaded or not.
+    pub fn id(&self) -> M::Id {
+        match self {
+            Self::Id(id) => id.clone(),
+            Self::Loaded(model) => model.id(),
+        }
+    }
+}
+
+impl<M, Tag> From<M> for NodeRef<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    fn from(model: M) -> Self {
+        Self::Loaded(model)
+    }
+}
+
+impl<M, Tag> From<&M> for NodeRef<M, Tag>
+where
+    M: ClosureTreeModel<Tag>,
+{
+    fn from(model: &M) -> Self {
+        Self::Loaded(model.clone())
+    }
+}
+
+/// Field types usable as [`OrderStrategy::FractionalColumn`] positions, which
+/// the derive macro converts through `f64`: `f64`, and `Decimal` with the
+/// `decimal` feature.
+pub trait FractionalPosition {
+    fn to_f64(&self) -> f64;
+    fn from_f64(value: f64) -> Self;
+}
+
+impl FractionalPosition for f64 {
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// `NUMERIC` columns, with the `decimal` feature.
+#[cfg(feature = "decimal")]
+impl FractionalPosition for sea_orm::prelude::Decimal {
+    fn to_f64(&self) -> f64 {
+        f64::try_from(*self).unwrap_or(f64::NAN)
+    }
+
+    fn from_f64(value: f64) -> Self {
+        Self::try_from(value).unwrap_or_default()
+    }
+}
+
+/// A [`ClosureTreeModel`] with a numeric sibling position, required by
+/// `move_up` and `move_down`.
+///
+/// The derive macro implements it for models with an `order_field`. A
+/// hand-written implementation should come with
+/// [`order_column`](ClosureTreeModel::order_column),
+/// [`order_value`](ClosureTreeModel::order_value) and
+/// [`set_order_value`](ClosureTreeModel::set_order_value).
+pub trait HasOrdering<Tag = DefaultTree>: ClosureTreeModel<Tag> {}
+
+/// A [`ClosureTreeModel`] with a
+/// [`children_count_column`](ClosureTreeConfig::children_count_column),
+/// required by `recount_children`.
+///
+/// The derive macro implements it for models with a `children_count_field`.
+pub trait HasCounterCache<Tag = DefaultTree>: ClosureTreeModel<Tag> {}
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::path;
+use crate::traits::{ClosureTreeModel, NamedClosureTreeModel};
+
+/// Nested description of a subtree to create, e.g. deserialized from JSON:
+///
+/// ```json
+/// { "name": "root", "children": [{ "name": "child" }] }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeInput {
+    pub name: String,
+    #[serde(default)]
+    pub children: Vec<TreeInput>,
+}
+
+impl TreeInput {
+    /// A leaf node with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Append a child subtree.
+    pub fn child(mut self, child: TreeInput) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Generations below this node down to its deepest descendant.
+    pub(crate) fn height(&self) -> u32 {
+        self.children
+            .iter()
+            .map(|child| child.height() + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A node with its children, in sibling order, as returned by
+/// [`ClosureTreeRepository::hash_tree`](crate::ClosureTreeRepository::hash_tree).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TreeNode<M> {
+    pub node: M,
+    #[serde(default)]
+    pub children: Vec<TreeNode<M>>,
+}
+
+impl<M> TreeNode<M> {
+    /// Nest `nodes` under the ones accepted by `is_root`, keeping their order
+    /// within each parent. Nodes not reachable from a root are dropped.
+    pub(crate) fn forest<Tag>(nodes: Vec<M>, is_root: impl Fn(&M) -> bool) -> Vec<Self>
+    where
+        M: ClosureTreeModel<Tag>,
+    {
+        let mut roots = Vec::new();
+        let mut children: HashMap<M::Id, Vec<M>> = HashMap::new();
+        for node in nodes {
+            match node.parent_id() {
+                _ if is_root(&node) => roots.push(node),
+                Some(parent) => children.entry(parent).or_default().push(node),
+                None => {}
+            }
+        }
+        roots
+            .into_iter()
+            .map(|root| Self::nest(root, &mut children))
+            .collect()
+    }
+
+    fn nest<Tag>(node: M, children: &mut HashMap<M::Id, Vec<M>>) -> Self
+    where
+        M: ClosureTreeModel<Tag>,
+    {
+        let nested = children
+            .remove(&node.id())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| Self::nest(child, children))
+            .collect();
+        Self {
+            node,
+            children: nested,
+        }
+    }
+
+    /// This node and its descendants, depth first, one line each: the name
+    /// after `indent` repeated `depth` times.
+    pub(crate) fn outline<Tag>(&self, indent: &str, depth: usize, lines: &mut Vec<String>)
+    where
+        M: NamedClosureTreeModel<Tag>,
+    {
+        let name = path::segment_text(&self.node.name().clone().into());
+        lines.push(format!("{}{name}", indent.repeat(depth)));
+        for child in &self.children {
+            child.outline(indent, depth + 1, lines);
+        }
+    }
+}
+
+impl<M: ClosureTreeModel> TreeNode<M> {
+    /// Every node of `forest`, depth first, with its parent.
+    fn flatten(forest: &[Self]) -> Vec<(&M, Option<M::Id>)> {
+        let mut nodes = Vec::new();
+        for tree in forest {
+            tree.walk(tree.node.parent_id(), &mut nodes);
+        }
+        nodes
+    }
+
+    /// This node and its descendants, depth first, each with the id of its
+    /// parent in the tree (`parent` for this node).
+    fn walk<'a>(&'a self, parent: Option<M::Id>, out: &mut Vec<(&'a M, Option<M::Id>)>) {
+        out.push((&self.node, parent));
+        for child in &self.children {
+            child.walk(Some(self.node.id()), out);
+        }
+    }
+}
+
+/// The generations of ancestors or descendants to load, counted from the node:
+/// `1` is the parent or the children, `2` the grandparent or grandchildren.
+/// Bounds are inclusive, and the node itself (generation `0`) is never
+/// included.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum GenerationRange {
+    Exactly(i32),
+    /// Generations `1..=n`.
+    UpTo(i32),
+    Between(i32, i32),
+    /// Generation `n` and everything beyond it.
+    AtLeast(i32),
+}
+
+impl GenerationRange {
+    /// Inclusive `(min, max)` generations, `max` being `None` when unbounded.
+    pub(crate) fn bounds(self) -> (i32, Option<i32>) {
+        let (min, max) = match self {
+            Self::Exactly(generation) => (generation, Some(generation)),
+            Self::UpTo(max) => (1, Some(max)),
+            Self::Between(min, max) => (min, Some(max)),
+            Self::AtLeast(min) => (min, None),
+        };
+        (min.max(1), max)
+    }
+}
+
+/// A difference between two materialized trees, reported by [`diff_trees`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NodeChange<Id> {
+    Added {
+        id: Id,
+        parent: Option<Id>,
+    },
+    Removed {
+        id: Id,
+    },
+    Moved {
+        id: Id,
+        from: Option<Id>,
+        to: Option<Id>,
+    },
+    Renamed {
+        id: Id,
+        from: String,
+        to: String,
+    },
+}
+
+/// Compare two snapshots of a forest (e.g. from
+/// [`hash_tree`](crate::ClosureTreeRepository::hash_tree)), matching nodes by id.
+///
+/// Parents come from the nesting; a top-level node's parent is its
+/// [`parent_id`](ClosureTreeModel::parent_id). Changes to nodes in `new` are
+/// listed depth first in `new`'s order, a node that moved and was renamed
+/// yielding both, followed by the nodes removed, in `old`'s order.
+pub fn diff_trees<M: NamedClosureTreeModel>(
+    old: &[TreeNode<M>],
+    new: &[TreeNode<M>],
+) -> Vec<NodeChange<M::Id>> {
+    let old = TreeNode::flatten(old);
+    let new = TreeNode::flatten(new);
+    let before: HashMap<M::Id, (&M, &Option<M::Id>)> = old
+        .iter()
+        .map(|(node, parent)| (node.id(), (*node, parent)))
+        .collect();
+    let after: HashSet<M::Id> = new.iter().map(|(node, _)| node.id()).collect();
+
+    let mut changes = Vec::new();
+    for (node, parent) in &new {
+        let id = node.id();
+        let Some((previous, previous_parent)) = before.get(&id) else {
+            changes.push(NodeChange::Added {
+                id,
+                parent: parent.clone(),
+            });
+            continue;
+        };
+        if *previous_parent != parent {
+            changes.push(NodeChange::Moved {
+                id: id.clone(),
+                from: (*previous_parent).clone(),
+                to: parent.clone(),
+            });
+        }
+        if previous.name() != node.name() {
+            changes.push(NodeChange::Renamed {
+                id,
+                from: crate::path::segment_text(&previous.name().clone().into()),
+                to: crate::path::segment_text(&node.name().clone().into()),
+            });
+        }
+    }
+    changes.extend(
+        old.iter()
+            .map(|(node, _)| node.id())
+            .filter(|id| !after.contains(id))
+            .map(|id| NodeChange::Removed { id }),
+    );
+    changes
+}
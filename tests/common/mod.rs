@@ -0,0 +1,1195 @@
+//! Entities shared by the backend-specific integration tests.
+
+#![allow(dead_code)]
+
+pub mod entity {
+    pub mod node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::node_hierarchy",
+            hierarchy_table = "node_hierarchies",
+            extension_trait = "NodeTreeExt",
+            hierarchy_relations
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `ordered_nodes` table with a numeric `position` column and
+    /// unique sibling names.
+    pub mod ranked_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            order_field = "position",
+            unique_sibling_names,
+            extension_trait
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// [`ranked_node`] with its roles marked on the fields instead of named in
+    /// the struct attribute.
+    pub mod marked_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            order_field = "position"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            #[closure_tree(id)]
+            pub id: i32,
+            #[closure_tree(parent)]
+            pub parent_id: Option<i32>,
+            #[closure_tree(name)]
+            pub name: String,
+            #[closure_tree(order)]
+            pub position: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// [`ranked_node`] with roots left out of the numeric ordering.
+    pub mod tenant_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            order_field = "position",
+            dont_order_roots
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `ordered_nodes` table, touching `touched_at` on ancestors.
+    pub mod touched_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            touch_field = "touched_at"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i32,
+            pub touched_at: Option<String>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `ordered_nodes` table with a children counter cache.
+    pub mod counted_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            children_count_field = "children_count",
+            path_separator = "::",
+            max_depth = 16,
+            max_children = 500,
+            features(paths, counter_cache)
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i32,
+            pub children_count: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `ordered_nodes` table, one forest per `tenant_id`.
+    pub mod scoped_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            scope_field = "tenant_id"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i32,
+            pub tenant_id: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `ordered_nodes` table, soft-deleting through `deleted_at`.
+    pub mod soft_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "ordered_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::common::ordered_node_hierarchy",
+            hierarchy_table = "ordered_node_hierarchies",
+            children_count_field = "children_count",
+            soft_delete_field = "deleted_at"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i32,
+            pub children_count: i32,
+            pub deleted_at: Option<String>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `nodes` table with a destroying dependent behavior and
+    /// a named advisory lock.
+    pub mod keyed_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::node_hierarchy",
+            hierarchy_table = "node_hierarchies",
+            dependent = "destroy",
+            advisory_lock_key = "keyed-nodes"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over the `nodes` table without an advisory lock.
+    pub mod unlocked_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::node_hierarchy",
+            hierarchy_table = "node_hierarchies",
+            dependent = "delete_all",
+            advisory_lock = "disabled"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Derived over `trees.nodes`, outside the default `search_path`.
+    pub mod trees_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(schema_name = "trees", table_name = "nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::trees_node_hierarchy",
+            hierarchy_table = "node_hierarchies"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree without names over `unnamed_nodes`, built by inserting rows and
+    /// rebuilding the hierarchy.
+    pub mod unnamed_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "unnamed_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::unnamed_node_hierarchy",
+            hierarchy_table = "unnamed_node_hierarchies",
+            no_name
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over `uuid_nodes`, whose ids are generated client-side.
+    pub mod uuid_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "uuid_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::uuid_node_hierarchy",
+            hierarchy_table = "uuid_node_hierarchies",
+            id_generator = "uuid::Uuid::new_v4"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub id: Uuid,
+            pub parent_id: Option<Uuid>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    pub mod uuid_node_hierarchy {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+        #[sea_orm(table_name = "uuid_node_hierarchies")]
+        pub struct Model {
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub ancestor_id: Uuid,
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub descendant_id: Uuid,
+            pub generations: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over `coded_nodes` whose nodes are named by integer codes, with a
+    /// generated `coded_node_hierarchy` entity.
+    pub mod coded_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "coded_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_name = "CodedNodeHierarchy",
+            hierarchy_table = "coded_node_hierarchies",
+            unique_sibling_names
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            #[closure_tree(name)]
+            pub code: i64,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over `renamed_nodes` whose fields map to differently named
+    /// columns.
+    pub mod renamed_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "renamed_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_name = "RenamedNodeHierarchy",
+            hierarchy_table = "renamed_node_hierarchies",
+            unique_sibling_names
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            #[closure_tree(parent)]
+            #[sea_orm(column_name = "parent_id")]
+            pub parent: Option<i32>,
+            #[closure_tree(name)]
+            #[sea_orm(column_name = "title")]
+            pub label: String,
+            #[closure_tree(order)]
+            #[sea_orm(column_name = "sort_key")]
+            pub rank: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree whose fields are not snake case, so their `Column` variants
+    /// (`ParentId` for `parentID`) differ from a plain capitalisation.
+    #[allow(non_snake_case)]
+    pub mod cased_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "cased_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_name = "CasedNodeHierarchy",
+            hierarchy_table = "cased_node_hierarchies"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            #[closure_tree(parent)]
+            pub parentID: Option<i32>,
+            #[closure_tree(name)]
+            pub nodeName: String,
+            #[closure_tree(order)]
+            pub sortKey_v2: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree whose fields end in digits (`parent_id2`, `uuid_v7`), and whose
+    /// hierarchy name starts with an acronym.
+    pub mod numbered_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "numbered_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_name = "HTTPNodeHierarchy",
+            hierarchy_table = "numbered_node_hierarchies"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            #[closure_tree(id)]
+            pub uuid_v7: i32,
+            #[closure_tree(parent)]
+            pub parent_id2: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree whose parent and name fields are keywords.
+    pub mod raw_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "raw_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_name = "RawNodeHierarchy",
+            parent_field = "r#ref",
+            order_field = "r#loop"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub r#ref: Option<i32>,
+            #[closure_tree(name)]
+            pub r#type: String,
+            pub r#loop: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over an expanded entity whose entity and column items are not
+    /// named `Entity` and `Column`.
+    pub mod item_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Copy, Clone, Default, Debug, DeriveEntity)]
+        #[sea_orm(column = ItemColumn)]
+        pub struct ItemEntity;
+
+        impl EntityName for ItemEntity {
+            fn table_name(&self) -> &str {
+                "items"
+            }
+        }
+
+        // `DeriveActiveModel` names the entity `Entity`.
+        type Entity = ItemEntity;
+
+        #[derive(Clone, Debug, PartialEq, DeriveModel, DeriveActiveModel, ClosureTreeModel)]
+        #[sea_orm(entity = ItemEntity)]
+        #[closure_tree(
+            entity = "ItemEntity",
+            column_enum = "ItemColumn",
+            active_model = "self::ActiveModel",
+            generate_hierarchy_entity,
+            hierarchy_name = "ItemHierarchy",
+            hierarchy_table = "item_hierarchies"
+        )]
+        pub struct Model {
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveColumn)]
+        pub enum ItemColumn {
+            Id,
+            ParentId,
+            Name,
+        }
+
+        impl ColumnTrait for ItemColumn {
+            type EntityName = ItemEntity;
+
+            fn def(&self) -> ColumnDef {
+                match self {
+                    Self::Id => ColumnType::Integer.def(),
+                    Self::ParentId => ColumnType::Integer.def().null(),
+                    Self::Name => ColumnType::Text.def(),
+                }
+            }
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveIden)]
+        pub enum PrimaryKey {
+            Id,
+        }
+
+        impl sea_orm::IdenStatic for PrimaryKey {
+            fn as_str(&self) -> &str {
+                "id"
+            }
+        }
+
+        impl PrimaryKeyTrait for PrimaryKey {
+            type ValueType = i32;
+
+            fn auto_increment() -> bool {
+                true
+            }
+        }
+
+        impl PrimaryKeyToColumn for PrimaryKey {
+            type Column = ItemColumn;
+
+            fn into_column(self) -> ItemColumn {
+                ItemColumn::Id
+            }
+
+            fn from_column(column: ItemColumn) -> Option<Self> {
+                matches!(column, ItemColumn::Id).then_some(Self::Id)
+            }
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over `composite_nodes`, keyed by `(tenant_id, id)`, whose nodes
+    /// are identified by the unique `id` alone.
+    pub mod composite_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "composite_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_name = "CompositeNodeHierarchy",
+            hierarchy_table = "composite_node_hierarchies"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub tenant_id: i32,
+            #[sea_orm(primary_key, auto_increment = false)]
+            #[closure_tree(id)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// Siblings ordered by a floating-point `rank`, placed at midpoints.
+    pub mod fractional_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "fractional_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_table = "fractional_node_hierarchies",
+            order_field = "rank",
+            fractional_order
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub rank: f64,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree ordered by an `i16` position, for positions that outgrow it.
+    pub mod small_ranked_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "small_ranked_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_table = "small_ranked_node_hierarchies",
+            order_field = "position"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub position: i16,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over `kinded_nodes`, whose required `kind` column is filled in
+    /// for nodes created by path.
+    pub mod kinded_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+        use sea_orm::Set;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "kinded_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_table = "kinded_node_hierarchies",
+            new_child_defaults = "Model::child_defaults"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+            pub kind: String,
+        }
+
+        impl Model {
+            fn child_defaults(active: &mut ActiveModel, parent: Option<&Self>) {
+                let kind = match parent {
+                    None => "drive",
+                    Some(parent) if parent.kind == "drive" => "folder",
+                    Some(_) => "file",
+                };
+                active.kind = Set(kind.to_owned());
+            }
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree keyed by the `NodeId` newtype, converted to a `Value` by
+    /// `id_to_value_with`.
+    pub mod newtype_node {
+        use std::sync::atomic::{AtomicI64, Ordering};
+
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+        use sea_orm::TryFromU64;
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, DeriveValueType)]
+        pub struct NodeId(pub i64);
+
+        impl NodeId {
+            pub fn next() -> Self {
+                static NEXT: AtomicI64 = AtomicI64::new(1);
+                Self(NEXT.fetch_add(1, Ordering::Relaxed))
+            }
+
+            pub fn value(id: &Self) -> Value {
+                Value::BigInt(Some(id.0))
+            }
+        }
+
+        impl TryFromU64 for NodeId {
+            fn try_from_u64(n: u64) -> Result<Self, DbErr> {
+                i64::try_from_u64(n).map(Self)
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "newtype_nodes")]
+        #[closure_tree(
+            generate_hierarchy_entity,
+            hierarchy_table = "newtype_node_hierarchies",
+            id_generator = "NodeId::next",
+            id_to_value_with = "NodeId::value"
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub id: NodeId,
+            pub parent_id: Option<NodeId>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// People in two trees at once: the org chart by `department_id` and the
+    /// reporting lines by `manager_id`, each with its own hierarchy table.
+    pub mod staff_member {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        // Clippy takes a flag repeated in each tree's attribute for a duplicate.
+        #[allow(clippy::duplicated_attributes)]
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "staff_members")]
+        #[closure_tree(tag = "org", parent_field = "department_id", generate_hierarchy_entity)]
+        #[closure_tree(
+            tag = "reporting",
+            parent_field = "manager_id",
+            generate_hierarchy_entity,
+            extension_trait,
+            hierarchy_relations
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub department_id: Option<i32>,
+            pub manager_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    pub mod unnamed_node_hierarchy {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+        #[sea_orm(table_name = "unnamed_node_hierarchies")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub ancestor_id: i32,
+            #[sea_orm(primary_key)]
+            pub descendant_id: i32,
+            pub generations: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    pub mod trees_node_hierarchy {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+        #[sea_orm(schema_name = "trees", table_name = "node_hierarchies")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub ancestor_id: i32,
+            #[sea_orm(primary_key)]
+            pub descendant_id: i32,
+            pub generations: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    /// A tree over a legacy closure table keyed by a surrogate `id`, with a
+    /// unique index on (ancestor, descendant) as the conflict target.
+    pub mod legacy_node {
+        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+        #[sea_orm(table_name = "legacy_nodes")]
+        #[closure_tree(
+            hierarchy_module = "crate::entity::legacy_node_hierarchy",
+            hierarchy_table = "legacy_node_hierarchies",
+            hierarchy_conflict_columns("ancestor_id", "descendant_id")
+        )]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i32,
+            pub parent_id: Option<i32>,
+            pub name: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    pub mod legacy_node_hierarchy {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+        #[sea_orm(table_name = "legacy_node_hierarchies")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i64,
+            pub ancestor_id: i32,
+            pub descendant_id: i32,
+            pub generations: i32,
+            /// A `TIMESTAMPTZ` filled in by the database.
+            #[sea_orm(select_as = "text")]
+            pub created_at: String,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+
+    pub mod node_hierarchy {
+        use sea_orm::entity::prelude::*;
+
+        #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+        #[sea_orm(table_name = "node_hierarchies")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub ancestor_id: i32,
+            #[sea_orm(primary_key)]
+            pub descendant_id: i32,
+            pub generations: i32,
+        }
+
+        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+        pub enum Relation {}
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+}
+
+/// Hand-written [`closure_tree::ClosureTreeModel`] impl over the `ordered_nodes`
+/// table, for configurations the derive macro cannot express.
+macro_rules! manual_node_entity {
+    ($module:ident, $options:expr) => {
+        pub mod $module {
+            use closure_tree::{
+                ClosureTreeConfig, ClosureTreeModel, ClosureTreeOptions, HasOrdering,
+                NamedClosureTreeModel,
+            };
+            use sea_orm::entity::prelude::*;
+
+            use crate::common::ordered_node_hierarchy as hierarchy;
+
+            #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+            #[sea_orm(table_name = "ordered_nodes")]
+            pub struct Model {
+                #[sea_orm(primary_key)]
+                pub id: i32,
+                pub parent_id: Option<i32>,
+                pub name: String,
+                pub position: i32,
+            }
+
+            #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+            pub enum Relation {}
+
+            impl ActiveModelBehavior for ActiveModel {}
+
+            impl ClosureTreeModel for Model {
+                type Entity = Entity;
+                type ActiveModel = ActiveModel;
+                type Id = i32;
+
+                type HierarchyEntity = hierarchy::Entity;
+                type HierarchyModel = hierarchy::Model;
+                type HierarchyActiveModel = hierarchy::ActiveModel;
+
+                fn closure_tree_config() -> &'static ClosureTreeConfig {
+                    static CONFIG: once_cell::sync::Lazy<ClosureTreeConfig> =
+                        once_cell::sync::Lazy::new(|| {
+                            let options: ClosureTreeOptions = $options;
+                            options.apply(ClosureTreeConfig::new(
+                                "OrderedNode",
+                                "OrderedNodeHierarchy",
+                            ))
+                        });
+                    &CONFIG
+                }
+
+                fn id(&self) -> i32 {
+                    self.id
+                }
+
+                fn parent_id(&self) -> Option<i32> {
+                    self.parent_id
+                }
+
+                fn set_parent(active: &mut ActiveModel, parent: Option<i32>) {
+                    active.parent_id = sea_orm::ActiveValue::Set(parent);
+                }
+
+                fn id_to_value(id: &i32) -> sea_orm::Value {
+                    (*id).into()
+                }
+
+                fn parent_column() -> Column {
+                    Column::ParentId
+                }
+
+                fn id_column() -> Column {
+                    Column::Id
+                }
+
+                fn name_column() -> Option<Column> {
+                    Some(Column::Name)
+                }
+
+                fn order_column() -> Option<Column> {
+                    Some(Column::Position)
+                }
+
+                fn order_value(&self) -> Option<i64> {
+                    Some(self.position.into())
+                }
+
+                fn set_order_value(
+                    active: &mut ActiveModel,
+                    value: i64,
+                ) -> Result<(), closure_tree::ClosureTreeError> {
+                    active.position = sea_orm::ActiveValue::Set(value as i32);
+                    Ok(())
+                }
+
+                fn hierarchy_ancestor_column() -> hierarchy::Column {
+                    hierarchy::Column::AncestorId
+                }
+
+                fn hierarchy_descendant_column() -> hierarchy::Column {
+                    hierarchy::Column::DescendantId
+                }
+
+                fn hierarchy_generations_column() -> hierarchy::Column {
+                    hierarchy::Column::Generations
+                }
+
+                fn hierarchy_id_to_value(id: &i32) -> sea_orm::Value {
+                    (*id).into()
+                }
+
+                fn hierarchy_model_ancestor(model: &hierarchy::Model) -> i32 {
+                    model.ancestor_id
+                }
+
+                fn hierarchy_model_descendant(model: &hierarchy::Model) -> i32 {
+                    model.descendant_id
+                }
+
+                fn hierarchy_model_generations(model: &hierarchy::Model) -> i32 {
+                    model.generations
+                }
+
+                fn hierarchy_build_row(
+                    ancestor: i32,
+                    descendant: i32,
+                    generations: i32,
+                ) -> hierarchy::ActiveModel {
+                    hierarchy::ActiveModel {
+                        ancestor_id: sea_orm::ActiveValue::Set(ancestor),
+                        descendant_id: sea_orm::ActiveValue::Set(descendant),
+                        generations: sea_orm::ActiveValue::Set(generations),
+                    }
+                }
+            }
+
+            impl NamedClosureTreeModel for Model {
+                type Name = String;
+
+                fn name(&self) -> &String {
+                    &self.name
+                }
+
+                fn set_name(active: &mut ActiveModel, name: String) {
+                    active.name = sea_orm::ActiveValue::Set(name);
+                }
+            }
+
+            impl HasOrdering for Model {}
+        }
+    };
+}
+
+pub mod ordered_node_hierarchy {
+    use sea_orm::entity::prelude::*;
+
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+    #[sea_orm(table_name = "ordered_node_hierarchies")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub ancestor_id: i32,
+        #[sea_orm(primary_key)]
+        pub descendant_id: i32,
+        pub generations: i32,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+manual_node_entity!(
+    ascending_node,
+    ClosureTreeOptions::default()
+        .order_strategy(closure_tree::OrderStrategy::numeric_column("position"))
+        .order_gap(10)
+);
+
+manual_node_entity!(
+    descending_node,
+    ClosureTreeOptions::default()
+        .order_strategy(closure_tree::OrderStrategy::numeric_column_desc("position"))
+        .name_order_descending(true)
+);
+
+manual_node_entity!(
+    lock_timeout_node,
+    ClosureTreeOptions::default().lock_acquisition(closure_tree::LockAcquisition::try_for(
+        std::time::Duration::from_millis(200)
+    ))
+);
+
+manual_node_entity!(
+    scoped_lock_node,
+    ClosureTreeOptions::default()
+        .lock_scope(closure_tree::AdvisoryLockScope::PerFirstSegment)
+        .lock_acquisition(closure_tree::LockAcquisition::try_for(
+            std::time::Duration::from_millis(200)
+        ))
+);
+
+manual_node_entity!(
+    destroying_node,
+    ClosureTreeOptions::default().dependent_behavior(closure_tree::DependentBehavior::Destroy)
+);
+
+manual_node_entity!(
+    strict_cleanup_node,
+    ClosureTreeOptions::default().cleanup_policy(closure_tree::CleanupPolicy::Strict)
+);
+
+manual_node_entity!(
+    unlocked_node,
+    ClosureTreeOptions::default()
+        .advisory_lock_strategy(closure_tree::AdvisoryLockStrategy::Disabled)
+);
+
+manual_node_entity!(
+    ltree_node,
+    ClosureTreeOptions::default().ltree_column("path")
+);
+
+manual_node_entity!(
+    name_desc_node,
+    ClosureTreeOptions::default().order_strategy(closure_tree::OrderStrategy::ByName {
+        direction: closure_tree::OrderDirection::Desc,
+    })
+);
+
+manual_node_entity!(
+    name_length_node,
+    ClosureTreeOptions::default().order_strategy(closure_tree::OrderStrategy::Expression {
+        expr: sea_orm::sea_query::Func::char_length(sea_orm::sea_query::Expr::col(
+            sea_orm::sea_query::Alias::new("name")
+        ))
+        .into(),
+        direction: closure_tree::OrderDirection::Desc,
+    })
+);
+
+manual_node_entity!(
+    unique_siblings_node,
+    ClosureTreeOptions::default().unique_sibling_names(true)
+);
+
+manual_node_entity!(
+    case_insensitive_node,
+    ClosureTreeOptions::default()
+        .name_matching(closure_tree::NameMatching::CaseInsensitive)
+        .unique_sibling_names(true)
+);
+
+/// Trims, lowercases and joins words with `-`.
+pub fn slugify(name: &str) -> String {
+    name.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+manual_node_entity!(
+    slugged_node,
+    ClosureTreeOptions::default().name_normalizer(super::slugify)
+);
+
+// The model has no `touched_at` field, so touching goes through raw SQL.
+manual_node_entity!(
+    untyped_touch_node,
+    ClosureTreeOptions::default().touch("touched_at")
+);
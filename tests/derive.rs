@@ -0,0 +1,512 @@
+use std::collections::HashSet;
+
+use closure_tree::{
+    diff_trees, AdvisoryLockKey, AdvisoryLockScope, AdvisoryLockStrategy, ClosureTreeConfig,
+    ClosureTreeError, ClosureTreeModel, ClosureTreeOptions, ClosureTreeRepository,
+    DependentBehavior, HasCounterCache, HasOrdering, NamedClosureTreeModel, NodeChange,
+    OrderStrategy, TreeNode,
+};
+use sea_orm::sea_query::{Alias, Asterisk, BinOper, Expr, PostgresQueryBuilder, Query};
+use sea_orm::ActiveValue;
+
+mod common;
+use common::entity;
+
+#[test]
+fn derived_config_matches_expected() {
+    let expected = ClosureTreeOptions::default()
+        .parent_column("parent_id")
+        .name_column("name")
+        .hierarchy_table("node_hierarchies")
+        .apply(ClosureTreeConfig::new("Model", "ModelHierarchy"));
+
+    let derived = entity::node::Model::closure_tree_config();
+    assert_eq!(derived, &expected);
+    assert_eq!(
+        (
+            derived.hierarchy_ancestor_column(),
+            derived.hierarchy_descendant_column(),
+            derived.hierarchy_generations_column(),
+        ),
+        ("ancestor_id", "descendant_id", "generations")
+    );
+    let renamed = ClosureTreeOptions::default()
+        .hierarchy_ancestor_column("parent_node_id")
+        .apply(expected.clone());
+    assert_eq!(renamed.hierarchy_ancestor_column(), "parent_node_id");
+    assert_ne!(derived, &renamed);
+
+    let changed = ClosureTreeOptions::default()
+        .dependent_behavior(DependentBehavior::Destroy)
+        .apply(expected.clone());
+    assert_ne!(derived, &changed);
+
+    let configs: HashSet<ClosureTreeConfig> = [expected, derived.clone(), changed].into();
+    assert_eq!(configs.len(), 2);
+}
+
+#[test]
+fn order_field_configures_numeric_ordering() {
+    use entity::ranked_node::{ActiveModel, Column, Model};
+
+    assert_eq!(
+        Model::closure_tree_config().order_strategy(),
+        Some(&OrderStrategy::numeric_column("position"))
+    );
+    assert!(matches!(Model::order_column(), Some(Column::Position)));
+
+    let mut active = ActiveModel::default();
+    Model::set_order_value(&mut active, 30).unwrap();
+    assert_eq!(active.position, ActiveValue::Set(30));
+
+    let model = Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_owned(),
+        position: 7,
+    };
+    assert_eq!(model.order_value(), Some(7));
+    assert!(!Model::closure_tree_config().dont_order_roots());
+    assert!(entity::tenant_node::Model::closure_tree_config().dont_order_roots());
+    assert!(entity::node::Model::order_column().is_none());
+}
+
+#[test]
+fn scope_field_exposes_the_scope_column() {
+    use entity::scoped_node::{ActiveModel, Column, Model};
+
+    assert_eq!(
+        Model::closure_tree_config().scope_column(),
+        Some("tenant_id")
+    );
+    assert!(matches!(Model::scope_column(), Some(Column::TenantId)));
+
+    let mut active = ActiveModel::default();
+    Model::set_scope_value(&mut active, 4.into());
+    assert_eq!(active.tenant_id, ActiveValue::Set(4));
+
+    let model = Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_owned(),
+        position: 0,
+        tenant_id: 4,
+    };
+    assert_eq!(model.scope_value(), Some(4.into()));
+    assert!(entity::node::Model::scope_column().is_none());
+}
+
+#[test]
+fn dependent_and_advisory_lock_attributes_feed_the_config() {
+    let keyed = entity::keyed_node::Model::closure_tree_config();
+    assert_eq!(keyed.dependent_behavior(), DependentBehavior::Destroy);
+    assert_eq!(
+        keyed.advisory_lock_strategy(),
+        &AdvisoryLockStrategy::Namespaced(AdvisoryLockKey::new("keyed-nodes"))
+    );
+
+    let unlocked = entity::unlocked_node::Model::closure_tree_config();
+    assert_eq!(unlocked.dependent_behavior(), DependentBehavior::DeleteAll);
+    assert_eq!(
+        unlocked.advisory_lock_strategy(),
+        &AdvisoryLockStrategy::Disabled
+    );
+}
+
+#[test]
+fn legacy_lock_keys_keep_the_old_named_derivation() {
+    assert_eq!(
+        AdvisoryLockKey::legacy_derived_from("nodes", "node_hierarchies"),
+        AdvisoryLockKey::new(format!(
+            "closure-tree::nodes::node_hierarchies::{:x}",
+            crc32fast::hash(b"nodes/node_hierarchies")
+        ))
+    );
+    assert_eq!(
+        AdvisoryLockKey::derived_from("nodes", "node_hierarchies"),
+        AdvisoryLockKey::numeric(crc32fast::hash(b"nodes/node_hierarchies").into())
+    );
+}
+
+#[test]
+fn per_tree_scopes_derive_documented_lock_keys() {
+    assert_eq!(
+        AdvisoryLockScope::per_tree(42),
+        AdvisoryLockScope::PerTree {
+            root_id: "42".to_owned()
+        }
+    );
+
+    // The low half is the CRC-32 of "root:42", the high half the key itself.
+    let key = AdvisoryLockKey::numeric(7);
+    assert_eq!(
+        key.scoped("root:42"),
+        AdvisoryLockKey::numeric((7 << 32) | 3_121_067_669)
+    );
+
+    // Keys wider than 32 bits hash all their bytes into the high half.
+    let wide = |high: i64| AdvisoryLockKey::numeric((high << 40) | 7);
+    assert_eq!(
+        wide(1).scoped("root:42"),
+        AdvisoryLockKey::numeric((807_049_583 << 32) | 3_121_067_669)
+    );
+    assert_ne!(wide(1).scoped("root:42"), wide(2).scoped("root:42"));
+}
+
+#[test]
+fn field_markers_name_the_same_columns_as_attribute_strings() {
+    let marked = entity::marked_node::Model::closure_tree_config();
+    let ranked = entity::ranked_node::Model::closure_tree_config();
+    assert_eq!(marked.parent_column(), "parent_id");
+    assert_eq!(marked.name_column(), Some("name"));
+    assert_eq!(marked.order_strategy(), ranked.order_strategy());
+    assert!(matches!(
+        entity::marked_node::Model::order_column(),
+        Some(entity::marked_node::Column::Position)
+    ));
+}
+
+#[test]
+fn descendant_subquery_quotes_the_qualified_hierarchy_table() {
+    let repo = ClosureTreeRepository::<entity::trees_node::Model>::new();
+    let sql = Query::select()
+        .column(Asterisk)
+        .from(Alias::new("products"))
+        .and_where(
+            Expr::col(Alias::new("category_id")).binary(BinOper::In, repo.descendant_subquery(&7)),
+        )
+        .to_string(PostgresQueryBuilder);
+    assert_eq!(
+        sql,
+        r#"SELECT * FROM "products" WHERE "category_id" IN (SELECT "descendant_id" FROM "trees"."node_hierarchies" WHERE "node_hierarchies"."ancestor_id" = 7)"#
+    );
+}
+
+#[test]
+fn generated_hierarchy_entities_use_the_configured_table_and_id_type() {
+    use entity::coded_node::coded_node_hierarchy;
+    use sea_orm::EntityName;
+
+    assert_eq!(
+        coded_node_hierarchy::Entity.table_name(),
+        "coded_node_hierarchies"
+    );
+    let row: coded_node_hierarchy::ActiveModel =
+        entity::coded_node::Model::hierarchy_build_row(1, 2, 1);
+    assert_eq!(row.ancestor_id, ActiveValue::Set(1_i32));
+    assert_eq!(row.descendant_id, ActiveValue::Set(2));
+    assert_eq!(row.generations, ActiveValue::Set(1));
+}
+
+#[test]
+fn renamed_columns_configure_their_physical_names() {
+    let config = entity::renamed_node::Model::closure_tree_config();
+    assert_eq!(config.parent_column(), "parent_id");
+    assert_eq!(config.name_column(), Some("title"));
+    assert!(matches!(
+        config.order_strategy(),
+        Some(OrderStrategy::NumericColumn { column, .. }) if column == "sort_key"
+    ));
+    assert!(config.validate().is_empty());
+}
+
+#[test]
+fn mixed_case_fields_resolve_their_column_variants() {
+    let config = entity::cased_node::Model::closure_tree_config();
+    assert_eq!(config.parent_column(), "parent_id");
+    assert_eq!(config.name_column(), Some("node_name"));
+    assert!(matches!(
+        config.order_strategy(),
+        Some(OrderStrategy::NumericColumn { column, .. }) if column == "sort_key_v2"
+    ));
+    assert!(matches!(
+        entity::cased_node::Model::parent_column(),
+        entity::cased_node::Column::ParentId
+    ));
+}
+
+#[test]
+fn fields_ending_in_digits_resolve_their_column_variants() {
+    use entity::numbered_node::{http_node_hierarchy, Column, Model};
+
+    assert_eq!(Model::closure_tree_config().parent_column(), "parent_id2");
+    assert!(matches!(Model::parent_column(), Column::ParentId2));
+    assert!(matches!(Model::id_column(), Column::UuidV7));
+    assert_eq!(
+        sea_orm::EntityName::table_name(&http_node_hierarchy::Entity),
+        "numbered_node_hierarchies"
+    );
+}
+
+#[test]
+fn raw_identifier_fields_configure_their_columns() {
+    let config = entity::raw_node::Model::closure_tree_config();
+    assert_eq!(config.parent_column(), "ref");
+    assert_eq!(config.name_column(), Some("type"));
+    assert!(matches!(
+        config.order_strategy(),
+        Some(OrderStrategy::NumericColumn { column, .. }) if column == "loop"
+    ));
+    let node = entity::raw_node::Model {
+        id: 2,
+        r#ref: Some(1),
+        r#type: "leaf".into(),
+        r#loop: 0,
+    };
+    assert_eq!(node.parent_id(), Some(1));
+    assert_eq!(node.name(), "leaf");
+}
+
+#[test]
+fn custom_item_names_resolve_the_entity_and_columns() {
+    use entity::item_node::{ItemColumn, Model};
+
+    assert!(matches!(Model::parent_column(), ItemColumn::ParentId));
+    assert!(matches!(Model::name_column(), Some(ItemColumn::Name)));
+    let config = Model::closure_tree_config();
+    assert_eq!(config.parent_column(), "parent_id");
+    assert_eq!(config.hierarchy_table(), "item_hierarchies");
+}
+
+#[test]
+fn extension_traits_take_the_given_name_or_the_entity_name() {
+    fn named<T: entity::node::NodeTreeExt>() {}
+    fn defaulted<T: entity::ranked_node::ModelTreeExt>() {}
+    named::<entity::node::Model>();
+    defaulted::<entity::ranked_node::Model>();
+}
+
+#[test]
+fn tagged_trees_derive_their_own_configs() {
+    use entity::staff_member::{Model, OrgTree, ReportingTree};
+
+    let org = <Model as ClosureTreeModel<OrgTree>>::closure_tree_config();
+    let reporting = <Model as ClosureTreeModel<ReportingTree>>::closure_tree_config();
+    assert_eq!(org.parent_column(), "department_id");
+    assert_eq!(org.hierarchy_table(), "staff_members_org_hierarchies");
+    assert_eq!(org.hierarchy_name(), "ModelOrgHierarchy");
+    assert_eq!(reporting.parent_column(), "manager_id");
+    assert_eq!(
+        reporting.hierarchy_table(),
+        "staff_members_reporting_hierarchies"
+    );
+    assert_eq!(org.name_column(), Some("name"));
+    assert_eq!(reporting.name_column(), Some("name"));
+}
+
+#[test]
+fn out_of_range_order_values_are_errors() {
+    use entity::small_ranked_node::{ActiveModel, Model};
+
+    let mut active = ActiveModel::default();
+    Model::set_order_value(&mut active, 32_767).unwrap();
+    assert_eq!(active.position, ActiveValue::Set(32_767));
+    assert!(matches!(
+        Model::set_order_value(&mut active, 32_768),
+        Err(ClosureTreeError::OrderValueOutOfRange { value: 32_768 })
+    ));
+}
+
+#[test]
+fn fractional_order_configures_a_fractional_column() {
+    use entity::fractional_node::{ActiveModel, Column, Model};
+
+    assert_eq!(
+        Model::closure_tree_config().order_strategy(),
+        Some(&OrderStrategy::fractional_column("rank"))
+    );
+    assert!(matches!(Model::order_column(), Some(Column::Rank)));
+
+    let mut active = ActiveModel::default();
+    Model::set_fractional_order_value(&mut active, 0.5);
+    assert_eq!(active.rank, ActiveValue::Set(0.5));
+}
+
+#[test]
+fn id_to_value_with_converts_newtype_ids() {
+    use entity::newtype_node::{Model, NodeId};
+
+    assert_eq!(
+        Model::id_to_value(&NodeId(7)),
+        sea_orm::Value::BigInt(Some(7))
+    );
+    assert_eq!(
+        Model::hierarchy_id_to_value(&NodeId(7)),
+        sea_orm::Value::BigInt(Some(7))
+    );
+}
+
+#[test]
+fn hierarchy_conflict_columns_are_configured_and_validated() {
+    let config = entity::legacy_node::Model::closure_tree_config();
+    assert_eq!(
+        config.hierarchy_conflict_columns(),
+        Some(&["ancestor_id".to_owned(), "descendant_id".to_owned()][..])
+    );
+    assert!(config.validate().is_empty());
+    assert_eq!(
+        entity::node::Model::closure_tree_config().hierarchy_conflict_columns(),
+        None
+    );
+    assert_eq!(
+        ClosureTreeOptions::default()
+            .hierarchy_conflict_columns(Vec::<String>::new())
+            .apply(config.clone())
+            .validate()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn capability_markers_follow_the_configured_fields() {
+    fn ordered<T: HasOrdering>() {}
+    fn counted<T: HasCounterCache>() {}
+    fn named<T: NamedClosureTreeModel>() {}
+    ordered::<entity::ranked_node::Model>();
+    ordered::<entity::marked_node::Model>();
+    counted::<entity::counted_node::Model>();
+    named::<entity::counted_node::Model>();
+}
+
+#[test]
+fn no_name_models_have_no_name_column() {
+    let config = entity::unnamed_node::Model::closure_tree_config();
+    assert_eq!(config.name_column(), None);
+    assert!(entity::unnamed_node::Model::name_column().is_none());
+    assert!(config.validate().is_empty());
+    assert_eq!(
+        ClosureTreeOptions::default()
+            .unique_sibling_names(true)
+            .apply(config.clone())
+            .validate()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn validate_reports_inconsistent_configs() {
+    assert!(entity::node::Model::closure_tree_config()
+        .validate()
+        .is_empty());
+    assert!(entity::soft_node::Model::closure_tree_config()
+        .validate()
+        .is_empty());
+
+    let broken = ClosureTreeOptions::default()
+        .touch("name")
+        .order_gap(0)
+        .dont_order_roots(true)
+        .hierarchy_table("")
+        .path_separator("")
+        .apply(entity::node::Model::closure_tree_config().clone());
+    let findings: Vec<String> = broken.validate().iter().map(ToString::to_string).collect();
+    assert_eq!(
+        findings,
+        [
+            "invalid configuration: the hierarchy table is empty",
+            "invalid configuration: `name` is both the name and the touch column",
+            "invalid configuration: the path separator must be non-empty and not contain `\\`, got \"\"",
+            "invalid configuration: order_gap must be positive, got 0",
+            "invalid configuration: dont_order_roots needs a numeric column order strategy",
+        ]
+    );
+}
+
+#[test]
+fn recommended_indexes_back_unique_sibling_names() {
+    assert!(entity::ranked_node::Model::closure_tree_config().unique_sibling_names());
+    assert_eq!(
+        ClosureTreeRepository::<entity::ranked_node::Model>::new().recommended_indexes(),
+        [
+            r#"CREATE INDEX IF NOT EXISTS "ordered_node_hierarchies_descendant_idx" ON "ordered_node_hierarchies" ("descendant_id")"#,
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "ordered_nodes_parent_name_key" ON "ordered_nodes" ("parent_id", "name") WHERE "parent_id" IS NOT NULL"#,
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "ordered_nodes_root_name_key" ON "ordered_nodes" ("name") WHERE "parent_id" IS NULL"#,
+        ]
+    );
+
+    let soft = ClosureTreeOptions::default()
+        .soft_delete_column("deleted_at")
+        .apply(entity::ranked_node::Model::closure_tree_config().clone());
+    assert_eq!(
+        ClosureTreeRepository::<entity::ranked_node::Model>::new()
+            .with_config(soft)
+            .recommended_indexes()[2],
+        r#"CREATE UNIQUE INDEX IF NOT EXISTS "ordered_nodes_root_name_key" ON "ordered_nodes" ("name") WHERE "parent_id" IS NULL AND "deleted_at" IS NULL"#
+    );
+
+    let plain = ClosureTreeRepository::<entity::node::Model>::new().recommended_indexes();
+    assert_eq!(
+        plain[1],
+        r#"CREATE INDEX IF NOT EXISTS "nodes_parent_name_idx" ON "nodes" ("parent_id", "name")"#
+    );
+}
+
+#[test]
+fn diff_trees_reports_added_removed_moved_and_renamed_nodes() {
+    let node = |id, parent_id, name: &str, children| TreeNode {
+        node: entity::node::Model {
+            id,
+            parent_id,
+            name: name.to_owned(),
+        },
+        children,
+    };
+    let old = [node(
+        1,
+        None,
+        "root",
+        vec![
+            node(2, Some(1), "a", vec![node(4, Some(2), "x", vec![])]),
+            node(3, Some(1), "b", vec![]),
+        ],
+    )];
+    let new = [node(
+        1,
+        None,
+        "root",
+        vec![
+            node(2, Some(1), "a", vec![]),
+            node(5, Some(1), "c", vec![node(4, Some(5), "x2", vec![])]),
+        ],
+    )];
+
+    assert_eq!(
+        diff_trees(&old, &new),
+        [
+            NodeChange::Added {
+                id: 5,
+                parent: Some(1)
+            },
+            NodeChange::Moved {
+                id: 4,
+                from: Some(2),
+                to: Some(5)
+            },
+            NodeChange::Renamed {
+                id: 4,
+                from: "x".to_owned(),
+                to: "x2".to_owned()
+            },
+            NodeChange::Removed { id: 3 },
+        ]
+    );
+    assert!(diff_trees(&new, &new).is_empty());
+}
+
+static SHARED_REPO: ClosureTreeRepository<entity::node::Model> = ClosureTreeRepository::new();
+
+#[test]
+fn repositories_can_be_static_and_shared_across_threads() {
+    let expected = ClosureTreeRepository::<entity::node::Model>::default().recommended_indexes();
+    let handles: Vec<_> = (0..2)
+        .map(|_| std::thread::spawn(|| SHARED_REPO.recommended_indexes()))
+        .collect();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), expected);
+    }
+
+    let dry_run = SHARED_REPO.clone().with_dry_run(true);
+    assert!(format!("{dry_run:?}").contains("dry_run: true"));
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "error-kind")]
+
+use std::time::Duration;
+
+use closure_tree::{ClosureTreeError, ClosureTreeErrorKind};
+use sea_orm::DbErr;
+
+#[test]
+fn errors_map_to_kinds() {
+    let not_found = ClosureTreeError::NodeNotFound {
+        entity: "Model".to_owned(),
+        id: "1".to_owned(),
+    };
+    let lock_timeout = ClosureTreeError::LockTimeout {
+        key: "nodes".to_owned(),
+        waited: Duration::from_millis(200),
+    };
+    let cleanup = ClosureTreeError::CleanupFailed {
+        source: Box::new(ClosureTreeError::EmptyPath),
+        cleanup: Box::new(ClosureTreeError::Database(DbErr::ConnectionAcquire(
+            sea_orm::ConnAcquireErr::Timeout,
+        ))),
+    };
+
+    assert_eq!(
+        ClosureTreeErrorKind::from(ClosureTreeError::EmptyPath),
+        ClosureTreeErrorKind::BadRequest
+    );
+    assert_eq!(not_found.kind(), ClosureTreeErrorKind::NotFound);
+    assert_eq!(lock_timeout.kind(), ClosureTreeErrorKind::Conflict);
+    assert_eq!(
+        ClosureTreeErrorKind::from(&ClosureTreeError::Database(DbErr::Custom("boom".into()))),
+        ClosureTreeErrorKind::ServerError
+    );
+    assert_eq!(
+        ClosureTreeError::CycleDetected {
+            node_id: "1".to_owned(),
+            new_parent_id: "2".to_owned(),
+        }
+        .kind(),
+        ClosureTreeErrorKind::Conflict
+    );
+    assert_eq!(
+        ClosureTreeError::invariant("source node has no hierarchy rows").kind(),
+        ClosureTreeErrorKind::ServerError
+    );
+    assert_eq!(cleanup.kind(), ClosureTreeErrorKind::BadRequest);
+    assert_eq!(
+        ClosureTreeError::DepthLimitExceeded {
+            depth: 7,
+            max_depth: 6
+        }
+        .kind(),
+        ClosureTreeErrorKind::BadRequest
+    );
+    assert_eq!(
+        ClosureTreeError::UnsupportedOperation {
+            operation: "move_up",
+            reason: "no numeric order column is configured"
+        }
+        .kind(),
+        ClosureTreeErrorKind::BadRequest
+    );
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "metrics")]
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use closure_tree::{ClosureTreeRepository, NodeRef, TreeObserver};
+use sea_orm::{DatabaseBackend, MockDatabase};
+
+mod common;
+use common::entity;
+
+#[derive(Default)]
+struct RecordingMetrics {
+    operations: Mutex<Vec<(String, &'static str, u32)>>,
+}
+
+impl TreeObserver<i32> for RecordingMetrics {
+    fn on_operation(&self, entity: &str, operation: &'static str, queries: u32, _: Duration) {
+        self.operations
+            .lock()
+            .unwrap()
+            .push((entity.to_owned(), operation, queries));
+    }
+}
+
+#[tokio::test]
+async fn operations_report_their_query_counts() -> Result<(), Box<dyn std::error::Error>> {
+    let root = entity::node::Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_string(),
+    };
+    let child = entity::node::Model {
+        id: 2,
+        parent_id: Some(1),
+        name: "child".to_string(),
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![entity::node_hierarchy::Model {
+            ancestor_id: 1,
+            descendant_id: 2,
+            generations: 1,
+        }]])
+        .append_query_results([vec![child.clone()]])
+        .append_query_results([vec![root.clone()]])
+        .append_query_results([vec![child.clone()]])
+        .append_query_results([vec![child.clone()]])
+        .into_connection();
+
+    let metrics = Arc::new(RecordingMetrics::default());
+    let repo = ClosureTreeRepository::<entity::node::Model>::new().with_observer(metrics.clone());
+    assert_eq!(repo.descendants(&db, &root).await?, vec![child.clone()]);
+    assert_eq!(
+        repo.children_of(&db, NodeRef::Id(1)).await?,
+        vec![child.clone()]
+    );
+    // A loaded node is not fetched again.
+    assert_eq!(repo.children_of(&db, &root).await?, vec![child]);
+
+    assert_eq!(
+        *metrics.operations.lock().unwrap(),
+        [
+            ("Model".to_owned(), "descendants", 2),
+            ("Model".to_owned(), "children_of", 1),
+            ("Model".to_owned(), "children", 1),
+            ("Model".to_owned(), "children", 1),
+        ]
+    );
+    Ok(())
+}
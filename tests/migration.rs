@@ -0,0 +1,77 @@
+#![cfg(feature = "migration")]
+
+use closure_tree::migration::{
+    create_hierarchy_table_statement, drop_hierarchy_table_statement,
+    hierarchy_foreign_key_statements, hierarchy_index_statements,
+};
+use closure_tree::ClosureTreeModel;
+use sea_orm::sea_query::{ColumnType, PostgresQueryBuilder};
+
+mod common;
+use common::entity;
+
+#[test]
+fn hierarchy_table_statements_use_the_configured_names() {
+    let config = entity::trees_node::Model::closure_tree_config();
+
+    assert_eq!(
+        create_hierarchy_table_statement(config, ColumnType::Integer)
+            .to_string(PostgresQueryBuilder),
+        r#"CREATE TABLE IF NOT EXISTS "trees"."node_hierarchies" ( "ancestor_id" integer NOT NULL, "descendant_id" integer NOT NULL, "generations" integer NOT NULL, PRIMARY KEY ("ancestor_id", "descendant_id") )"#
+    );
+    let indexes: Vec<String> = hierarchy_index_statements(config)
+        .iter()
+        .map(|index| index.to_string(PostgresQueryBuilder))
+        .collect();
+    assert_eq!(
+        indexes,
+        [
+            r#"CREATE INDEX IF NOT EXISTS "node_hierarchies_descendant_idx" ON "trees"."node_hierarchies" ("descendant_id")"#
+        ]
+    );
+    let foreign_keys: Vec<String> = hierarchy_foreign_key_statements(config, "nodes", "id")
+        .iter()
+        .map(|key| key.to_string(PostgresQueryBuilder))
+        .collect();
+    assert_eq!(
+        foreign_keys,
+        [
+            r#"ALTER TABLE "trees"."node_hierarchies" ADD CONSTRAINT "node_hierarchies_ancestor_id_fkey" FOREIGN KEY ("ancestor_id") REFERENCES "trees"."nodes" ("id") ON DELETE CASCADE"#,
+            r#"ALTER TABLE "trees"."node_hierarchies" ADD CONSTRAINT "node_hierarchies_descendant_id_fkey" FOREIGN KEY ("descendant_id") REFERENCES "trees"."nodes" ("id") ON DELETE CASCADE"#,
+        ]
+    );
+    assert_eq!(
+        drop_hierarchy_table_statement(config).to_string(PostgresQueryBuilder),
+        r#"DROP TABLE IF EXISTS "trees"."node_hierarchies""#
+    );
+}
+
+#[test]
+fn hierarchy_tables_take_the_id_type() {
+    let config = entity::uuid_node::Model::closure_tree_config();
+    let sql =
+        create_hierarchy_table_statement(config, ColumnType::Uuid).to_string(PostgresQueryBuilder);
+    assert!(
+        sql.starts_with(
+            r#"CREATE TABLE IF NOT EXISTS "uuid_node_hierarchies" ( "ancestor_id" uuid NOT NULL, "descendant_id" uuid NOT NULL,"#
+        ),
+        "{sql}"
+    );
+}
+
+#[test]
+fn conflict_columns_get_a_unique_index() {
+    let config = entity::legacy_node::Model::closure_tree_config();
+
+    let indexes: Vec<String> = hierarchy_index_statements(config)
+        .iter()
+        .map(|index| index.to_string(PostgresQueryBuilder))
+        .collect();
+    assert_eq!(
+        indexes,
+        [
+            r#"CREATE INDEX IF NOT EXISTS "legacy_node_hierarchies_descendant_idx" ON "legacy_node_hierarchies" ("descendant_id")"#,
+            r#"CREATE UNIQUE INDEX IF NOT EXISTS "legacy_node_hierarchies_conflict_idx" ON "legacy_node_hierarchies" ("ancestor_id", "descendant_id")"#,
+        ]
+    );
+}
@@ -0,0 +1,136 @@
+use closure_tree::{
+    AdvisoryLockStrategy, ClosureTreeError, ClosureTreeModel, ClosureTreeModelExt,
+    ClosureTreeOptions, ClosureTreeRepository, LockedTransaction,
+};
+use sea_orm::{DatabaseBackend, MockDatabase};
+
+mod common;
+use common::entity;
+
+#[tokio::test]
+async fn portable_reads_work_on_other_backends() -> Result<(), Box<dyn std::error::Error>> {
+    let root = entity::node::Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_string(),
+    };
+    let child = entity::node::Model {
+        id: 2,
+        parent_id: Some(1),
+        name: "child".to_string(),
+    };
+    let db = MockDatabase::new(DatabaseBackend::MySql)
+        .append_query_results([vec![child.clone()]])
+        .into_connection();
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    assert_eq!(repo.children(&db, &root).await?, vec![child]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reads_run_on_the_given_connection_without_a_transaction(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = entity::node::Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_string(),
+    };
+    let child = entity::node::Model {
+        id: 2,
+        parent_id: Some(1),
+        name: "child".to_string(),
+    };
+    let replica = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![entity::node_hierarchy::Model {
+            ancestor_id: 1,
+            descendant_id: 2,
+            generations: 1,
+        }]])
+        .append_query_results([vec![child.clone()]])
+        .append_query_results([vec![root.clone()]])
+        .into_connection();
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    assert_eq!(
+        repo.descendants(&replica, &root).await?,
+        vec![child.clone()]
+    );
+    assert_eq!(child.parent(&replica).await?, Some(root));
+
+    // Reads are single statements: no BEGIN and no advisory lock.
+    let log = replica.into_transaction_log();
+    assert_eq!(log.len(), 3);
+    for transaction in log {
+        let statements = transaction.statements();
+        assert_eq!(statements.len(), 1);
+        assert!(statements[0].sql.starts_with("SELECT"));
+        assert!(!statements[0].sql.contains("pg_advisory"));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recursive_cte_ancestors_take_one_statement() -> Result<(), Box<dyn std::error::Error>> {
+    let root = entity::node::Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_string(),
+    };
+    let child = entity::node::Model {
+        id: 2,
+        parent_id: Some(1),
+        name: "child".to_string(),
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![root.clone()]])
+        .into_connection();
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .prefer_recursive_cte(true)
+            .apply(entity::node::Model::closure_tree_config().clone()),
+    );
+    assert_eq!(repo.ancestors(&db, &child).await?, vec![root]);
+
+    let log = db.into_transaction_log();
+    assert_eq!(log.len(), 1);
+    assert!(log[0].statements()[0].sql.starts_with("WITH RECURSIVE"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn advisory_locking_reports_unsupported_backend() {
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+
+    let err = repo
+        .find_or_create_by_path(&db, &["root"])
+        .await
+        .expect_err("MySQL has no advisory lock support yet");
+    assert!(matches!(
+        err,
+        ClosureTreeError::UnsupportedBackend {
+            backend: DatabaseBackend::MySql,
+            operation: "advisory locking",
+        }
+    ));
+}
+
+#[tokio::test]
+async fn disabled_strategy_opens_plain_transaction() -> Result<(), Box<dyn std::error::Error>> {
+    let db = MockDatabase::new(DatabaseBackend::MySql).into_connection();
+
+    let locked = LockedTransaction::acquire(&AdvisoryLockStrategy::Disabled, &db).await?;
+    assert!(!locked.is_locked());
+    assert_eq!(locked.key(), None);
+    locked.commit().await?;
+
+    let locked = LockedTransaction::acquire(&AdvisoryLockStrategy::Disabled, &db).await?;
+    locked.into_transaction().rollback().await?;
+
+    Ok(())
+}
@@ -1,76 +1,3381 @@
-use closure_tree::ClosureTreeRepository;
+use std::sync::Arc;
+use std::time::Duration;
+
+use closure_tree::{
+    diff_trees, AdvisoryLockStrategy, ClosureTreeError, ClosureTreeModel, ClosureTreeModelExt,
+    ClosureTreeOptions, ClosureTreeRepository, GenerationRange, LockAcquisition, LockedTransaction,
+    NodeChange, NodeRef, OrderDirection, OrderScope, OrderStrategy, SchemaFinding,
+    SessionLockGuard, TreeInput, TreeNode, TreeObserver,
+};
 use sea_orm::entity::prelude::*;
-use sea_orm::{Database, DatabaseConnection, DbBackend, Statement};
-
-mod entity {
-    pub mod node {
-        use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
-        use sea_orm::entity::prelude::*;
-
-        #[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
-        #[sea_orm(table_name = "nodes")]
-        #[closure_tree(
-            hierarchy_module = "crate::entity::node_hierarchy",
-            hierarchy_table = "node_hierarchies"
-        )]
-        pub struct Model {
-            #[sea_orm(primary_key)]
-            pub id: i32,
-            pub parent_id: Option<i32>,
-            pub name: String,
+use sea_orm::sea_query::BinOper;
+use sea_orm::{
+    Database, DatabaseConnection, DbBackend, FromQueryResult, QuerySelect, Statement,
+    TransactionTrait,
+};
+use tokio::sync::{Mutex, MutexGuard};
+
+mod common;
+use common::entity;
+
+/// Tests share the same tables, so they run one at a time.
+static DATABASE_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[tokio::test]
+async fn find_or_create_path_builds_hierarchy() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "child", "leaf"])
+        .await?;
+
+    assert_eq!(leaf.name, "leaf");
+
+    let child = repo
+        .find_by_path(&db, &["root", "child"])
+        .await?
+        .expect("child node exists");
+
+    let descendants = repo.descendants(&db, &child).await?;
+    let names: Vec<String> = descendants.into_iter().map(|node| node.name).collect();
+    assert_eq!(names, vec!["leaf"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn id_lookups_load_the_node_or_report_it_missing() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "child", "leaf"])
+        .await?;
+    let child_id = leaf.parent_id.expect("leaf has a parent");
+
+    assert_eq!(
+        repo.children_of(&db, NodeRef::Id(child_id)).await?,
+        vec![leaf.clone()]
+    );
+    let missing = repo.descendants_of(&db, NodeRef::Id(leaf.id + 100)).await;
+    assert!(matches!(
+        missing,
+        Err(ClosureTreeError::NodeNotFound { ref entity, ref id })
+            if entity == "Model" && *id == (leaf.id + 100).to_string()
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn node_refs_take_loaded_models_or_ids() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "child", "leaf"])
+        .await?;
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+
+    let by_model = repo.descendants_of(&db, &root).await?;
+    let by_id = repo.descendants_of(&db, NodeRef::Id(root.id)).await?;
+    assert_eq!(by_model, by_id);
+    assert_eq!(by_model.last(), Some(&leaf));
+    assert_eq!(NodeRef::<entity::node::Model>::from(&leaf).id(), leaf.id);
+    assert_eq!(repo.children_of(&db, leaf).await?, []);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn roots_count_and_node_count_count_rows() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    assert_eq!(repo.roots_count(&db).await?, 0);
+    assert_eq!(repo.node_count(&db).await?, 0);
+
+    repo.find_or_create_by_path(&db, &["root", "child", "leaf"])
+        .await?;
+    assert_eq!(repo.roots_count(&db).await?, 1);
+    assert_eq!(repo.node_count(&db).await?, 3);
+
+    repo.find_or_create_by_path(&db, &["other"]).await?;
+    assert_eq!(repo.roots_count(&db).await?, 2);
+    assert_eq!(repo.node_count(&db).await?, 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn subtree_size_and_has_children_count_the_subtree() -> Result<(), Box<dyn std::error::Error>>
+{
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "child", "leaf"])
+        .await?;
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let child = repo
+        .find_by_path(&db, &["root", "child"])
+        .await?
+        .expect("child exists");
+
+    assert_eq!(repo.subtree_size(&db, &root).await?, 3);
+    assert_eq!(repo.subtree_size(&db, &leaf).await?, 1);
+    assert!(repo.has_children(&db, &child).await?);
+    assert!(!repo.has_children(&db, &leaf).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn find_or_create_path_extends_existing_branch() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+
+    let child = repo.find_or_create_by_path(&db, &["root", "child"]).await?;
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "child", "a", "b", "leaf"])
+        .await?;
+
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let names: Vec<String> = repo
+        .descendants(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["a", "b", "child", "leaf"]);
+
+    let rows = db
+        .query_all(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT ancestor_id, generations FROM node_hierarchies WHERE descendant_id = $1 ORDER BY generations",
+            [leaf.id.into()],
+        ))
+        .await?;
+    let chain: Vec<(i32, i32)> = rows
+        .iter()
+        .map(|row| {
+            Ok((
+                row.try_get("", "ancestor_id")?,
+                row.try_get("", "generations")?,
+            ))
+        })
+        .collect::<Result<_, sea_orm::DbErr>>()?;
+    assert_eq!(chain.len(), 5);
+    assert_eq!(chain[0], (leaf.id, 0));
+    assert_eq!(chain[3], (child.id, 3));
+    assert_eq!(chain[4], (root.id, 4));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn model_ext_methods_query_through_a_default_repository(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let c = repo
+        .find_or_create_by_path(&db, &["root", "a", "b", "c"])
+        .await?;
+    let names = |nodes: Vec<entity::node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+
+    assert_eq!(names(c.ancestors(&db).await?), ["b", "a", "root"]);
+    let b = c.parent(&db).await?.expect("c has a parent");
+    assert_eq!(names(b.children(&db).await?), ["c"]);
+    assert!(b.has_children(&db).await?);
+    assert!(!c.has_children(&db).await?);
+    assert_eq!(b.count_children(&db).await?, 1);
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    assert!(root.ancestors(&db).await?.is_empty());
+    assert_eq!(names(root.descendants(&db).await?), ["a", "b", "c"]);
+    assert_eq!(root.subtree_size(&db).await?, 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn hash_tree_nests_nodes_in_sibling_order() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    repo.find_or_create_by_path(&db, &["root", "b", "c"])
+        .await?;
+    repo.find_or_create_by_path(&db, &["root", "a"]).await?;
+    repo.find_or_create_by_path(&db, &["other"]).await?;
+    fn shape(trees: &[TreeNode<entity::node::Model>]) -> String {
+        trees
+            .iter()
+            .map(|tree| match tree.children.as_slice() {
+                [] => tree.node.name.clone(),
+                children => format!("{}({})", tree.node.name, shape(children)),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    let before = repo.hash_tree(&db, None).await?;
+    assert_eq!(shape(&before), "other root(a b(c))");
+    let b = repo
+        .find_by_path(&db, &["root", "b"])
+        .await?
+        .expect("b exists");
+    assert_eq!(shape(&repo.hash_tree(&db, Some(&b)).await?), "b(c)");
+
+    let renamed = repo.rename(&db, &b, "b2").await?;
+    let after = repo.hash_tree(&db, None).await?;
+    assert_eq!(
+        diff_trees(&before, &after),
+        [NodeChange::Renamed {
+            id: renamed.id,
+            from: "b".to_owned(),
+            to: "b2".to_owned(),
+        }]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn path_strings_split_and_join_on_the_separator() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo
+        .find_or_create_by_path_str(&db, "/docs//a\\/b\\\\/c/")
+        .await?;
+    let names: Vec<String> = repo
+        .ancestors(&db, &leaf)
+        .await?
+        .into_iter()
+        .rev()
+        .map(|node| node.name)
+        .chain([leaf.name.clone()])
+        .collect();
+    assert_eq!(names, ["docs", "a/b\\", "c"]);
+
+    let path = repo.ancestry_path_str(&db, &leaf).await?;
+    assert_eq!(path, "docs/a\\/b\\\\/c");
+    assert_eq!(repo.find_by_path_str(&db, &path).await?, Some(leaf));
+    assert_eq!(repo.find_by_path_str(&db, "docs/a/b").await?, None);
+    for empty in ["", "//"] {
+        assert!(matches!(
+            repo.find_by_path_str(&db, empty).await,
+            Err(ClosureTreeError::EmptyPath)
+        ));
+        assert!(matches!(
+            repo.find_or_create_by_path_str(&db, empty).await,
+            Err(ClosureTreeError::EmptyPath)
+        ));
+    }
+
+    let counted = ClosureTreeRepository::<entity::counted_node::Model>::new();
+    let node = counted
+        .find_or_create_by_path_str(&db, "std::a:b::c")
+        .await?;
+    assert_eq!(
+        counted.ancestry_path_str(&db, &node).await?,
+        "std::a\\:b::c"
+    );
+    assert_eq!(
+        counted.find_by_path_str(&db, "std::a\\:b::c").await?,
+        Some(node)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn depth_and_child_limits_reject_oversized_trees() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let derived = entity::counted_node::Model::closure_tree_config();
+    assert_eq!(
+        (derived.max_depth(), derived.max_children()),
+        (Some(16), Some(500))
+    );
+    let repo = ClosureTreeRepository::<entity::counted_node::Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .max_depth(2)
+            .max_children(2)
+            .apply(derived.clone()),
+    );
+
+    repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    assert!(matches!(
+        repo.find_or_create_by_path(&db, &["a", "b", "c", "d"])
+            .await,
+        Err(ClosureTreeError::DepthLimitExceeded {
+            depth: 3,
+            max_depth: 2
+        })
+    ));
+    repo.find_or_create_by_path(&db, &["a", "x"]).await?;
+    let err = repo
+        .find_or_create_by_path(&db, &["a", "y"])
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        ClosureTreeError::ChildLimitExceeded {
+            max_children: 2,
+            ..
+        }
+    ));
+    assert_eq!(repo.node_count(&db).await?, 4);
+
+    let wide = TreeInput::new("w")
+        .child(TreeInput::new("1"))
+        .child(TreeInput::new("2"))
+        .child(TreeInput::new("3"));
+    assert!(matches!(
+        repo.import_tree(&db, &wide, None).await,
+        Err(ClosureTreeError::ChildLimitExceeded { .. })
+    ));
+    let tall = TreeInput::new("t").child(TreeInput::new("u").child(TreeInput::new("v")));
+    let x = repo
+        .find_by_path(&db, &["a", "x"])
+        .await?
+        .expect("x exists");
+    assert!(matches!(
+        repo.import_tree(&db, &tall, Some(&x.id)).await,
+        Err(ClosureTreeError::DepthLimitExceeded { depth: 4, .. })
+    ));
+
+    // The moved subtree's deepest node, not its root, decides the depth.
+    let other = repo.find_or_create_by_path(&db, &["other"]).await?;
+    let b = repo
+        .find_by_path(&db, &["a", "b"])
+        .await?
+        .expect("b exists");
+    repo.graft(&db, &b.id, &other.id).await?;
+    assert!(matches!(
+        repo.graft(&db, &b.id, &x.id).await,
+        Err(ClosureTreeError::DepthLimitExceeded { depth: 3, .. })
+    ));
+    // Grafting onto the current parent does not count the node twice.
+    let full = repo.find_or_create_by_path(&db, &["other", "z"]).await?;
+    repo.graft(&db, &full.id, &other.id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ensure_path_returns_every_node_along_the_path() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let existing = repo.find_or_create_by_path(&db, &["root", "a"]).await?;
+    let chain = repo.ensure_path(&db, &["root", "a", "b", "c"]).await?;
+    let names: Vec<&str> = chain.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, ["root", "a", "b", "c"]);
+    assert_eq!(chain[1], existing);
+    for pair in chain.windows(2) {
+        assert_eq!(pair[1].parent_id, Some(pair[0].id));
+    }
+    assert_eq!(repo.node_count(&db).await?, 4);
+    assert_eq!(
+        repo.ancestors(&db, &chain[3]).await?,
+        chain[..3].iter().rev().cloned().collect::<Vec<_>>()
+    );
+
+    let txn = repo.lock(&db).await?;
+    assert_eq!(repo.ensure_path_in(&txn, &["root", "a"]).await?, chain[..2]);
+    txn.commit().await?;
+    assert!(matches!(
+        repo.ensure_path::<&str>(&db, &[]).await,
+        Err(ClosureTreeError::EmptyPath)
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn blank_path_segments_are_rejected_or_trimmed() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    for path in [&["a", "", "b"][..], &["a", " \t", "b"][..]] {
+        assert!(matches!(
+            repo.find_or_create_by_path(&db, path).await,
+            Err(ClosureTreeError::BlankSegment { index: 1 })
+        ));
+    }
+    assert_eq!(repo.node_count(&db).await?, 0);
+
+    let trimming = repo.clone().with_config(
+        ClosureTreeOptions::default()
+            .trim_segments(true)
+            .apply(entity::node::Model::closure_tree_config().clone()),
+    );
+    let b = trimming
+        .find_or_create_by_path(&db, &[" a ", "b\n"])
+        .await?;
+    assert_eq!(b.name, "b");
+    assert_eq!(
+        trimming.find_by_path(&db, &["a", "  b"]).await?,
+        Some(b.clone())
+    );
+    assert_eq!(repo.find_by_path(&db, &["a", "  b"]).await?, None);
+
+    let lenient = repo.clone().with_config(
+        ClosureTreeOptions::default()
+            .reject_blank_segments(false)
+            .apply(entity::node::Model::closure_tree_config().clone()),
+    );
+    let blank = lenient.find_or_create_by_path(&db, &["a", ""]).await?;
+    assert_eq!(blank.name, "");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn descendants_query_composes_with_filters_and_paging(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    for path in [
+        &["root", "b", "d"][..],
+        &["root", "a"],
+        &["root", "c"],
+        &["other", "x"],
+    ] {
+        repo.find_or_create_by_path(&db, path).await?;
+    }
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+
+    assert_eq!(
+        repo.descendants_query(&root).all(&db).await?,
+        repo.descendants(&db, &root).await?
+    );
+    let page: Vec<String> = repo
+        .descendants_query(&root)
+        .filter(entity::node::Column::Name.ne("b"))
+        .offset(1)
+        .limit(2)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(page, ["c", "d"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn descendant_subquery_filters_other_queries_by_subtree(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    for path in [&["root", "b", "d"][..], &["root", "a"], &["other", "x"]] {
+        repo.find_or_create_by_path(&db, path).await?;
+    }
+    let b = repo
+        .find_by_path(&db, &["root", "b"])
+        .await?
+        .expect("b exists");
+
+    let mut within: Vec<String> = entity::node::Entity::find()
+        .filter(
+            Expr::col(entity::node::Column::ParentId)
+                .binary(BinOper::In, repo.descendant_subquery(&b.parent_id.unwrap())),
+        )
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    within.sort();
+    assert_eq!(within, ["a", "b", "d"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn trees_without_names_support_structure_queries_and_moves(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use entity::unnamed_node;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "CREATE TABLE IF NOT EXISTS unnamed_nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES unnamed_nodes(id) ON DELETE CASCADE
+        )",
+        "CREATE TABLE IF NOT EXISTS unnamed_node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES unnamed_nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES unnamed_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+        "TRUNCATE TABLE unnamed_node_hierarchies, unnamed_nodes RESTART IDENTITY CASCADE",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let repo = ClosureTreeRepository::<unnamed_node::Model>::new();
+    for index in repo.recommended_indexes() {
+        db.execute_unprepared(&index).await?;
+    }
+    assert_eq!(repo.check_schema(&db).await?, []);
+
+    // Rows are inserted directly; `rebuild` derives their hierarchy.
+    for parent_id in [None, Some(1), Some(2), None] {
+        unnamed_node::ActiveModel {
+            parent_id: sea_orm::ActiveValue::Set(parent_id),
+            ..Default::default()
         }
+        .insert(&db)
+        .await?;
+    }
+    repo.rebuild(&db).await?;
+    let ids = |nodes: Vec<unnamed_node::Model>| -> Vec<i32> {
+        nodes.into_iter().map(|node| node.id).collect()
+    };
+    let root = repo.roots(&db).await?.remove(0);
+    let leaf = unnamed_node::Entity::find_by_id(3)
+        .one(&db)
+        .await?
+        .expect("leaf exists");
+    assert_eq!(ids(repo.roots(&db).await?), [1, 4]);
+    assert_eq!(ids(repo.descendants(&db, &root).await?), [2, 3]);
+    assert_eq!(ids(repo.ancestors(&db, &leaf).await?), [2, 1]);
+
+    let copy = repo.duplicate_subtree(&db, &root, Some(&4)).await?;
+    assert_eq!(repo.subtree_size(&db, &copy).await?, 3);
+    repo.graft(&db, &2, &4).await?;
+    assert_eq!(ids(repo.ancestors(&db, &leaf).await?), [2, 4]);
+    repo.destroy(&db, &root).await?;
+    assert_eq!(ids(repo.roots(&db).await?), [4]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn generated_uuid_ids_need_no_database_default() -> Result<(), Box<dyn std::error::Error>> {
+    use entity::uuid_node;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "CREATE TABLE IF NOT EXISTS uuid_nodes (
+            id UUID PRIMARY KEY,
+            parent_id UUID REFERENCES uuid_nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL
+        )",
+        "CREATE TABLE IF NOT EXISTS uuid_node_hierarchies (
+            ancestor_id UUID NOT NULL REFERENCES uuid_nodes(id) ON DELETE CASCADE,
+            descendant_id UUID NOT NULL REFERENCES uuid_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+        "TRUNCATE TABLE uuid_node_hierarchies, uuid_nodes CASCADE",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let repo = ClosureTreeRepository::<uuid_node::Model>::new();
+    assert_eq!(repo.check_schema(&db).await?, []);
+
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    let root = repo.find_by_path(&db, &["a"]).await?.expect("root exists");
+    let names = |nodes: Vec<uuid_node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+    assert_eq!(names(repo.ancestors(&db, &leaf).await?), ["b", "a"]);
+    assert_eq!(names(repo.descendants(&db, &root).await?), ["b", "c"]);
+    let mut rows: Vec<(Uuid, Uuid, i32)> = entity::uuid_node_hierarchy::Entity::find()
+        .filter(entity::uuid_node_hierarchy::Column::DescendantId.eq(leaf.id))
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|row| (row.ancestor_id, row.descendant_id, row.generations))
+        .collect();
+    rows.sort_by_key(|row| row.2);
+    let parent = leaf.parent_id.expect("leaf has a parent");
+    assert_eq!(
+        rows,
+        [
+            (leaf.id, leaf.id, 0),
+            (parent, leaf.id, 1),
+            (root.id, leaf.id, 2)
+        ]
+    );
+
+    let copy = repo.duplicate_subtree(&db, &root, None).await?;
+    assert_ne!(copy.id, root.id);
+    assert_eq!(names(repo.descendants(&db, &copy).await?), ["b", "c"]);
+    assert_eq!(repo.subtree_size(&db, &copy).await?, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn composite_primary_keys_use_the_marked_id() -> Result<(), Box<dyn std::error::Error>> {
+    use entity::composite_node;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "CREATE TABLE IF NOT EXISTS composite_nodes (
+            tenant_id INTEGER NOT NULL DEFAULT 7,
+            id SERIAL UNIQUE,
+            parent_id INTEGER REFERENCES composite_nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            PRIMARY KEY (tenant_id, id)
+        )",
+        "CREATE TABLE IF NOT EXISTS composite_node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES composite_nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES composite_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+        "TRUNCATE TABLE composite_node_hierarchies, composite_nodes CASCADE",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let repo = ClosureTreeRepository::<composite_node::Model>::new();
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    assert_eq!(leaf.tenant_id, 7);
+    let root = repo.find_by_path(&db, &["a"]).await?.expect("root exists");
+    let names = |nodes: Vec<composite_node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+    assert_eq!(names(repo.descendants(&db, &root).await?), ["b", "c"]);
+
+    let renamed = repo.rename(&db, &leaf, "d").await?;
+    assert_eq!((renamed.tenant_id, renamed.id), (7, leaf.id));
+    assert!(repo.find_by_path(&db, &["a", "b", "d"]).await?.is_some());
+
+    repo.destroy(&db, &root).await?;
+    assert_eq!(names(repo.roots(&db).await?), ["b"]);
+
+    Ok(())
+}
+
+#[cfg(feature = "migration")]
+#[tokio::test]
+async fn migration_statements_create_a_checked_hierarchy_table(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use closure_tree::migration::{
+        create_hierarchy_table_statement, drop_hierarchy_table_statement,
+        hierarchy_foreign_key_statements, hierarchy_index_statements,
+    };
+    use entity::uuid_node;
+    use sea_orm::sea_query::ColumnType;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    db.execute_unprepared(
+        "CREATE TABLE IF NOT EXISTS uuid_nodes (
+            id UUID PRIMARY KEY,
+            parent_id UUID REFERENCES uuid_nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL
+        )",
+    )
+    .await?;
+    let config = uuid_node::Model::closure_tree_config();
+    let backend = db.get_database_backend();
+    db.execute(backend.build(&drop_hierarchy_table_statement(config)))
+        .await?;
+    db.execute_unprepared("TRUNCATE TABLE uuid_nodes CASCADE")
+        .await?;
+    db.execute(backend.build(&create_hierarchy_table_statement(config, ColumnType::Uuid)))
+        .await?;
+    for index in hierarchy_index_statements(config) {
+        db.execute(backend.build(&index)).await?;
+    }
+    for key in hierarchy_foreign_key_statements(config, "uuid_nodes", "id") {
+        db.execute(backend.build(&key)).await?;
+    }
+
+    let repo = ClosureTreeRepository::<uuid_node::Model>::new();
+    assert_eq!(repo.check_schema(&db).await?, []);
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b"]).await?;
+    assert_eq!(repo.ancestors(&db, &leaf).await?.len(), 1);
+    repo.destroy(&db, &leaf).await?;
+    assert_eq!(repo.node_count(&db).await?, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn renamed_columns_are_used_for_queries_and_raw_sql() -> Result<(), Box<dyn std::error::Error>>
+{
+    use entity::renamed_node;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "CREATE TABLE IF NOT EXISTS renamed_nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES renamed_nodes(id) ON DELETE CASCADE,
+            title TEXT NOT NULL,
+            sort_key INTEGER NOT NULL DEFAULT 0
+        )",
+        "CREATE TABLE IF NOT EXISTS renamed_node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES renamed_nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES renamed_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+        "TRUNCATE TABLE renamed_node_hierarchies, renamed_nodes RESTART IDENTITY CASCADE",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let repo = ClosureTreeRepository::<renamed_node::Model>::new();
+    for index in repo.recommended_indexes() {
+        db.execute_unprepared(&index).await?;
+    }
+    assert_eq!(repo.check_schema(&db).await?, []);
+
+    let b = repo.find_or_create_by_path(&db, &["root", "b"]).await?;
+    let a = repo.find_or_create_by_path(&db, &["root", "a"]).await?;
+    assert_eq!((b.rank, a.rank), (0, 1));
+    assert!(repo.move_up(&db, &a).await?);
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let labels: Vec<String> = repo
+        .children(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| node.label)
+        .collect();
+    assert_eq!(labels, ["a", "b"]);
+    assert!(matches!(
+        repo.rename(&db, &a, "b").await,
+        Err(ClosureTreeError::DuplicateName { .. })
+    ));
+    // Nullify reparents the children through the renamed parent column.
+    repo.destroy(&db, &root).await?;
+    assert_eq!(repo.roots_count(&db).await?, 2);
+    assert_eq!(
+        repo.find_by_path(&db, &["a"]).await?.map(|node| node.id),
+        Some(a.id)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn check_schema_accepts_floating_point_fractional_positions(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use entity::fractional_node;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "DROP TABLE IF EXISTS fractional_node_hierarchies, fractional_nodes",
+        "CREATE TABLE fractional_nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES fractional_nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            rank DOUBLE PRECISION NOT NULL
+        )",
+        "CREATE TABLE fractional_node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES fractional_nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES fractional_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let repo = ClosureTreeRepository::<fractional_node::Model>::new();
+    assert_eq!(repo.check_schema(&db).await?, []);
+
+    db.execute_unprepared("ALTER TABLE fractional_nodes ALTER COLUMN rank TYPE TEXT")
+        .await?;
+    assert!(matches!(
+        repo.check_schema(&db).await?.as_slice(),
+        [SchemaFinding::IncompatibleType { column, .. }] if column == "rank"
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn surrogate_key_hierarchy_tables_write_through_the_conflict_target(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use entity::{legacy_node, legacy_node_hierarchy};
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "DROP TABLE IF EXISTS legacy_node_hierarchies, legacy_nodes",
+        "CREATE TABLE legacy_nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES legacy_nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL
+        )",
+        "CREATE TABLE legacy_node_hierarchies (
+            id BIGSERIAL PRIMARY KEY,
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+    let repo = ClosureTreeRepository::<legacy_node::Model>::new();
+    assert_eq!(repo.check_schema(&db).await?, []);
+    for index in repo.recommended_indexes() {
+        db.execute_unprepared(&index).await?;
+    }
+
+    let b = repo.find_or_create_by_path(&db, &["a", "b"]).await?;
+    let c = repo.find_or_create_by_path(&db, &["c"]).await?;
+    repo.move_to_child_of_at(&db, &b, &c.id, 0).await?;
+    let rows = legacy_node_hierarchy::Entity::find().all(&db).await?;
+    assert_eq!(rows.len(), 4);
+    assert!(rows.iter().all(|row| !row.created_at.is_empty()));
+
+    // A stale self row for the next node id, left by a delete that bypassed
+    // the repository, is skipped rather than failing the insert.
+    db.execute_unprepared(
+        "INSERT INTO legacy_node_hierarchies (ancestor_id, descendant_id, generations) \
+         VALUES (4, 4, 0)",
+    )
+    .await?;
+    let d = repo.find_or_create_by_path(&db, &["c", "b", "d"]).await?;
+    assert_eq!(d.id, 4);
+    let names: Vec<String> = repo
+        .ancestors(&db, &d)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, ["b", "c"]);
+
+    db.execute_unprepared("DELETE FROM legacy_node_hierarchies WHERE generations > 0")
+        .await?;
+    assert_eq!(repo.rebuild(&db).await?.rebuilt, [b.id, d.id]);
+    assert_eq!(repo.ancestors(&db, &d).await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn integer_names_support_path_lookups() -> Result<(), Box<dyn std::error::Error>> {
+    use entity::coded_node;
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    for sql in [
+        "CREATE TABLE IF NOT EXISTS coded_nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES coded_nodes(id) ON DELETE CASCADE,
+            code BIGINT NOT NULL
+        )",
+        "CREATE TABLE IF NOT EXISTS coded_node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES coded_nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES coded_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+        "TRUNCATE TABLE coded_node_hierarchies, coded_nodes RESTART IDENTITY CASCADE",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let repo = ClosureTreeRepository::<coded_node::Model>::new();
+    assert_eq!(repo.check_schema(&db).await?, []);
+
+    let leaf = repo.find_or_create_by_path(&db, &[10_i64, 20, 30]).await?;
+    assert_eq!(leaf.code, 30);
+    assert_eq!(
+        repo.find_by_path(&db, &[10_i64, 20, 30]).await?,
+        Some(leaf.clone())
+    );
+    assert_eq!(repo.find_by_path(&db, &[10_i64, 30]).await?, None);
+    assert_eq!(repo.ancestry_path_str(&db, &leaf).await?, "10/20/30");
+
+    let sibling = repo.find_or_create_by_path(&db, &[10_i64, 21]).await?;
+    assert_eq!(repo.roots(&db).await?.len(), 1);
+    let err = repo
+        .rename(&db, &sibling, 20_i64)
+        .await
+        .expect_err("20 is taken");
+    assert!(
+        matches!(&err, ClosureTreeError::DuplicateName { name, .. } if name == "20"),
+        "{err:?}"
+    );
+    let renamed = repo.rename(&db, &sibling, 22_i64).await?;
+    assert_eq!(renamed.code, 22);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn trees_in_a_non_default_schema_are_qualified() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+    for sql in [
+        "CREATE SCHEMA IF NOT EXISTS trees",
+        "CREATE TABLE IF NOT EXISTS trees.nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES trees.nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            path ltree
+        )",
+        "CREATE TABLE IF NOT EXISTS trees.node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES trees.nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES trees.nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        )",
+        "TRUNCATE TABLE trees.node_hierarchies, trees.nodes RESTART IDENTITY CASCADE",
+    ] {
+        db.execute_unprepared(sql).await?;
+    }
+
+    let config = entity::trees_node::Model::closure_tree_config();
+    assert_eq!(config.schema_name(), Some("trees"));
+    let repo = ClosureTreeRepository::<entity::trees_node::Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .ltree_column("path")
+            .unique_sibling_names(true)
+            .apply(config.clone()),
+    );
+    for index in repo.recommended_indexes() {
+        assert!(index.contains(r#" ON "trees"."#), "{index}");
+        db.execute_unprepared(&index).await?;
+    }
+    assert_eq!(repo.check_schema(&db).await?, []);
+
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    assert_eq!(repo.ancestors(&db, &leaf).await?.len(), 2);
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    assert_eq!(repo.descendants(&db, &a).await?.len(), 2);
+    assert_eq!(repo.descendants_query(&a).all(&db).await?.len(), 2);
+    assert!(repo.has_children(&db, &a).await?);
+    assert_eq!(repo.subtree_size(&db, &a).await?, 3);
+    assert!(repo.rebuild(&db).await?.is_empty());
+    let path: Option<String> = db
+        .query_one(Statement::from_string(
+            DbBackend::Postgres,
+            format!(
+                "SELECT path::text AS path FROM trees.nodes WHERE id = {}",
+                leaf.id
+            ),
+        ))
+        .await?
+        .expect("leaf row")
+        .try_get("", "path")?;
+    assert_eq!(path.as_deref(), Some("1.2.3"));
+    assert_eq!(
+        ClosureTreeRepository::<entity::node::Model>::new()
+            .node_count(&db)
+            .await?,
+        0
+    );
+
+    let findings = ClosureTreeRepository::<entity::trees_node::Model>::new()
+        .with_config(
+            ClosureTreeOptions::default()
+                .schema_name("elsewhere")
+                .apply(config.clone()),
+        )
+        .check_schema(&db)
+        .await?;
+    assert_eq!(
+        findings,
+        [SchemaFinding::InvalidConfig(
+            "schema Some(\"elsewhere\") is not the hierarchy entity's schema Some(\"trees\")"
+                .to_owned()
+        )]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ancestors_to_depth_returns_the_nearest_ancestors() -> Result<(), Box<dyn std::error::Error>>
+{
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let d = repo
+        .find_or_create_by_path(&db, &["root", "a", "b", "c", "d"])
+        .await?;
+    let names = |nodes: Vec<entity::node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+
+    assert_eq!(
+        names(repo.ancestors_to_depth(&db, &d, 2).await?),
+        ["c", "b"]
+    );
+    assert_eq!(
+        names(repo.ancestors_to_depth(&db, &d, 10).await?),
+        ["c", "b", "a", "root"]
+    );
+    assert!(repo.ancestors_to_depth(&db, &d, 0).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ancestor_directions_are_stable() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    // Names sort against the depth order, so neither direction can come from
+    // the name ordering.
+    let leaf = repo
+        .find_or_create_by_path(&db, &["z", "m", "a", "leaf"])
+        .await?;
+    let root = repo.find_by_path(&db, &["z"]).await?.expect("root exists");
+    let names = |nodes: Vec<entity::node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+
+    assert_eq!(
+        names(repo.ancestors_root_first(&db, &leaf).await?),
+        ["z", "m", "a"]
+    );
+    assert_eq!(
+        names(repo.ancestors_node_first(&db, &leaf).await?),
+        ["a", "m", "z"]
+    );
+    assert!(repo.ancestors_root_first(&db, &root).await?.is_empty());
+    assert!(repo.ancestors_node_first(&db, &root).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn generation_ranges_bound_ancestors_and_descendants(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let d = repo
+        .find_or_create_by_path(&db, &["a", "b", "c", "d"])
+        .await?;
+    repo.find_or_create_by_path(&db, &["a", "b", "x"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("root exists");
+    let names = |nodes: Vec<entity::node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+
+    let cases = [
+        (GenerationRange::Exactly(2), vec!["b"], vec!["c", "x"]),
+        (
+            GenerationRange::UpTo(2),
+            vec!["c", "b"],
+            vec!["b", "c", "x"],
+        ),
+        (
+            GenerationRange::Between(2, 3),
+            vec!["b", "a"],
+            vec!["c", "d", "x"],
+        ),
+        (GenerationRange::AtLeast(3), vec!["a"], vec!["d"]),
+        (
+            GenerationRange::AtLeast(0),
+            vec!["c", "b", "a"],
+            vec!["b", "c", "d", "x"],
+        ),
+        (GenerationRange::Between(3, 2), vec![], vec![]),
+    ];
+    for (range, ancestors, descendants) in cases {
+        assert_eq!(
+            names(repo.ancestors_within(&db, &d, range).await?),
+            ancestors,
+            "ancestors of d within {range:?}"
+        );
+        let mut found = names(repo.descendants_within(&db, &a, range).await?);
+        found.sort();
+        assert_eq!(found, descendants, "descendants of a within {range:?}");
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn filter_descendants_of_keeps_candidates_in_the_subtree(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let deep = repo
+        .find_or_create_by_path(&db, &["allowed", "team", "doc"])
+        .await?;
+    let outside = repo.find_or_create_by_path(&db, &["other", "doc"]).await?;
+    let allowed = repo
+        .find_by_path(&db, &["allowed"])
+        .await?
+        .expect("root exists");
+    let team = deep.parent_id.expect("doc has a parent");
+
+    let candidates = [outside.id, deep.id, allowed.id, team, 0];
+    assert_eq!(
+        repo.filter_descendants_of(&db, &allowed.id, &candidates)
+            .await?,
+        [deep.id, team]
+    );
+    assert!(repo
+        .filter_descendants_of(&db, &allowed.id, &[])
+        .await?
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reads_accept_transactions_and_separate_connections(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+    // Stands in for a read replica: a second pool that never writes.
+    let replica = setup_database().await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let txn = repo.lock(&db).await?;
+    let leaf = repo
+        .find_or_create_by_path_in(&txn, &["root", "leaf"])
+        .await?;
+    let root = repo
+        .find_by_path(txn.connection(), &["root"])
+        .await?
+        .expect("visible inside the transaction");
+    assert_eq!(
+        repo.descendants(txn.connection(), &root).await?,
+        vec![leaf.clone()]
+    );
+    assert!(repo.find_by_path(&replica, &["root"]).await?.is_none());
+    txn.commit().await?;
+
+    assert_eq!(repo.descendants(&replica, &root).await?, vec![leaf.clone()]);
+    assert_eq!(leaf.ancestors(&replica).await?, vec![root]);
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, FromQueryResult)]
+struct NodeLabel {
+    id: i32,
+    name: String,
+}
+
+#[tokio::test]
+async fn descendants_as_decodes_projections() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let b = repo
+        .find_or_create_by_path(&db, &["root", "a", "b"])
+        .await?;
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+
+    let labels: Vec<NodeLabel> = repo.descendants_as(&db, &root).await?;
+    let names: Vec<&str> = labels.iter().map(|label| label.name.as_str()).collect();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(labels[1].id, b.id);
+    assert!(repo.descendants_as::<NodeLabel>(&db, &b).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn numeric_order_strategy_sorts_both_directions() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let ascending = ClosureTreeRepository::<common::ascending_node::Model>::new();
+    let descending = ClosureTreeRepository::<common::descending_node::Model>::new();
+
+    for name in ["a", "b", "c"] {
+        ascending
+            .find_or_create_by_path(&db, &["root", name])
+            .await?;
+    }
+    db.execute_unprepared(
+        "UPDATE ordered_nodes SET position = CASE name WHEN 'a' THEN 2 ELSE 1 END",
+    )
+    .await?;
+
+    let root = ascending
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let names: Vec<String> = ascending
+        .children(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["b", "c", "a"]);
+
+    let root = descending
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let names: Vec<String> = descending
+        .children(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["a", "c", "b"]);
+
+    db.execute_unprepared("UPDATE ordered_nodes SET position = 7 WHERE name = 'root'")
+        .await?;
+    let found = ascending
+        .find_by_attribute_path(&db, common::ascending_node::Column::Position, &[7, 2])
+        .await?
+        .expect("a has position 2");
+    assert_eq!(found.name, "a");
+    assert!(ascending
+        .find_by_attribute_path(&db, common::ascending_node::Column::Position, &[7, 3])
+        .await?
+        .is_none());
+
+    let names: Vec<String> = descending
+        .descendants(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["a", "c", "b"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn dont_order_roots_keeps_roots_out_of_numeric_order(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let ranked = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    let tenants = ClosureTreeRepository::<entity::tenant_node::Model>::new();
+    ranked.find_or_create_by_path(&db, &["b"]).await?;
+    ranked.find_or_create_by_path(&db, &["a"]).await?;
+
+    let roots: Vec<(String, i32)> = ranked
+        .roots(&db)
+        .await?
+        .into_iter()
+        .map(|node| (node.name, node.position))
+        .collect();
+    assert_eq!(roots, vec![("b".into(), 0), ("a".into(), 1)]);
+
+    let c = tenants.find_or_create_by_path(&db, &["c"]).await?;
+    assert_eq!(c.position, 0, "roots are not assigned order values");
+    tenants.find_or_create_by_path(&db, &["a", "y"]).await?;
+    tenants.find_or_create_by_path(&db, &["a", "x"]).await?;
+
+    let names: Vec<String> = tenants
+        .roots(&db)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["a", "b", "c"]);
+    let names: Vec<String> = tenants
+        .siblings(&db, &c)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["a", "b"]);
+
+    let a = tenants.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let children: Vec<(String, i32)> = tenants
+        .children(&db, &a)
+        .await?
+        .into_iter()
+        .map(|node| (node.name, node.position))
+        .collect();
+    assert_eq!(children, vec![("y".into(), 0), ("x".into(), 1)]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn children_by_parent_groups_a_subtree_in_sibling_order(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    for path in [
+        ["root", "b", "y"],
+        ["root", "b", "x"],
+        ["root", "a", "z"],
+        ["other", "c", "w"],
+    ] {
+        repo.find_or_create_by_path(&db, &path).await?;
+    }
+    let z = repo
+        .find_by_path(&db, &["root", "a", "z"])
+        .await?
+        .expect("z exists");
+    let a = repo.parent(&db, &z).await?.expect("a exists");
+    let root = repo.parent(&db, &a).await?.expect("root exists");
+    let b = repo
+        .find_by_path(&db, &["root", "b"])
+        .await?
+        .expect("b exists");
+
+    let grouped = repo.children_by_parent(&db, &root).await?;
+    let names = |id| -> Vec<&str> {
+        grouped[&id]
+            .iter()
+            .map(|node: &entity::ranked_node::Model| node.name.as_str())
+            .collect()
+    };
+    assert_eq!(grouped.len(), 3);
+    assert_eq!(names(root.id), ["b", "a"]);
+    assert_eq!(names(b.id), ["y", "x"]);
+    assert_eq!(names(a.id), ["z"]);
+
+    assert!(repo.children_by_parent(&db, &z).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn move_up_and_down_swap_neighbouring_siblings() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    let mut nodes = Vec::new();
+    for name in ["a", "b", "c"] {
+        nodes.push(repo.find_or_create_by_path(&db, &["root", name]).await?);
+    }
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let child_names = |children: Vec<entity::ranked_node::Model>| -> Vec<String> {
+        children.into_iter().map(|node| node.name).collect()
+    };
+
+    assert!(repo.move_up(&db, &nodes[2]).await?);
+    assert_eq!(
+        child_names(repo.children(&db, &root).await?),
+        ["a", "c", "b"]
+    );
+    assert!(!repo.move_up(&db, &nodes[0]).await?);
+    assert!(!repo.move_down(&db, &nodes[1]).await?);
+    assert!(repo.move_down(&db, &nodes[0]).await?);
+    assert_eq!(
+        child_names(repo.children(&db, &root).await?),
+        ["c", "a", "b"]
+    );
+    assert!(!repo.move_up(&db, &root).await?, "only root");
+
+    let descending = ClosureTreeRepository::<common::descending_node::Model>::new();
+    let mut p = None;
+    for name in ["p", "q", "r"] {
+        p.get_or_insert(
+            descending
+                .find_or_create_by_path(&db, &["desc", name])
+                .await?,
+        );
+    }
+    let p = p.expect("p created");
+    assert!(descending.move_up(&db, &p).await?);
+    let parent = descending
+        .find_by_path(&db, &["desc"])
+        .await?
+        .expect("desc exists");
+    let names: Vec<String> = descending
+        .children(&db, &parent)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, ["r", "p", "q"]);
+
+    let tenants = ClosureTreeRepository::<entity::tenant_node::Model>::new();
+    let tenant_root = tenants
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let err = tenants.move_down(&db, &tenant_root).await.unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::NotOrderable { .. }),
+        "{err:?}"
+    );
+
+    // An order strategy dropped at runtime is still reported.
+    let unordered = ClosureTreeRepository::<entity::ranked_node::Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .order_strategy(OrderStrategy::Manual)
+            .apply(entity::ranked_node::Model::closure_tree_config().clone()),
+    );
+    let leaf = unordered.find_or_create_by_path(&db, &["x", "y"]).await?;
+    let err = unordered.move_up(&db, &leaf).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ClosureTreeError::UnsupportedOperation {
+                operation: "move_up",
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn order_scopes_decide_how_moves_renumber() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+    let positions = |nodes: Vec<entity::ranked_node::Model>| -> Vec<(String, i32)> {
+        nodes
+            .into_iter()
+            .map(|node| (node.name, node.position))
+            .collect()
+    };
+
+    // Per parent: the source closes its gap and the target appends.
+    let repo = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    for name in ["x", "y", "z"] {
+        repo.find_or_create_by_path(&db, &["a", name]).await?;
+    }
+    repo.find_or_create_by_path(&db, &["b", "p"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let b = repo.find_by_path(&db, &["b"]).await?.expect("b exists");
+    let y = repo
+        .find_by_path(&db, &["a", "y"])
+        .await?
+        .expect("y exists");
+    repo.graft(&db, &y.id, &b.id).await?;
+    assert_eq!(
+        positions(repo.children(&db, &a).await?),
+        [("x".into(), 0), ("z".into(), 1)]
+    );
+    assert_eq!(
+        positions(repo.children(&db, &b).await?),
+        [("p".into(), 0), ("y".into(), 1)]
+    );
+    // `reparent_preserving_order` leaves every position alone.
+    let x = repo
+        .find_by_path(&db, &["a", "x"])
+        .await?
+        .expect("x exists");
+    repo.reparent_preserving_order(&db, &x.id, Some(&b.id))
+        .await?;
+    assert_eq!(positions(repo.children(&db, &a).await?), [("z".into(), 1)]);
+    assert_eq!(
+        positions(repo.children(&db, &b).await?),
+        [("p".into(), 0), ("x".into(), 0), ("y".into(), 1)]
+    );
+
+    // Global: new nodes continue the tree's numbering and moves keep it.
+    truncate_tables(&db).await?;
+    let global = repo.clone().with_config(
+        ClosureTreeOptions::default()
+            .order_scope(OrderScope::Global)
+            .apply(entity::ranked_node::Model::closure_tree_config().clone()),
+    );
+    global.find_or_create_by_path(&db, &["g", "x"]).await?;
+    let y = global.find_or_create_by_path(&db, &["g", "y"]).await?;
+    let p = global.find_or_create_by_path(&db, &["h", "p"]).await?;
+    assert_eq!((y.position, p.position), (2, 4));
+    let g = global.find_by_path(&db, &["g"]).await?.expect("g exists");
+    let h = global.find_by_path(&db, &["h"]).await?.expect("h exists");
+    global.graft(&db, &y.id, &h.id).await?;
+    assert_eq!(
+        positions(global.children(&db, &g).await?),
+        [("x".into(), 1)]
+    );
+    assert_eq!(
+        positions(global.children(&db, &h).await?),
+        [("y".into(), 2), ("p".into(), 4)]
+    );
+    // The index of `move_to_child_of_at` is ignored.
+    global.move_to_child_of_at(&db, &p, &g.id, 0).await?;
+    assert_eq!(
+        positions(global.children(&db, &g).await?),
+        [("x".into(), 1), ("p".into(), 4)]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn move_to_child_of_at_inserts_at_the_given_slot() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    for name in ["a", "b", "c"] {
+        repo.find_or_create_by_path(&db, &["target", name]).await?;
+    }
+    let moved = repo.find_or_create_by_path(&db, &["source", "m"]).await?;
+    repo.find_or_create_by_path(&db, &["source", "m", "leaf"])
+        .await?;
+    let target = repo
+        .find_by_path(&db, &["target"])
+        .await?
+        .expect("target exists");
+    let child_names = |children: Vec<entity::ranked_node::Model>| -> Vec<String> {
+        children.into_iter().map(|node| node.name).collect()
+    };
+
+    repo.move_to_child_of_at(&db, &moved, &target.id, 1).await?;
+    assert_eq!(
+        child_names(repo.children(&db, &target).await?),
+        ["a", "m", "b", "c"]
+    );
+    assert!(repo
+        .find_by_path(&db, &["target", "m", "leaf"])
+        .await?
+        .is_some());
+    let positions: Vec<i32> = repo
+        .children(&db, &target)
+        .await?
+        .iter()
+        .map(|node| node.position)
+        .collect();
+    assert!(
+        positions.windows(2).all(|pair| pair[0] < pair[1]),
+        "{positions:?}"
+    );
+
+    // Out-of-range positions clamp to the ends.
+    repo.move_to_child_of_at(&db, &moved, &target.id, 99)
+        .await?;
+    assert_eq!(
+        child_names(repo.children(&db, &target).await?),
+        ["a", "b", "c", "m"]
+    );
+    repo.move_to_child_of_at(&db, &moved, &target.id, -1)
+        .await?;
+    assert_eq!(
+        child_names(repo.children(&db, &target).await?),
+        ["m", "a", "b", "c"]
+    );
+
+    // Without an order column the position is ignored.
+    let plain = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = plain.find_or_create_by_path(&db, &["x", "y"]).await?;
+    let onto = plain.find_or_create_by_path(&db, &["z"]).await?;
+    plain.move_to_child_of_at(&db, &leaf, &onto.id, 5).await?;
+    assert!(plain.find_by_path(&db, &["z", "y"]).await?.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn move_many_reparents_subtrees_in_one_transaction() -> Result<(), Box<dyn std::error::Error>>
+{
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let b = repo.find_or_create_by_path(&db, &["root", "b"]).await?;
+    repo.find_or_create_by_path(&db, &["root", "b", "leaf"])
+        .await?;
+    let c = repo.find_or_create_by_path(&db, &["root", "c"]).await?;
+    let folder = repo.find_or_create_by_path(&db, &["folder"]).await?;
+
+    repo.move_many(&db, &[b.id, c.id, b.id], Some(&folder.id))
+        .await?;
+    let folder_children: Vec<String> = repo
+        .children(&db, &folder)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(folder_children, ["b", "c"]);
+    let leaf = repo
+        .find_by_path(&db, &["folder", "b", "leaf"])
+        .await?
+        .expect("leaf moved with b");
+    assert_eq!(repo.ancestors(&db, &leaf).await?.len(), 2);
+
+    // A cycle anywhere rejects the whole batch before anything moves.
+    let err = repo
+        .move_many(&db, &[c.id, folder.id], Some(&leaf.id))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::CycleDetected { .. }),
+        "{err:?}"
+    );
+    assert!(repo.find_by_path(&db, &["folder", "c"]).await?.is_some());
+
+    repo.move_many(&db, &[b.id, c.id], None).await?;
+    assert_eq!(repo.roots_count(&db).await?, 4);
+    assert!(repo.find_by_path(&db, &["b", "leaf"]).await?.is_some());
+    assert!(repo.children(&db, &folder).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn order_strategies_apply_to_roots_and_siblings() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let by_name = ClosureTreeRepository::<common::name_desc_node::Model>::new();
+    for path in [["bb", "x"], ["a", "x"], ["ccc", "x"], ["dd", "x"]] {
+        by_name.find_or_create_by_path(&db, &path).await?;
+    }
+    let names: Vec<String> = by_name
+        .roots(&db)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["dd", "ccc", "bb", "a"]);
+
+    let by_length = ClosureTreeRepository::<common::name_length_node::Model>::new();
+    let bb = by_length
+        .find_by_path(&db, &["bb"])
+        .await?
+        .expect("bb exists");
+    let names: Vec<String> = by_length
+        .siblings(&db, &bb)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["ccc", "dd", "a"]);
+
+    let x = by_length
+        .find_by_path(&db, &["a", "x"])
+        .await?
+        .expect("x exists");
+    assert!(by_length.siblings(&db, &x).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn case_insensitive_name_matching() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<common::case_insensitive_node::Model>::new();
+    let exact = ClosureTreeRepository::<common::ascending_node::Model>::new();
+    let phones = repo
+        .find_or_create_by_path(&db, &["Electronics", "Phones"])
+        .await?;
+    let laptops = repo
+        .find_or_create_by_path(&db, &["electronics", "LAPTOPS"])
+        .await?;
+    assert_eq!(laptops.parent_id, phones.parent_id);
+    assert_eq!(laptops.name, "LAPTOPS");
+
+    let found = repo
+        .find_by_path(&db, &["ELECTRONICS", "phones"])
+        .await?
+        .expect("found regardless of case");
+    assert_eq!(found.id, phones.id);
+    assert!(exact
+        .find_by_path(&db, &["ELECTRONICS", "phones"])
+        .await?
+        .is_none());
+    assert_eq!(
+        repo.find_or_create_by_path(&db, &["eLeCtRoNiCs", "pHoNeS"])
+            .await?
+            .id,
+        phones.id
+    );
+    assert_eq!(repo.node_count(&db).await?, 3);
+
+    let err = repo.rename(&db, &laptops, "phones").await.unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::DuplicateName { .. }),
+        "{err:?}"
+    );
+    assert_eq!(repo.rename(&db, &laptops, "Laptops").await?.name, "Laptops");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn name_normalizer_applies_to_lookups_and_writes() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<common::slugged_node::Model>::new();
+    let cables = repo
+        .find_or_create_by_path(&db, &["Home Office ", "USB Cables"])
+        .await?;
+    assert_eq!(cables.name, "usb-cables");
+    assert_eq!(
+        repo.find_or_create_by_path(&db, &[" home   office", "usb-cables"])
+            .await?
+            .id,
+        cables.id
+    );
+    assert_eq!(repo.node_count(&db).await?, 2);
+
+    let found = repo
+        .find_by_path(&db, &["HOME OFFICE", "Usb Cables"])
+        .await?
+        .expect("found through the normalizer");
+    assert_eq!(found.id, cables.id);
+
+    let renamed = repo.rename(&db, &cables, " Power Strips ").await?;
+    assert_eq!(renamed.name, "power-strips");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn touch_updates_changed_nodes_and_their_ancestors() -> Result<(), Box<dyn std::error::Error>>
+{
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::touched_node::Model>::new();
+    let untyped = ClosureTreeRepository::<common::untyped_touch_node::Model>::new();
+    let b = repo.find_or_create_by_path(&db, &["a", "b"]).await?;
+    repo.find_or_create_by_path(&db, &["x"]).await?;
+
+    reset_touched(&db).await?;
+    repo.find_or_create_by_path(&db, &["a", "b", "c", "d"])
+        .await?;
+    assert_eq!(touched_names(&db).await?, ["a", "b"]);
+
+    reset_touched(&db).await?;
+    let c = untyped
+        .find_by_path(&db, &["a", "b", "c"])
+        .await?
+        .expect("c exists");
+    untyped.rename(&db, &c, "c2").await?;
+    assert_eq!(touched_names(&db).await?, ["a", "b", "c2"]);
+
+    reset_touched(&db).await?;
+    let c = repo
+        .find_by_path(&db, &["a", "b", "c2"])
+        .await?
+        .expect("c2 exists");
+    repo.destroy(&db, &c).await?;
+    assert_eq!(touched_names(&db).await?, ["a", "b"]);
+
+    reset_touched(&db).await?;
+    let b = repo.rename(&db, &b, "b2").await?;
+    repo.find_or_create_by_path(&db, &["x"]).await?;
+    assert_eq!(touched_names(&db).await?, ["a", "b2"]);
+
+    reset_touched(&db).await?;
+    let local = repo.clone().with_config(
+        ClosureTreeOptions::default()
+            .touch_ancestors(false)
+            .apply(entity::touched_node::Model::closure_tree_config().clone()),
+    );
+    local.rename(&db, &b, "b3").await?;
+    local.find_or_create_by_path(&db, &["a", "e"]).await?;
+    assert_eq!(touched_names(&db).await?, ["b3"]);
+
+    Ok(())
+}
+
+async fn reset_touched(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr> {
+    db.execute_unprepared("UPDATE ordered_nodes SET touched_at = NULL")
+        .await?;
+    Ok(())
+}
+
+async fn touched_names(db: &DatabaseConnection) -> Result<Vec<String>, sea_orm::DbErr> {
+    db.query_all(Statement::from_string(
+        DbBackend::Postgres,
+        "SELECT name FROM ordered_nodes WHERE touched_at IS NOT NULL ORDER BY name",
+    ))
+    .await?
+    .iter()
+    .map(|row| row.try_get("", "name"))
+    .collect()
+}
+
+#[tokio::test]
+async fn children_counts_follow_writes() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::counted_node::Model>::new();
+    let b = repo.find_or_create_by_path(&db, &["a", "b"]).await?;
+    repo.find_or_create_by_path(&db, &["a", "c", "d"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    repo.import_tree(
+        &db,
+        &TreeInput::new("e")
+            .child(TreeInput::new("f"))
+            .child(TreeInput::new("g")),
+        Some(&a.id),
+    )
+    .await?;
+    repo.duplicate_subtree(&db, &a, None).await?;
+    assert_eq!(
+        children_counts(&db).await?,
+        [
+            "a:3", "a:3", "b:0", "b:0", "c:1", "c:1", "d:0", "d:0", "e:2", "e:2", "f:0", "f:0",
+            "g:0", "g:0"
+        ]
+    );
+
+    // Nullify turns `d` into a root.
+    let c = repo
+        .children(&db, &a)
+        .await?
+        .into_iter()
+        .find(|child| child.name == "c")
+        .expect("c exists");
+    repo.destroy(&db, &c).await?;
+    repo.destroy(&db, &b).await?;
+    let a = entity::counted_node::Entity::find_by_id(a.id)
+        .one(&db)
+        .await?
+        .expect("a exists");
+    assert_eq!(a.children_count, 1);
+
+    db.execute_unprepared("UPDATE ordered_nodes SET children_count = 42")
+        .await?;
+    assert_eq!(repo.recount_children(&db).await?, 12);
+    assert_eq!(
+        children_counts(&db).await?,
+        ["a:1", "a:3", "b:0", "c:1", "d:0", "d:0", "e:2", "e:2", "f:0", "f:0", "g:0", "g:0"]
+    );
+
+    Ok(())
+}
+
+async fn children_counts(db: &DatabaseConnection) -> Result<Vec<String>, sea_orm::DbErr> {
+    db.query_all(Statement::from_string(
+        DbBackend::Postgres,
+        "SELECT name, children_count FROM ordered_nodes ORDER BY name, children_count",
+    ))
+    .await?
+    .iter()
+    .map(|row| {
+        let name: String = row.try_get("", "name")?;
+        let count: i32 = row.try_get("", "children_count")?;
+        Ok(format!("{name}:{count}"))
+    })
+    .collect()
+}
+
+#[tokio::test]
+async fn scoped_repositories_keep_tenants_apart() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::scoped_node::Model>::new();
+    let first = repo.scoped(1)?;
+    let second = repo.scoped(2)?;
+    let b1 = first.find_or_create_by_path(&db, &["a", "b"]).await?;
+    let b2 = second.find_or_create_by_path(&db, &["a", "b"]).await?;
+    assert_ne!(b1.id, b2.id);
+    assert_eq!((b1.tenant_id, b2.tenant_id), (1, 2));
+    second.import_tree(&db, &TreeInput::new("c"), None).await?;
+
+    let names = |nodes: Vec<entity::scoped_node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+    assert_eq!(names(first.roots(&db).await?), ["a"]);
+    assert_eq!(names(second.roots(&db).await?), ["a", "c"]);
+    assert_eq!(names(repo.roots(&db).await?), ["a", "a", "c"]);
+    assert!(first.find_by_path(&db, &["c"]).await?.is_none());
+    assert_eq!(
+        first
+            .find_by_path(&db, &["a", "b"])
+            .await?
+            .map(|node| node.id),
+        Some(b1.id)
+    );
+
+    let root = second.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let copy = second.duplicate_subtree(&db, &root, None).await?;
+    assert_eq!(copy.tenant_id, 2);
+    assert_eq!(
+        second
+            .descendants(&db, &copy)
+            .await?
+            .iter()
+            .map(|node| node.tenant_id)
+            .collect::<Vec<_>>(),
+        [2]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn clear_empties_one_scope_or_the_whole_table() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::scoped_node::Model>::new();
+    repo.scoped(1)?
+        .find_or_create_by_path(&db, &["a", "b", "c"])
+        .await?;
+    let kept = repo
+        .scoped(2)?
+        .find_or_create_by_path(&db, &["a", "b"])
+        .await?;
+
+    repo.scoped(1)?.clear(&db).await?;
+    assert!(repo.scoped(1)?.roots(&db).await?.is_empty());
+    assert_eq!(repo.node_count(&db).await?, 2);
+    assert_eq!(repo.scoped(2)?.subtree_size(&db, &kept).await?, 1);
+    assert_eq!(
+        common::ordered_node_hierarchy::Entity::find()
+            .count(&db)
+            .await?,
+        3
+    );
+
+    repo.clear(&db).await?;
+    assert_eq!(repo.node_count(&db).await?, 0);
+    assert_eq!(
+        common::ordered_node_hierarchy::Entity::find()
+            .count(&db)
+            .await?,
+        0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn scoped_needs_a_scope_column() -> Result<(), Box<dyn std::error::Error>> {
+    let unscoped = ClosureTreeRepository::<entity::node::Model>::new();
+    assert!(matches!(
+        unscoped.scoped(1),
+        Err(ClosureTreeError::UnsupportedOperation {
+            operation: "scoped",
+            ..
+        })
+    ));
+
+    // A config without the column swapped in afterwards fails the write
+    // instead of creating a node outside every scope.
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+    let config = closure_tree::ClosureTreeConfig::new("scoped_nodes", "scoped_node_hierarchies");
+    let repo = ClosureTreeRepository::<entity::scoped_node::Model>::new()
+        .scoped(1)?
+        .with_config(config);
+    assert!(matches!(
+        repo.find_or_create_by_path(&db, &["a"]).await,
+        Err(ClosureTreeError::UnsupportedOperation {
+            operation: "create",
+            ..
+        })
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn scoped_writes_lock_the_scope_by_its_sql_literal() -> Result<(), Box<dyn std::error::Error>>
+{
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let scoped_key = entity::scoped_node::Model::closure_tree_config()
+        .advisory_lock_strategy()
+        .key()
+        .expect("derived key")
+        .scoped("scope:1");
+    let holder = setup_database().await?;
+    let holder_txn = holder.begin().await?;
+    holder_txn
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_advisory_xact_lock($1)",
+            [scoped_key.as_i64().expect("numeric key").into()],
+        ))
+        .await?;
+
+    let repo = ClosureTreeRepository::<entity::scoped_node::Model>::new()
+        .with_lock_acquisition(LockAcquisition::try_for(Duration::from_millis(200)));
+    repo.scoped(2)?.find_or_create_by_path(&db, &["a"]).await?;
+    let err = repo
+        .scoped(1)?
+        .find_or_create_by_path(&db, &["a"])
+        .await
+        .expect_err("scope 1 is locked elsewhere");
+    assert!(matches!(
+        err,
+        ClosureTreeError::LockTimeout { ref key, .. } if *key == scoped_key.to_string()
+    ));
+    holder_txn.rollback().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn soft_destroy_hides_subtrees_until_restored() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::soft_node::Model>::new();
+    let c = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    repo.find_or_create_by_path(&db, &["a", "d"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let b = repo
+        .find_by_path(&db, &["a", "b"])
+        .await?
+        .expect("b exists");
+
+    let report = repo.soft_destroy(&db, &b).await?;
+    let mut deleted = report.deleted.clone();
+    deleted.sort();
+    assert_eq!(deleted, [b.id, c.id]);
+
+    // The path to `c` runs through the soft-deleted `b`.
+    assert!(repo.find_by_path(&db, &["a", "b", "c"]).await?.is_none());
+    assert!(repo.find_by_path(&db, &["a", "b"]).await?.is_none());
+    let names = |nodes: Vec<entity::soft_node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+    assert_eq!(names(repo.children(&db, &a).await?), ["d"]);
+    assert_eq!(names(repo.descendants(&db, &a).await?), ["d"]);
+    assert_eq!(repo.subtree_size(&db, &a).await?, 2);
+    assert_eq!(repo.node_count(&db).await?, 2);
+    assert_eq!(repo.count_children(&db, &a).await?, 1);
+    let a = entity::soft_node::Entity::find_by_id(a.id)
+        .one(&db)
+        .await?
+        .expect("a exists");
+    assert_eq!(a.children_count, 1);
+
+    let admin = repo.clone().with_deleted(true);
+    let hidden = admin
+        .find_by_path(&db, &["a", "b", "c"])
+        .await?
+        .expect("c is still stored");
+    assert!(hidden.deleted_at.is_some());
+    assert_eq!(admin.subtree_size(&db, &a).await?, 4);
+
+    assert_eq!(repo.restore_subtree(&db, &b).await?, 2);
+    assert_eq!(
+        repo.find_by_path(&db, &["a", "b", "c"])
+            .await?
+            .map(|node| node.id),
+        Some(c.id)
+    );
+    assert_eq!(names(repo.descendants(&db, &a).await?), ["b", "c", "d"]);
+    assert_eq!(children_counts(&db).await?, ["a:2", "b:1", "c:0", "d:0"]);
+
+    let err = ClosureTreeRepository::<entity::counted_node::Model>::new()
+        .soft_destroy(
+            &db,
+            &entity::counted_node::Model {
+                id: a.id,
+                parent_id: None,
+                name: a.name.clone(),
+                position: a.position,
+                children_count: a.children_count,
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::UnsupportedOperation { .. }),
+        "{err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn graft_attaches_roots_and_moves_attached_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let b = repo.find_or_create_by_path(&db, &["a", "b"]).await?;
+    let y = repo.find_or_create_by_path(&db, &["x", "y"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let x = repo.find_by_path(&db, &["x"]).await?.expect("x exists");
+    let names = |nodes: Vec<entity::node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+
+    repo.graft(&db, &x.id, &b.id).await?;
+    assert_eq!(names(repo.roots(&db).await?), ["a"]);
+    assert_eq!(names(repo.ancestors(&db, &y).await?), ["x", "b", "a"]);
+
+    // `x` now has ancestors, so this is a general move.
+    repo.graft(&db, &x.id, &a.id).await?;
+    assert_eq!(names(repo.ancestors(&db, &y).await?), ["x", "a"]);
+    assert_eq!(
+        names(repo.descendants(&db, &b).await?),
+        Vec::<String>::new()
+    );
+    assert_eq!(repo.subtree_size(&db, &a).await?, 4);
+
+    let err = repo.graft(&db, &a.id, &y.id).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ClosureTreeError::CycleDetected { ref node_id, ref new_parent_id }
+                if *node_id == a.id.to_string() && *new_parent_id == y.id.to_string()
+        ),
+        "{err:?}"
+    );
+    let err = repo.graft(&db, &x.id, &-1).await.unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::NodeNotFound { .. }),
+        "{err:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recursive_cte_ancestors_match_the_hierarchy_table(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let recursive = repo.clone().with_config(
+        ClosureTreeOptions::default()
+            .prefer_recursive_cte(true)
+            .apply(entity::node::Model::closure_tree_config().clone()),
+    );
+    let leaf = repo
+        .find_or_create_by_path(&db, &["a", "b", "c", "d"])
+        .await?;
+    let root = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+
+    assert_eq!(
+        recursive.ancestors(&db, &leaf).await?,
+        repo.ancestors(&db, &leaf).await?
+    );
+    assert_eq!(
+        recursive.ancestors_root_first(&db, &leaf).await?,
+        repo.ancestors_root_first(&db, &leaf).await?
+    );
+    for range in [
+        GenerationRange::Exactly(2),
+        GenerationRange::Between(2, 3),
+        GenerationRange::UpTo(1),
+        GenerationRange::AtLeast(3),
+    ] {
+        assert_eq!(
+            recursive.ancestors_within(&db, &leaf, range).await?,
+            repo.ancestors_within(&db, &leaf, range).await?,
+            "{range:?}"
+        );
+    }
+    assert_eq!(recursive.ancestry_path_str(&db, &leaf).await?, "a/b/c/d");
+    assert!(recursive.ancestors(&db, &root).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn with_config_overrides_the_static_config() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let tuned = ClosureTreeRepository::<entity::node::Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .order_strategy(OrderStrategy::ByName {
+                direction: OrderDirection::Desc,
+            })
+            .unique_sibling_names(true)
+            .apply(entity::node::Model::closure_tree_config().clone()),
+    );
+    for name in ["a", "b", "c"] {
+        repo.find_or_create_by_path(&db, &["root", name]).await?;
+    }
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let names = |children: Vec<entity::node::Model>| -> Vec<String> {
+        children.into_iter().map(|node| node.name).collect()
+    };
+    assert_eq!(names(repo.children(&db, &root).await?), ["a", "b", "c"]);
+    assert_eq!(names(tuned.children(&db, &root).await?), ["c", "b", "a"]);
+
+    let a = repo
+        .find_by_path(&db, &["root", "a"])
+        .await?
+        .expect("a exists");
+    let err = tuned.rename(&db, &a, "b").await.unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::DuplicateName { .. }),
+        "{err:?}"
+    );
+    assert_eq!(repo.rename(&db, &a, "b").await?.name, "b");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn numeric_order_values_are_assigned_on_insert() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let ranked = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    for name in ["c", "a", "b"] {
+        ranked.find_or_create_by_path(&db, &["root", name]).await?;
+    }
+    let root = ranked
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let children: Vec<(String, i32)> = ranked
+        .children(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| (node.name, node.position))
+        .collect();
+    assert_eq!(
+        children,
+        vec![("c".into(), 0), ("a".into(), 1), ("b".into(), 2)]
+    );
+
+    truncate_tables(&db).await?;
+    let gapped = ClosureTreeRepository::<common::ascending_node::Model>::new();
+    let root = gapped
+        .import_tree(
+            &db,
+            &TreeInput::new("root")
+                .child(TreeInput::new("y"))
+                .child(TreeInput::new("x")),
+            None,
+        )
+        .await?;
+    gapped.find_or_create_by_path(&db, &["root", "w"]).await?;
+    let children: Vec<(String, i32)> = gapped
+        .children(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| (node.name, node.position))
+        .collect();
+    assert_eq!(
+        children,
+        vec![("y".into(), 0), ("x".into(), 10), ("w".into(), 20)]
+    );
+
+    let copy = gapped.duplicate_subtree(&db, &root, None).await?;
+    assert_eq!((root.position, copy.position), (0, 10));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ltree_paths_follow_the_hierarchy() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<common::ltree_node::Model>::new();
+    let root = repo
+        .import_tree(
+            &db,
+            &TreeInput::new("root")
+                .child(TreeInput::new("a").child(TreeInput::new("b")))
+                .child(TreeInput::new("c")),
+            None,
+        )
+        .await?;
+    repo.find_or_create_by_path(&db, &["root", "a", "d"])
+        .await?;
+    assert_eq!(
+        ltree_paths(&db).await?,
+        vec![
+            ("root".into(), "1".into()),
+            ("a".into(), "1.2".into()),
+            ("c".into(), "1.3".into()),
+            ("b".into(), "1.2.4".into()),
+            ("d".into(), "1.2.5".into()),
+        ]
+    );
+
+    let a = repo
+        .find_by_path(&db, &["root", "a"])
+        .await?
+        .expect("a exists");
+    let names: Vec<String> = repo
+        .descendants(&db, &a)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["b", "d"]);
+    assert_eq!(repo.self_and_descendants(&db, &root).await?.len(), 5);
+
+    repo.destroy(&db, &a).await?;
+    db.execute_unprepared("UPDATE ordered_nodes SET path = NULL WHERE name = 'c'")
+        .await?;
+    repo.rebuild(&db).await?;
+    assert_eq!(
+        ltree_paths(&db).await?,
+        vec![
+            ("root".into(), "1".into()),
+            ("b".into(), "4".into()),
+            ("d".into(), "5".into()),
+            ("c".into(), "1.3".into()),
+        ]
+    );
+
+    Ok(())
+}
+
+async fn ltree_paths(
+    db: &DatabaseConnection,
+) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let rows = db
+        .query_all(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT name, path::text AS path FROM ordered_nodes ORDER BY nlevel(path), id",
+        ))
+        .await?;
+    let mut paths = Vec::new();
+    for row in rows {
+        paths.push((row.try_get("", "name")?, row.try_get("", "path")?));
+    }
+    Ok(paths)
+}
+
+#[tokio::test]
+async fn unique_sibling_names_are_checked_on_write() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<common::unique_siblings_node::Model>::new();
+    let a = repo.find_or_create_by_path(&db, &["root", "a"]).await?;
+    repo.find_or_create_by_path(&db, &["root", "b"]).await?;
+    repo.find_or_create_by_path(&db, &["other", "c"]).await?;
+
+    let err = repo.rename(&db, &a, "b").await.unwrap_err();
+    assert!(
+        matches!(&err, ClosureTreeError::DuplicateName { name, .. } if name == "b"),
+        "unexpected error: {err:?}"
+    );
+    assert_eq!(repo.rename(&db, &a, "a").await?.name, "a");
+    let renamed = repo.rename(&db, &a, "c").await?;
+    assert_eq!(renamed.name, "c");
+    assert_eq!(
+        repo.find_by_path(&db, &["root", "c"])
+            .await?
+            .map(|node| node.id),
+        Some(a.id)
+    );
+
+    let err = repo
+        .import_tree(&db, &TreeInput::new("other"), None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, ClosureTreeError::DuplicateName { parent_id: None, name } if name == "other"),
+        "unexpected error: {err:?}"
+    );
+    let err = repo
+        .import_tree(
+            &db,
+            &TreeInput::new("fresh")
+                .child(TreeInput::new("twin"))
+                .child(TreeInput::new("twin")),
+            None,
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, ClosureTreeError::DuplicateName { name, .. } if name == "twin"),
+        "unexpected error: {err:?}"
+    );
+    assert!(repo.find_by_path(&db, &["fresh"]).await?.is_none());
+    let err = repo
+        .duplicate_subtree(&db, &renamed, renamed.parent_id.as_ref())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, ClosureTreeError::DuplicateName { .. }),
+        "{err:?}"
+    );
+
+    let plain = ClosureTreeRepository::<entity::node::Model>::new();
+    let x = plain.find_or_create_by_path(&db, &["x"]).await?;
+    plain.find_or_create_by_path(&db, &["y"]).await?;
+    assert_eq!(plain.rename(&db, &x, "y").await?.name, "y");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn try_lock_times_out_while_lock_is_held() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let key = common::lock_timeout_node::Model::closure_tree_config()
+        .advisory_lock_strategy()
+        .key()
+        .and_then(|key| key.as_i64())
+        .expect("derived numeric key");
+    assert_eq!(
+        key,
+        i64::from(crc32fast::hash(b"OrderedNode/OrderedNodeHierarchy"))
+    );
+
+    let holder = setup_database().await?;
+    let holder_txn = holder.begin().await?;
+    holder_txn
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_advisory_lock($1)",
+            [key.into()],
+        ))
+        .await?;
+
+    let repo = ClosureTreeRepository::<common::lock_timeout_node::Model>::new();
+    let err = repo
+        .find_or_create_by_path(&db, &["root"])
+        .await
+        .expect_err("lock is held elsewhere");
+    assert!(matches!(
+        err,
+        ClosureTreeError::LockTimeout { key: ref timed_out, waited }
+            if *timed_out == key.to_string() && waited >= Duration::from_millis(200)
+    ));
+
+    holder_txn
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_advisory_unlock($1)",
+            [key.into()],
+        ))
+        .await?;
+    holder_txn.commit().await?;
+
+    let root = repo.find_or_create_by_path(&db, &["root"]).await?;
+    assert_eq!(root.name, "root");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_lock_spans_transactions_on_its_connection(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let key = common::lock_timeout_node::Model::closure_tree_config()
+        .advisory_lock_strategy()
+        .key()
+        .expect("derived key");
+    let session = SessionLockGuard::acquire(key, &db).await?;
+    let repo = ClosureTreeRepository::<common::lock_timeout_node::Model>::new();
+
+    repo.find_or_create_by_path(session.connection(), &["first"])
+        .await?;
+    repo.find_or_create_by_path(session.connection(), &["second"])
+        .await?;
+
+    let err = repo
+        .find_or_create_by_path(&db, &["elsewhere"])
+        .await
+        .expect_err("session lock is held");
+    assert!(matches!(err, ClosureTreeError::LockTimeout { .. }));
+
+    session.release().await?;
+    repo.find_or_create_by_path(&db, &["elsewhere"]).await?;
+    assert_eq!(repo.roots(&db).await?.len(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn check_schema_reports_missing_and_mistyped_columns(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+
+    let ranked = ClosureTreeRepository::<entity::ranked_node::Model>::new();
+    assert_eq!(ranked.check_schema(&db).await?, []);
+    let soft = ClosureTreeRepository::<entity::soft_node::Model>::new();
+    assert_eq!(soft.check_schema(&db).await?, []);
+
+    let broken = ClosureTreeRepository::<entity::ranked_node::Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .touch("missing_at")
+            .ltree_column("name")
+            .soft_delete_column("position")
+            .apply(entity::ranked_node::Model::closure_tree_config().clone()),
+    );
+    let findings: Vec<String> = broken
+        .check_schema(&db)
+        .await?
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        findings,
+        [
+            "invalid configuration: `name` is both the name and the ltree column",
+            "invalid configuration: `position` is both the order and the soft-delete column",
+            "ltree column `ordered_nodes.name` has type `text`, expected `ltree`",
+            "touch column `ordered_nodes.missing_at` does not exist",
+            "soft-delete column `ordered_nodes.position` has type `integer`, expected a timestamp, date or text type",
+            "soft-delete column `ordered_nodes.position` must be nullable",
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn lock_overrides_apply_per_repository() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let held = repo.lock(&db).await?;
+
+    let err = repo
+        .clone()
+        .with_lock_acquisition(LockAcquisition::try_for(Duration::from_millis(100)))
+        .find_or_create_by_path(&db, &["impatient"])
+        .await
+        .expect_err("the lock is held");
+    assert!(matches!(err, ClosureTreeError::LockTimeout { .. }));
+
+    repo.clone()
+        .with_lock_strategy(AdvisoryLockStrategy::Disabled)
+        .find_or_create_by_path(&db, &["unlocked"])
+        .await?;
+    held.commit().await?;
+    assert_eq!(repo.roots(&db).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn locked_transaction_reports_the_default_key_as_text(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+
+    let config = entity::node::Model::closure_tree_config();
+    let strategy = config.advisory_lock_strategy();
+    let key = strategy.key().expect("derived key");
+    let value = key.as_i64().expect("derived keys are numeric");
+    let locked = LockedTransaction::acquire(strategy, &db).await?;
+    assert_eq!(
+        locked.key_str().as_deref(),
+        Some(value.to_string().as_str())
+    );
+    assert_eq!(locked.key_str().as_deref(), Some(key.to_string().as_str()));
+    locked.rollback().await?;
+
+    let disabled = LockedTransaction::acquire(&AdvisoryLockStrategy::Disabled, &db).await?;
+    assert_eq!(disabled.key_str(), None);
+    disabled.rollback().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn locked_transaction_reports_held_key() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+
+    let config = entity::node::Model::closure_tree_config();
+    let strategy = config.advisory_lock_strategy();
+    let locked = LockedTransaction::acquire_scoped(
+        strategy,
+        Some("segment:root"),
+        config.lock_acquisition(),
+        &db,
+    )
+    .await?;
+    assert!(locked.is_locked());
+    let scoped = strategy.key().expect("derived key").scoped("segment:root");
+    assert_eq!(locked.key(), Some(&scoped));
+
+    let txn = locked.into_transaction();
+    let row = txn
+        .query_one(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_try_advisory_xact_lock($1) AS locked",
+            [scoped.as_i64().expect("numeric key").into()],
+        ))
+        .await?
+        .expect("row");
+    assert!(row.try_get::<bool>("", "locked")?);
+    txn.commit().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn nested_operations_reuse_held_locks() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<common::scoped_lock_node::Model>::new();
+    let txn = repo.lock(&db).await?;
+
+    // The table-wide exclusive lock covers the shared one the scoped operations need,
+    // so only their per-tree locks are added.
+    repo.find_or_create_by_path_in(&txn, &["tenant-a", "child"])
+        .await?;
+    let leaf = repo
+        .find_or_create_by_path_in(&txn, &["tenant-a", "child", "leaf"])
+        .await?;
+    repo.destroy_in(&txn, &leaf).await?;
+
+    let row = txn
+        .connection()
+        .query_one(Statement::from_string(
+            DbBackend::Postgres,
+            "SELECT COUNT(*) FILTER (WHERE mode = 'ExclusiveLock') AS exclusive, \
+                    COUNT(*) FILTER (WHERE mode = 'ShareLock') AS shared \
+             FROM pg_locks \
+             WHERE locktype = 'advisory' AND granted AND pid = pg_backend_pid()",
+        ))
+        .await?
+        .expect("row");
+    assert_eq!(row.try_get::<i64>("", "exclusive")?, 2);
+    assert_eq!(row.try_get::<i64>("", "shared")?, 0);
+    txn.commit().await?;
+
+    let root = repo
+        .find_by_path(&db, &["tenant-a"])
+        .await?
+        .expect("root exists");
+    assert_eq!(repo.subtree_size(&db, &root).await?, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn locked_mutations_and_own_writes_roll_back_together(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let txn = repo.lock(&db).await?;
+    assert!(txn.is_locked());
+
+    let root = repo
+        .import_tree_in(
+            &txn,
+            &TreeInput::new("root").child(TreeInput::new("a")),
+            None,
+        )
+        .await?;
+    repo.duplicate_subtree_in(&txn, &root, None).await?;
+    txn.connection()
+        .execute_unprepared("UPDATE nodes SET name = 'renamed' WHERE parent_id IS NULL")
+        .await?;
+    assert!(repo.rebuild_in(&txn).await?.is_empty());
+    let renamed = entity::node::Entity::find()
+        .filter(entity::node::Column::Name.eq("renamed"))
+        .count(txn.connection())
+        .await?;
+    assert_eq!(renamed, 2);
+    txn.rollback().await?;
+
+    assert_eq!(repo.node_count(&db).await?, 0);
+
+    Ok(())
+}
 
-        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-        pub enum Relation {}
+#[derive(Default)]
+struct RecordingObserver {
+    events: std::sync::Mutex<Vec<String>>,
+    lock_waits: std::sync::Mutex<usize>,
+}
 
-        impl ActiveModelBehavior for ActiveModel {}
+impl RecordingObserver {
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.events.lock().unwrap())
     }
+}
 
-    pub mod node_hierarchy {
-        use sea_orm::entity::prelude::*;
+impl TreeObserver<i32> for RecordingObserver {
+    fn on_node_created(&self, entity: &str, id: &i32) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("{entity} created {id}"));
+    }
 
-        #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
-        #[sea_orm(table_name = "node_hierarchies")]
-        pub struct Model {
-            #[sea_orm(primary_key)]
-            pub ancestor_id: i32,
-            #[sea_orm(primary_key)]
-            pub descendant_id: i32,
-            pub generations: i32,
-        }
+    fn on_node_moved(&self, _entity: &str, id: &i32, from: Option<&i32>, to: Option<&i32>) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("moved {id} {from:?} -> {to:?}"));
+    }
 
-        #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-        pub enum Relation {}
+    fn on_subtree_deleted(&self, _entity: &str, root: &i32, deleted: &[i32]) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("deleted {root} {deleted:?}"));
+    }
 
-        impl ActiveModelBehavior for ActiveModel {}
+    fn on_lock_wait(&self, _entity: &str, _waited: Duration) {
+        *self.lock_waits.lock().unwrap() += 1;
     }
 }
 
 #[tokio::test]
-async fn find_or_create_path_builds_hierarchy() -> Result<(), Box<dyn std::error::Error>> {
+async fn observer_sees_committed_changes_only() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let observer = Arc::new(RecordingObserver::default());
+    let repo = ClosureTreeRepository::<entity::node::Model>::new().with_observer(observer.clone());
+
+    let txn = repo.lock(&db).await?;
+    repo.import_tree_in(&txn, &TreeInput::new("discarded"), None)
+        .await?;
+    txn.rollback().await?;
+    assert!(observer.take().is_empty());
+
+    let txn = repo.lock(&db).await?;
+    let kept = repo.find_or_create_by_path_in(&txn, &["kept"]).await?;
+    assert!(observer.take().is_empty(), "reported before commit");
+    txn.commit().await?;
+    assert_eq!(observer.take(), vec![format!("Model created {}", kept.id)]);
+
+    let leaf = repo.find_or_create_by_path(&db, &["kept", "leaf"]).await?;
+    assert_eq!(observer.take(), vec![format!("Model created {}", leaf.id)]);
+
+    repo.destroy(&db, &kept).await?;
+    assert_eq!(
+        observer.take(),
+        vec![
+            format!("moved {} Some({}) -> None", leaf.id, kept.id),
+            format!("deleted {0} [{0}]", kept.id),
+        ]
+    );
+    assert_eq!(*observer.lock_waits.lock().unwrap(), 4);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn per_first_segment_scope_only_blocks_the_same_tree(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let scoped_key = common::scoped_lock_node::Model::closure_tree_config()
+        .advisory_lock_strategy()
+        .key()
+        .expect("derived key")
+        .scoped("segment:tenant-a");
+
+    let holder = setup_database().await?;
+    let holder_txn = holder.begin().await?;
+    holder_txn
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_advisory_xact_lock($1)",
+            [scoped_key.as_i64().expect("numeric key").into()],
+        ))
+        .await?;
+
+    let repo = ClosureTreeRepository::<common::scoped_lock_node::Model>::new();
+    let other = repo
+        .find_or_create_by_path(&db, &["tenant-b", "child"])
+        .await?;
+    assert_eq!(other.name, "child");
+
+    let err = repo
+        .find_or_create_by_path(&db, &["tenant-a", "child"])
+        .await
+        .expect_err("tree is locked elsewhere");
+    assert!(matches!(
+        err,
+        ClosureTreeError::LockTimeout { ref key, .. } if *key == scoped_key.to_string()
+    ));
+
+    holder_txn.rollback().await?;
+
+    let node = repo
+        .find_or_create_by_path(&db, &["tenant-a", "child"])
+        .await?;
+    assert_eq!(node.name, "child");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn destroy_dry_run_reports_without_deleting() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
     let db = setup_database().await?;
     truncate_tables(&db).await?;
 
     let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "a", "b"])
+        .await?;
+    let a = repo
+        .find_by_path(&db, &["root", "a"])
+        .await?
+        .expect("a exists");
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+
+    let preview = ClosureTreeRepository::<entity::node::Model>::new()
+        .with_dry_run(true)
+        .destroy(&db, &a)
+        .await?;
+    assert!(preview.dry_run);
+    assert_eq!(preview.deleted, vec![a.id]);
+    assert_eq!(preview.reparented, vec![leaf.id]);
+    assert_eq!(repo.subtree_size(&db, &root).await?, 3);
+
+    let report = repo.destroy(&db, &a).await?;
+    assert!(!report.dry_run);
+    assert_eq!(report.deleted, preview.deleted);
+    assert_eq!(repo.subtree_size(&db, &root).await?, 1);
+    let roots: Vec<String> = repo
+        .roots(&db)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(roots, vec!["b", "root"]);
+    assert!(repo.rebuild(&db).await?.is_empty());
+
+    let repo = ClosureTreeRepository::<common::destroying_node::Model>::new();
+    let leaf = repo.find_or_create_by_path(&db, &["x", "y", "z"]).await?;
+    let x = repo.find_by_path(&db, &["x"]).await?.expect("x exists");
+
+    let preview = ClosureTreeRepository::<common::destroying_node::Model>::new()
+        .with_dry_run(true)
+        .destroy(&db, &x)
+        .await?;
+    assert_eq!(preview.deleted.first(), Some(&leaf.id));
+    assert_eq!(preview.deleted.len(), 3);
+    assert_eq!(repo.roots(&db).await?.len(), 1);
+
+    repo.destroy(&db, &x).await?;
+    assert!(repo.roots(&db).await?.is_empty());
 
+    Ok(())
+}
+
+#[tokio::test]
+async fn rebuild_restores_missing_hierarchy_rows() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
     let leaf = repo
         .find_or_create_by_path(&db, &["root", "child", "leaf"])
         .await?;
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM node_hierarchies WHERE descendant_id = $1 AND generations > 0",
+        [leaf.id.into()],
+    ))
+    .await?;
 
-    assert_eq!(leaf.name, "leaf");
+    let preview = ClosureTreeRepository::<entity::node::Model>::new()
+        .with_dry_run(true)
+        .rebuild(&db)
+        .await?;
+    assert_eq!(preview.rebuilt, vec![leaf.id]);
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    assert_eq!(repo.subtree_size(&db, &root).await?, 2);
 
-    let child = repo
-        .find_by_path(&db, &["root", "child"])
+    assert_eq!(repo.rebuild(&db).await?.rebuilt, vec![leaf.id]);
+    assert_eq!(repo.subtree_size(&db, &root).await?, 3);
+    assert!(repo.rebuild(&db).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rebuild_subtree_repairs_one_branch() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let c = repo
+        .find_or_create_by_path(&db, &["root", "a", "b", "c"])
+        .await?;
+    let x = repo.find_or_create_by_path(&db, &["root", "x"]).await?;
+    let a = repo
+        .find_by_path(&db, &["root", "a"])
         .await?
-        .expect("child node exists");
+        .expect("a exists");
+    let b = repo
+        .find_by_path(&db, &["root", "a", "b"])
+        .await?
+        .expect("b exists");
+    // Move `b` under `x` and drop one of `a`'s rows behind the repository's back.
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "UPDATE nodes SET parent_id = $1 WHERE id = $2",
+        [x.id.into(), b.id.into()],
+    ))
+    .await?;
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM node_hierarchies WHERE descendant_id = $1 AND generations > 0",
+        [a.id.into()],
+    ))
+    .await?;
 
-    let descendants = repo.descendants(&db, &child).await?;
-    let names: Vec<String> = descendants.into_iter().map(|node| node.name).collect();
-    assert_eq!(names, vec!["leaf"]);
+    let preview = ClosureTreeRepository::<entity::node::Model>::new()
+        .with_dry_run(true)
+        .rebuild_subtree(&db, &x)
+        .await?;
+    assert_eq!(preview.rebuilt, [b.id, c.id]);
+    assert_eq!(repo.ancestors(&db, &c).await?.len(), 3);
+
+    assert_eq!(repo.rebuild_subtree(&db, &x).await?.rebuilt, [b.id, c.id]);
+    let ancestors: Vec<String> = repo
+        .ancestors(&db, &c)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(ancestors, ["b", "x", "root"]);
+    assert_eq!(repo.subtree_size(&db, &x).await?, 3);
+    assert!(repo.rebuild_subtree(&db, &x).await?.is_empty());
+
+    // Rows outside the branch are left alone.
+    assert_eq!(repo.rebuild(&db).await?.rebuilt, [a.id]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn rollback_failure_on_killed_connection_is_reported(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let key = common::strict_cleanup_node::Model::closure_tree_config()
+        .advisory_lock_strategy()
+        .key()
+        .and_then(|key| key.as_i64())
+        .expect("derived numeric key");
+    let holder = setup_database().await?;
+    let holder_txn = holder.begin().await?;
+    holder_txn
+        .execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "SELECT pg_advisory_xact_lock($1)",
+            [key.into()],
+        ))
+        .await?;
+
+    // The waiting backend is killed, so both the lock query and the rollback fail.
+    let strict = ClosureTreeRepository::<common::strict_cleanup_node::Model>::new();
+    let (result, killed) = tokio::join!(
+        strict.find_or_create_by_path(&db, &["root"]),
+        terminate_lock_waiter(&holder_txn),
+    );
+    killed?;
+    let err = result.expect_err("connection was killed");
+    assert!(
+        matches!(err, ClosureTreeError::CleanupFailed { .. }),
+        "unexpected error: {err:?}"
+    );
+
+    let best_effort = ClosureTreeRepository::<common::ascending_node::Model>::new();
+    let (result, killed) = tokio::join!(
+        best_effort.find_or_create_by_path(&db, &["root"]),
+        terminate_lock_waiter(&holder_txn),
+    );
+    killed?;
+    let err = result.expect_err("connection was killed");
+    assert!(
+        matches!(err, ClosureTreeError::Database(_)),
+        "unexpected error: {err:?}"
+    );
+
+    holder_txn.rollback().await?;
+    Ok(())
+}
+
+/// Terminate the first backend found waiting for an advisory lock.
+async fn terminate_lock_waiter(conn: &impl ConnectionTrait) -> Result<(), sea_orm::DbErr> {
+    loop {
+        let row = conn
+            .query_one(Statement::from_string(
+                DbBackend::Postgres,
+                "SELECT pg_terminate_backend(pid) AS terminated FROM pg_stat_activity \
+                 WHERE wait_event_type = 'Lock' AND wait_event = 'advisory' LIMIT 1",
+            ))
+            .await?;
+        if row.is_some() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[tokio::test]
+async fn concurrent_find_or_create_without_lock_is_idempotent(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    // No advisory lock: only the unique index on (parent_id, name) arbitrates.
+    let repo = ClosureTreeRepository::<common::unlocked_node::Model>::new();
+    let root = repo.find_or_create_by_path(&db, &["root"]).await?;
+
+    let mut attempts = tokio::task::JoinSet::new();
+    for _ in 0..8 {
+        let db = setup_database().await?;
+        attempts.spawn(async move {
+            ClosureTreeRepository::<common::unlocked_node::Model>::new()
+                .find_or_create_by_path(&db, &["root", "shared", "leaf"])
+                .await
+        });
+    }
+    let mut ids = Vec::new();
+    while let Some(leaf) = attempts.join_next().await {
+        ids.push(leaf??.id);
+    }
+    assert!(ids.iter().all(|id| *id == ids[0]));
+
+    assert_eq!(repo.children(&db, &root).await?.len(), 1);
+    assert_eq!(repo.subtree_size(&db, &root).await?, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn case_insensitive_first_segments_share_a_lock() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = || {
+        ClosureTreeRepository::<common::scoped_lock_node::Model>::new()
+            .with_config(
+                ClosureTreeOptions::default()
+                    .name_matching(closure_tree::NameMatching::CaseInsensitive)
+                    .apply(common::scoped_lock_node::Model::closure_tree_config().clone()),
+            )
+            .with_lock_acquisition(LockAcquisition::Blocking)
+    };
+    let mut attempts = tokio::task::JoinSet::new();
+    for spelling in ["Foo", "foo", "FOO", "fOo"].repeat(2) {
+        let db = setup_database().await?;
+        let repo = repo();
+        attempts.spawn(async move { repo.find_or_create_by_path(&db, &[spelling, "leaf"]).await });
+    }
+    let mut ids = Vec::new();
+    while let Some(leaf) = attempts.join_next().await {
+        ids.push(leaf??.id);
+    }
+    assert!(ids.iter().all(|id| *id == ids[0]));
+    assert_eq!(repo().roots(&db).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn import_tree_creates_nested_structure() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let parent = repo.find_or_create_by_path(&db, &["catalog"]).await?;
+
+    let input: TreeInput = serde_json::from_str(
+        r#"{
+            "name": "electronics",
+            "children": [
+                { "name": "phones", "children": [{ "name": "android" }, { "name": "ios" }] },
+                { "name": "laptops" }
+            ]
+        }"#,
+    )?;
+    let imported = repo.import_tree(&db, &input, Some(&parent.id)).await?;
+    assert_eq!(imported.parent_id, Some(parent.id));
+
+    let android = repo
+        .find_by_path(&db, &["catalog", "electronics", "phones", "android"])
+        .await?
+        .expect("android imported");
+    assert_eq!(repo.subtree_size(&db, &parent).await?, 6);
+    assert_eq!(repo.subtree_size(&db, &imported).await?, 5);
+
+    let names: Vec<String> = repo
+        .descendants(&db, &parent)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(
+        names,
+        vec!["android", "electronics", "ios", "laptops", "phones"]
+    );
+    let missing = repo
+        .import_tree(&db, &input, Some(&(android.id + 100)))
+        .await;
+    assert!(matches!(
+        missing,
+        Err(ClosureTreeError::NodeNotFound { .. })
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn importing_siblings_loads_the_parent_ancestry_once(
+) -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Default)]
+    struct QueryCounts {
+        imports: std::sync::Mutex<Vec<u32>>,
+    }
+
+    impl TreeObserver<i32> for QueryCounts {
+        fn on_operation(&self, _entity: &str, operation: &'static str, queries: u32, _: Duration) {
+            if operation == "import_tree" {
+                self.imports.lock().unwrap().push(queries);
+            }
+        }
+    }
+
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let counts = Arc::new(QueryCounts::default());
+    let repo = ClosureTreeRepository::<entity::node::Model>::new().with_observer(counts.clone());
+    let parent = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+
+    let wide = |name: &str, width: usize| {
+        (0..width).fold(TreeInput::new(name), |tree, index| {
+            tree.child(TreeInput::new(index.to_string()))
+        })
+    };
+    repo.import_tree(&db, &wide("narrow", 5), Some(&parent.id))
+        .await?;
+    repo.import_tree(&db, &wide("wide", 50), Some(&parent.id))
+        .await?;
+
+    // Each extra sibling costs its own insert and nothing else: the parent's
+    // ancestors are loaded once and the closure rows go out in one batch.
+    let imports = counts.imports.lock().unwrap().clone();
+    assert_eq!(imports.len(), 2);
+    assert_eq!(imports[1] - imports[0], 45);
+
+    let wide_root = repo
+        .find_by_path(&db, &["a", "b", "c", "wide"])
+        .await?
+        .expect("wide exists");
+    assert_eq!(repo.subtree_size(&db, &wide_root).await?, 51);
+    let leaf = repo
+        .find_by_path(&db, &["a", "b", "c", "wide", "49"])
+        .await?
+        .expect("leaf exists");
+    assert_eq!(
+        repo.ancestors_root_first(&db, &leaf)
+            .await?
+            .into_iter()
+            .map(|node| node.name)
+            .collect::<Vec<_>>(),
+        ["a", "b", "c", "wide"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn statement_timeout_surfaces_as_timeout_error() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let holder = setup_database().await?;
+    let holder_txn = holder.begin().await?;
+    holder_txn
+        .execute_unprepared("LOCK TABLE nodes IN ACCESS EXCLUSIVE MODE")
+        .await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new()
+        .with_statement_timeout(Duration::from_millis(100));
+    let err = repo
+        .find_or_create_by_path(&db, &["root"])
+        .await
+        .expect_err("table is locked elsewhere");
+    assert!(matches!(err, ClosureTreeError::Timeout(_)), "{err:?}");
+
+    holder_txn.commit().await?;
+    repo.find_or_create_by_path(&db, &["root"]).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn duplicate_subtree_copies_structure() -> Result<(), Box<dyn std::error::Error>> {
+    let _guard = lock_database().await;
+    let db = setup_database().await?;
+    truncate_tables(&db).await?;
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    repo.find_or_create_by_path(&db, &["templates", "site", "pages", "home"])
+        .await?;
+    repo.find_or_create_by_path(&db, &["templates", "site", "assets"])
+        .await?;
+    let target = repo.find_or_create_by_path(&db, &["sites"]).await?;
+    let site = repo
+        .find_by_path(&db, &["templates", "site"])
+        .await?
+        .expect("site exists");
+
+    let copy = repo.duplicate_subtree(&db, &site, Some(&target.id)).await?;
+    assert_ne!(copy.id, site.id);
+    assert_eq!(copy.name, "site");
+    assert_eq!(copy.parent_id, Some(target.id));
+
+    let home = repo
+        .find_by_path(&db, &["sites", "site", "pages", "home"])
+        .await?
+        .expect("copied leaf exists");
+    assert!(repo
+        .find_by_path(&db, &["sites", "site", "assets"])
+        .await?
+        .is_some());
+    assert_eq!(repo.subtree_size(&db, &target).await?, 5);
+    assert_eq!(repo.subtree_size(&db, &site).await?, 4);
+
+    let copied_names: Vec<String> = repo
+        .descendants(&db, &copy)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(copied_names, vec!["assets", "home", "pages"]);
+    assert_eq!(repo.subtree_size(&db, &home).await?, 1);
 
     Ok(())
 }
 
+async fn lock_database() -> MutexGuard<'static, ()> {
+    DATABASE_LOCK.lock().await
+}
+
 async fn setup_database() -> Result<DatabaseConnection, sea_orm::DbErr> {
     let url = std::env::var("CLOSURE_TREE_TEST_DATABASE_URL")
         .or_else(|_| std::env::var("DATABASE_URL"))
@@ -109,13 +3414,64 @@ async fn truncate_tables(db: &DatabaseConnection) -> Result<(), sea_orm::DbErr>
 
     db.execute(Statement::from_string(
         DbBackend::Postgres,
-        "TRUNCATE TABLE node_hierarchies RESTART IDENTITY CASCADE;",
+        r#"
+        CREATE TABLE IF NOT EXISTS ordered_nodes (
+            id SERIAL PRIMARY KEY,
+            parent_id INTEGER REFERENCES ordered_nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0
+        );
+        "#,
+    ))
+    .await?;
+
+    db.execute(Statement::from_string(
+        DbBackend::Postgres,
+        "CREATE UNIQUE INDEX IF NOT EXISTS ordered_nodes_parent_name ON ordered_nodes (parent_id, name)",
+    ))
+    .await?;
+
+    db.execute_unprepared("CREATE EXTENSION IF NOT EXISTS ltree")
+        .await?;
+    db.execute_unprepared("ALTER TABLE ordered_nodes ADD COLUMN IF NOT EXISTS path ltree")
+        .await?;
+    // TEXT keeps the tests free of a date-time crate; PostgreSQL casts the
+    // timestamp on assignment.
+    db.execute_unprepared("ALTER TABLE ordered_nodes ADD COLUMN IF NOT EXISTS touched_at TEXT")
+        .await?;
+    db.execute_unprepared(
+        "ALTER TABLE ordered_nodes ADD COLUMN IF NOT EXISTS children_count INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    db.execute_unprepared(
+        "ALTER TABLE ordered_nodes ADD COLUMN IF NOT EXISTS tenant_id INTEGER NOT NULL DEFAULT 0",
+    )
+    .await?;
+    db.execute_unprepared("ALTER TABLE ordered_nodes ADD COLUMN IF NOT EXISTS deleted_at TEXT")
+        .await?;
+
+    db.execute(Statement::from_string(
+        DbBackend::Postgres,
+        r#"
+        CREATE TABLE IF NOT EXISTS ordered_node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES ordered_nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES ordered_nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    ))
+    .await?;
+
+    db.execute(Statement::from_string(
+        DbBackend::Postgres,
+        "TRUNCATE TABLE node_hierarchies, ordered_node_hierarchies RESTART IDENTITY CASCADE;",
     ))
     .await?;
 
     db.execute(Statement::from_string(
         DbBackend::Postgres,
-        "TRUNCATE TABLE nodes RESTART IDENTITY CASCADE;",
+        "TRUNCATE TABLE nodes, ordered_nodes RESTART IDENTITY CASCADE;",
     ))
     .await?;
 
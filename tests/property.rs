@@ -0,0 +1,151 @@
+#![cfg(all(feature = "testing", feature = "sqlite"))]
+
+use std::collections::BTreeSet;
+
+use closure_tree::testing::rebuild_from_edges;
+use closure_tree::{ClosureTreeRepository, TreeInput};
+use sea_orm::{ConnectionTrait, Database, DatabaseConnection, EntityTrait};
+
+mod common;
+use common::entity;
+use entity::{node, node_hierarchy};
+
+/// Random inserts, moves and deletes, checking after every step that the
+/// closure table and the tree queries agree with a naive recursion over the
+/// parent edges.
+#[tokio::test]
+async fn random_edits_keep_the_closure_table_in_sync() -> Result<(), Box<dyn std::error::Error>> {
+    for seed in 1..=8 {
+        let db = setup_database().await?;
+        let repo = ClosureTreeRepository::<node::Model>::new();
+        let mut rng = Rng(seed);
+        for step in 0..60 {
+            let nodes = node::Entity::find().all(&db).await?;
+            let pick = |rng: &mut Rng| (!nodes.is_empty()).then(|| &nodes[rng.below(nodes.len())]);
+            match rng.below(10) {
+                0..=4 => {
+                    let parent = pick(&mut rng).filter(|_| rng.below(4) > 0);
+                    repo.import_tree(
+                        &db,
+                        &TreeInput::new(format!("n{step}")),
+                        parent.map(|node| &node.id),
+                    )
+                    .await?;
+                }
+                5..=7 => {
+                    if let (Some(moved), Some(onto)) = (pick(&mut rng), pick(&mut rng)) {
+                        // Grafting below its own subtree is rejected; the move is skipped.
+                        let closure =
+                            rebuild_from_edges(nodes.iter().map(|node| (node.id, node.parent_id)));
+                        if !closure.iter().any(|&(ancestor, descendant, _)| {
+                            (ancestor, descendant) == (moved.id, onto.id)
+                        }) {
+                            repo.graft(&db, &moved.id, &onto.id).await?;
+                        }
+                    }
+                }
+                8 => {
+                    if let (Some(first), Some(second)) = (pick(&mut rng), pick(&mut rng)) {
+                        repo.move_many(&db, &[first.id, second.id], None).await?;
+                    }
+                }
+                _ => {
+                    if let Some(destroyed) = pick(&mut rng) {
+                        repo.destroy(&db, destroyed).await?;
+                    }
+                }
+            }
+            assert_matches_edges(&db, &repo, seed, step).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn assert_matches_edges(
+    db: &DatabaseConnection,
+    repo: &ClosureTreeRepository<node::Model>,
+    seed: u64,
+    step: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let nodes = node::Entity::find().all(db).await?;
+    let expected = rebuild_from_edges(nodes.iter().map(|node| (node.id, node.parent_id)));
+    let mut stored: Vec<(i32, i32, i32)> = node_hierarchy::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| (row.ancestor_id, row.descendant_id, row.generations))
+        .collect();
+    stored.sort();
+    assert_eq!(stored, expected, "closure table (seed {seed}, step {step})");
+
+    let ids =
+        |nodes: Vec<node::Model>| -> BTreeSet<i32> { nodes.iter().map(|node| node.id).collect() };
+    for node in &nodes {
+        let descendants: BTreeSet<i32> = expected
+            .iter()
+            .filter(|(ancestor, _, generations)| *ancestor == node.id && *generations > 0)
+            .map(|(_, descendant, _)| *descendant)
+            .collect();
+        assert_eq!(
+            ids(repo.descendants(db, node).await?),
+            descendants,
+            "descendants of {} (seed {seed}, step {step})",
+            node.id
+        );
+        let mut ancestors: Vec<(i32, i32)> = expected
+            .iter()
+            .filter(|(_, descendant, generations)| *descendant == node.id && *generations > 0)
+            .map(|(ancestor, _, generations)| (*generations, *ancestor))
+            .collect();
+        ancestors.sort();
+        let ancestors: Vec<i32> = ancestors
+            .into_iter()
+            .map(|(_, ancestor)| ancestor)
+            .collect();
+        let found: Vec<i32> = repo
+            .ancestors(db, node)
+            .await?
+            .into_iter()
+            .map(|node| node.id)
+            .collect();
+        assert_eq!(
+            found, ancestors,
+            "ancestors of {} (seed {seed}, step {step})",
+            node.id
+        );
+    }
+    Ok(())
+}
+
+/// xorshift64: a seeded generator, so a failing seed can be replayed.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+async fn setup_database() -> Result<DatabaseConnection, sea_orm::DbErr> {
+    let db = Database::connect("sqlite::memory:").await?;
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE nodes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER REFERENCES nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL
+        );
+        CREATE TABLE node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+    Ok(db)
+}
@@ -0,0 +1,739 @@
+#![cfg(feature = "sqlite")]
+
+use std::sync::Arc;
+
+use closure_tree::{
+    async_trait, ClosureTreeError, ClosureTreeHooks, ClosureTreeModel, ClosureTreeOptions,
+    ClosureTreeRepository, HookConnection, RootMarker,
+};
+use sea_orm::sea_query::{Alias, Expr};
+use sea_orm::{
+    ConnectionTrait, Database, DatabaseConnection, DbBackend, ModelTrait, Order, QueryFilter,
+    QueryOrder, Statement,
+};
+
+mod common;
+use common::entity;
+use entity::node::NodeTreeExt;
+use entity::node_hierarchy;
+
+#[tokio::test]
+async fn find_or_create_path_builds_hierarchy() -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+
+    let leaf = repo
+        .find_or_create_by_path(&db, &["root", "child", "leaf"])
+        .await?;
+    assert_eq!(leaf.name, "leaf");
+
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let names: Vec<String> = repo
+        .descendants(&db, &root)
+        .await?
+        .into_iter()
+        .map(|node| node.name)
+        .collect();
+    assert_eq!(names, vec!["child", "leaf"]);
+
+    let roots = repo.roots(&db).await?;
+    assert_eq!(roots, vec![root.clone()]);
+    assert_eq!(repo.roots_count(&db).await?, 1);
+    assert_eq!(repo.node_count(&db).await?, 3);
+
+    assert_eq!(repo.subtree_size(&db, &root).await?, 3);
+    assert!(repo.has_children(&db, &root).await?);
+    assert!(!repo.has_children(&db, &leaf).await?);
+    assert_eq!(repo.count_children(&db, &root).await?, 1);
+    assert_eq!(repo.count_children(&db, &leaf).await?, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_find_or_create_does_not_duplicate() -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+
+    let (first, second) = tokio::join!(
+        repo.find_or_create_by_path(&db, &["root", "shared"]),
+        repo.find_or_create_by_path(&db, &["root", "shared"]),
+    );
+    assert_eq!(first?.id, second?.id);
+
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    assert_eq!(repo.children(&db, &root).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn prune_orphans_removes_rows_of_missing_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo.find_or_create_by_path(&db, &["root", "leaf"]).await?;
+
+    db.execute_unprepared("PRAGMA foreign_keys = OFF").await?;
+    db.execute_unprepared(&format!("DELETE FROM nodes WHERE id = {}", leaf.id))
+        .await?;
+
+    let preview = ClosureTreeRepository::<entity::node::Model>::new()
+        .with_dry_run(true)
+        .prune_orphans(&db)
+        .await?;
+    assert_eq!(preview.pruned.len(), 2);
+    assert!(preview
+        .pruned
+        .iter()
+        .all(|(_, descendant)| *descendant == leaf.id));
+
+    assert_eq!(repo.prune_orphans(&db).await?.pruned, preview.pruned);
+    assert!(repo.prune_orphans(&db).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn to_indented_string_outlines_the_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    repo.find_or_create_by_path(&db, &["root", "b", "c"])
+        .await?;
+    repo.find_or_create_by_path(&db, &["root", "a"]).await?;
+    let other = repo.find_or_create_by_path(&db, &["other"]).await?;
+
+    assert_eq!(
+        repo.to_indented_string(&db, None, "  ").await?,
+        "other\nroot\n  a\n  b\n    c"
+    );
+    let b = repo
+        .find_by_path(&db, &["root", "b"])
+        .await?
+        .expect("b exists");
+    assert_eq!(
+        repo.to_indented_string(&db, Some(&b), "- ").await?,
+        "b\n- c"
+    );
+    assert_eq!(
+        repo.to_indented_string(&db, Some(&other), "\t").await?,
+        "other"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn hierarchy_relations_join_nodes_through_the_closure_table(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let generations = || Expr::col((Alias::new("r0"), node_hierarchy::Column::Generations));
+    let names = |nodes: Vec<entity::node::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+
+    let ancestors = leaf
+        .find_linked(entity::node::AncestorsLink)
+        .filter(generations().gt(0))
+        .order_by(generations(), Order::Asc)
+        .all(&db)
+        .await?;
+    assert_eq!(names(ancestors), ["b", "a"]);
+    let subtree = a
+        .find_linked(entity::node::DescendantsLink)
+        .order_by(generations(), Order::Asc)
+        .all(&db)
+        .await?;
+    assert_eq!(names(subtree), ["a", "b", "c"]);
+
+    let rows = a.find_related(node_hierarchy::Entity).all(&db).await?;
+    assert_eq!(rows.len(), 3);
+    assert!(rows.iter().all(|row| row.ancestor_id == a.id));
+    Ok(())
+}
+
+#[tokio::test]
+async fn ancestor_names_label_a_batch_of_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+
+    let phone = repo
+        .find_or_create_by_path(&db, &["electronics", "phones", "pixel"])
+        .await?;
+    let laptops = repo
+        .find_or_create_by_path(&db, &["electronics", "laptops"])
+        .await?;
+    let books = repo.find_or_create_by_path(&db, &["books"]).await?;
+
+    let labels = repo
+        .ancestor_names(&db, &[phone.id, laptops.id, books.id, -1])
+        .await?;
+    assert_eq!(labels.len(), 4);
+    assert_eq!(labels[&phone.id], ["electronics", "phones"]);
+    assert_eq!(labels[&laptops.id], ["electronics"]);
+    assert!(labels[&books.id].is_empty());
+    assert!(labels[&-1].is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn newtype_ids_round_trip_through_the_tree() -> Result<(), Box<dyn std::error::Error>> {
+    use entity::newtype_node::Model;
+
+    let db = Database::connect("sqlite::memory:").await?;
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE newtype_nodes (
+            id INTEGER PRIMARY KEY,
+            parent_id INTEGER REFERENCES newtype_nodes(id),
+            name TEXT NOT NULL
+        );
+        CREATE TABLE newtype_node_hierarchies (
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+    let repo = ClosureTreeRepository::<Model>::new();
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    let other = repo.find_or_create_by_path(&db, &["z"]).await?;
+
+    repo.reparent_preserving_order(&db, &leaf.id, Some(&other.id))
+        .await?;
+    let moved = repo
+        .find_by_path(&db, &["z", "c"])
+        .await?
+        .expect("c moved under z");
+    assert_eq!((moved.id, moved.parent_id), (leaf.id, Some(other.id)));
+    let b = repo
+        .find_by_path(&db, &["a", "b"])
+        .await?
+        .expect("b exists");
+    assert!(repo.children(&db, &b).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn positions_past_a_small_order_field_fail_the_write(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use entity::small_ranked_node::Model;
+
+    let db = Database::connect("sqlite::memory:").await?;
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE small_ranked_nodes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER REFERENCES small_ranked_nodes(id),
+            name TEXT NOT NULL,
+            position SMALLINT NOT NULL
+        );
+        CREATE TABLE small_ranked_node_hierarchies (
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+    let repo = ClosureTreeRepository::<Model>::new().with_config(
+        ClosureTreeOptions::default()
+            .order_gap(20_000)
+            .apply(Model::closure_tree_config().clone()),
+    );
+    repo.find_or_create_by_path(&db, &["root", "a"]).await?;
+    repo.find_or_create_by_path(&db, &["root", "b"]).await?;
+    let err = repo
+        .find_or_create_by_path(&db, &["root", "c"])
+        .await
+        .expect_err("40000 does not fit an i16");
+    assert!(matches!(
+        err,
+        ClosureTreeError::OrderValueOutOfRange { value: 40_000 }
+    ));
+
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    assert_eq!(repo.children(&db, &root).await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn fractional_positions_split_the_gap_between_neighbours(
+) -> Result<(), Box<dyn std::error::Error>> {
+    use entity::fractional_node::Model;
+
+    let db = Database::connect("sqlite::memory:").await?;
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE fractional_nodes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER REFERENCES fractional_nodes(id),
+            name TEXT NOT NULL,
+            rank REAL NOT NULL
+        );
+        CREATE TABLE fractional_node_hierarchies (
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+    let repo = ClosureTreeRepository::<Model>::new();
+    for name in ["a", "b", "c"] {
+        repo.find_or_create_by_path(&db, &["root", name]).await?;
+    }
+    let root = repo
+        .find_by_path(&db, &["root"])
+        .await?
+        .expect("root exists");
+    let ranks = |nodes: Vec<Model>| -> Vec<(String, f64)> {
+        nodes
+            .into_iter()
+            .map(|node| (node.name, node.rank))
+            .collect()
+    };
+    assert_eq!(
+        ranks(repo.children(&db, &root).await?),
+        [("a".into(), 0.0), ("b".into(), 1.0), ("c".into(), 2.0)]
+    );
+
+    // Only the moved node is written.
+    let d = repo.find_or_create_by_path(&db, &["other", "d"]).await?;
+    repo.move_to_child_of_at(&db, &d, &root.id, 1).await?;
+    repo.move_to_child_of_at(&db, &d, &root.id, 0).await?;
+    assert_eq!(
+        ranks(repo.children(&db, &root).await?),
+        [
+            ("d".into(), -1.0),
+            ("a".into(), 0.0),
+            ("b".into(), 1.0),
+            ("c".into(), 2.0)
+        ]
+    );
+    let c = repo
+        .find_by_path(&db, &["root", "c"])
+        .await?
+        .expect("c exists");
+    assert!(repo.move_up(&db, &c).await?);
+    assert_eq!(
+        ranks(repo.children(&db, &root).await?)[2..],
+        [("c".into(), 1.0), ("b".into(), 2.0)]
+    );
+
+    // Splitting the same gap until no value fits renumbers the siblings once.
+    let mut expected = vec!["d".to_string()];
+    for index in 0..70 {
+        let name = format!("n{index}");
+        let node = repo.find_or_create_by_path(&db, &["other", &name]).await?;
+        repo.move_to_child_of_at(&db, &node, &root.id, 1).await?;
+        expected.insert(1, name);
+    }
+    expected.extend(["a", "c", "b"].map(String::from));
+    let children = ranks(repo.children(&db, &root).await?);
+    let names: Vec<&str> = children.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, expected);
+    assert!(children.windows(2).all(|pair| pair[0].1 < pair[1].1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn tagged_trees_keep_separate_hierarchies() -> Result<(), Box<dyn std::error::Error>> {
+    use entity::staff_member::{self, ModelReportingTreeExt, OrgTree, ReportingTree};
+
+    let db = Database::connect("sqlite::memory:").await?;
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE staff_members (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            department_id INTEGER REFERENCES staff_members(id),
+            manager_id INTEGER REFERENCES staff_members(id),
+            name TEXT NOT NULL
+        );
+        CREATE TABLE staff_members_org_hierarchies (
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        CREATE TABLE staff_members_reporting_hierarchies (
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+    let org = ClosureTreeRepository::<staff_member::Model, OrgTree>::new();
+    let reporting = ClosureTreeRepository::<staff_member::Model, ReportingTree>::new();
+
+    let alice = org.find_or_create_by_path(&db, &["eng", "alice"]).await?;
+    let bob = org.find_or_create_by_path(&db, &["eng", "bob"]).await?;
+    let carol = org.find_or_create_by_path(&db, &["eng", "carol"]).await?;
+    // People created in the org chart enter the reporting lines as roots.
+    reporting.rebuild(&db).await?;
+    reporting.graft(&db, &bob.id, &alice.id).await?;
+    reporting.graft(&db, &carol.id, &bob.id).await?;
+
+    let names = |nodes: Vec<staff_member::Model>| -> Vec<String> {
+        nodes.into_iter().map(|node| node.name).collect()
+    };
+    assert_eq!(names(org.ancestors(&db, &carol).await?), ["eng"]);
+    assert_eq!(
+        names(reporting.ancestors(&db, &carol).await?),
+        ["bob", "alice"]
+    );
+    assert_eq!(names(carol.tree_ancestors(&db).await?), ["bob", "alice"]);
+    assert_eq!(org.descendants(&db, &alice).await?, vec![]);
+    assert_eq!(
+        names(reporting.descendants(&db, &alice).await?),
+        ["bob", "carol"]
+    );
+    let reports = alice
+        .find_linked(staff_member::ReportingDescendantsLink)
+        .all(&db)
+        .await?;
+    assert_eq!(reports.len(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn extension_trait_methods_delegate_to_the_repository(
+) -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    let leaf = repo.find_or_create_by_path(&db, &["a", "b", "c"]).await?;
+    let other = repo.find_or_create_by_path(&db, &["z"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let b = repo
+        .find_by_path(&db, &["a", "b"])
+        .await?
+        .expect("b exists");
+
+    assert_eq!(a.tree_children(&db).await?, vec![b.clone()]);
+    assert_eq!(leaf.tree_ancestors(&db).await?, vec![b.clone(), a.clone()]);
+    assert_eq!(b.tree_parent(&db).await?, Some(a.clone()));
+    assert_eq!(a.tree_siblings(&db).await?, vec![other.clone()]);
+    assert_eq!(
+        a.tree_descendants(&db).await?,
+        vec![b.clone(), leaf.clone()]
+    );
+
+    b.tree_move_to_child_of(&db, &other).await?;
+    assert!(a.tree_children(&db).await?.is_empty());
+    let ancestors: Vec<i32> = leaf
+        .tree_ancestors(&db)
+        .await?
+        .iter()
+        .map(|node| node.id)
+        .collect();
+    assert_eq!(ancestors, [b.id, other.id]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn root_markers_stand_in_for_null_parents() -> Result<(), Box<dyn std::error::Error>> {
+    for marker in [RootMarker::Zero, RootMarker::SelfReference] {
+        // Database-assigned ids are only known after the insert, so a
+        // self-referencing root briefly has a NULL parent.
+        let parent_column = match marker {
+            RootMarker::Zero => "parent_id INTEGER NOT NULL",
+            _ => "parent_id INTEGER REFERENCES nodes(id)",
+        };
+        let db = Database::connect("sqlite::memory:").await?;
+        db.execute_unprepared(&format!(
+            r#"
+            CREATE TABLE nodes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                {parent_column},
+                name TEXT NOT NULL
+            );
+            CREATE TABLE node_hierarchies (
+                ancestor_id INTEGER NOT NULL,
+                descendant_id INTEGER NOT NULL,
+                generations INTEGER NOT NULL,
+                PRIMARY KEY (ancestor_id, descendant_id)
+            );
+            "#
+        ))
+        .await?;
+        let repo = ClosureTreeRepository::<entity::node::Model>::new().with_config(
+            ClosureTreeOptions::default()
+                .root_marker(marker)
+                .apply(entity::node::Model::closure_tree_config().clone()),
+        );
+        let stored = |node: &entity::node::Model| match marker {
+            RootMarker::SelfReference => Some(node.id),
+            _ => Some(0),
+        };
+
+        let leaf = repo
+            .find_or_create_by_path(&db, &["root", "child", "leaf"])
+            .await?;
+        let other = repo.find_or_create_by_path(&db, &["other"]).await?;
+        let root = repo
+            .find_by_path(&db, &["root"])
+            .await?
+            .expect("root exists");
+        let child = repo
+            .find_by_path(&db, &["root", "child"])
+            .await?
+            .expect("child exists");
+        assert_eq!(root.parent_id, stored(&root), "{marker:?}");
+        assert!(repo.is_root(&root) && !repo.is_root(&child));
+        assert_eq!(repo.roots(&db).await?, vec![other.clone(), root.clone()]);
+        assert_eq!(repo.roots_count(&db).await?, 2);
+        assert_eq!(repo.siblings(&db, &root).await?, vec![other.clone()]);
+        assert_eq!(repo.children(&db, &root).await?, vec![child.clone()]);
+        assert_eq!(repo.parent(&db, &root).await?, None);
+        assert_eq!(repo.hash_tree(&db, None).await?.len(), 2);
+
+        repo.move_many(&db, &[leaf.id], None).await?;
+        let leaf = repo
+            .find_by_path(&db, &["leaf"])
+            .await?
+            .expect("leaf moved");
+        assert_eq!(leaf.parent_id, stored(&leaf), "{marker:?}");
+        assert!(repo.ancestors(&db, &leaf).await?.is_empty());
+
+        repo.move_many(&db, &[leaf.id], Some(&child.id)).await?;
+        repo.destroy(&db, &root).await?;
+        let child = repo
+            .find_by_path(&db, &["child"])
+            .await?
+            .expect("child nullified");
+        assert_eq!(child.parent_id, stored(&child), "{marker:?}");
+        assert!(repo.find_by_path(&db, &["child", "leaf"]).await?.is_some());
+        assert!(repo.rebuild(&db).await?.is_empty(), "{marker:?}");
+    }
+    Ok(())
+}
+
+/// Logs every callback to `hook_log` through the operation's transaction, and
+/// rejects nodes named `forbidden` and the destruction of `keep`.
+struct LoggingHooks;
+
+impl LoggingHooks {
+    async fn log(conn: &HookConnection<'_>, entry: String) -> Result<(), ClosureTreeError> {
+        conn.execute(Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            "INSERT INTO hook_log (entry) VALUES (?)",
+            [entry.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ClosureTreeHooks<entity::node::Model> for LoggingHooks {
+    async fn before_create_child(
+        &self,
+        conn: &HookConnection<'_>,
+        parent: Option<&i32>,
+        child: &mut entity::node::ActiveModel,
+    ) -> Result<(), ClosureTreeError> {
+        let name = child.name.as_ref().clone();
+        Self::log(conn, format!("before_create {name} under {parent:?}")).await?;
+        if name == "forbidden" {
+            return Err(ClosureTreeError::invariant("forbidden name"));
+        }
+        Ok(())
+    }
+
+    async fn after_create_child(
+        &self,
+        conn: &HookConnection<'_>,
+        child: &entity::node::Model,
+    ) -> Result<(), ClosureTreeError> {
+        Self::log(conn, format!("after_create {}", child.name)).await
+    }
+
+    async fn before_move(
+        &self,
+        conn: &HookConnection<'_>,
+        node: &entity::node::Model,
+        new_parent: Option<&i32>,
+    ) -> Result<(), ClosureTreeError> {
+        Self::log(conn, format!("before_move {} to {new_parent:?}", node.name)).await
+    }
+
+    async fn after_move(
+        &self,
+        conn: &HookConnection<'_>,
+        node: &entity::node::Model,
+        old_parent: Option<&i32>,
+    ) -> Result<(), ClosureTreeError> {
+        Self::log(
+            conn,
+            format!(
+                "after_move {} from {old_parent:?} to {:?}",
+                node.name, node.parent_id
+            ),
+        )
+        .await
+    }
+
+    async fn before_destroy_subtree(
+        &self,
+        conn: &HookConnection<'_>,
+        root: &entity::node::Model,
+    ) -> Result<(), ClosureTreeError> {
+        Self::log(conn, format!("before_destroy {}", root.name)).await?;
+        if root.name == "keep" {
+            return Err(ClosureTreeError::invariant("keep is protected"));
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn new_child_defaults_fill_required_columns() -> Result<(), Box<dyn std::error::Error>> {
+    use closure_tree::TreeInput;
+    use entity::kinded_node::Model;
+
+    let db = Database::connect("sqlite::memory:").await?;
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE kinded_nodes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER REFERENCES kinded_nodes(id),
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL
+        );
+        CREATE TABLE kinded_node_hierarchies (
+            ancestor_id INTEGER NOT NULL,
+            descendant_id INTEGER NOT NULL,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+    let repo = ClosureTreeRepository::<Model>::new();
+    let leaf = repo
+        .find_or_create_by_path(&db, &["c", "docs", "notes.txt"])
+        .await?;
+    let kinds =
+        |nodes: Vec<Model>| -> Vec<String> { nodes.into_iter().map(|node| node.kind).collect() };
+    assert_eq!(leaf.kind, "file");
+    assert_eq!(
+        kinds(repo.ancestors(&db, &leaf).await?),
+        ["folder", "drive"]
+    );
+
+    let docs = repo
+        .find_by_path(&db, &["c", "docs"])
+        .await?
+        .expect("docs exists");
+    let photos = TreeInput::new("photos").child(TreeInput::new("cat.png"));
+    let imported = repo.import_tree(&db, &photos, Some(&docs.id)).await?;
+    assert_eq!(
+        kinds(repo.self_and_descendants(&db, &imported).await?),
+        ["file", "file"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn hooks_run_inside_the_operation_transaction() -> Result<(), Box<dyn std::error::Error>> {
+    let db = setup_database().await?;
+    db.execute_unprepared(
+        "CREATE TABLE hook_log (id INTEGER PRIMARY KEY AUTOINCREMENT, entry TEXT NOT NULL)",
+    )
+    .await?;
+    let log = || async {
+        let rows = db
+            .query_all(Statement::from_string(
+                DbBackend::Sqlite,
+                "SELECT entry FROM hook_log ORDER BY id",
+            ))
+            .await?;
+        rows.iter()
+            .map(|row| row.try_get::<String>("", "entry"))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let repo =
+        ClosureTreeRepository::<entity::node::Model>::new().with_hooks(Arc::new(LoggingHooks));
+
+    let b = repo.find_or_create_by_path(&db, &["a", "b"]).await?;
+    let a = repo.find_by_path(&db, &["a"]).await?.expect("a exists");
+    let keep = repo.find_or_create_by_path(&db, &["keep"]).await?;
+    repo.reparent_preserving_order(&db, &b.id, Some(&keep.id))
+        .await?;
+    assert_eq!(
+        log().await?,
+        [
+            "before_create a under None".to_owned(),
+            format!("before_create b under Some({})", a.id),
+            "after_create a".to_owned(),
+            "after_create b".to_owned(),
+            "before_create keep under None".to_owned(),
+            "after_create keep".to_owned(),
+            format!("before_move b to Some({})", keep.id),
+            format!("after_move b from Some({}) to Some({})", a.id, keep.id),
+        ]
+    );
+
+    // A failing hook rolls back the operation and everything hooks wrote in it.
+    let before = log().await?;
+    assert!(repo
+        .find_or_create_by_path(&db, &["a", "forbidden"])
+        .await
+        .is_err());
+    assert!(repo.destroy(&db, &keep).await.is_err());
+    assert_eq!(log().await?, before);
+    assert!(repo.find_by_path(&db, &["a", "forbidden"]).await?.is_none());
+    assert!(repo.find_by_path(&db, &["keep", "b"]).await?.is_some());
+
+    repo.destroy(&db, &a).await?;
+    assert_eq!(
+        log().await?.last().map(String::as_str),
+        Some("before_destroy a")
+    );
+    Ok(())
+}
+
+async fn setup_database() -> Result<DatabaseConnection, sea_orm::DbErr> {
+    let db = Database::connect("sqlite::memory:").await?;
+
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE nodes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            parent_id INTEGER REFERENCES nodes(id) ON DELETE CASCADE,
+            name TEXT NOT NULL
+        );
+        "#,
+    )
+    .await?;
+
+    db.execute_unprepared(
+        r#"
+        CREATE TABLE node_hierarchies (
+            ancestor_id INTEGER NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+            descendant_id INTEGER NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+            generations INTEGER NOT NULL,
+            PRIMARY KEY (ancestor_id, descendant_id)
+        );
+        "#,
+    )
+    .await?;
+
+    Ok(db)
+}
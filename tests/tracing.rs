@@ -0,0 +1,95 @@
+#![cfg(feature = "tracing")]
+
+use std::sync::{Arc, Mutex};
+
+use closure_tree::ClosureTreeRepository;
+use sea_orm::{DatabaseBackend, MockDatabase};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+mod common;
+use common::entity;
+
+/// Span name, declared field names and recorded `(field, value)` pairs.
+type CapturedSpan = (String, Vec<String>, Vec<(String, String)>);
+
+#[derive(Clone, Default)]
+struct CaptureLayer {
+    spans: Arc<Mutex<Vec<CapturedSpan>>>,
+}
+
+struct Values<'a>(&'a mut Vec<(String, String)>);
+
+impl Visit for Values<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.push((field.name().to_owned(), format!("{value:?}")));
+    }
+}
+
+impl<S> Layer<S> for CaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        let metadata = attrs.metadata();
+        let mut values = Vec::new();
+        attrs.record(&mut Values(&mut values));
+        self.spans.lock().unwrap().push((
+            metadata.name().to_owned(),
+            metadata
+                .fields()
+                .iter()
+                .map(|f| f.name().to_owned())
+                .collect(),
+            values,
+        ));
+    }
+
+    fn on_record(&self, id: &Id, record: &Record<'_>, ctx: Context<'_, S>) {
+        let name = ctx.span(id).expect("span exists").name();
+        let mut spans = self.spans.lock().unwrap();
+        if let Some((_, _, values)) = spans.iter_mut().rev().find(|(n, _, _)| n == name) {
+            record.record(&mut Values(values));
+        }
+    }
+}
+
+#[tokio::test]
+async fn repository_spans_carry_operation_fields() -> Result<(), Box<dyn std::error::Error>> {
+    let layer = CaptureLayer::default();
+    let _default =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(layer.clone()));
+
+    let root = entity::node::Model {
+        id: 1,
+        parent_id: None,
+        name: "root".to_string(),
+    };
+    let child = entity::node::Model {
+        id: 2,
+        parent_id: Some(1),
+        name: "child".to_string(),
+    };
+    let db = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_query_results([vec![child.clone()]])
+        .into_connection();
+
+    let repo = ClosureTreeRepository::<entity::node::Model>::new();
+    assert_eq!(repo.children(&db, &root).await?, vec![child]);
+
+    let spans = layer.spans.lock().unwrap();
+    let (_, fields, values) = spans
+        .iter()
+        .find(|(name, _, _)| name == "closure_tree.children")
+        .expect("children span");
+    assert_eq!(fields, &["entity", "node_id", "rows"]);
+    assert!(values.contains(&("entity".to_owned(), "Model".to_owned())));
+    assert!(values.contains(&("node_id".to_owned(), "1".to_owned())));
+    assert!(values.contains(&("rows".to_owned(), "1".to_owned())));
+
+    Ok(())
+}
@@ -0,0 +1,7 @@
+//! Compile errors the `ClosureTreeModel` derive reports for bad input.
+
+#[test]
+fn derive_errors() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,9 @@
+use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+
+#[derive(ClosureTreeModel)]
+#[closure_tree(generate_hierarchy_entity)]
+pub enum Model {
+    Root,
+}
+
+fn main() {}
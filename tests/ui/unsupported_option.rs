@@ -0,0 +1,19 @@
+use closure_tree::ClosureTreeModelDerive as ClosureTreeModel;
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, ClosureTreeModel)]
+#[sea_orm(table_name = "nodes")]
+#[closure_tree(generate_hierarchy_entity, hierarchy_tabel = "node_hierarchies")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+fn main() {}